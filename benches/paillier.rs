@@ -1,5 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use pht_crypto::paillier::generate_key_pair;
+use pht_crypto::prepared::PreparedCiphertext;
 use rug::rand::RandState;
 
 pub fn key_gen(c: &mut Criterion) {
@@ -77,7 +78,7 @@ pub fn combine_shares(c: &mut Criterion) {
         .collect();
     group.bench_function("2048 bits", |b| {
         b.iter(|| {
-            pk.share_combine(&partial_decs);
+            pk.share_combine(&partial_decs).unwrap();
         })
     });
 
@@ -90,17 +91,69 @@ pub fn combine_shares(c: &mut Criterion) {
         .collect();
     group.bench_function("3072 bits", |b| {
         b.iter(|| {
-            pk.share_combine(&partial_decs);
+            pk.share_combine(&partial_decs).unwrap();
         })
     });
 }
 
+pub fn decrypt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decrypt");
+    let mut rand = RandState::new();
+    let (pk, sk) = generate_key_pair(3072, 1, 1).unwrap();
+    let cipher = pk.encrypt(42.into(), &mut rand);
+    group.bench_function("3072 bits crt", |b| b.iter(|| sk.decrypt(&cipher)));
+    // the DER encoding does not contain the factors
+    #[cfg(feature = "asn1")]
+    {
+        let sk_without_factors = pht_crypto::paillier::PrivateKey::from_der(&sk.to_der()).unwrap();
+        group.bench_function("3072 bits", |b| {
+            b.iter(|| sk_without_factors.decrypt(&cipher))
+        });
+    }
+}
+
+pub fn mul_plain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mul_plain");
+    let mut rand = RandState::new();
+    let (pk, _sk) = generate_key_pair(2048, 1, 1).unwrap();
+    let cipher = pk.encrypt(42.into(), &mut rand);
+    let plain = rug::Integer::from(pk.modulus() - 1).into();
+    group.bench_function("2048 bits", |b| {
+        b.iter(|| pk.mul_plain(&mut cipher.clone(), &plain))
+    });
+    let prepared = PreparedCiphertext::new(&pk, &cipher, 2048);
+    group.bench_function("2048 bits prepared", |b| {
+        b.iter(|| prepared.mul_plain(&plain))
+    });
+}
+
+pub fn sum_encrypted(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sum_encrypted");
+    let mut rand = RandState::new();
+    let (pk, _sk) = generate_key_pair(3072, 1, 1).unwrap();
+    let cipher = pk.encrypt(42.into(), &mut rand);
+    let ciphers = vec![cipher; 1000];
+    group.bench_function("3072 bits add_encrypted", |b| {
+        b.iter(|| {
+            let mut acc = ciphers[0].clone();
+            for cipher in &ciphers[1..] {
+                pk.add_encrypted(&mut acc, cipher);
+            }
+            acc
+        })
+    });
+    group.bench_function("3072 bits", |b| b.iter(|| pk.sum_encrypted(&ciphers)));
+}
+
 criterion_group!(
     benches,
     key_gen,
     encrypt,
     add_ciphertexts,
     share_decrypt,
-    combine_shares
+    combine_shares,
+    decrypt,
+    mul_plain,
+    sum_encrypted
 );
 criterion_main!(benches);
@@ -0,0 +1,44 @@
+// Generates the gRPC service of `pht_crypto::server` (feature `server`) from its
+// definition below, so no protoc is needed. It mirrors `ThresholdDecryption` in
+// `proto/pht_crypto.proto`.
+fn main() {
+    #[cfg(feature = "server")]
+    {
+        use tonic_build::manual::{Builder, Method, Service};
+
+        let method = |name: &str, route: &str, input: &str, output: &str| {
+            Method::builder()
+                .name(name)
+                .route_name(route)
+                .input_type(format!("crate::server::{}", input))
+                .output_type(format!("crate::server::{}", output))
+                .codec_path("tonic::codec::ProstCodec")
+                .build()
+        };
+        let service = Service::builder()
+            .name("ThresholdDecryption")
+            .package("pht_crypto")
+            .method(method(
+                "submit_ciphertext",
+                "SubmitCiphertext",
+                "SubmitCiphertextRequest",
+                "SubmitCiphertextResponse",
+            ))
+            .method(method(
+                "get_partial_decryption",
+                "GetPartialDecryption",
+                "GetPartialDecryptionRequest",
+                "GetPartialDecryptionResponse",
+            ))
+            .method(method(
+                "health",
+                "Health",
+                "HealthRequest",
+                "HealthResponse",
+            ))
+            .build();
+        // the generated `connect` relies on the prelude of edition 2021,
+        // `DecryptionClient::connect` opens the channel itself
+        Builder::new().build_transport(false).compile(&[service]);
+    }
+}
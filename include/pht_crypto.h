@@ -0,0 +1,236 @@
+#ifndef PHT_CRYPTO_H
+#define PHT_CRYPTO_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result of every function of the C ABI
+typedef enum PhtStatus {
+  PHT_STATUS_OK = 0,
+  // A required pointer was null
+  PHT_STATUS_NULL_POINTER = 1,
+  // An argument could not be parsed or is out of range
+  PHT_STATUS_INVALID_ARGUMENT = 2,
+  // The operation failed, e.g. partial decryptions of different ciphertexts were
+  // combined
+  PHT_STATUS_FAILED = 3,
+  // The library panicked, which is a bug
+  PHT_STATUS_PANIC = 4,
+} PhtStatus;
+
+typedef struct PhtCiphertext PhtCiphertext;
+
+typedef struct PhtKeyShare PhtKeyShare;
+
+typedef struct PhtPartialDecryption PhtPartialDecryption;
+
+typedef struct PhtPrivateKey PhtPrivateKey;
+
+typedef struct PhtPublicKey PhtPublicKey;
+
+// Bytes allocated by the library, released with [`pht_buffer_free`]
+typedef struct PhtBuffer {
+  uint8_t *data;
+  size_t len;
+} PhtBuffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The message of the last error on the calling thread, or null if there was none.
+// The string is valid until the next failing call on the same thread.
+const char *pht_last_error_message(void);
+
+// Releases a buffer returned by the library
+//
+// # Safety
+// `buffer` must have been returned by the library and not been freed before.
+void pht_buffer_free(struct PhtBuffer buffer);
+
+// Generates a key pair with a `bits` bit modulus for `threshold` of
+// `decryption_servers` servers
+//
+// # Safety
+// `pk_out` and `sk_out` must be valid for writes.
+enum PhtStatus pht_generate_key_pair(uint32_t bits,
+                                     uint32_t decryption_servers,
+                                     uint32_t threshold,
+                                     struct PhtPublicKey **pk_out,
+                                     struct PhtPrivateKey **sk_out);
+
+// Deals the key shares of all decryption servers. `shares_out` must have room for
+// exactly as many handles as there are servers, the i-th share belongs to server i.
+//
+// # Safety
+// `pk` and `sk` must be valid handles, `shares_out` must be valid for `shares_len`
+// writes.
+enum PhtStatus pht_deal_key_shares(const struct PhtPublicKey *pk,
+                                   const struct PhtPrivateKey *sk,
+                                   struct PhtKeyShare **shares_out,
+                                   size_t shares_len);
+
+// Encrypts the unsigned big endian plaintext `data`, which must be less than n
+//
+// # Safety
+// `pk` must be a valid handle, `data` must be valid for `len` reads and `out` for
+// writes.
+enum PhtStatus pht_encrypt(const struct PhtPublicKey *pk,
+                           const uint8_t *data,
+                           size_t len,
+                           struct PhtCiphertext **out);
+
+// Encrypts `m`
+//
+// # Safety
+// `pk` must be a valid handle and `out` valid for writes.
+enum PhtStatus pht_encrypt_u64(const struct PhtPublicKey *pk,
+                               uint64_t m,
+                               struct PhtCiphertext **out);
+
+// Encrypts the sum of the plaintexts of `a` and `b`
+//
+// # Safety
+// All handles must be valid and `out` valid for writes.
+enum PhtStatus pht_add(const struct PhtPublicKey *pk,
+                       const struct PhtCiphertext *a,
+                       const struct PhtCiphertext *b,
+                       struct PhtCiphertext **out);
+
+// Decrypts `cipher` with the full private key into an unsigned big endian buffer
+//
+// # Safety
+// All handles must be valid and `out` valid for writes.
+enum PhtStatus pht_decrypt(const struct PhtPrivateKey *sk,
+                           const struct PhtCiphertext *cipher,
+                           struct PhtBuffer *out);
+
+// Computes the partial decryption of `cipher` with `share`
+//
+// # Safety
+// All handles must be valid and `out` valid for writes.
+enum PhtStatus pht_share_decrypt(const struct PhtPublicKey *pk,
+                                 const struct PhtKeyShare *share,
+                                 const struct PhtCiphertext *cipher,
+                                 struct PhtPartialDecryption **out);
+
+// Combines the partial decryptions of at least w servers into an unsigned big
+// endian buffer
+//
+// # Safety
+// `pk` must be a valid handle, `partials` must point to `count` valid handles and
+// `out` must be valid for writes.
+enum PhtStatus pht_share_combine(const struct PhtPublicKey *pk,
+                                 const struct PhtPartialDecryption *const *partials,
+                                 size_t count,
+                                 struct PhtBuffer *out);
+
+// Serializes the public key with the versioned encoding
+//
+// # Safety
+// `pk` must be a valid handle and `out` valid for writes.
+enum PhtStatus pht_public_key_to_bytes(const struct PhtPublicKey *pk, struct PhtBuffer *out);
+
+// Parses a public key serialized with [`pht_public_key_to_bytes`]
+//
+// # Safety
+// `data` must be valid for `len` reads and `out` for writes.
+enum PhtStatus pht_public_key_from_bytes(const uint8_t *data,
+                                         size_t len,
+                                         struct PhtPublicKey **out);
+
+// Releases a public key, null is ignored
+//
+// # Safety
+// `pk` must be null or a handle which has not been freed before.
+void pht_public_key_free(struct PhtPublicKey *pk);
+
+// Serializes the private key with the versioned encoding
+//
+// # Safety
+// `sk` must be a valid handle and `out` valid for writes.
+enum PhtStatus pht_private_key_to_bytes(const struct PhtPrivateKey *sk, struct PhtBuffer *out);
+
+// Parses a private key serialized with [`pht_private_key_to_bytes`]
+//
+// # Safety
+// `data` must be valid for `len` reads and `out` for writes.
+enum PhtStatus pht_private_key_from_bytes(const uint8_t *data,
+                                          size_t len,
+                                          struct PhtPrivateKey **out);
+
+// Releases a private key, null is ignored
+//
+// # Safety
+// `sk` must be null or a handle which has not been freed before.
+void pht_private_key_free(struct PhtPrivateKey *sk);
+
+// Serializes the key share with the versioned encoding
+//
+// # Safety
+// `share` must be a valid handle and `out` valid for writes.
+enum PhtStatus pht_key_share_to_bytes(const struct PhtKeyShare *share, struct PhtBuffer *out);
+
+// Parses a key share serialized with [`pht_key_share_to_bytes`]
+//
+// # Safety
+// `data` must be valid for `len` reads and `out` for writes.
+enum PhtStatus pht_key_share_from_bytes(const uint8_t *data, size_t len, struct PhtKeyShare **out);
+
+// Releases a key share, null is ignored
+//
+// # Safety
+// `share` must be null or a handle which has not been freed before.
+void pht_key_share_free(struct PhtKeyShare *share);
+
+// Serializes the ciphertext with the versioned encoding
+//
+// # Safety
+// `cipher` must be a valid handle and `out` valid for writes.
+enum PhtStatus pht_ciphertext_to_bytes(const struct PhtCiphertext *cipher, struct PhtBuffer *out);
+
+// Parses a ciphertext serialized with [`pht_ciphertext_to_bytes`]
+//
+// # Safety
+// `data` must be valid for `len` reads and `out` for writes.
+enum PhtStatus pht_ciphertext_from_bytes(const uint8_t *data,
+                                         size_t len,
+                                         struct PhtCiphertext **out);
+
+// Releases a ciphertext, null is ignored
+//
+// # Safety
+// `cipher` must be null or a handle which has not been freed before.
+void pht_ciphertext_free(struct PhtCiphertext *cipher);
+
+// Serializes the partial decryption with the versioned encoding
+//
+// # Safety
+// `partial` must be a valid handle and `out` valid for writes.
+enum PhtStatus pht_partial_decryption_to_bytes(const struct PhtPartialDecryption *partial,
+                                               struct PhtBuffer *out);
+
+// Parses a partial decryption serialized with [`pht_partial_decryption_to_bytes`]
+//
+// # Safety
+// `data` must be valid for `len` reads and `out` for writes.
+enum PhtStatus pht_partial_decryption_from_bytes(const uint8_t *data,
+                                                 size_t len,
+                                                 struct PhtPartialDecryption **out);
+
+// Releases a partial decryption, null is ignored
+//
+// # Safety
+// `partial` must be null or a handle which has not been freed before.
+void pht_partial_decryption_free(struct PhtPartialDecryption *partial);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* PHT_CRYPTO_H */
@@ -0,0 +1,32 @@
+package pht.crypto;
+
+/**
+ * Encryption under a threshold Paillier public key of pht-crypto, implemented in the
+ * native library built with {@code cargo rustc --release --features jni --crate-type cdylib}.
+ *
+ * <p>Public keys are passed in the versioned encoding of {@code pht_crypto::wire},
+ * ciphertexts in its canonical fixed size encoding and plaintexts as unsigned big endian
+ * bytes. Invalid input throws an {@link IllegalArgumentException}.
+ */
+public final class PhtCrypto {
+    static {
+        System.loadLibrary("pht_crypto");
+    }
+
+    private PhtCrypto() {}
+
+    /** Encrypts {@code value}, negative values are encoded as n + value. */
+    public static native byte[] encrypt(byte[] publicKey, long value);
+
+    /** Encrypts the unsigned big endian {@code value}, which must be less than n. */
+    public static native byte[] encryptBytes(byte[] publicKey, byte[] value);
+
+    /** Encrypts all {@code values} and returns the concatenation of the ciphertexts. */
+    public static native byte[] encryptBatch(byte[] publicKey, long[] values);
+
+    /** Encrypts the sum of the plaintexts of {@code a} and {@code b}. */
+    public static native byte[] addEncrypted(byte[] publicKey, byte[] a, byte[] b);
+
+    /** Length of an encoded ciphertext in bytes. */
+    public static native int ciphertextLength(byte[] publicKey);
+}
@@ -0,0 +1,365 @@
+//! Secure aggregation rounds with threshold Paillier.
+//!
+//! Clients encrypt their contributions under the committee's public key, the aggregator
+//! homomorphically sums them and the w-of-l decryption committee decrypts only the sum.
+//! Clients that drop out before the round is closed are simply excluded from the sum
+//! and recorded in the [`AggregationTranscript`]. To prevent the sum from revealing
+//! an individual contribution, a round is only closed if at least `min_clients`
+//! clients contributed.
+//!
+//! A round is identified by the [`PublicKey::fingerprint`] of the committee's key and
+//! its round number. Contributions name both and their client, and a round accepts
+//! one contribution per client and rejects ciphertexts it already received. Over an
+//! unauthenticated channel this doesn't stop a re-randomized copy of a contribution
+//! from being submitted under another client id. With the `signed` feature,
+//! `AggregationRound::with_client_keys` requires every contribution to be signed by
+//! its client over the key fingerprint, round, client id and ciphertext, so it can't
+//! be replayed into another round or under another id.
+//! [`AggregationTranscript::summary`] records who was included in an
+//! [`AggregationSummary`], whose [`AggregationSummary::digest`] the aggregator signs,
+//! e.g. as a [`crate::signed`] envelope with the `signed` feature.
+//!
+//! ```
+//! use pht_crypto::aggregation::{AggregationRound, Contribution};
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 2, 2).unwrap();
+//! let key_shares = sk.share(&[0, 1], &mut rand);
+//!
+//! let mut round = AggregationRound::new(&pk, 1, vec![1, 2, 3], 2);
+//! for (client, value) in [(1, 10), (3, 32)] {
+//!     let contribution = Contribution::new(&pk, 1, client, value.into(), &mut rand);
+//!     round.submit(&pk, contribution).unwrap();
+//! }
+//! let transcript = round.close(&pk).unwrap();
+//! assert_eq!(transcript.dropped(), &[2]);
+//!
+//! let partials: Vec<_> = key_shares
+//!     .iter()
+//!     .map(|share| transcript.share_decrypt(&pk, share))
+//!     .collect();
+//! let result = transcript.finish(&pk, &partials).unwrap();
+//! assert_eq!(result.sum, 42);
+//! ```
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::proofs::in_mult_group;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::collections::{BTreeMap, HashSet};
+
+const SUMMARY_LABEL: &[u8] = b"pht-crypto aggregation summary v1";
+
+/// An encrypted contribution of a client to a round
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contribution {
+    key_fingerprint: [u8; 32],
+    round: u64,
+    client: u32,
+    cipher: Ciphertext,
+}
+
+/// State of the aggregator while a round is open
+#[derive(Debug, Clone)]
+pub struct AggregationRound {
+    key_fingerprint: [u8; 32],
+    round: u64,
+    clients: Vec<u32>,
+    min_clients: usize,
+    contributions: BTreeMap<u32, Ciphertext>,
+    /// [`Ciphertext::digest`] of every accepted contribution
+    digests: HashSet<[u8; 32]>,
+    /// Verifying keys of the clients if contributions must be signed
+    #[cfg(feature = "signed")]
+    pub(crate) client_keys: Option<BTreeMap<u32, ed25519_dalek::VerifyingKey>>,
+}
+
+/// Record of a closed round. It contains the encrypted sum which is handed to the
+/// decryption committee.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregationTranscript {
+    key_fingerprint: [u8; 32],
+    round: u64,
+    included: Vec<u32>,
+    dropped: Vec<u32>,
+    sum: Ciphertext,
+}
+
+/// Who was included in a round, to be signed by the aggregator
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AggregationSummary {
+    pub key_fingerprint: [u8; 32],
+    pub round: u64,
+    pub included: Vec<u32>,
+    pub dropped: Vec<u32>,
+    /// SHA3-256 of the canonical encoding of the encrypted sum
+    pub sum_digest: [u8; 32],
+}
+
+/// The decrypted sum of a round
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregationResult {
+    pub round: u64,
+    pub included: Vec<u32>,
+    pub dropped: Vec<u32>,
+    pub sum: Plaintext,
+}
+
+impl Contribution {
+    pub fn new(
+        pk: &PublicKey,
+        round: u64,
+        client: u32,
+        value: Plaintext,
+        rand: &mut dyn MutRandState,
+    ) -> Self {
+        Self {
+            key_fingerprint: pk.fingerprint(),
+            round,
+            client,
+            cipher: pk.encrypt(value, rand),
+        }
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn client(&self) -> u32 {
+        self.client
+    }
+
+    /// Deterministic bincode encoding of the key fingerprint, round, client and
+    /// ciphertext
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("serialization into a Vec can't fail")
+    }
+}
+
+impl AggregationRound {
+    /// Opens round `round` under `pk` for the expected `clients`. The round can only
+    /// be closed once at least `min_clients` of them contributed.
+    pub fn new(pk: &PublicKey, round: u64, mut clients: Vec<u32>, min_clients: usize) -> Self {
+        clients.sort_unstable();
+        clients.dedup();
+        Self {
+            key_fingerprint: pk.fingerprint(),
+            round,
+            clients,
+            min_clients,
+            contributions: BTreeMap::new(),
+            digests: HashSet::new(),
+            #[cfg(feature = "signed")]
+            client_keys: None,
+        }
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Accepts a contribution of an expected client. Each client may contribute once
+    /// and every ciphertext is accepted once.
+    pub fn submit(&mut self, pk: &PublicKey, contribution: Contribution) -> Result<()> {
+        #[cfg(feature = "signed")]
+        ensure!(
+            self.client_keys.is_none(),
+            "contributions to this round must be signed"
+        );
+        self.accept(pk, contribution)
+    }
+
+    pub(crate) fn accept(&mut self, pk: &PublicKey, contribution: Contribution) -> Result<()> {
+        ensure!(
+            contribution.key_fingerprint == self.key_fingerprint
+                && pk.fingerprint() == self.key_fingerprint,
+            "contribution is for another key"
+        );
+        ensure!(
+            contribution.round == self.round,
+            "contribution is for round {} but round {} is open",
+            contribution.round,
+            self.round
+        );
+        ensure!(
+            self.clients.binary_search(&contribution.client).is_ok(),
+            "client {} is not part of this round",
+            contribution.client
+        );
+        ensure!(
+            !self.contributions.contains_key(&contribution.client),
+            "client {} already contributed",
+            contribution.client
+        );
+        ensure!(
+            in_mult_group(contribution.cipher.as_ref(), &pk.n, &pk.n2),
+            "ciphertext is not in Z*_n^2"
+        );
+        ensure!(
+            self.digests.insert(contribution.cipher.digest()),
+            "ciphertext was already submitted"
+        );
+        self.contributions
+            .insert(contribution.client, contribution.cipher);
+        Ok(())
+    }
+
+    /// Clients that have not contributed yet
+    pub fn missing(&self) -> Vec<u32> {
+        self.clients
+            .iter()
+            .filter(|c| !self.contributions.contains_key(c))
+            .copied()
+            .collect()
+    }
+
+    /// Closes the round, treating all missing clients as dropped out, and sums the
+    /// received contributions.
+    pub fn close(self, pk: &PublicKey) -> Result<AggregationTranscript> {
+        ensure!(
+            self.contributions.len() >= self.min_clients.max(1),
+            "only {} of the required {} clients contributed",
+            self.contributions.len(),
+            self.min_clients
+        );
+        let dropped = self.missing();
+        let sum = pk.sum_encrypted(self.contributions.values());
+        Ok(AggregationTranscript {
+            key_fingerprint: self.key_fingerprint,
+            round: self.round,
+            included: self.contributions.into_keys().collect(),
+            dropped,
+            sum,
+        })
+    }
+}
+
+impl AggregationSummary {
+    /// SHA3-256 digest of the summary, to be signed by the aggregator
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(SUMMARY_LABEL);
+        hasher.update(self.canonical_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Deterministic bincode encoding of the summary
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("serialization into a Vec can't fail")
+    }
+}
+
+impl AggregationTranscript {
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn included(&self) -> &[u32] {
+        &self.included
+    }
+
+    pub fn dropped(&self) -> &[u32] {
+        &self.dropped
+    }
+
+    pub fn sum(&self) -> &Ciphertext {
+        &self.sum
+    }
+
+    /// The summary of the round for the aggregator to sign
+    pub fn summary(&self) -> AggregationSummary {
+        AggregationSummary {
+            key_fingerprint: self.key_fingerprint,
+            round: self.round,
+            included: self.included.clone(),
+            dropped: self.dropped.clone(),
+            sum_digest: Sha3_256::digest(self.sum.to_cbor()).into(),
+        }
+    }
+
+    /// Partial decryption of the sum by a committee member
+    pub fn share_decrypt(&self, pk: &PublicKey, key_share: &PrivateKeyShare) -> PartialDecryption {
+        key_share.share_decrypt(pk, self.sum.clone())
+    }
+
+    /// Combines the partial decryptions of at least w committee members
+    pub fn finish(
+        &self,
+        pk: &PublicKey,
+        partials: &[PartialDecryption],
+    ) -> Result<AggregationResult> {
+        ensure!(
+            pk.fingerprint() == self.key_fingerprint,
+            "round was aggregated under another key"
+        );
+        ensure!(
+            partials.len() >= pk.w as usize,
+            "at least {} partial decryptions are needed",
+            pk.w
+        );
+        Ok(AggregationResult {
+            round: self.round,
+            included: self.included.clone(),
+            dropped: self.dropped.clone(),
+            sum: pk.share_combine(partials)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AggregationRound, Contribution};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_round_rejects_invalid_contributions() {
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(512, 1, 1).unwrap();
+        let (other_pk, _) = generate_key_pair(512, 1, 1).unwrap();
+        let mut round = AggregationRound::new(&pk, 7, vec![1, 2, 3], 2);
+
+        let wrong_round = Contribution::new(&pk, 6, 1, 1.into(), &mut rand);
+        assert!(round.submit(&pk, wrong_round).is_err());
+        let other_key = Contribution::new(&other_pk, 7, 2, 1.into(), &mut rand);
+        assert!(round.submit(&other_pk, other_key.clone()).is_err());
+        assert!(round.submit(&pk, other_key).is_err());
+        let unknown = Contribution::new(&pk, 7, 4, 1.into(), &mut rand);
+        assert!(round.submit(&pk, unknown).is_err());
+        let valid = Contribution::new(&pk, 7, 1, 1.into(), &mut rand);
+        round.submit(&pk, valid.clone()).unwrap();
+        assert!(round.submit(&pk, valid.clone()).is_err());
+        // the ciphertext of client 1 resubmitted under another id
+        let mut copied = Contribution::new(&pk, 7, 2, 0.into(), &mut rand);
+        copied.cipher = valid.cipher;
+        assert!(round.submit(&pk, copied).is_err());
+        assert_eq!(round.missing(), vec![2, 3]);
+        assert!(round.close(&pk).is_err());
+    }
+
+    #[test]
+    fn test_round_summary() {
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(512, 1, 1).unwrap();
+        let mut round = AggregationRound::new(&pk, 2, vec![1, 2, 3], 2);
+        for client in [3, 1] {
+            let contribution = Contribution::new(&pk, 2, client, 5.into(), &mut rand);
+            round.submit(&pk, contribution).unwrap();
+        }
+        let transcript = round.close(&pk).unwrap();
+        let summary = transcript.summary();
+        assert_eq!(summary.key_fingerprint, pk.fingerprint());
+        assert_eq!((summary.round, &summary.included[..]), (2, &[1, 3][..]));
+        assert_eq!(summary.dropped, [2]);
+        assert_eq!(summary.digest(), transcript.summary().digest());
+
+        let mut other = summary.clone();
+        other.included.push(2);
+        assert_ne!(other.digest(), summary.digest());
+    }
+}
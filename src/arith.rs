@@ -0,0 +1,204 @@
+//! Montgomery multiplication for long chains of products mod n^2.
+//!
+//! Summing ciphertexts multiplies them mod n^2, and with 4096 to 8192 bit moduli the
+//! reductions dominate loops of [`PublicKey::add_encrypted`]. An [`ArithContext`]
+//! replaces the divisions of these reductions by Montgomery reductions, which only need
+//! multiplications and shifts. It is obtained with [`PublicKey::arith_context`] and
+//! used by [`PublicKey::sum_encrypted`].
+//!
+//! Note that GMP's division with a precomputed inverse is nearly as fast as a Montgomery
+//! reduction on `rug` integers, so a single chain is only modestly faster than repeated
+//! [`PublicKey::add_encrypted`]. [`PublicKey::sum_encrypted`] additionally splits the
+//! product into chunks which are multiplied in parallel.
+
+use crate::paillier::PublicKey;
+use crate::par::prelude::*;
+use crate::Ciphertext;
+use rug::{Assign, Complete, Integer};
+use std::borrow::Cow;
+
+/// Number of ciphertexts multiplied sequentially by one task of
+/// [`PublicKey::sum_encrypted`]
+const SUM_CHUNK: usize = 64;
+
+/// Montgomery reduction modulo an odd modulus m with R = 2^bits > m
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArithContext {
+    m: Integer,
+    bits: u32,
+    /// -m^-1 mod R
+    m_neg_inv: Integer,
+    /// R mod m
+    r_mod_m: Integer,
+}
+
+impl ArithContext {
+    /// Creates the context for the odd modulus `m`.
+    ///
+    /// Panics if `m` is even.
+    pub fn new(m: &Integer) -> Self {
+        assert!(m.is_odd(), "montgomery modulus must be odd");
+        let bits = m.significant_bits();
+        let r = Integer::from(1) << bits;
+        let m_inv = m.invert_ref(&r).unwrap().complete();
+        let m_neg_inv = r.clone() - m_inv;
+        let r_mod_m = r % m;
+        Self {
+            m: m.clone(),
+            bits,
+            m_neg_inv,
+            r_mod_m,
+        }
+    }
+
+    pub fn modulus(&self) -> &Integer {
+        &self.m
+    }
+
+    /// t * R^-1 mod m for 0 <= t < m * R
+    fn redc(&self, mut t: Integer) -> Integer {
+        let mut u = Integer::new();
+        self.redc_mut(&mut t, &mut u);
+        t
+    }
+
+    /// In place variant of [`ArithContext::redc`] reusing the scratch integer `u`
+    fn redc_mut(&self, t: &mut Integer, u: &mut Integer) {
+        u.assign(t.keep_bits_ref(self.bits));
+        *u *= &self.m_neg_inv;
+        u.keep_bits_mut(self.bits);
+        *u *= &self.m;
+        *t += &*u;
+        *t >>= self.bits;
+        if *t >= self.m {
+            *t -= &self.m;
+        }
+    }
+
+    /// Converts 0 <= a < m into Montgomery form a * R mod m
+    pub fn to_montgomery(&self, a: &Integer) -> Integer {
+        (a * &self.r_mod_m).complete() % &self.m
+    }
+
+    /// Converts a from Montgomery form back into a * R^-1 mod m
+    pub fn from_montgomery(&self, a: Integer) -> Integer {
+        self.redc(a)
+    }
+
+    /// a * b * R^-1 mod m, i.e. the product of a and b in Montgomery form
+    pub fn mul(&self, a: &Integer, b: &Integer) -> Integer {
+        self.redc((a * b).complete())
+    }
+
+    /// The product of `values` mod m, each in [0, m). Multiplies in Montgomery form
+    /// and corrects the accumulated R^-1 factors with a single reduction at the end.
+    pub fn product<'a>(&self, values: impl IntoIterator<Item = &'a Integer>) -> Integer {
+        let (acc, reductions) = self.product_unscaled(values);
+        self.rescale(acc, reductions)
+    }
+
+    /// The product of `values` times R^-k mod m and the number of reductions k
+    fn product_unscaled<'a>(
+        &self,
+        values: impl IntoIterator<Item = &'a Integer>,
+    ) -> (Integer, u64) {
+        let mut values = values.into_iter();
+        let mut acc = match values.next() {
+            Some(first) => first.clone(),
+            None => return (Integer::from(1) % &self.m, 0),
+        };
+        let mut reductions = 0;
+        let mut scratch = Integer::new();
+        for value in values {
+            acc *= value;
+            self.redc_mut(&mut acc, &mut scratch);
+            reductions += 1;
+        }
+        (acc, reductions)
+    }
+
+    /// a * R^k mod m
+    fn rescale(&self, a: Integer, k: u64) -> Integer {
+        if k == 0 {
+            return a % &self.m;
+        }
+        let correction = self
+            .r_mod_m
+            .pow_mod_ref(&Integer::from(k), &self.m)
+            .unwrap()
+            .complete();
+        a * correction % &self.m
+    }
+}
+
+impl PublicKey {
+    /// Montgomery context for arithmetic mod n^2
+    pub fn arith_context(&self) -> ArithContext {
+        ArithContext::new(&self.n2)
+    }
+
+    /// Homomorphically sums `ciphers` in parallel, which is faster than repeated
+    /// [`PublicKey::add_encrypted`] for many ciphertexts. The sum of no ciphertexts is
+    /// the trivial encryption of 0.
+    pub fn sum_encrypted<'a>(
+        &self,
+        ciphers: impl IntoIterator<Item = &'a Ciphertext>,
+    ) -> Ciphertext {
+        let ctx = self.arith_context();
+        // ciphertexts are usually already reduced, avoid copying them
+        let reduced: Vec<Cow<Integer>> = ciphers
+            .into_iter()
+            .map(|c| match c.as_ref() {
+                c if *c >= 0 && *c < self.n2 => Cow::Borrowed(c),
+                c => Cow::Owned(c.modulo_ref(&self.n2).complete()),
+            })
+            .collect();
+        let partial: Vec<(Integer, u64)> = reduced
+            .par_chunks(SUM_CHUNK)
+            .map(|chunk| ctx.product_unscaled(chunk.iter().map(AsRef::as_ref)))
+            .collect();
+        let (acc, reductions) = ctx.product_unscaled(partial.iter().map(|(p, _)| p));
+        let reductions = reductions + partial.iter().map(|(_, k)| k).sum::<u64>();
+        ctx.rescale(acc, reductions).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ArithContext;
+    use crate::paillier::generate_key_pair;
+    use crate::Ciphertext;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_montgomery_product() {
+        let mut rand = RandState::new();
+        let m = Integer::from(Integer::u_pow_u(3, 300)) + 2;
+        let ctx = ArithContext::new(&m);
+        let values: Vec<Integer> = (0..10)
+            .map(|_| Integer::from(m.random_below_ref(&mut rand)))
+            .collect();
+        for k in 0..values.len() {
+            let expected = values[..k]
+                .iter()
+                .fold(Integer::from(1), |acc, v| acc * v % &m);
+            assert_eq!(ctx.product(&values[..k]), expected);
+        }
+        let (a, b) = (&values[0], &values[1]);
+        let ab = ctx.mul(&ctx.to_montgomery(a), &ctx.to_montgomery(b));
+        assert_eq!(ctx.from_montgomery(ab), Integer::from(a * b) % &m);
+    }
+
+    #[test]
+    fn test_sum_encrypted() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let ciphers: Vec<Ciphertext> = (0..200).map(|i| pk.encrypt(i.into(), &mut rand)).collect();
+        assert_eq!(
+            Integer::from(sk.decrypt(&pk.sum_encrypted(&ciphers))),
+            19900
+        );
+        assert_eq!(Integer::from(sk.decrypt(&pk.sum_encrypted(&[]))), 0);
+    }
+}
@@ -0,0 +1,219 @@
+//! DER and PEM encodings of Paillier keys.
+//!
+//! The keys are encoded with the following ASN.1 structures, all integers being
+//! non-negative. Precomputations are not encoded and recomputed on decoding.
+//!
+//! ```text
+//! PaillierPublicKey ::= SEQUENCE {
+//!     version           INTEGER,  -- 0
+//!     modulus           INTEGER,  -- n
+//!     decryptionServers INTEGER,  -- l
+//!     threshold         INTEGER   -- w
+//! }
+//!
+//! PaillierPrivateKey ::= SEQUENCE {
+//!     version           INTEGER,  -- 0
+//!     modulus           INTEGER,  -- n
+//!     decryptionServers INTEGER,  -- l
+//!     threshold         INTEGER,  -- w
+//!     decryptionExp     INTEGER,  -- d
+//!     nm                INTEGER   -- n * m
+//! }
+//!
+//! PaillierPrivateKeyShare ::= SEQUENCE {
+//!     version           INTEGER,  -- 0
+//!     index             INTEGER,  -- i
+//!     share             INTEGER   -- s_i
+//! }
+//! ```
+//!
+//! The PEM labels are `PAILLIER PUBLIC KEY`, `PAILLIER PRIVATE KEY` and
+//! `PAILLIER PRIVATE KEY SHARE`. The DER can be inspected with standard tooling, e.g.
+//! `openssl asn1parse`.
+
+use crate::paillier::{PrivateKey, PrivateKeyShare, PublicKey};
+use anyhow::{anyhow, ensure, Result};
+use rug::integer::Order;
+use rug::Integer;
+use yasna::{ASN1Result, BERReader, DERWriter};
+
+const VERSION: u32 = 0;
+const PUBLIC_KEY_LABEL: &str = "PAILLIER PUBLIC KEY";
+const PRIVATE_KEY_LABEL: &str = "PAILLIER PRIVATE KEY";
+const PRIVATE_KEY_SHARE_LABEL: &str = "PAILLIER PRIVATE KEY SHARE";
+
+fn write_integer(writer: DERWriter, x: &Integer) {
+    let bytes = x.to_digits::<u8>(Order::MsfBe);
+    writer.write_bigint_bytes(&bytes, *x >= 0)
+}
+
+fn read_integer(reader: BERReader) -> ASN1Result<Integer> {
+    let (bytes, positive) = reader.read_bigint_bytes()?;
+    let x = Integer::from_digits(&bytes, Order::MsfBe);
+    Ok(if positive { x } else { -x })
+}
+
+fn read_version(reader: BERReader) -> ASN1Result<()> {
+    match reader.read_u32()? {
+        VERSION => Ok(()),
+        _ => Err(yasna::ASN1Error::new(yasna::ASN1ErrorKind::Invalid)),
+    }
+}
+
+fn from_pem(pem: &str, label: &str) -> Result<Vec<u8>> {
+    let pem = pem::parse(pem).map_err(|e| anyhow!("invalid PEM: {}", e))?;
+    ensure!(
+        pem.tag() == label,
+        "expected PEM label {} but found {}",
+        label,
+        pem.tag()
+    );
+    Ok(pem.into_contents())
+}
+
+fn to_pem(der: Vec<u8>, label: &str) -> String {
+    pem::encode(&pem::Pem::new(label, der))
+}
+
+impl PublicKey {
+    pub fn to_der(&self) -> Vec<u8> {
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_u32(VERSION);
+                write_integer(writer.next(), &self.n);
+                writer.next().write_u32(self.l);
+                writer.next().write_u32(self.w);
+            })
+        })
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (n, l, w) = yasna::parse_der(der, |reader| {
+            reader.read_sequence(|reader| {
+                read_version(reader.next())?;
+                let n = read_integer(reader.next())?;
+                let l = reader.next().read_u32()?;
+                let w = reader.next().read_u32()?;
+                Ok((n, l, w))
+            })
+        })
+        .map_err(|e| anyhow!("invalid DER: {}", e))?;
+        PublicKey::from_modulus(n, l, w)
+    }
+
+    pub fn to_pem(&self) -> String {
+        to_pem(self.to_der(), PUBLIC_KEY_LABEL)
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&from_pem(pem, PUBLIC_KEY_LABEL)?)
+    }
+}
+
+impl PrivateKey {
+    pub fn to_der(&self) -> Vec<u8> {
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_u32(VERSION);
+                write_integer(writer.next(), &self.n);
+                writer.next().write_u32(self.l);
+                writer.next().write_u32(self.w);
+                write_integer(writer.next(), &self.d);
+                write_integer(writer.next(), &self.nm);
+            })
+        })
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (n, l, w, d, nm) = yasna::parse_der(der, |reader| {
+            reader.read_sequence(|reader| {
+                read_version(reader.next())?;
+                let n = read_integer(reader.next())?;
+                let l = reader.next().read_u32()?;
+                let w = reader.next().read_u32()?;
+                let d = read_integer(reader.next())?;
+                let nm = read_integer(reader.next())?;
+                Ok((n, l, w, d, nm))
+            })
+        })
+        .map_err(|e| anyhow!("invalid DER: {}", e))?;
+        PrivateKey::from_parts(n, l, w, d, nm)
+    }
+
+    pub fn to_pem(&self) -> String {
+        to_pem(self.to_der(), PRIVATE_KEY_LABEL)
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&from_pem(pem, PRIVATE_KEY_LABEL)?)
+    }
+}
+
+impl PrivateKeyShare {
+    pub fn to_der(&self) -> Vec<u8> {
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_u32(VERSION);
+                writer.next().write_u32(self.i);
+                write_integer(writer.next(), &self.si);
+            })
+        })
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (i, si) = yasna::parse_der(der, |reader| {
+            reader.read_sequence(|reader| {
+                read_version(reader.next())?;
+                let i = reader.next().read_u32()?;
+                let si = read_integer(reader.next())?;
+                Ok((i, si))
+            })
+        })
+        .map_err(|e| anyhow!("invalid DER: {}", e))?;
+        ensure!(i > 0, "share index must be positive");
+        Ok(PrivateKeyShare {
+            i,
+            si,
+            key_fingerprint: None,
+        })
+    }
+
+    pub fn to_pem(&self) -> String {
+        to_pem(self.to_der(), PRIVATE_KEY_SHARE_LABEL)
+    }
+
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        Self::from_der(&from_pem(pem, PRIVATE_KEY_SHARE_LABEL)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paillier::{generate_key_pair, PrivateKey, PrivateKeyShare, PublicKey};
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_pem_roundtrip() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+
+        let pem = pk.to_pem();
+        assert!(pem.starts_with("-----BEGIN PAILLIER PUBLIC KEY-----"));
+        assert_eq!(PublicKey::from_pem(&pem).unwrap(), pk);
+        assert!(PrivateKey::from_pem(&pem).is_err());
+
+        assert_eq!(PrivateKey::from_pem(&sk.to_pem()).unwrap(), sk);
+
+        let shares = sk.share(&[0, 1], &mut rand);
+        let decoded: Vec<_> = shares
+            .iter()
+            .map(|s| PrivateKeyShare::from_der(&s.to_der()).unwrap())
+            .collect();
+        let c = pk.encrypt(42.into(), &mut rand);
+        let partials: Vec<_> = decoded
+            .iter()
+            .map(|s| s.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&partials).unwrap(), 42);
+    }
+}
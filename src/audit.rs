@@ -0,0 +1,292 @@
+//! Audit records of key ceremonies.
+//!
+//! [`PrivateKey::share_audited`] deals the shares like
+//! [`PrivateKey::share_verifiable`] and additionally returns a [`DealingTranscript`]
+//! of everything public about the dealing: the key fingerprint, the server indices,
+//! the commitments to the polynomial and the verification key v^{s_i} of every
+//! share. It never contains the secret coefficients or shares.
+//!
+//! The transcript is serializable, e.g. with [`Versioned`], and its
+//! [`DealingTranscript::digest`] can be signed by the dealer. Auditors later check
+//! it with [`DealingTranscript::verify`], and servers check their shares with
+//! [`DealingTranscript::verify_share`] or [`PrivateKeyShare::verify`] with their
+//! [`VerificationKey`].
+//!
+//! [`PrivateKey::share_verifiable`]: crate::paillier::PrivateKey::share_verifiable
+//! [`Versioned`]: crate::wire::Versioned
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::wire::Versioned;
+//! use pht_crypto::audit::DealingTranscript;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 3, 2).unwrap();
+//! let (shares, transcript) = sk.share_audited(&[0, 2], &mut rand);
+//!
+//! let bytes = transcript.to_versioned_bytes();
+//! let replayed = DealingTranscript::from_versioned_bytes(&bytes).unwrap();
+//! replayed.verify(&pk).unwrap();
+//! assert!(shares.iter().all(|share| replayed.verify_share(share)));
+//! ```
+
+use crate::paillier::{self, PolynomialCommitments, PrivateKey, PrivateKeyShare, PublicKey};
+use crate::par;
+use crate::util;
+use crate::wire::Versioned;
+use crate::Ciphertext;
+use anyhow::{ensure, Result};
+use rug::integer::Order;
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+
+const FINGERPRINT_LABEL: &[u8] = b"pht-crypto paillier public key v1";
+const TRANSCRIPT_LABEL: &[u8] = b"pht-crypto dealing transcript v1";
+const CIPHERTEXT_LABEL: &[u8] = b"pht-crypto ciphertext v1";
+
+/// The public record of a dealing of key shares
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DealingTranscript {
+    /// [`PublicKey::fingerprint`] of the shared key
+    key_fingerprint: [u8; 32],
+    #[serde(with = "paillier::serde_compact")]
+    public_key: PublicKey,
+    /// Server indices the shares were dealt to, share i is evaluated at i + 1
+    server_indices: Vec<u32>,
+    commitments: PolynomialCommitments,
+    /// v^{s_i} mod n^2 for every server in `server_indices`
+    #[serde(with = "crate::util::serde_integer_vec")]
+    verification_keys: Vec<Integer>,
+}
+
+/// The public verification key v^{s_i} mod n^2 of the share at an evaluation point
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct VerificationKey {
+    point: u32,
+    /// Generator of the dealer's commitments
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) v: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    key: Integer,
+}
+
+fn fingerprint(w: u32, l: u32, n: &Integer) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(FINGERPRINT_LABEL);
+    hasher.update(w.to_be_bytes());
+    hasher.update(l.to_be_bytes());
+    hasher.update(n.to_digits::<u8>(Order::MsfBe));
+    hasher.finalize().into()
+}
+
+impl PublicKey {
+    /// SHA3-256 fingerprint of the threshold parameters and the modulus
+    pub fn fingerprint(&self) -> [u8; 32] {
+        fingerprint(self.w, self.l, &self.n)
+    }
+}
+
+impl Ciphertext {
+    /// SHA3-256 digest of the ciphertext, recorded in its partial decryptions
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(CIPHERTEXT_LABEL);
+        hasher.update(self.as_ref().to_digits::<u8>(Order::MsfBe));
+        hasher.finalize().into()
+    }
+}
+
+impl PrivateKey {
+    /// The [`PublicKey::fingerprint`] of the corresponding public key
+    pub fn fingerprint(&self) -> [u8; 32] {
+        fingerprint(self.w, self.l, &self.n)
+    }
+}
+
+impl VerificationKey {
+    pub fn point(&self) -> u32 {
+        self.point
+    }
+
+    /// v^{s_i} mod n^2
+    pub fn key(&self) -> &Integer {
+        &self.key
+    }
+}
+
+impl PrivateKeyShare {
+    /// Checks that this share was dealt for `pk`, if it records a key fingerprint, and
+    /// that it matches its verification key
+    pub fn verify(&self, pk: &PublicKey, verification_key: &VerificationKey) -> Result<()> {
+        if let Some(key_fingerprint) = &self.key_fingerprint {
+            ensure!(
+                *key_fingerprint == pk.fingerprint(),
+                "share was dealt for another key"
+            );
+        }
+        ensure!(
+            verification_key.point == self.i,
+            "verification key is for share {}, not {}",
+            verification_key.point,
+            self.i
+        );
+        ensure!(
+            util::secure_pow_mod(&verification_key.v, &self.si, &pk.n2) == verification_key.key,
+            "share does not match its verification key"
+        );
+        Ok(())
+    }
+}
+
+impl PrivateKey {
+    /// Like [`PrivateKey::share_verifiable`] but records the dealing in a
+    /// [`DealingTranscript`]
+    pub fn share_audited(
+        self,
+        server_indices: &[u32],
+        rand_state: &mut dyn MutRandState,
+    ) -> (Vec<PrivateKeyShare>, DealingTranscript) {
+        let public_key = PublicKey::from_modulus(self.n.clone(), self.l, self.w)
+            .expect("private key has a valid modulus");
+        let (shares, commitments) = self.share_verifiable(server_indices, rand_state);
+        let verification_keys = par::map_collect(&shares, |_, share| {
+            util::secure_pow_mod(&commitments.v, &share.si, &public_key.n2)
+        });
+        let transcript = DealingTranscript {
+            key_fingerprint: public_key.fingerprint(),
+            public_key,
+            server_indices: server_indices.to_vec(),
+            commitments,
+            verification_keys,
+        };
+        (shares, transcript)
+    }
+}
+
+impl DealingTranscript {
+    pub fn key_fingerprint(&self) -> &[u8; 32] {
+        &self.key_fingerprint
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    pub fn server_indices(&self) -> &[u32] {
+        &self.server_indices
+    }
+
+    pub fn commitments(&self) -> &PolynomialCommitments {
+        &self.commitments
+    }
+
+    /// The verification key v^{s_i} mod n^2 of the server `server_index`
+    pub fn verification_key(&self, server_index: u32) -> Option<VerificationKey> {
+        let pos = self
+            .server_indices
+            .iter()
+            .position(|idx| *idx == server_index)?;
+        Some(VerificationKey {
+            point: server_index.checked_add(1)?,
+            v: self.commitments.v.clone(),
+            key: self.verification_keys.get(pos)?.clone(),
+        })
+    }
+
+    /// SHA3-256 digest of the versioned encoding, to be signed by the dealer
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(TRANSCRIPT_LABEL);
+        hasher.update(self.to_versioned_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Checks that the transcript belongs to `pk` and is consistent: one commitment
+    /// per coefficient, unique servers and verification keys matching the commitments.
+    pub fn verify(&self, pk: &PublicKey) -> Result<()> {
+        ensure!(
+            self.key_fingerprint == pk.fingerprint() && self.public_key == *pk,
+            "transcript is for another key"
+        );
+        ensure!(
+            self.commitments.commitments.len() == pk.w as usize,
+            "expected {} commitments",
+            pk.w
+        );
+        ensure!(
+            self.server_indices.iter().all(|idx| *idx < pk.l),
+            "server index out of range"
+        );
+        let points: Vec<u32> = self.server_indices.iter().map(|idx| idx + 1).collect();
+        util::check_evaluation_points(&points)?;
+        ensure!(
+            self.verification_keys.len() == points.len(),
+            "expected {} verification keys",
+            points.len()
+        );
+        let valid = par::map_collect(&points, |pos, point| {
+            self.commitments.evaluate(pk, *point).as_ref() == Some(&self.verification_keys[pos])
+        });
+        ensure!(
+            valid.iter().all(|valid| *valid),
+            "verification keys don't match the commitments"
+        );
+        Ok(())
+    }
+
+    /// Checks `share` against the key and its verification key in the transcript
+    pub fn verify_share(&self, share: &PrivateKeyShare) -> bool {
+        match share
+            .i
+            .checked_sub(1)
+            .and_then(|idx| self.verification_key(idx))
+        {
+            Some(vk) => share.verify(&self.public_key, &vk).is_ok(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DealingTranscript;
+    use crate::paillier::generate_key_pair;
+    use crate::wire::Versioned;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_dealing_transcript() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 4, 3).unwrap();
+        let (other_pk, _) = generate_key_pair(128, 4, 3).unwrap();
+        let (mut shares, transcript) = sk.share_audited(&[3, 0, 1], &mut rand);
+        transcript.verify(&pk).unwrap();
+        assert!(transcript.verify(&other_pk).is_err());
+        assert!(shares.iter().all(|share| transcript.verify_share(share)));
+        assert!(transcript.verification_key(2).is_none());
+        let vk = transcript.verification_key(3).unwrap();
+        shares[0].verify(&pk, &vk).unwrap();
+        assert!(shares[1].verify(&pk, &vk).is_err());
+        assert!(shares[0].verify(&other_pk, &vk).is_err());
+        assert_eq!(shares[0].key_fingerprint(), Some(&pk.fingerprint()));
+
+        let decoded =
+            DealingTranscript::from_versioned_bytes(&transcript.to_versioned_bytes()).unwrap();
+        assert_eq!(decoded, transcript);
+        assert_eq!(decoded.digest(), transcript.digest());
+
+        shares[0].si += 1;
+        assert!(!transcript.verify_share(&shares[0]));
+
+        let mut tampered = transcript.clone();
+        tampered.verification_keys.swap(0, 1);
+        assert!(tampered.verify(&pk).is_err());
+        assert_ne!(tampered.digest(), transcript.digest());
+        let mut tampered = transcript;
+        tampered.server_indices[0] = 1;
+        assert!(tampered.verify(&pk).is_err());
+    }
+}
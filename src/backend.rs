@@ -0,0 +1,300 @@
+//! Encryption-only big integer backends for the client side operations of paillier.
+//!
+//! The [`Arithmetic`] trait abstracts over the integer operations needed to encrypt
+//! and to compute on ciphertexts, and [`Encryptor`] implements these operations
+//! generically on top of it. Besides [`Gmp`], which is backed by `rug` like the rest
+//! of the crate, the pure-Rust `NumBigint` backend is available with the
+//! `num-bigint` feature.
+//!
+//! The backend does not cover key generation, decryption, threshold decryption or the
+//! proofs. These are implemented on `rug` directly and need GMP, so a pure-Rust build
+//! can encrypt for a committee but can't be one of its decryption servers.
+//! [`Arithmetic`] is sealed, only the backends of this module implement it, so it can
+//! grow with the operations of further backends without breaking changes.
+//!
+//! Without the default `gmp` feature this module is all the crate provides. Building
+//! with `--no-default-features --features num-bigint` thereby allows encrypting and
+//! adding ciphertexts on targets without GMP and openssl, like `wasm32-unknown-unknown`
+//! in the browser or musl and Windows cross builds of clients.
+//!
+//! Integers are exchanged between backends as unsigned big endian bytes, so a
+//! ciphertext produced by an [`Encryptor`] can be decrypted with the
+//! [`crate::paillier`] keys:
+//!
+//! ```
+//! # #[cfg(feature = "gmp")] {
+//! use pht_crypto::backend::{Encryptor, Gmp};
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::Ciphertext;
+//! use rug::Integer;
+//!
+//! let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+//! let key_shares = sk.share(&[0], &mut rug::rand::RandState::new());
+//!
+//! let enc = Encryptor::<Gmp>::from_modulus_bytes(&pk.modulus().to_digits(rug::integer::Order::Msf));
+//! let mut c = enc.encrypt(&Integer::from(20), &mut rand::thread_rng());
+//! enc.add(&mut c, &enc.encrypt(&Integer::from(22), &mut rand::thread_rng()));
+//!
+//! let c: Ciphertext = c.into();
+//! let share = key_shares[0].share_decrypt(&pk, c);
+//! assert_eq!(Integer::from(pk.share_combine(&[share]).unwrap()), 42);
+//! # }
+//! ```
+
+use rand::RngCore;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Integer arithmetic of a big integer backend, limited to what [`Encryptor`] needs.
+/// All values are non-negative.
+pub trait Arithmetic: private::Sealed {
+    type Int: Clone + Debug + Eq + Ord;
+
+    fn from_u64(x: u64) -> Self::Int;
+    fn from_bytes_be(bytes: &[u8]) -> Self::Int;
+    /// Unsigned big endian bytes without leading zeros
+    fn to_bytes_be(x: &Self::Int) -> Vec<u8>;
+    fn bits(x: &Self::Int) -> u64;
+
+    fn add(a: &Self::Int, b: &Self::Int) -> Self::Int;
+    fn mul(a: &Self::Int, b: &Self::Int) -> Self::Int;
+    fn rem(a: &Self::Int, m: &Self::Int) -> Self::Int;
+    fn pow_mod(base: &Self::Int, exp: &Self::Int, m: &Self::Int) -> Self::Int;
+    /// a^-1 mod m, or `None` if a is not invertible
+    fn invert_mod(a: &Self::Int, m: &Self::Int) -> Option<Self::Int>;
+
+    fn mul_mod(a: &Self::Int, b: &Self::Int, m: &Self::Int) -> Self::Int {
+        Self::rem(&Self::mul(a, b), m)
+    }
+
+    /// Uniformly random value in [0, bound) via rejection sampling
+    fn random_below(bound: &Self::Int, rng: &mut dyn RngCore) -> Self::Int {
+        let bits = Self::bits(bound);
+        let mut bytes = vec![0; bits.div_ceil(8) as usize];
+        loop {
+            rng.fill_bytes(&mut bytes);
+            if bits % 8 != 0 {
+                bytes[0] &= (1 << (bits % 8)) - 1;
+            }
+            let x = Self::from_bytes_be(&bytes);
+            if x.cmp(bound) == Ordering::Less {
+                break x;
+            }
+        }
+    }
+
+    /// Uniformly random value in Z*_m
+    fn random_in_mult_group(m: &Self::Int, rng: &mut dyn RngCore) -> Self::Int {
+        loop {
+            let x = Self::random_below(m, rng);
+            if Self::invert_mod(&x, m).is_some() {
+                break x;
+            }
+        }
+    }
+}
+
+/// Backend based on GMP via `rug`
+#[cfg(feature = "gmp")]
+#[derive(Debug, Clone, Copy)]
+pub struct Gmp;
+
+#[cfg(feature = "gmp")]
+impl private::Sealed for Gmp {}
+
+#[cfg(feature = "gmp")]
+impl Arithmetic for Gmp {
+    type Int = rug::Integer;
+
+    fn from_u64(x: u64) -> rug::Integer {
+        x.into()
+    }
+
+    fn from_bytes_be(bytes: &[u8]) -> rug::Integer {
+        rug::Integer::from_digits(bytes, rug::integer::Order::Msf)
+    }
+
+    fn to_bytes_be(x: &rug::Integer) -> Vec<u8> {
+        x.to_digits(rug::integer::Order::Msf)
+    }
+
+    fn bits(x: &rug::Integer) -> u64 {
+        x.significant_bits().into()
+    }
+
+    fn add(a: &rug::Integer, b: &rug::Integer) -> rug::Integer {
+        (a + b).into()
+    }
+
+    fn mul(a: &rug::Integer, b: &rug::Integer) -> rug::Integer {
+        (a * b).into()
+    }
+
+    fn rem(a: &rug::Integer, m: &rug::Integer) -> rug::Integer {
+        (a % m).into()
+    }
+
+    fn pow_mod(base: &rug::Integer, exp: &rug::Integer, m: &rug::Integer) -> rug::Integer {
+        base.pow_mod_ref(exp, m).unwrap().into()
+    }
+
+    fn invert_mod(a: &rug::Integer, m: &rug::Integer) -> Option<rug::Integer> {
+        a.invert_ref(m).map(Into::into)
+    }
+}
+
+/// Pure-Rust backend based on `num-bigint`
+#[cfg(feature = "num-bigint")]
+#[derive(Debug, Clone, Copy)]
+pub struct NumBigint;
+
+#[cfg(feature = "num-bigint")]
+impl private::Sealed for NumBigint {}
+
+#[cfg(feature = "num-bigint")]
+impl Arithmetic for NumBigint {
+    type Int = num_bigint::BigUint;
+
+    fn from_u64(x: u64) -> num_bigint::BigUint {
+        x.into()
+    }
+
+    fn from_bytes_be(bytes: &[u8]) -> num_bigint::BigUint {
+        num_bigint::BigUint::from_bytes_be(bytes)
+    }
+
+    fn to_bytes_be(x: &num_bigint::BigUint) -> Vec<u8> {
+        if x.bits() == 0 {
+            // BigUint encodes zero as a single zero byte
+            return Vec::new();
+        }
+        x.to_bytes_be()
+    }
+
+    fn bits(x: &num_bigint::BigUint) -> u64 {
+        x.bits()
+    }
+
+    fn add(a: &num_bigint::BigUint, b: &num_bigint::BigUint) -> num_bigint::BigUint {
+        a + b
+    }
+
+    fn mul(a: &num_bigint::BigUint, b: &num_bigint::BigUint) -> num_bigint::BigUint {
+        a * b
+    }
+
+    fn rem(a: &num_bigint::BigUint, m: &num_bigint::BigUint) -> num_bigint::BigUint {
+        a % m
+    }
+
+    fn pow_mod(
+        base: &num_bigint::BigUint,
+        exp: &num_bigint::BigUint,
+        m: &num_bigint::BigUint,
+    ) -> num_bigint::BigUint {
+        base.modpow(exp, m)
+    }
+
+    fn invert_mod(a: &num_bigint::BigUint, m: &num_bigint::BigUint) -> Option<num_bigint::BigUint> {
+        a.modinv(m)
+    }
+}
+
+/// Encryption and homomorphic operations under a paillier public key with modulus n
+/// and generator g = n + 1, generic over the integer backend.
+#[derive(Debug, Clone)]
+pub struct Encryptor<A: Arithmetic> {
+    n: A::Int,
+    n2: A::Int,
+}
+
+impl<A: Arithmetic> Encryptor<A> {
+    pub fn new(n: A::Int) -> Self {
+        let n2 = A::mul(&n, &n);
+        Self { n, n2 }
+    }
+
+    /// Creates the encryptor from the big endian bytes of the modulus as returned by
+    /// e.g. `PublicKey::modulus().to_digits(Order::Msf)`.
+    pub fn from_modulus_bytes(n: &[u8]) -> Self {
+        Self::new(A::from_bytes_be(n))
+    }
+
+    pub fn modulus(&self) -> &A::Int {
+        &self.n
+    }
+
+    /// Encrypts 0 <= m < n as (1 + m * n) * r^n mod n^2, using g^m = 1 + m * n mod n^2
+    pub fn encrypt(&self, m: &A::Int, rng: &mut dyn RngCore) -> A::Int {
+        let r = A::random_in_mult_group(&self.n, rng);
+        let gm = A::rem(&A::add(&A::mul(m, &self.n), &A::from_u64(1)), &self.n2);
+        A::mul_mod(&gm, &A::pow_mod(&r, &self.n, &self.n2), &self.n2)
+    }
+
+    pub fn reencrypt(&self, cipher: &mut A::Int, rng: &mut dyn RngCore) {
+        let r = A::random_in_mult_group(&self.n, rng);
+        *cipher = A::mul_mod(cipher, &A::pow_mod(&r, &self.n, &self.n2), &self.n2);
+    }
+
+    /// Homomorphically adds the plaintext of `other` to `cipher`
+    pub fn add(&self, cipher: &mut A::Int, other: &A::Int) {
+        *cipher = A::mul_mod(cipher, other, &self.n2);
+    }
+
+    pub fn add_plain(&self, cipher: &mut A::Int, plain: &A::Int) {
+        let gm = A::rem(&A::add(&A::mul(plain, &self.n), &A::from_u64(1)), &self.n2);
+        *cipher = A::mul_mod(cipher, &gm, &self.n2);
+    }
+
+    pub fn mul_plain(&self, cipher: &mut A::Int, plain: &A::Int) {
+        *cipher = A::pow_mod(cipher, plain, &self.n2);
+    }
+
+    /// Big endian bytes of `cipher`, e.g. to create a [`crate::Ciphertext`] from them
+    pub fn to_bytes(&self, cipher: &A::Int) -> Vec<u8> {
+        A::to_bytes_be(cipher)
+    }
+}
+
+#[cfg(all(test, feature = "gmp"))]
+mod tests {
+    use super::{Arithmetic, Encryptor, Gmp};
+    use crate::paillier::generate_key_pair;
+    use crate::Ciphertext;
+    use rug::integer::Order;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    fn sum_decrypts_to<A: Arithmetic>() {
+        let mut rand = RandState::new();
+        let mut rng = rand::thread_rng();
+        let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+
+        let enc = Encryptor::<A>::from_modulus_bytes(&pk.modulus().to_digits(Order::Msf));
+        let mut c = enc.encrypt(&A::from_u64(5), &mut rng);
+        enc.mul_plain(&mut c, &A::from_u64(7));
+        enc.add(&mut c, &enc.encrypt(&A::from_u64(3), &mut rng));
+        enc.add_plain(&mut c, &A::from_u64(4));
+        enc.reencrypt(&mut c, &mut rng);
+
+        let c: Ciphertext = Integer::from_digits(&enc.to_bytes(&c), Order::Msf).into();
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, c.clone()))
+            .collect();
+        let m: Integer = pk.share_combine(&shares).unwrap().into();
+        assert_eq!(m, 42);
+    }
+
+    #[test]
+    fn test_backends() {
+        sum_decrypts_to::<Gmp>();
+        #[cfg(feature = "num-bigint")]
+        sum_decrypts_to::<super::NumBigint>();
+    }
+}
@@ -0,0 +1,165 @@
+//! Conversions between this crate's integer types and the big integers of
+//! [`num-bigint`](https://docs.rs/num-bigint) (feature `num-bigint`) and
+//! [`crypto-bigint`](https://docs.rs/crypto-bigint) (feature `crypto-bigint`), so
+//! applications built on them don't need to convert through strings.
+//!
+//! Conversions go through the limbs of the integers and are therefore cheap.
+
+#[cfg(feature = "num-bigint")]
+mod num {
+    use crate::paillier::PublicKey;
+    use crate::{Ciphertext, Plaintext};
+    use anyhow::{ensure, Result};
+    use num_bigint::BigUint;
+    use rug::integer::Order;
+    use rug::Integer;
+
+    fn to_biguint(x: &Integer) -> Result<BigUint> {
+        ensure!(*x >= 0, "integer must not be negative");
+        Ok(BigUint::from_slice(&x.to_digits::<u32>(Order::Lsf)))
+    }
+
+    fn from_biguint(x: &BigUint) -> Integer {
+        Integer::from_digits(&x.to_u32_digits(), Order::Lsf)
+    }
+
+    macro_rules! impl_biguint {
+        ($($ty:ty)+) => {
+            $(
+                impl $ty {
+                    /// Converts into an unsigned integer. Fails if the value is negative.
+                    pub fn to_biguint(&self) -> Result<BigUint> {
+                        to_biguint(&self.val)
+                    }
+
+                    pub fn from_biguint(x: &BigUint) -> Self {
+                        from_biguint(x).into()
+                    }
+                }
+            )+
+        };
+    }
+
+    impl_biguint!(Ciphertext Plaintext);
+
+    impl PublicKey {
+        /// The modulus n as `BigUint`
+        pub fn modulus_biguint(&self) -> BigUint {
+            to_biguint(&self.n).expect("the modulus is positive")
+        }
+
+        /// Like [`PublicKey::from_modulus`] for a `BigUint` modulus
+        pub fn from_biguint_modulus(
+            n: &BigUint,
+            decryption_servers: u32,
+            threshold: u32,
+        ) -> Result<Self> {
+            PublicKey::from_modulus(from_biguint(n), decryption_servers, threshold)
+        }
+    }
+}
+
+#[cfg(feature = "crypto-bigint")]
+mod crypto {
+    use crate::paillier::PublicKey;
+    use crate::{Ciphertext, Plaintext};
+    use anyhow::{ensure, Result};
+    use crypto_bigint::{Uint, Word};
+    use rug::integer::Order;
+    use rug::Integer;
+
+    fn to_uint<const LIMBS: usize>(x: &Integer) -> Result<Uint<LIMBS>> {
+        ensure!(*x >= 0, "integer must not be negative");
+        let digits = x.to_digits::<Word>(Order::Lsf);
+        ensure!(
+            digits.len() <= LIMBS,
+            "integer does not fit into {} limbs",
+            LIMBS
+        );
+        let mut words = [0; LIMBS];
+        words[..digits.len()].copy_from_slice(&digits);
+        Ok(Uint::from_words(words))
+    }
+
+    fn from_uint<const LIMBS: usize>(x: &Uint<LIMBS>) -> Integer {
+        Integer::from_digits(x.as_words(), Order::Lsf)
+    }
+
+    macro_rules! impl_uint {
+        ($($ty:ty)+) => {
+            $(
+                impl $ty {
+                    /// Converts into a fixed size integer. Fails if the value doesn't fit.
+                    pub fn to_uint<const LIMBS: usize>(&self) -> Result<Uint<LIMBS>> {
+                        to_uint(&self.val)
+                    }
+
+                    pub fn from_uint<const LIMBS: usize>(x: &Uint<LIMBS>) -> Self {
+                        from_uint(x).into()
+                    }
+                }
+            )+
+        };
+    }
+
+    impl_uint!(Ciphertext Plaintext);
+
+    impl PublicKey {
+        /// The modulus n as fixed size integer. Fails if it doesn't fit.
+        pub fn modulus_uint<const LIMBS: usize>(&self) -> Result<Uint<LIMBS>> {
+            to_uint(&self.n)
+        }
+
+        /// Like [`PublicKey::from_modulus`] for a fixed size modulus
+        pub fn from_uint_modulus<const LIMBS: usize>(
+            n: &Uint<LIMBS>,
+            decryption_servers: u32,
+            threshold: u32,
+        ) -> Result<Self> {
+            PublicKey::from_modulus(from_uint(n), decryption_servers, threshold)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn test_num_bigint() {
+        use crate::paillier::{generate_key_pair, PublicKey};
+        use crate::{Ciphertext, Plaintext};
+        use rug::rand::RandState;
+        use rug::Integer;
+
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(256, 2, 1).unwrap();
+        let n = pk.modulus_biguint();
+        assert_eq!(n.to_str_radix(16), pk.modulus().to_string_radix(16));
+        assert_eq!(PublicKey::from_biguint_modulus(&n, 2, 1).unwrap(), pk);
+        let c = pk.encrypt(5.into(), &mut rand);
+        assert_eq!(
+            Ciphertext::from_biguint(&c.to_biguint().unwrap()).as_ref(),
+            c.as_ref()
+        );
+        assert!(Plaintext::from(Integer::from(-5)).to_biguint().is_err());
+    }
+
+    #[cfg(feature = "crypto-bigint")]
+    #[test]
+    fn test_crypto_bigint() {
+        use crate::paillier::{generate_key_pair, PublicKey};
+        use crate::Plaintext;
+        use crypto_bigint::U256;
+        use rug::Integer;
+
+        let (pk, _) = generate_key_pair(256, 1, 1).unwrap();
+        let n = pk.modulus_uint::<{ U256::LIMBS }>().unwrap();
+        assert_eq!(PublicKey::from_uint_modulus(&n, 1, 1).unwrap(), pk);
+        assert!(pk.modulus_uint::<1>().is_err());
+        let m = Plaintext::from(Integer::from(1) << 200);
+        assert_eq!(
+            Plaintext::from_uint(&m.to_uint::<{ U256::LIMBS }>().unwrap()),
+            m
+        );
+    }
+}
@@ -0,0 +1,229 @@
+//! Command line tool for key ceremonies and offline operations (feature `cli`).
+//!
+//! Keys, key shares and ciphertexts are stored in the versioned encoding of
+//! `pht_crypto::wire`, partial decryptions in the deterministic CBOR encoding of
+//! `pht_crypto::cbor`.
+
+use anyhow::{anyhow, ensure, Context, Result};
+use clap::{Parser, Subcommand};
+use pht_crypto::keygen::{KeygenParams, PrimeKind};
+use pht_crypto::paillier::{PartialDecryption, PrivateKey, PrivateKeyShare, PublicKey};
+use pht_crypto::wire::Versioned;
+use pht_crypto::Ciphertext;
+use rug::Integer;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "pht",
+    version,
+    about = "Threshold Paillier key ceremonies and operations"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Generates a key pair for `servers` decryption servers of which `threshold` are
+    /// needed to decrypt
+    Keygen {
+        #[arg(long, default_value_t = 2048)]
+        bits: usize,
+        #[arg(long)]
+        servers: u32,
+        #[arg(long)]
+        threshold: u32,
+        /// Use standard instead of safe primes, faster but only for testing
+        #[arg(long)]
+        insecure_standard_primes: bool,
+        #[arg(long)]
+        public: PathBuf,
+        #[arg(long)]
+        private: PathBuf,
+    },
+    /// Deals a key share to every decryption server, written as share-<index>.bin
+    Deal {
+        #[arg(long)]
+        private: PathBuf,
+        #[arg(long)]
+        out_dir: PathBuf,
+    },
+    /// Encrypts a decimal integer
+    Encrypt {
+        #[arg(long)]
+        public: PathBuf,
+        #[arg(long, allow_hyphen_values = true)]
+        value: String,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Homomorphically adds ciphertexts
+    Add {
+        #[arg(long)]
+        public: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+        #[arg(required = true)]
+        ciphertexts: Vec<PathBuf>,
+    },
+    /// Partially decrypts a ciphertext with a key share
+    PartialDecrypt {
+        #[arg(long)]
+        public: PathBuf,
+        #[arg(long)]
+        share: PathBuf,
+        #[arg(long)]
+        ciphertext: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Combines partial decryptions and prints the plaintext
+    Combine {
+        #[arg(long)]
+        public: PathBuf,
+        #[arg(required = true)]
+        partials: Vec<PathBuf>,
+    },
+    /// Prints the type and public parameters of a file
+    Inspect { file: PathBuf },
+}
+
+fn read(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).with_context(|| format!("can't read {}", path.display()))
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    fs::write(path, bytes).with_context(|| format!("can't write {}", path.display()))
+}
+
+fn load<T: Versioned>(path: &Path) -> Result<T> {
+    T::from_versioned_bytes(&read(path)?).with_context(|| format!("invalid {}", path.display()))
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Keygen {
+            bits,
+            servers,
+            threshold,
+            insecure_standard_primes,
+            public,
+            private,
+        } => {
+            let mut params = KeygenParams::new(bits, servers, threshold);
+            if insecure_standard_primes {
+                params = params.with_prime_kind(PrimeKind::Standard);
+            }
+            let (pk, sk) = params.generate()?;
+            write(&public, &pk.to_versioned_bytes())?;
+            write(&private, &sk.to_versioned_bytes())?;
+        }
+        Command::Deal { private, out_dir } => {
+            let sk: PrivateKey = load(&private)?;
+            fs::create_dir_all(&out_dir)?;
+            let points: Vec<u32> = (1..=sk.decryption_servers()).collect();
+            let shares = sk.share_at_points(&points, &mut pht_crypto::rng::secure())?;
+            for (idx, share) in shares.iter().enumerate() {
+                let path = out_dir.join(format!("share-{}.bin", idx));
+                write(&path, &share.to_versioned_bytes())?;
+            }
+        }
+        Command::Encrypt { public, value, out } => {
+            let pk: PublicKey = load(&public)?;
+            let m = Integer::from_str_radix(&value, 10)
+                .map_err(|_| anyhow!("{} is not a decimal integer", value))?;
+            let c = pk.encrypt_default(m.into());
+            write(&out, &c.to_versioned_bytes())?;
+        }
+        Command::Add {
+            public,
+            out,
+            ciphertexts,
+        } => {
+            let pk: PublicKey = load(&public)?;
+            let ciphers = ciphertexts
+                .iter()
+                .map(|path| load::<Ciphertext>(path))
+                .collect::<Result<Vec<_>>>()?;
+            let sum = pk.sum_encrypted(&ciphers);
+            write(&out, &sum.to_versioned_bytes())?;
+        }
+        Command::PartialDecrypt {
+            public,
+            share,
+            ciphertext,
+            out,
+        } => {
+            let pk: PublicKey = load(&public)?;
+            let share: PrivateKeyShare = load(&share)?;
+            let c: Ciphertext = load(&ciphertext)?;
+            write(&out, &share.share_decrypt(&pk, c).to_cbor())?;
+        }
+        Command::Combine { public, partials } => {
+            let pk: PublicKey = load(&public)?;
+            let partials = partials
+                .iter()
+                .map(|path| {
+                    PartialDecryption::from_cbor(&read(path)?)
+                        .with_context(|| format!("invalid {}", path.display()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let m: Integer = pk.share_combine(&partials)?.into();
+            println!("{}", m);
+        }
+        Command::Inspect { file } => println!("{}", inspect(&read(&file)?)?),
+    }
+    Ok(())
+}
+
+fn inspect(bytes: &[u8]) -> Result<String> {
+    if let Ok(pk) = PublicKey::from_versioned_bytes(bytes) {
+        return Ok(format!(
+            "public key: {} bit modulus, {} of {} servers, fingerprint {}",
+            pk.bit_length(),
+            pk.threshold(),
+            pk.decryption_servers(),
+            hex(&pk.fingerprint())
+        ));
+    }
+    if let Ok(sk) = PrivateKey::from_versioned_bytes(bytes) {
+        return Ok(format!(
+            "private key: {} of {} servers",
+            sk.threshold(),
+            sk.decryption_servers()
+        ));
+    }
+    if let Ok(share) = PrivateKeyShare::from_versioned_bytes(bytes) {
+        let key = match share.key_fingerprint() {
+            Some(fingerprint) => format!("fingerprint {}", hex(fingerprint)),
+            None => "unknown key".to_string(),
+        };
+        return Ok(format!(
+            "private key share: server {}, {}",
+            share.index(),
+            key
+        ));
+    }
+    if let Ok(c) = Ciphertext::from_versioned_bytes(bytes) {
+        return Ok(format!(
+            "ciphertext: {} bits",
+            c.as_ref().significant_bits()
+        ));
+    }
+    if PartialDecryption::from_cbor_legacy(bytes).is_ok() {
+        return Ok("partial decryption".to_string());
+    }
+    ensure!(bytes.starts_with(b"PHT"), "not a pht-crypto file");
+    Err(anyhow!("unsupported version or type of pht-crypto file"))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn main() -> Result<()> {
+    run(Cli::parse().command)
+}
@@ -0,0 +1,160 @@
+//! Outsourced decryption of masked values.
+//!
+//! [`PublicKey::blind`] adds a uniformly random mask ρ mod n to the plaintext of a
+//! ciphertext and re-randomizes it. The decryption committee then decrypts m + ρ,
+//! which is independent of m, and only the holder of the [`BlindingFactor`] recovers
+//! m with [`PublicKey::unblind`].
+//!
+//! Multiplicative blinding tests two ciphertexts for equality without revealing
+//! their plaintexts: [`PublicKey::blind_difference`] yields Enc(r * (a - b)) for a
+//! random unit r, which decrypts to 0 if a = b and to a uniformly random unit
+//! otherwise, as long as a - b is invertible mod n, which holds unless it reveals a
+//! factor of n. Since r is discarded, not even the requester learns a - b.
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+//! let key_shares = sk.share(&[0, 1], &mut rand);
+//!
+//! let c = pk.encrypt(42.into(), &mut rand);
+//! let (blinded, factor) = pk.blind(&c, &mut rand);
+//! let partials: Vec<_> = key_shares
+//!     .iter()
+//!     .map(|share| share.share_decrypt(&pk, blinded.clone()))
+//!     .collect();
+//! let masked = pk.share_combine(&partials).unwrap();
+//! assert_eq!(pk.unblind(&masked, &factor), 42);
+//!
+//! let d = pk.blind_difference(&c, &pk.encrypt(42.into(), &mut rand), &mut rand);
+//! let partials: Vec<_> = key_shares
+//!     .iter()
+//!     .map(|share| share.share_decrypt(&pk, d.clone()))
+//!     .collect();
+//! assert!(pk.share_combine_equality(&partials).unwrap());
+//! ```
+
+use crate::paillier::{PartialDecryption, PublicKey};
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::{Ciphertext, Plaintext};
+use anyhow::Result;
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// The mask ρ added by [`PublicKey::blind`]. It must be kept secret from the
+/// decryption committee.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlindingFactor {
+    #[serde(with = "crate::util::serde_integer")]
+    rho: Integer,
+}
+
+impl PublicKey {
+    /// Enc(m + ρ) for the plaintext m of `cipher` and a uniformly random ρ mod n,
+    /// re-randomized so it can't be linked to `cipher`
+    pub fn blind(
+        &self,
+        cipher: &Ciphertext,
+        rand: &mut dyn MutRandState,
+    ) -> (Ciphertext, BlindingFactor) {
+        let rho = Integer::from(self.n.random_below_ref(rand));
+        let blinded = self.affine_randomized(cipher, &1.into(), &rho.clone().into(), rand);
+        (blinded, BlindingFactor { rho })
+    }
+
+    /// Removes the mask from the decryption of a blinded ciphertext
+    pub fn unblind(&self, plaintext: &Plaintext, factor: &BlindingFactor) -> Plaintext {
+        Integer::from(plaintext.as_ref() - &factor.rho)
+            .modulo(&self.n)
+            .into()
+    }
+
+    /// Enc(r * (a - b)) for the plaintexts a and b of the ciphertexts and a fresh
+    /// random unit r, which is discarded. Decrypts to 0 iff a = b.
+    pub fn blind_difference(
+        &self,
+        cipher1: &Ciphertext,
+        cipher2: &Ciphertext,
+        rand: &mut dyn MutRandState,
+    ) -> Ciphertext {
+        let mut diff = self.affine(cipher2, &(-1).into(), &0.into());
+        self.add_encrypted(&mut diff, cipher1);
+        self.blind_multiplicative(&diff, rand)
+    }
+
+    /// Enc(r * m) for the plaintext m of `cipher` and a fresh random unit r,
+    /// re-randomized. If every committee member applies it to the output of
+    /// [`PublicKey::blind_difference`] in turn before decrypting, the equality test
+    /// stays private even if the requester chose r dishonestly.
+    pub fn blind_multiplicative(
+        &self,
+        cipher: &Ciphertext,
+        rand: &mut dyn MutRandState,
+    ) -> Ciphertext {
+        let r = random_in_mult_group(&self.n, UnitCheck::Gcd, rand);
+        self.affine_randomized(cipher, &r.into(), &0.into(), rand)
+    }
+
+    /// Combines the partial decryptions of a blinded difference and returns whether
+    /// the compared ciphertexts encrypt equal values
+    pub fn share_combine_equality(&self, shares: &[PartialDecryption]) -> Result<bool> {
+        Ok(*self.share_combine(shares)?.as_ref() == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_blind_unblind() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let c = pk.encrypt(7.into(), &mut rand);
+        let (blinded1, factor1) = pk.blind(&c, &mut rand);
+        let (blinded2, factor2) = pk.blind(&c, &mut rand);
+        let masked1 = sk.decrypt(&blinded1);
+        let masked2 = sk.decrypt(&blinded2);
+        assert_ne!(masked1, masked2);
+        assert_eq!(pk.unblind(&masked1, &factor1), 7);
+        assert_eq!(pk.unblind(&masked2, &factor2), 7);
+
+        let max = pk.encrypt(Integer::from(pk.modulus() - 1).into(), &mut rand);
+        let (blinded, factor) = pk.blind(&max, &mut rand);
+        let unblinded = pk.unblind(&sk.decrypt(&blinded), &factor);
+        assert_eq!(Integer::from(unblinded), pk.modulus().clone() - 1);
+    }
+
+    #[test]
+    fn test_equality() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 2, 2).unwrap();
+        let key_shares = sk.clone().share(&[0, 1], &mut rand);
+        let a = pk.encrypt(5.into(), &mut rand);
+        let b = pk.encrypt(5.into(), &mut rand);
+        let c = pk.encrypt(6.into(), &mut rand);
+        let equal = |d: &crate::Ciphertext| {
+            let partials: Vec<_> = key_shares
+                .iter()
+                .map(|share| share.share_decrypt(&pk, d.clone()))
+                .collect();
+            pk.share_combine_equality(&partials).unwrap()
+        };
+        assert!(equal(&pk.blind_difference(&a, &b, &mut rand)));
+        assert!(!equal(&pk.blind_difference(&a, &c, &mut rand)));
+        assert!(!equal(&pk.blind_difference(&c, &a, &mut rand)));
+
+        let d = pk.blind_difference(&a, &c, &mut rand);
+        let reblinded = pk.blind_multiplicative(&d, &mut rand);
+        assert_ne!(sk.decrypt(&d), sk.decrypt(&reblinded));
+        assert!(equal(&pk.blind_multiplicative(
+            &pk.blind_difference(&b, &a, &mut rand),
+            &mut rand
+        )));
+    }
+}
@@ -0,0 +1,121 @@
+//! Overflow tracking for Paillier ciphertexts.
+//!
+//! Homomorphic operations silently wrap modulo n, so a sum that exceeds n decrypts to
+//! a wrong but plausible result. A [`BoundedCiphertext`] carries an upper bound on its
+//! non-negative plaintext and refuses any operation after which the bound could reach n.
+
+use crate::paillier::{PartialDecryption, PublicKey};
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoundedCiphertext {
+    cipher: Ciphertext,
+    /// Inclusive upper bound on the plaintext
+    #[serde(with = "crate::util::serde_integer")]
+    bound: Integer,
+}
+
+impl BoundedCiphertext {
+    /// Encrypts `m` which must be in [0, n) with bound m
+    pub fn encrypt(pk: &PublicKey, m: Plaintext, rand: &mut dyn MutRandState) -> Result<Self> {
+        let bound = m.as_ref().clone();
+        ensure!(bound >= 0 && bound < pk.n, "plaintext must be in [0, n)");
+        Ok(Self {
+            cipher: pk.encrypt(m, rand),
+            bound,
+        })
+    }
+
+    /// Wraps a ciphertext whose plaintext is known to be in [0, bound]
+    pub fn from_ciphertext(pk: &PublicKey, cipher: Ciphertext, bound: Integer) -> Result<Self> {
+        ensure!(bound >= 0 && bound < pk.n, "bound must be in [0, n)");
+        Ok(Self { cipher, bound })
+    }
+
+    pub fn cipher(&self) -> &Ciphertext {
+        &self.cipher
+    }
+
+    pub fn bound(&self) -> &Integer {
+        &self.bound
+    }
+
+    pub fn into_ciphertext(self) -> Ciphertext {
+        self.cipher
+    }
+
+    pub fn add(&mut self, pk: &PublicKey, other: &BoundedCiphertext) -> Result<()> {
+        let bound = Integer::from(&self.bound + &other.bound);
+        ensure!(bound < pk.n, "sum could exceed n");
+        pk.add_encrypted(&mut self.cipher, &other.cipher);
+        self.bound = bound;
+        Ok(())
+    }
+
+    /// Adds the non-negative `plain`
+    pub fn add_plain(&mut self, pk: &PublicKey, plain: &Plaintext) -> Result<()> {
+        ensure!(*plain >= 0, "plaintext must not be negative");
+        let bound = Integer::from(&self.bound + plain.as_ref());
+        ensure!(bound < pk.n, "sum could exceed n");
+        pk.add_plain(&mut self.cipher, plain);
+        self.bound = bound;
+        Ok(())
+    }
+
+    /// Multiplies with the non-negative `plain`
+    pub fn mul_plain(&mut self, pk: &PublicKey, plain: &Plaintext) -> Result<()> {
+        ensure!(*plain >= 0, "plaintext must not be negative");
+        let bound = Integer::from(&self.bound * plain.as_ref());
+        ensure!(bound < pk.n, "product could exceed n");
+        pk.mul_plain(&mut self.cipher, plain);
+        self.bound = bound;
+        Ok(())
+    }
+
+    pub fn reencrypt(&mut self, pk: &PublicKey, rand: &mut dyn MutRandState) {
+        pk.reencrypt(&mut self.cipher, rand)
+    }
+
+    /// Combines partial decryptions of this ciphertext and checks the result against
+    /// the bound.
+    pub fn share_combine(
+        &self,
+        pk: &PublicKey,
+        partials: &[PartialDecryption],
+    ) -> Result<Plaintext> {
+        let m = pk.share_combine(partials)?;
+        ensure!(*m.as_ref() <= self.bound, "plaintext exceeds its bound");
+        Ok(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BoundedCiphertext;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_overflow_is_detected() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+        let half = Integer::from(&pk.n >> 1);
+
+        let mut c = BoundedCiphertext::encrypt(&pk, half.clone().into(), &mut rand).unwrap();
+        let c2 = c.clone();
+        c.add_plain(&pk, &1.into()).unwrap();
+        assert!(c.add(&pk, &c2).is_err());
+        assert!(c.mul_plain(&pk, &2.into()).is_err());
+        assert_eq!(*c.bound(), Integer::from(&half + 1));
+
+        let partial = key_share.share_decrypt(&pk, c.cipher().clone());
+        assert_eq!(c.share_combine(&pk, &[partial]).unwrap(), half + 1);
+        assert!(BoundedCiphertext::encrypt(&pk, pk.n.clone().into(), &mut rand).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Encryption of arbitrary byte strings with threshold Paillier.
+//!
+//! The payload is prefixed with its length as a big endian u64, padded with zeros and
+//! split into blocks of (|n| - 1) / 8 bytes. Each block is interpreted as a big endian
+//! integer, which is therefore less than n, and encrypted separately.
+//!
+//! ```
+//! use pht_crypto::bytes::{decode_bytes, encrypt_bytes};
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 1, 1).unwrap();
+//! let key_share = sk.share(&[0], &mut rand).remove(0);
+//!
+//! let blob = encrypt_bytes(&pk, b"patient-4711", &mut rand);
+//! let partials = vec![blob.share_decrypt(&pk, &key_share)];
+//! assert_eq!(blob.combine(&pk, &partials).unwrap(), b"patient-4711");
+//! ```
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::{Ciphertext, Plaintext};
+use anyhow::{anyhow, ensure, Result};
+use rug::integer::Order;
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+const LENGTH_BYTES: usize = 8;
+
+/// Encrypted blocks of a byte string
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CiphertextBlob {
+    blocks: Vec<Ciphertext>,
+}
+
+/// Number of payload bytes per plaintext block for `pk`
+pub fn block_size(pk: &PublicKey) -> usize {
+    (pk.n.significant_bits() as usize - 1) / 8
+}
+
+/// Encodes `bytes` into length framed plaintext blocks
+pub fn encode_bytes(pk: &PublicKey, bytes: &[u8]) -> Vec<Plaintext> {
+    let block_size = block_size(pk);
+    let mut framed = Vec::with_capacity(LENGTH_BYTES + bytes.len());
+    framed.extend_from_slice(&(bytes.len() as u64).to_be_bytes());
+    framed.extend_from_slice(bytes);
+    framed
+        .chunks(block_size)
+        .map(|chunk| {
+            let mut block = chunk.to_vec();
+            block.resize(block_size, 0);
+            Integer::from_digits(&block, Order::MsfBe).into()
+        })
+        .collect()
+}
+
+/// Inverse of [`encode_bytes`]
+pub fn decode_bytes(pk: &PublicKey, blocks: &[Plaintext]) -> Result<Vec<u8>> {
+    let block_size = block_size(pk);
+    let mut framed = Vec::with_capacity(blocks.len() * block_size);
+    for block in blocks {
+        let block = block.as_ref();
+        ensure!(
+            *block >= 0 && block.significant_bits() as usize <= block_size * 8,
+            "block is out of range"
+        );
+        let digits = block.to_digits::<u8>(Order::MsfBe);
+        framed.resize(framed.len() + block_size - digits.len(), 0);
+        framed.extend_from_slice(&digits);
+    }
+    ensure!(framed.len() >= LENGTH_BYTES, "missing length prefix");
+    let len = u64::from_be_bytes(framed[..LENGTH_BYTES].try_into().unwrap());
+    let end = (len as usize)
+        .checked_add(LENGTH_BYTES)
+        .filter(|end| *end <= framed.len())
+        .ok_or_else(|| anyhow!("length prefix exceeds the payload"))?;
+    ensure!(
+        framed.len() - end < block_size,
+        "payload has superfluous blocks"
+    );
+    Ok(framed[LENGTH_BYTES..end].to_vec())
+}
+
+pub fn encrypt_bytes(pk: &PublicKey, bytes: &[u8], rand: &mut dyn MutRandState) -> CiphertextBlob {
+    let blocks = encode_bytes(pk, bytes)
+        .into_iter()
+        .map(|block| pk.encrypt(block, rand))
+        .collect();
+    CiphertextBlob { blocks }
+}
+
+impl CiphertextBlob {
+    pub fn blocks(&self) -> &[Ciphertext] {
+        &self.blocks
+    }
+
+    /// Partially decrypts all blocks with a server's key share
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> Vec<PartialDecryption> {
+        self.blocks
+            .iter()
+            .map(|block| key_share.share_decrypt(pk, block.clone()))
+            .collect()
+    }
+
+    /// Combines the partial decryptions of at least w servers, one vector per server,
+    /// and decodes the payload.
+    pub fn combine(&self, pk: &PublicKey, partials: &[Vec<PartialDecryption>]) -> Result<Vec<u8>> {
+        ensure!(
+            partials.iter().all(|p| p.len() == self.blocks.len()),
+            "partial decryptions don't match the number of blocks"
+        );
+        let blocks = (0..self.blocks.len())
+            .map(|k| {
+                let shares: Vec<_> = partials.iter().map(|p| p[k].clone()).collect();
+                pk.share_combine(&shares)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        decode_bytes(pk, &blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{block_size, decode_bytes, encode_bytes};
+    use crate::paillier::generate_key_pair;
+
+    #[test]
+    fn test_encode_decode() {
+        let (pk, _) = generate_key_pair(256, 1, 1).unwrap();
+        let block_size = block_size(&pk);
+        for len in [0, 1, block_size - 8, block_size - 7, 3 * block_size + 5] {
+            let bytes: Vec<u8> = (0..len).map(|i| (i * 7 + 1) as u8).collect();
+            let blocks = encode_bytes(&pk, &bytes);
+            assert!(blocks.iter().all(|b| *b < pk.n));
+            assert_eq!(decode_bytes(&pk, &blocks).unwrap(), bytes);
+        }
+        let mut blocks = encode_bytes(&pk, b"abc");
+        blocks.push(0.into());
+        assert!(decode_bytes(&pk, &blocks).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+//! Deterministic CBOR ([RFC 8949](https://www.rfc-editor.org/rfc/rfc8949)) encoding
+//! of ciphertexts and partial decryptions for constrained clients.
+//!
+//! Values are wrapped in a CBOR tag identifying their type and follow the core
+//! deterministic encoding requirements: all heads use the shortest form, lengths are
+//! definite and integers are big endian byte strings without leading zeros. Every
+//! value thus has exactly one encoding, which makes the output suitable as payload of
+//! a signed COSE envelope. Decoding rejects any other encoding of a value.
+//!
+//! | Type                  | Encoding                                       |
+//! |-----------------------|------------------------------------------------|
+//! | [`Ciphertext`]        | `#6.1346917376(bstr)`                          |
+//! | [`PartialDecryption`] | `#6.1346917377([id: uint, bstr, digest, fpr])` |
+//!
+//! `digest` and `fpr` are the 32 byte [`PartialDecryption::cipher_digest`] and
+//! [`PartialDecryption::key_fingerprint`], or null if unknown.
+//! [`PartialDecryption::from_cbor`] rejects partial decryptions without them,
+//! [`PartialDecryption::from_cbor_legacy`] also accepts them and the previous
+//! encoding `#6.1346917377([id: uint, bstr])`.
+
+use crate::paillier::PartialDecryption;
+use crate::Ciphertext;
+use anyhow::{bail, ensure, Result};
+use rug::integer::Order;
+use rug::Integer;
+
+/// CBOR tag of an encoded [`Ciphertext`] (`"PHT\x00"` as big endian u32)
+pub const CIPHERTEXT_TAG: u64 = 0x5048_5400;
+/// CBOR tag of an encoded [`PartialDecryption`]
+pub const PARTIAL_DECRYPTION_TAG: u64 = 0x5048_5401;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_TAG: u8 = 6;
+const MAJOR_SIMPLE: u8 = 7;
+const SIMPLE_NULL: u8 = 22;
+
+/// Writes a head with the shortest possible encoding of `val`
+fn write_head(out: &mut Vec<u8>, major: u8, val: u64) {
+    let major = major << 5;
+    if val < 24 {
+        out.push(major | val as u8);
+    } else if val <= u8::MAX as u64 {
+        out.extend_from_slice(&[major | 24, val as u8]);
+    } else if val <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(val as u16).to_be_bytes());
+    } else if val <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(val as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&val.to_be_bytes());
+    }
+}
+
+fn write_integer(out: &mut Vec<u8>, val: &Integer) {
+    let bytes = val.to_digits::<u8>(Order::Msf);
+    write_head(out, MAJOR_BYTES, bytes.len() as u64);
+    out.extend_from_slice(&bytes);
+}
+
+fn write_digest(out: &mut Vec<u8>, digest: Option<&[u8; 32]>) {
+    match digest {
+        Some(digest) => {
+            write_head(out, MAJOR_BYTES, 32);
+            out.extend_from_slice(digest);
+        }
+        None => write_head(out, MAJOR_SIMPLE, SIMPLE_NULL as u64),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.bytes.len() >= len, "unexpected end of input");
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    /// Reads a head of the `expected` major type, rejecting non-shortest forms
+    fn head(&mut self, expected: u8) -> Result<u64> {
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        ensure!(
+            major == expected,
+            "expected major type {}, found {}",
+            expected,
+            major
+        );
+        let (val, min) = match info {
+            0..=23 => return Ok(info as u64),
+            24 => (self.take(1)?[0] as u64, 24),
+            25 => {
+                let mut buf = [0; 2];
+                buf.copy_from_slice(self.take(2)?);
+                (u16::from_be_bytes(buf) as u64, 1 << 8)
+            }
+            26 => {
+                let mut buf = [0; 4];
+                buf.copy_from_slice(self.take(4)?);
+                (u32::from_be_bytes(buf) as u64, 1 << 16)
+            }
+            27 => {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(self.take(8)?);
+                (u64::from_be_bytes(buf), 1 << 32)
+            }
+            _ => bail!("indefinite or reserved length encoding"),
+        };
+        ensure!(val >= min, "non-canonical head encoding");
+        Ok(val)
+    }
+
+    fn integer(&mut self) -> Result<Integer> {
+        let len = self.head(MAJOR_BYTES)?;
+        let bytes = self.take(len as usize)?;
+        ensure!(bytes.first() != Some(&0), "integer with leading zero bytes");
+        Ok(Integer::from_digits(bytes, Order::Msf))
+    }
+
+    /// Reads a 32 byte string or null
+    fn digest(&mut self) -> Result<Option<[u8; 32]>> {
+        if self.bytes.first() == Some(&(MAJOR_SIMPLE << 5 | SIMPLE_NULL)) {
+            self.take(1)?;
+            return Ok(None);
+        }
+        ensure!(self.head(MAJOR_BYTES)? == 32, "expected a 32 byte digest");
+        let mut digest = [0; 32];
+        digest.copy_from_slice(self.take(32)?);
+        Ok(Some(digest))
+    }
+
+    fn tag(&mut self, expected: u64) -> Result<()> {
+        let tag = self.head(MAJOR_TAG)?;
+        ensure!(tag == expected, "expected tag {}, found {}", expected, tag);
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<()> {
+        ensure!(self.bytes.is_empty(), "trailing bytes after value");
+        Ok(())
+    }
+}
+
+impl Ciphertext {
+    /// Deterministic CBOR encoding of the ciphertext
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_head(&mut out, MAJOR_TAG, CIPHERTEXT_TAG);
+        write_integer(&mut out, &self.val);
+        out
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+        reader.tag(CIPHERTEXT_TAG)?;
+        let val = reader.integer()?;
+        reader.finish()?;
+        Ok(val.into())
+    }
+}
+
+impl PartialDecryption {
+    /// Deterministic CBOR encoding of the partial decryption
+    pub fn to_cbor(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_head(&mut out, MAJOR_TAG, PARTIAL_DECRYPTION_TAG);
+        write_head(&mut out, MAJOR_ARRAY, 4);
+        write_head(&mut out, MAJOR_UINT, self.id as u64);
+        write_integer(&mut out, &self.val);
+        write_digest(&mut out, self.cipher_digest.as_ref());
+        write_digest(&mut out, self.key_fingerprint.as_ref());
+        out
+    }
+
+    /// Decodes a partial decryption, which must carry its ciphertext digest and key
+    /// fingerprint
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self> {
+        let share = Self::from_cbor_legacy(bytes)?;
+        ensure!(
+            share.cipher_digest.is_some() && share.key_fingerprint.is_some(),
+            "partial decryption without ciphertext digest or key fingerprint"
+        );
+        Ok(share)
+    }
+
+    /// Decodes a partial decryption which may lack its ciphertext digest and key
+    /// fingerprint, also in the previous encoding without them
+    pub fn from_cbor_legacy(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+        reader.tag(PARTIAL_DECRYPTION_TAG)?;
+        let len = reader.head(MAJOR_ARRAY)?;
+        ensure!(len == 2 || len == 4, "expected array of length 4");
+        let id = reader.head(MAJOR_UINT)?;
+        ensure!(
+            id > 0 && id <= u32::MAX as u64,
+            "share id {} out of range",
+            id
+        );
+        let val = reader.integer()?;
+        let (cipher_digest, key_fingerprint) = if len == 4 {
+            (reader.digest()?, reader.digest()?)
+        } else {
+            (None, None)
+        };
+        reader.finish()?;
+        Ok(PartialDecryption {
+            val,
+            id: id as u32,
+            cipher_digest,
+            key_fingerprint,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paillier::{generate_key_pair, PartialDecryption};
+    use crate::Ciphertext;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_golden_vectors() {
+        let cipher: Ciphertext = Integer::from(0x0102_0304).into();
+        let encoded = [0xda, 0x50, 0x48, 0x54, 0x00, 0x44, 0x01, 0x02, 0x03, 0x04];
+        assert_eq!(cipher.to_cbor(), encoded);
+        assert_eq!(
+            Ciphertext::from_cbor(&encoded).unwrap().as_ref(),
+            cipher.as_ref()
+        );
+
+        let mut share = PartialDecryption {
+            val: Integer::from(0xff),
+            id: 300,
+            cipher_digest: None,
+            key_fingerprint: None,
+        };
+        let encoded = [
+            0xda, 0x50, 0x48, 0x54, 0x01, 0x84, 0x19, 0x01, 0x2c, 0x41, 0xff, 0xf6, 0xf6,
+        ];
+        assert_eq!(share.to_cbor(), encoded);
+        assert!(PartialDecryption::from_cbor(&encoded).is_err());
+        let decoded = PartialDecryption::from_cbor_legacy(&encoded).unwrap();
+        assert_eq!((decoded.id, decoded.val), (300, Integer::from(0xff)));
+        let legacy = [
+            0xda, 0x50, 0x48, 0x54, 0x01, 0x82, 0x19, 0x01, 0x2c, 0x41, 0xff,
+        ];
+        assert!(PartialDecryption::from_cbor(&legacy).is_err());
+        let decoded = PartialDecryption::from_cbor_legacy(&legacy).unwrap();
+        assert_eq!((decoded.id, decoded.cipher_digest), (300, None));
+
+        share.cipher_digest = Some([1; 32]);
+        share.key_fingerprint = Some([2; 32]);
+        let mut encoded = vec![
+            0xda, 0x50, 0x48, 0x54, 0x01, 0x84, 0x19, 0x01, 0x2c, 0x41, 0xff,
+        ];
+        encoded.extend([0x58, 0x20].iter().chain(&[1; 32]));
+        encoded.extend([0x58, 0x20].iter().chain(&[2; 32]));
+        assert_eq!(share.to_cbor(), encoded);
+        let decoded = PartialDecryption::from_cbor(&encoded).unwrap();
+        assert_eq!(decoded.cipher_digest, Some([1; 32]));
+        assert_eq!(decoded.key_fingerprint, Some([2; 32]));
+
+        // non-canonical encodings of the same values
+        let long_head = [0xda, 0x50, 0x48, 0x54, 0x00, 0x58, 0x04, 1, 2, 3, 4];
+        assert!(Ciphertext::from_cbor(&long_head).is_err());
+        let leading_zero = [0xda, 0x50, 0x48, 0x54, 0x00, 0x45, 0, 1, 2, 3, 4];
+        assert!(Ciphertext::from_cbor(&leading_zero).is_err());
+        let mut trailing = cipher.to_cbor();
+        trailing.push(0);
+        assert!(Ciphertext::from_cbor(&trailing).is_err());
+        assert!(PartialDecryption::from_cbor(&cipher.to_cbor()).is_err());
+    }
+
+    #[test]
+    fn test_cbor_decrypt() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+        let cipher = pk.encrypt(42.into(), &mut rand);
+        let cipher = Ciphertext::from_cbor(&cipher.to_cbor()).unwrap();
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, cipher.clone()).to_cbor())
+            .map(|bytes| PartialDecryption::from_cbor(&bytes).unwrap())
+            .collect();
+        let m: Integer = pk.share_combine(&shares).unwrap().into();
+        assert_eq!(m, 42);
+    }
+}
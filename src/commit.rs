@@ -0,0 +1,213 @@
+//! Commitment schemes for protocols layered on the crate.
+//!
+//! [`PedersenCommitment`]s to integers are s^x * t^r mod N̂ under
+//! [`RingPedersenParams`]. They are computationally binding for anyone who doesn't
+//! know the factors of N̂ or the discrete logarithm of s to the base t, statistically
+//! hiding and additively homomorphic. [`RingPedersenParams::from_private_key`] derives
+//! parameters from the modulus of a paillier key, so the holder of the private key can
+//! publish them without a separate key generation. It must not commit under them
+//! itself.
+//!
+//! [`HashCommitment`]s to byte strings are SHA3-256(label || nonce || message) with a
+//! random 32 byte nonce, e.g. for commit-then-reveal rounds.
+//!
+//! ```
+//! use pht_crypto::commit::{commit_hash, commit_pedersen};
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::proofs::RingPedersenParams;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (_, sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let params = RingPedersenParams::from_private_key(&sk, &mut rand).unwrap();
+//! let (a, open_a) = commit_pedersen(&params, &3.into(), &mut rand).unwrap();
+//! let (b, open_b) = commit_pedersen(&params, &4.into(), &mut rand).unwrap();
+//! let sum = a.add(&b, &params);
+//! assert!(sum.verify(&params, &open_a.add(&open_b)));
+//!
+//! let (c, nonce) = commit_hash(b"reveal me later", &mut rand);
+//! assert!(c.verify(b"reveal me later", &nonce));
+//! ```
+
+use crate::paillier::PrivateKey;
+use crate::proofs::RingPedersenParams;
+use crate::rand::{random_bytes, random_in_mult_group, UnitCheck, MILLER_RABIN_ROUNDS};
+use anyhow::{anyhow, ensure, Result};
+use rug::integer::IsPrime;
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::convert::TryInto;
+use subtle::ConstantTimeEq;
+
+/// Statistical hiding parameter of the Pedersen commitments
+pub const HIDING_BITS: u32 = 128;
+
+const HASH_LABEL: &[u8] = b"pht-crypto/commit/hash";
+
+/// Commitment s^x * t^r mod N̂ to an integer x
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct PedersenCommitment {
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+}
+
+/// The committed value and randomness of a [`PedersenCommitment`]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PedersenOpening {
+    #[serde(with = "crate::util::serde_integer")]
+    pub value: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub randomness: Integer,
+}
+
+/// SHA3-256 commitment to a byte string
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct HashCommitment([u8; 32]);
+
+impl RingPedersenParams {
+    /// Derives parameters with N̂ = n from the paillier key `sk`, which must know the
+    /// factors of its modulus, see [`PrivateKey::factors`], and these must be safe
+    /// primes. Anyone knowing `sk` can open commitments to any value.
+    pub fn from_private_key(sk: &PrivateKey, rand: &mut dyn MutRandState) -> Result<Self> {
+        let factors = sk
+            .factors()
+            .ok_or_else(|| anyhow!("private key does not know the factors of its modulus"))?;
+        let is_prime = |x: &Integer| x.is_probably_prime(MILLER_RABIN_ROUNDS) != IsPrime::No;
+        let (p1, q1) = (factors.p.clone() >> 1, factors.q.clone() >> 1);
+        ensure!(
+            is_prime(&factors.p) && is_prime(&factors.q) && is_prime(&p1) && is_prime(&q1),
+            "modulus is not a product of safe primes"
+        );
+        // the squares in Z*_n have order m = p'q'
+        let order = p1 * q1;
+        let mut t = random_in_mult_group(&sk.n, UnitCheck::Gcd, rand);
+        t.square_mut();
+        t %= &sk.n;
+        let lambda = Integer::from(order.random_below_ref(rand));
+        let s = t.clone().pow_mod(&lambda, &sk.n).unwrap();
+        let params = Self {
+            n_hat: sk.n.clone(),
+            s,
+            t,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+}
+
+/// Commits to `value` with randomness from [0, N̂ * 2^HIDING_BITS). Fails if the
+/// `params` are invalid, see [`RingPedersenParams::validate`].
+pub fn commit_pedersen(
+    params: &RingPedersenParams,
+    value: &Integer,
+    rand: &mut dyn MutRandState,
+) -> Result<(PedersenCommitment, PedersenOpening)> {
+    params.validate()?;
+    let bound = Integer::from(&params.n_hat << HIDING_BITS);
+    let randomness = Integer::from(bound.random_below_ref(rand));
+    let opening = PedersenOpening {
+        value: value.clone(),
+        randomness,
+    };
+    // s and t are units, so negative exponents can be inverted
+    let val = params
+        .commit(&opening.value, &opening.randomness)
+        .ok_or_else(|| anyhow!("ring-Pedersen parameters must be in Z*_N̂"))?;
+    Ok((PedersenCommitment { val }, opening))
+}
+
+impl PedersenCommitment {
+    /// Checks that `opening` opens this commitment
+    pub fn verify(&self, params: &RingPedersenParams, opening: &PedersenOpening) -> bool {
+        params.commit(&opening.value, &opening.randomness).as_ref() == Some(&self.val)
+    }
+
+    /// Commitment to the sum of the committed values, opened by
+    /// [`PedersenOpening::add`]
+    pub fn add(&self, other: &PedersenCommitment, params: &RingPedersenParams) -> Self {
+        let val = Integer::from(&self.val * &other.val) % &params.n_hat;
+        Self { val }
+    }
+
+    pub fn value(&self) -> &Integer {
+        &self.val
+    }
+}
+
+impl PedersenOpening {
+    /// Opening of the sum of two commitments
+    pub fn add(&self, other: &PedersenOpening) -> Self {
+        Self {
+            value: Integer::from(&self.value + &other.value),
+            randomness: Integer::from(&self.randomness + &other.randomness),
+        }
+    }
+}
+
+/// Commits to `msg`, returning the commitment and the nonce which opens it
+pub fn commit_hash(msg: &[u8], rand: &mut dyn MutRandState) -> (HashCommitment, [u8; 32]) {
+    let nonce: [u8; 32] = random_bytes(32, rand).try_into().unwrap();
+    (HashCommitment::compute(msg, &nonce), nonce)
+}
+
+impl HashCommitment {
+    fn compute(msg: &[u8], nonce: &[u8; 32]) -> Self {
+        let mut hasher = Sha3_256::new();
+        hasher.update(HASH_LABEL);
+        hasher.update(nonce);
+        hasher.update(msg);
+        Self(hasher.finalize().into())
+    }
+
+    /// Checks in constant time that `msg` and `nonce` open this commitment
+    pub fn verify(&self, msg: &[u8], nonce: &[u8; 32]) -> bool {
+        self.0.ct_eq(&Self::compute(msg, nonce).0).into()
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl From<[u8; 32]> for HashCommitment {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{commit_hash, commit_pedersen};
+    use crate::paillier::{generate_key_pair, PrivateKey};
+    use crate::proofs::RingPedersenParams;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_commitments() {
+        let mut rand = RandState::new();
+        let (_, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let params = RingPedersenParams::from_private_key(&sk, &mut rand).unwrap();
+        let (a, mut open_a) = commit_pedersen(&params, &Integer::from(-5), &mut rand).unwrap();
+        let (b, open_b) = commit_pedersen(&params, &Integer::from(7), &mut rand).unwrap();
+        assert!(a.verify(&params, &open_a));
+        assert!(a.add(&b, &params).verify(&params, &open_a.add(&open_b)));
+        open_a.value += 1;
+        assert!(!a.verify(&params, &open_a));
+
+        let mut invalid = params.clone();
+        invalid.t = invalid.n_hat.clone();
+        assert!(commit_pedersen(&invalid, &Integer::from(-5), &mut rand).is_err());
+        let without_factors =
+            PrivateKey::from_parts(sk.n.clone(), 1, 1, sk.d.clone(), sk.nm.clone()).unwrap();
+        assert!(RingPedersenParams::from_private_key(&without_factors, &mut rand).is_err());
+
+        let (c, mut nonce) = commit_hash(b"msg", &mut rand);
+        assert!(c.verify(b"msg", &nonce));
+        assert!(!c.verify(b"msh", &nonce));
+        nonce[0] ^= 1;
+        assert!(!c.verify(b"msg", &nonce));
+    }
+}
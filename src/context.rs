@@ -0,0 +1,143 @@
+//! Ciphertexts bound to the context they were contributed to.
+//!
+//! A plain [`Ciphertext`] can be replayed: a contribution to one aggregation round
+//! could be submitted again in a later round or another study. A
+//! [`ContextCiphertext`] from [`PublicKey::encrypt_with_context`] carries a proof of
+//! knowledge of its plaintext whose Fiat-Shamir transcript contains associated data,
+//! e.g. the study id, the round number and the [`PublicKey::fingerprint`]. Producing
+//! a valid proof for other associated data requires the plaintext and randomness, so
+//! only the original contributor can move a ciphertext to another context.
+//! [`PublicKey::sum_with_context`] checks the binding before summing and rejects
+//! ciphertexts passed more than once.
+//!
+//! Note that this does not hide whether two contexts received the same ciphertext,
+//! it only rejects ciphertexts whose binding does not match.
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let c = pk.encrypt_with_context(5.into(), b"study 1, round 1", &mut rand);
+//! let sum = pk.sum_with_context(&[c.clone()], b"study 1, round 1").unwrap();
+//! assert_eq!(sk.decrypt(&sum), 5);
+//! assert!(pk.sum_with_context(&[c], b"study 1, round 2").is_err());
+//! ```
+
+use crate::paillier::PublicKey;
+use crate::proofs::{
+    prove_plaintext_knowledge, verify_plaintext_knowledge, PlaintextKnowledgeProof,
+};
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+const LABEL: &[u8] = b"pht-crypto/context-ciphertext";
+
+/// A ciphertext with a proof binding it to its associated data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextCiphertext {
+    cipher: Ciphertext,
+    proof: PlaintextKnowledgeProof,
+}
+
+fn transcript(aad: &[u8]) -> Transcript {
+    let mut transcript = Transcript::new(LABEL);
+    transcript.append_message(b"aad", aad);
+    transcript
+}
+
+impl ContextCiphertext {
+    /// Checks the binding to `aad`
+    pub fn verify(&self, pk: &PublicKey, aad: &[u8]) -> bool {
+        verify_plaintext_knowledge(pk, &mut transcript(aad), &self.cipher, &self.proof)
+    }
+
+    /// Returns the ciphertext if it is bound to `aad`
+    pub fn open(self, pk: &PublicKey, aad: &[u8]) -> Result<Ciphertext> {
+        ensure!(
+            self.verify(pk, aad),
+            "ciphertext is not bound to this context"
+        );
+        Ok(self.cipher)
+    }
+
+    /// The ciphertext, whose binding is not checked by this method
+    pub fn ciphertext(&self) -> &Ciphertext {
+        &self.cipher
+    }
+}
+
+impl PublicKey {
+    /// Encrypts `m` and binds the ciphertext to the associated data `aad`
+    pub fn encrypt_with_context(
+        &self,
+        m: Plaintext,
+        aad: &[u8],
+        rand: &mut dyn MutRandState,
+    ) -> ContextCiphertext {
+        let (cipher, r) = self.encrypt_with_randomness(m.clone(), rand);
+        let proof = prove_plaintext_knowledge(self, &mut transcript(aad), &cipher, &m, &r, rand);
+        ContextCiphertext { cipher, proof }
+    }
+
+    /// Homomorphically sums `ciphers` after checking that every one is bound to `aad`
+    /// and that no ciphertext is replayed within `ciphers`
+    pub fn sum_with_context(
+        &self,
+        ciphers: &[ContextCiphertext],
+        aad: &[u8],
+    ) -> Result<Ciphertext> {
+        let mut seen = HashSet::with_capacity(ciphers.len());
+        for (pos, cipher) in ciphers.iter().enumerate() {
+            ensure!(
+                seen.insert(&cipher.cipher),
+                "ciphertext {} was already passed",
+                pos
+            );
+            ensure!(
+                cipher.verify(self, aad),
+                "ciphertext {} is not bound to this context",
+                pos
+            );
+        }
+        Ok(self.sum_encrypted(ciphers.iter().map(|c| &c.cipher)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_context_binding() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let (other_pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let aad = b"study 7 round 3";
+        let ciphers: Vec<_> = (1..4)
+            .map(|m| pk.encrypt_with_context(m.into(), aad, &mut rand))
+            .collect();
+        let sum = pk.sum_with_context(&ciphers, aad).unwrap();
+        assert_eq!(Integer::from(sk.decrypt(&sum)), 6);
+
+        assert!(pk.sum_with_context(&ciphers, b"study 7 round 4").is_err());
+        assert!(!ciphers[0].verify(&other_pk, aad));
+        assert!(ciphers[1].clone().open(&pk, aad).is_ok());
+
+        // moving the ciphertext into another contribution breaks the binding
+        let mut forged = ciphers[2].clone();
+        forged.cipher = ciphers[0].cipher.clone();
+        assert!(pk.sum_with_context(&[forged], aad).is_err());
+
+        // replaying a bound ciphertext within one sum
+        let replayed = [ciphers[0].clone(), ciphers[1].clone(), ciphers[0].clone()];
+        assert!(pk.sum_with_context(&replayed, aad).is_err());
+    }
+}
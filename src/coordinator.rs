@@ -0,0 +1,497 @@
+//! Orchestration of threshold decryption rounds over an arbitrary transport.
+//!
+//! A [`Coordinator`] drives one decryption: it sends a [`DecryptionRequest`] to every
+//! decryption server, collects their partial decryptions until w valid ones arrived and
+//! combines them. Servers which don't answer within the timeout are asked again up to
+//! the configured number of retries. Messages are exchanged through a [`Transport`],
+//! so the coordinator works with any network stack and async runtime. Timeouts are
+//! implemented by the transport as well.
+//!
+//! Servers answer a request with [`DecryptionRequest::respond`]. Every request carries
+//! a random session id, so late answers to earlier requests are rejected. Every answer
+//! carries a [`DecryptionProof`] bound to the session, which the coordinator checks
+//! against the verification key of the server in the [`DealingTranscript`] of the key.
+//! So a server can't get a wrong partial decryption combined, as long as the
+//! transcript was checked with [`DealingTranscript::verify`]. Further checks, e.g. of
+//! [`crate::signed`] envelopes exchanged out of band, can be plugged in with
+//! [`Coordinator::with_verifier`].
+//!
+//! If decryption fails, a [`FailureReport`] lists which servers answered, which
+//! didn't and whose answers were rejected.
+
+use crate::audit::{DealingTranscript, VerificationKey};
+use crate::metrics;
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::proofs::{in_mult_group, prove_decryption, verify_decryption, DecryptionProof};
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+const TRANSCRIPT_LABEL: &[u8] = b"pht-crypto/coordinator/decryption";
+
+/// Message transport between the coordinator and the decryption servers. Peers are
+/// identified by their server index.
+pub trait Transport {
+    /// Sends `bytes` to the server `peer`
+    fn send(&mut self, peer: u32, bytes: Vec<u8>) -> impl Future<Output = Result<()>> + Send;
+
+    /// The next message of any server together with its sender, or `None` if none
+    /// arrived within `timeout`
+    fn recv(
+        &mut self,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Option<(u32, Vec<u8>)>>> + Send;
+}
+
+/// Request to partially decrypt a ciphertext, sent to every server
+#[derive(Debug, Clone)]
+pub struct DecryptionRequest {
+    session: u64,
+    cipher: Ciphertext,
+}
+
+impl DecryptionRequest {
+    /// Encodes the session id followed by the canonical ciphertext encoding
+    pub fn to_bytes(&self, pk: &PublicKey) -> Vec<u8> {
+        let mut bytes = self.session.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.cipher.to_bytes(pk));
+        bytes
+    }
+
+    pub fn from_bytes(pk: &PublicKey, bytes: &[u8]) -> Result<Self> {
+        ensure!(bytes.len() >= 8, "request is too short");
+        let (session, cipher) = bytes.split_at(8);
+        Ok(Self {
+            session: u64::from_be_bytes(session.try_into().unwrap()),
+            cipher: Ciphertext::from_bytes(pk, cipher)?,
+        })
+    }
+
+    pub fn ciphertext(&self) -> &Ciphertext {
+        &self.cipher
+    }
+
+    /// The answer of the server holding `key_share` with `verification_key`: the
+    /// session id, the length of the CBOR encoding of the partial decryption as big
+    /// endian u32, the CBOR encoding itself and the proof of its correctness
+    pub fn respond(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+        verification_key: &VerificationKey,
+        rand: &mut dyn MutRandState,
+    ) -> Result<Vec<u8>> {
+        let (partial, proof) = prove_decryption(
+            pk,
+            &mut self.transcript(),
+            &self.cipher,
+            key_share,
+            verification_key,
+            rand,
+        )?;
+        let partial = partial.to_cbor();
+        let mut bytes = self.session.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&(partial.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&partial);
+        bytes.extend_from_slice(&bincode::serialize(&proof)?);
+        Ok(bytes)
+    }
+
+    /// Transcript binding the decryption proofs to the session
+    fn transcript(&self) -> Transcript {
+        let mut transcript = Transcript::new(TRANSCRIPT_LABEL);
+        transcript.append_u64(b"session", self.session);
+        transcript
+    }
+}
+
+/// Why a decryption round failed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FailureReport {
+    /// Servers which sent a valid partial decryption
+    pub responded: Vec<u32>,
+    /// Servers which did not answer in time
+    pub missing: Vec<u32>,
+    /// Servers whose answers were rejected, with the reason
+    pub rejected: Vec<(u32, String)>,
+    /// Error of the transport or of combining the partial decryptions
+    pub error: Option<String>,
+    /// Number of times the request was sent
+    pub attempts: u32,
+}
+
+impl fmt::Display for FailureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "decryption failed after {} attempts: {} responded, missing {:?}, rejected {:?}",
+            self.attempts,
+            self.responded.len(),
+            self.missing,
+            self.rejected
+                .iter()
+                .map(|(server, _)| server)
+                .collect::<Vec<_>>()
+        )?;
+        if let Some(error) = &self.error {
+            write!(f, ": {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FailureReport {}
+
+impl FailureReport {
+    fn is_rejected(&self, server: u32) -> bool {
+        self.rejected
+            .iter()
+            .any(|(rejected, _)| *rejected == server)
+    }
+}
+
+type Verifier<'a> = Box<dyn Fn(u32, &PartialDecryption) -> Result<()> + Send + Sync + 'a>;
+
+/// Drives threshold decryptions with the servers reachable through `T`
+pub struct Coordinator<'a, T> {
+    pk: &'a PublicKey,
+    dealing: &'a DealingTranscript,
+    transport: T,
+    servers: Vec<u32>,
+    timeout: Duration,
+    retries: u32,
+    verifier: Option<Verifier<'a>>,
+}
+
+impl<'a, T: Transport> Coordinator<'a, T> {
+    /// Creates a coordinator for the decryption `servers` of the key dealt in
+    /// `dealing`, waiting 10 seconds for answers and retrying twice by default
+    pub fn new(dealing: &'a DealingTranscript, transport: T, servers: Vec<u32>) -> Self {
+        Self {
+            pk: dealing.public_key(),
+            dealing,
+            transport,
+            servers,
+            timeout: Duration::from_secs(10),
+            retries: 2,
+            verifier: None,
+        }
+    }
+
+    /// Time to wait for the answers of one attempt
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Number of times the request is sent again to servers which didn't answer
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Additionally checks every partial decryption with `verifier`, which receives
+    /// the index of the sending server
+    pub fn with_verifier<F>(mut self, verifier: F) -> Self
+    where
+        F: Fn(u32, &PartialDecryption) -> Result<()> + Send + Sync + 'a,
+    {
+        self.verifier = Some(Box::new(verifier));
+        self
+    }
+
+    pub fn transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    /// Runs a full decryption round for `cipher`
+    pub async fn decrypt(&mut self, cipher: &Ciphertext) -> Result<Plaintext, FailureReport> {
+        let request = DecryptionRequest {
+            session: rand::random(),
+            cipher: cipher.clone(),
+        };
+        let request_bytes = request.to_bytes(self.pk);
+        let needed = self.pk.w as usize;
+        let mut partials: BTreeMap<u32, PartialDecryption> = BTreeMap::new();
+        let mut report = FailureReport::default();
+
+        while partials.len() < needed && report.attempts <= self.retries {
+            report.attempts += 1;
+            for server in &self.servers {
+                // servers whose answer was rejected are not asked again
+                if partials.contains_key(server) || report.is_rejected(*server) {
+                    continue;
+                }
+                if let Err(err) = self.transport.send(*server, request_bytes.clone()).await {
+                    return Err(self.fail(report, &partials, err.to_string()));
+                }
+            }
+            let deadline = Instant::now() + self.timeout;
+            while partials.len() < needed {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let (server, bytes) = match self.transport.recv(remaining).await {
+                    Ok(Some(message)) => message,
+                    Ok(None) => break,
+                    Err(err) => return Err(self.fail(report, &partials, err.to_string())),
+                };
+                if !self.servers.contains(&server)
+                    || partials.contains_key(&server)
+                    || report.is_rejected(server)
+                {
+                    continue;
+                }
+                match self.check_response(&request, server, &bytes) {
+                    Ok(Some(partial)) => {
+                        partials.insert(server, partial);
+                    }
+                    // answer to an earlier request
+                    Ok(None) => {}
+                    Err(err) => {
+                        metrics::invalid_partial_decryption();
+                        report.rejected.push((server, err.to_string()))
+                    }
+                }
+            }
+        }
+
+        if partials.len() < needed {
+            let error = format!("only {} of {} partial decryptions", partials.len(), needed);
+            return Err(self.fail(report, &partials, error));
+        }
+        let shares: Vec<_> = partials.values().cloned().collect();
+        self.pk
+            .share_combine(&shares)
+            .map_err(|err| self.fail(report, &partials, err.to_string()))
+    }
+
+    fn check_response(
+        &self,
+        request: &DecryptionRequest,
+        server: u32,
+        bytes: &[u8],
+    ) -> Result<Option<PartialDecryption>> {
+        ensure!(bytes.len() >= 12, "answer is too short");
+        let (session, rest) = bytes.split_at(8);
+        if session != request.session.to_be_bytes() {
+            return Ok(None);
+        }
+        let (len, rest) = rest.split_at(4);
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        ensure!(rest.len() >= len, "answer is too short");
+        let (partial, proof) = rest.split_at(len);
+        let partial = PartialDecryption::from_cbor(partial)?;
+        let proof: DecryptionProof = bincode::deserialize(proof)?;
+        ensure!(
+            partial.id.checked_sub(1) == Some(server),
+            "partial decryption of another server"
+        );
+        ensure!(
+            in_mult_group(&partial.val, &self.pk.n, &self.pk.n2),
+            "partial decryption is not in Z*_{{n^2}}"
+        );
+        ensure!(
+            partial.cipher_digest() == Some(&request.cipher.digest()),
+            "partial decryption of another ciphertext"
+        );
+        let verification_key = self
+            .dealing
+            .verification_key(server)
+            .ok_or_else(|| anyhow!("no verification key for server {}", server))?;
+        ensure!(
+            verify_decryption(
+                self.pk,
+                &mut request.transcript(),
+                &request.cipher,
+                &partial,
+                &verification_key,
+                &proof
+            ),
+            "invalid decryption proof"
+        );
+        if let Some(verifier) = &self.verifier {
+            verifier(server, &partial)?;
+        }
+        Ok(Some(partial))
+    }
+
+    fn fail(
+        &self,
+        mut report: FailureReport,
+        partials: &BTreeMap<u32, PartialDecryption>,
+        error: String,
+    ) -> FailureReport {
+        report.responded = partials.keys().copied().collect();
+        report.missing = self
+            .servers
+            .iter()
+            .filter(|server| !partials.contains_key(server) && !report.is_rejected(**server))
+            .copied()
+            .collect();
+        report.error = Some(error);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coordinator, DecryptionRequest, Transport};
+    use crate::audit::DealingTranscript;
+    use crate::paillier::{generate_key_pair, PrivateKeyShare, PublicKey};
+    use anyhow::{bail, Result};
+    use rug::rand::RandState;
+    use std::collections::VecDeque;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+    use std::time::Duration;
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Behavior {
+        Honest,
+        Offline,
+        /// Ignores the first request
+        Slow,
+        /// Answers with the share of another server
+        Impersonate,
+        /// Answers with a wrong partial decryption
+        Corrupt,
+    }
+
+    /// Servers answering synchronously within `send`
+    struct LocalTransport<'a> {
+        pk: &'a PublicKey,
+        dealing: &'a DealingTranscript,
+        shares: Vec<PrivateKeyShare>,
+        behaviors: Vec<Behavior>,
+        requests: Vec<u32>,
+        inbox: VecDeque<(u32, Vec<u8>)>,
+    }
+
+    impl Transport for LocalTransport<'_> {
+        async fn send(&mut self, peer: u32, bytes: Vec<u8>) -> Result<()> {
+            let server = peer as usize;
+            if server >= self.shares.len() {
+                bail!("unknown server {}", peer);
+            }
+            self.requests[server] += 1;
+            let request = DecryptionRequest::from_bytes(self.pk, &bytes)?;
+            let (share, key) = match self.behaviors[server] {
+                Behavior::Honest => (self.shares[server].clone(), server),
+                Behavior::Slow if self.requests[server] > 1 => {
+                    (self.shares[server].clone(), server)
+                }
+                Behavior::Impersonate => {
+                    let other = (server + 1) % self.shares.len();
+                    (self.shares[other].clone(), other)
+                }
+                Behavior::Corrupt => {
+                    let si = self.shares[server].si.clone() + 1;
+                    (PrivateKeyShare::at_point(si, peer + 1), server)
+                }
+                _ => return Ok(()),
+            };
+            let vk = self.dealing.verification_key(key as u32).unwrap();
+            let answer = request.respond(self.pk, &share, &vk, &mut RandState::new())?;
+            self.inbox.push_back((peer, answer));
+            Ok(())
+        }
+
+        async fn recv(&mut self, _timeout: Duration) -> Result<Option<(u32, Vec<u8>)>> {
+            Ok(self.inbox.pop_front())
+        }
+    }
+
+    #[test]
+    fn test_coordinator() {
+        use Behavior::*;
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 4, 3).unwrap();
+        let (shares, dealing) = sk.share_audited(&[0, 1, 2, 3], &mut rand);
+        dealing.verify(&pk).unwrap();
+        let c = pk.encrypt(42.into(), &mut rand);
+        let transport = |behaviors: Vec<Behavior>| LocalTransport {
+            pk: &pk,
+            dealing: &dealing,
+            shares: shares.clone(),
+            behaviors,
+            requests: vec![0; 4],
+            inbox: VecDeque::new(),
+        };
+
+        let mut coordinator = Coordinator::new(
+            &dealing,
+            transport(vec![Honest, Offline, Slow, Honest]),
+            vec![0, 1, 2, 3],
+        );
+        assert_eq!(block_on(coordinator.decrypt(&c)).unwrap(), 42);
+        assert_eq!(coordinator.transport_mut().requests, vec![1, 2, 2, 1]);
+
+        let mut coordinator = Coordinator::new(
+            &dealing,
+            transport(vec![Honest, Offline, Slow, Impersonate]),
+            vec![0, 1, 2, 3],
+        )
+        .with_retries(0);
+        let report = block_on(coordinator.decrypt(&c)).unwrap_err();
+        assert_eq!(report.attempts, 1);
+        assert_eq!(report.responded, vec![0]);
+        assert_eq!(report.missing, vec![1, 2]);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(report.rejected[0].0, 3);
+
+        // a rejected server is neither asked again nor reported twice
+        let mut coordinator = Coordinator::new(
+            &dealing,
+            transport(vec![Honest, Offline, Slow, Impersonate]),
+            vec![0, 1, 2, 3],
+        )
+        .with_retries(2);
+        let report = block_on(coordinator.decrypt(&c)).unwrap_err();
+        assert_eq!(report.attempts, 3);
+        assert_eq!(report.responded, vec![0, 2]);
+        assert_eq!(report.rejected.len(), 1);
+        assert_eq!(coordinator.transport_mut().requests, vec![1, 3, 2, 1]);
+
+        let mut coordinator =
+            Coordinator::new(&dealing, transport(vec![Honest; 4]), vec![0, 1, 2, 3]).with_verifier(
+                |server, _| match server {
+                    0 => bail!("invalid proof"),
+                    _ => Ok(()),
+                },
+            );
+        assert_eq!(block_on(coordinator.decrypt(&c)).unwrap(), 42);
+
+        // a wrong partial decryption fails its proof
+        let mut coordinator = Coordinator::new(
+            &dealing,
+            transport(vec![Corrupt, Offline, Honest, Honest]),
+            vec![0, 1, 2, 3],
+        )
+        .with_retries(0);
+        let report = block_on(coordinator.decrypt(&c)).unwrap_err();
+        assert_eq!(report.responded, vec![2, 3]);
+        assert_eq!(report.rejected[0].0, 0);
+        assert!(report.rejected[0].1.contains("invalid decryption proof"));
+
+        let mut coordinator =
+            Coordinator::new(&dealing, transport(vec![Honest; 4]), vec![0, 1, 2, 3, 4]);
+        let report = block_on(coordinator.decrypt(&c)).unwrap_err();
+        assert!(report.error.unwrap().contains("unknown server"));
+    }
+}
@@ -0,0 +1,168 @@
+//! Encrypted counters, e.g. for cohort counting.
+//!
+//! An [`EncryptedCounter`] is bound to the fingerprint of the public key it was
+//! created for and declares an upper bound on its count. Operations with another key
+//! and merges of counters of different keys fail, and decoding fails if the count
+//! exceeds the bound, which also catches sums that wrapped around n.
+//!
+//! ```
+//! use pht_crypto::counter::EncryptedCounter;
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let key_share = sk.share(&[0], &mut rand).remove(0);
+//!
+//! let mut counter = EncryptedCounter::new(&pk, 1000, &mut rand).unwrap();
+//! counter.increment(&pk, &mut rand).unwrap();
+//! let mut other = EncryptedCounter::new(&pk, 1000, &mut rand).unwrap();
+//! other.add(&pk, 41, &mut rand).unwrap();
+//! counter.merge(&pk, &other).unwrap();
+//!
+//! let partial = counter.share_decrypt(&pk, &key_share).unwrap();
+//! assert_eq!(counter.share_combine(&pk, &[partial]).unwrap(), 42);
+//! ```
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::Ciphertext;
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedCounter {
+    key_fingerprint: [u8; 32],
+    cipher: Ciphertext,
+    /// Inclusive upper bound on the count
+    bound: u64,
+}
+
+impl EncryptedCounter {
+    /// Creates a counter at 0 whose count may not exceed `bound`
+    pub fn new(pk: &PublicKey, bound: u64, rand: &mut dyn MutRandState) -> Result<Self> {
+        Self::from_ciphertext(pk, pk.encrypt(0.into(), rand), bound)
+    }
+
+    /// Wraps a ciphertext of a count in [0, bound]
+    pub fn from_ciphertext(pk: &PublicKey, cipher: Ciphertext, bound: u64) -> Result<Self> {
+        ensure!(pk.n > bound, "bound must be less than n");
+        Ok(Self {
+            key_fingerprint: pk.fingerprint(),
+            cipher,
+            bound,
+        })
+    }
+
+    pub fn key_fingerprint(&self) -> &[u8; 32] {
+        &self.key_fingerprint
+    }
+
+    pub fn cipher(&self) -> &Ciphertext {
+        &self.cipher
+    }
+
+    pub fn bound(&self) -> u64 {
+        self.bound
+    }
+
+    pub fn into_ciphertext(self) -> Ciphertext {
+        self.cipher
+    }
+
+    /// Adds 1 with a fresh encryption, so the counter is re-randomized
+    pub fn increment(&mut self, pk: &PublicKey, rand: &mut dyn MutRandState) -> Result<()> {
+        self.add(pk, 1, rand)
+    }
+
+    /// Adds `amount` with a fresh encryption, so the counter is re-randomized
+    pub fn add(&mut self, pk: &PublicKey, amount: u64, rand: &mut dyn MutRandState) -> Result<()> {
+        self.check_key(pk)?;
+        let summand = pk.encrypt(amount.into(), rand);
+        pk.add_encrypted(&mut self.cipher, &summand);
+        Ok(())
+    }
+
+    /// Adds the count of `other`, which must belong to the same key. The bound of the
+    /// merged counter is the sum of both bounds.
+    pub fn merge(&mut self, pk: &PublicKey, other: &EncryptedCounter) -> Result<()> {
+        ensure!(
+            self.key_fingerprint == other.key_fingerprint,
+            "counters belong to different keys"
+        );
+        self.check_key(pk)?;
+        let bound = self
+            .bound
+            .checked_add(other.bound)
+            .filter(|bound| pk.n > *bound)
+            .ok_or_else(|| anyhow!("merged bound could exceed n"))?;
+        pk.add_encrypted(&mut self.cipher, &other.cipher);
+        self.bound = bound;
+        Ok(())
+    }
+
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> Result<PartialDecryption> {
+        self.check_key(pk)?;
+        Ok(key_share.share_decrypt(pk, self.cipher.clone()))
+    }
+
+    /// Combines partial decryptions of the counter and checks the count against the
+    /// bound
+    pub fn share_combine(&self, pk: &PublicKey, partials: &[PartialDecryption]) -> Result<u64> {
+        self.check_key(pk)?;
+        let count = pk.share_combine(partials)?;
+        ensure!(
+            *count.as_ref() <= self.bound,
+            "count exceeds the bound {}",
+            self.bound
+        );
+        Ok(count.as_ref().to_u64().unwrap())
+    }
+
+    fn check_key(&self, pk: &PublicKey) -> Result<()> {
+        ensure!(
+            self.key_fingerprint == pk.fingerprint(),
+            "counter belongs to another key"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EncryptedCounter;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_counter_checks() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let (other_pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+
+        let mut counter = EncryptedCounter::new(&pk, 2, &mut rand).unwrap();
+        let foreign = EncryptedCounter::new(&other_pk, 2, &mut rand).unwrap();
+        assert!(counter.merge(&pk, &foreign).is_err());
+        assert!(counter.increment(&other_pk, &mut rand).is_err());
+
+        let before = counter.cipher().clone();
+        counter.increment(&pk, &mut rand).unwrap();
+        assert_ne!(before.as_ref(), counter.cipher().as_ref());
+        counter.add(&pk, 2, &mut rand).unwrap();
+        let partials = vec![counter.share_decrypt(&pk, &key_share).unwrap()];
+        assert!(counter.share_combine(&pk, &partials).is_err());
+        assert!(counter.share_combine(&other_pk, &partials).is_err());
+
+        let mut merged = EncryptedCounter::new(&pk, 4, &mut rand).unwrap();
+        merged.increment(&pk, &mut rand).unwrap();
+        merged.merge(&pk, &counter).unwrap();
+        assert_eq!(merged.bound(), 6);
+        let partial = merged.share_decrypt(&pk, &key_share).unwrap();
+        assert_eq!(merged.share_combine(&pk, &[partial]).unwrap(), 4);
+    }
+}
@@ -0,0 +1,203 @@
+//! Private key operations delegated to a [`KeyBackend`], e.g. a PKCS#11 token or a
+//! cloud KMS, so the secret exponent never enters process memory.
+//!
+//! Decryption only needs base^s mod n^2 for the secret s of a [`PrivateKey`] or
+//! [`PrivateKeyShare`]; all other steps work on public values. A token which supports
+//! raw RSA (`CKM_RSA_X_509`) computes this if the share is imported as an RSA private
+//! key with modulus n^2 and private exponent s, other tokens need a vendor mechanism.
+//! The software keys implement [`KeyBackend`] themselves and are the default backend.
+//!
+//! ```
+//! use pht_crypto::custody::BackedKeyShare;
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let share = BackedKeyShare::software(sk.share(&[0], &mut rand).remove(0));
+//! let partial = share.share_decrypt(&pk, pk.encrypt(7.into(), &mut rand)).unwrap();
+//! assert_eq!(pk.share_combine(&[partial]).unwrap(), 7);
+//! ```
+
+use crate::paillier::{PartialDecryption, PrivateKey, PrivateKeyShare, PublicKey};
+use crate::util;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::Integer;
+
+/// Holder of a secret exponent s, e.g. an HSM session with a handle to the key object
+pub trait KeyBackend: Send + Sync {
+    /// base^s mod `modulus` in constant time. Backends bound to a key should fail for
+    /// any other modulus.
+    fn pow_secret(&self, base: &Integer, modulus: &Integer) -> Result<Integer>;
+}
+
+impl KeyBackend for PrivateKeyShare {
+    fn pow_secret(&self, base: &Integer, modulus: &Integer) -> Result<Integer> {
+        Ok(util::secure_pow_mod(base, &self.si, modulus))
+    }
+}
+
+impl KeyBackend for PrivateKey {
+    fn pow_secret(&self, base: &Integer, modulus: &Integer) -> Result<Integer> {
+        ensure!(*modulus == self.n2, "modulus does not match the key");
+        Ok(self.secure_pow_mod_n2(base, &self.d, &mut crate::rng::secure()))
+    }
+}
+
+/// A key share whose secret is held by `B`
+#[derive(Debug, Clone)]
+pub struct BackedKeyShare<B: KeyBackend = PrivateKeyShare> {
+    point: u32,
+    key_fingerprint: Option<[u8; 32]>,
+    backend: B,
+}
+
+impl BackedKeyShare {
+    /// Keeps the share in memory, like [`PrivateKeyShare::share_decrypt`]
+    pub fn software(share: PrivateKeyShare) -> Self {
+        Self {
+            point: share.i,
+            key_fingerprint: share.key_fingerprint,
+            backend: share,
+        }
+    }
+}
+
+impl<B: KeyBackend> BackedKeyShare<B> {
+    /// The share at the nonzero evaluation `point` of the key with `key_fingerprint`,
+    /// held by `backend`
+    pub fn new(point: u32, key_fingerprint: Option<[u8; 32]>, backend: B) -> Result<Self> {
+        ensure!(point > 0, "evaluation point must be nonzero");
+        Ok(Self {
+            point,
+            key_fingerprint,
+            backend,
+        })
+    }
+
+    /// Evaluation point of the share
+    pub fn point(&self) -> u32 {
+        self.point
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Partial decryption of `cipher`, the same as [`PrivateKeyShare::share_decrypt`]
+    pub fn share_decrypt(&self, pk: &PublicKey, cipher: Ciphertext) -> Result<PartialDecryption> {
+        let fingerprint = pk.fingerprint();
+        ensure!(
+            self.key_fingerprint.iter().all(|f| *f == fingerprint),
+            "key share belongs to a different key"
+        );
+        trace_span!("backed_share_decrypt", point = self.point);
+        // c^{2 delta s_i} = (c^{2 delta})^{s_i}, the inner power is public
+        let exponent = Integer::from(&pk.delta << 1);
+        let base = cipher.val.pow_mod_ref(&exponent, &pk.n2).unwrap().into();
+        Ok(PartialDecryption {
+            val: self.backend.pow_secret(&base, &pk.n2)?,
+            id: self.point,
+            cipher_digest: Some(cipher.digest()),
+            key_fingerprint: Some(fingerprint),
+        })
+    }
+}
+
+/// A private key whose secret d is held by `B`
+#[derive(Debug, Clone)]
+pub struct BackedPrivateKey<B: KeyBackend = PrivateKey> {
+    backend: B,
+}
+
+impl BackedPrivateKey {
+    /// Keeps the key in memory, like [`PrivateKey::decrypt`]
+    pub fn software(sk: PrivateKey) -> Self {
+        Self { backend: sk }
+    }
+}
+
+impl<B: KeyBackend> BackedPrivateKey<B> {
+    pub fn new(backend: B) -> Self {
+        Self { backend }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Decrypts `cipher` under `pk`, the same as [`PrivateKey::decrypt`]
+    pub fn decrypt(&self, pk: &PublicKey, cipher: &Ciphertext) -> Result<Plaintext> {
+        // c^{2d} = (c^2)^d
+        let base = Integer::from(cipher.val.square_ref()) % &pk.n2;
+        let c = self.backend.pow_secret(&base, &pk.n2)?;
+        let t: Integer = (c - 1) / pk.modulus();
+        let two_inv = Integer::from(2).invert(pk.modulus()).unwrap();
+        let m: Integer = t * two_inv % pk.modulus();
+        Ok(m.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BackedKeyShare, BackedPrivateKey, KeyBackend};
+    use crate::dealer::Dealer;
+    use crate::paillier::{generate_key_pair, PrivateKeyShare};
+    use anyhow::{ensure, Result};
+    use rug::rand::RandState;
+    use rug::Integer;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Stands in for a token: only exposes the operation and counts its uses
+    struct Token {
+        share: PrivateKeyShare,
+        modulus: Integer,
+        calls: AtomicUsize,
+    }
+
+    impl KeyBackend for Token {
+        fn pow_secret(&self, base: &Integer, modulus: &Integer) -> Result<Integer> {
+            ensure!(*modulus == self.modulus, "wrong modulus");
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            self.share.pow_secret(base, modulus)
+        }
+    }
+
+    #[test]
+    fn test_backed_decryption() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 3, 2).unwrap();
+        let shares = Dealer::from_key_pair(pk.clone(), sk.clone()).deal(&mut rand);
+        let cipher = pk.encrypt(42.into(), &mut rand);
+
+        let backed = BackedPrivateKey::software(sk.clone());
+        assert_eq!(backed.decrypt(&pk, &cipher).unwrap(), 42);
+
+        let token = BackedKeyShare::new(
+            shares[0].i,
+            shares[0].key_fingerprint,
+            Token {
+                share: shares[0].clone(),
+                modulus: pk.n2.clone(),
+                calls: AtomicUsize::new(0),
+            },
+        )
+        .unwrap();
+        let partials = vec![
+            token.share_decrypt(&pk, cipher.clone()).unwrap(),
+            BackedKeyShare::software(shares[2].clone())
+                .share_decrypt(&pk, cipher.clone())
+                .unwrap(),
+        ];
+        assert_eq!(token.backend().calls.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            partials[0].val,
+            shares[0].share_decrypt(&pk, cipher.clone()).val
+        );
+        assert_eq!(pk.share_combine(&partials).unwrap(), 42);
+
+        let (other, _) = generate_key_pair(256, 3, 2).unwrap();
+        assert!(token.share_decrypt(&other, cipher).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+//! Threshold Damgård-Jurik encryption, the generalization of paillier to the modulus
+//! n^{s+1} with plaintexts in Z_{n^s}. For s = 1 this is exactly threshold paillier,
+//! larger s allow a single ciphertext to carry plaintexts much larger than n at a
+//! ciphertext expansion of only (s + 1) / s.
+//!
+//! Source: Damgård, Jurik "A Generalisation, a Simplification and Some Applications
+//! of Paillier's Probabilistic Public-Key System"
+
+use crate::par;
+use crate::rand::{generate_modulus_safe_primes, random_in_mult_group, UnitCheck};
+use crate::{util, Ciphertext, Plaintext, Randomness};
+use anyhow::{anyhow, ensure, Result};
+use rug::ops::Pow;
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+use subtle::{Choice, ConstantTimeEq};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivateKeyShare {
+    i: u32,
+    /// Polynomial evaluation at i
+    #[serde(with = "crate::util::serde_integer")]
+    si: Integer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialDecryption {
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+    id: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PublicKey {
+    /// The exponent s of the plaintext space Z_{n^s}
+    s: u32,
+    /// The number of servers req to successfully decrypt
+    w: u32,
+    /// The number of decryption servers in total
+    l: u32,
+    /// Modulus of the key. n = p * q
+    #[serde(with = "crate::util::serde_integer")]
+    n: Integer,
+    /// Precomputation: n + 1
+    #[serde(with = "crate::util::serde_integer")]
+    g: Integer,
+    /// Precomputation: n^s, the plaintext modulus
+    #[serde(with = "crate::util::serde_integer")]
+    ns: Integer,
+    /// Precomputation: n^{s+1}, the ciphertext modulus
+    #[serde(with = "crate::util::serde_integer")]
+    ns1: Integer,
+    /// Precomputation: l!
+    #[serde(with = "crate::util::serde_integer")]
+    delta: Integer,
+    /// Precomputation (4*delta^2)^{-1} mod n^s
+    #[serde(with = "crate::util::serde_integer")]
+    combine_shares_constant: Integer,
+}
+
+/// Compares the secrets in constant time
+#[derive(Debug, Clone, Serialize, Deserialize, Eq)]
+pub struct PrivateKey {
+    /// The exponent s of the plaintext space Z_{n^s}
+    s: u32,
+    /// The number of servers req to decrypt
+    w: u32,
+    /// The number of decryption servers in total
+    l: u32,
+    /// d = 0 mod m and d = 1 mod n^s
+    #[serde(with = "crate::util::serde_integer")]
+    d: Integer,
+    /// Modulus of the key: p * q
+    #[serde(with = "crate::util::serde_integer")]
+    n: Integer,
+    /// Precomputation: n^s * m
+    #[serde(with = "crate::util::serde_integer")]
+    nsm: Integer,
+}
+
+impl ConstantTimeEq for PrivateKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        let public = (self.s, self.w, self.l, &self.n) == (other.s, other.w, other.l, &other.n);
+        Choice::from(u8::from(public))
+            & util::ct_eq_integer(&self.d, &other.d)
+            & util::ct_eq_integer(&self.nsm, &other.nsm)
+    }
+}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+pub struct Polynomial<'a> {
+    sk: &'a PrivateKey,
+    coefficients: Vec<Integer>,
+}
+
+/// Generates a key pair with a modulus of `bits` bits for plaintexts in Z_{n^s}.
+pub fn generate_key_pair(
+    bits: usize,
+    s: u32,
+    decryption_servers: u32,
+    threshold: u32,
+) -> Result<(PublicKey, PrivateKey)> {
+    ensure!(s >= 1, "s must be at least 1");
+    ensure!(
+        threshold >= 1 && threshold <= decryption_servers,
+        "threshold must be in [1, decryption_servers]"
+    );
+    let (p, p1, q, q1) = generate_modulus_safe_primes(bits)?;
+    let n = p * q;
+    let ns = n.clone().pow(s);
+    let ns1 = (&ns * &n).complete();
+    let g = n.clone() + 1;
+    let m = p1 * q1;
+    let nsm = (&ns * &m).complete();
+    let d = util::crt2(&Integer::from(1), &ns, &Integer::from(0), &m);
+    let delta = Integer::factorial(decryption_servers).complete();
+    let mut combine_shares_constant = delta.clone().square();
+    combine_shares_constant *= 4;
+    if combine_shares_constant.invert_mut(&ns).is_err() {
+        return Err(anyhow!("No inverse"));
+    }
+
+    let pk = PublicKey {
+        s,
+        w: threshold,
+        l: decryption_servers,
+        n: n.clone(),
+        g,
+        ns,
+        ns1,
+        delta,
+        combine_shares_constant,
+    };
+
+    let sk = PrivateKey {
+        s,
+        w: threshold,
+        l: decryption_servers,
+        d,
+        n,
+        nsm,
+    };
+
+    Ok((pk, sk))
+}
+
+impl PrivateKeyShare {
+    pub fn new(si: Integer, i: u32) -> Self {
+        // i + 1 needed for zero indexed servers
+        Self { i: i + 1, si }
+    }
+
+    /// Computes the partial decryption c^{2 * Δ * s_i} mod n^{s+1} in constant time
+    pub fn share_decrypt(&self, pk: &PublicKey, cipher: Ciphertext) -> PartialDecryption {
+        let exponent = self.si.clone() * &pk.delta * 2;
+        let share = util::secure_pow_mod(&cipher.val, &exponent, &pk.ns1);
+        PartialDecryption {
+            val: share,
+            id: self.i,
+        }
+    }
+}
+
+impl PublicKey {
+    /// The exponent s of the plaintext space Z_{n^s}
+    pub fn s(&self) -> u32 {
+        self.s
+    }
+
+    /// The plaintext modulus n^s
+    pub fn plaintext_modulus(&self) -> &Integer {
+        &self.ns
+    }
+
+    pub fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Ciphertext {
+        self.encrypt_with_randomness(m, rand).0
+    }
+
+    /// Encrypts `m` as (n+1)^m * r^{n^s} mod n^{s+1} and additionally returns r.
+    pub fn encrypt_with_randomness(
+        &self,
+        m: Plaintext,
+        rand: &mut dyn MutRandState,
+    ) -> (Ciphertext, Randomness) {
+        let r = random_in_mult_group(&self.n, UnitCheck::Skip, rand);
+        let mut rop = self.g.clone().pow_mod(m.as_ref(), &self.ns1).unwrap();
+        rop *= Integer::from(r.pow_mod_ref(&self.ns, &self.ns1).unwrap());
+        rop %= &self.ns1;
+        (rop.into(), r.into())
+    }
+
+    pub fn reencrypt(&self, cipher: &mut Ciphertext, rand: &mut dyn MutRandState) {
+        let cipher = cipher.as_mut();
+        let mut tmp = random_in_mult_group(&self.n, UnitCheck::Skip, rand);
+        tmp.pow_mod_mut(&self.ns, &self.ns1).unwrap();
+        *cipher *= tmp;
+        *cipher %= &self.ns1;
+    }
+
+    pub fn add_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        let cipher = cipher.as_mut();
+        let tmp = self.g.clone().pow_mod(plain.as_ref(), &self.ns1).unwrap();
+        *cipher *= tmp;
+        *cipher %= &self.ns1;
+    }
+
+    pub fn add_encrypted(&self, cipher1: &mut Ciphertext, cipher2: &Ciphertext) {
+        *cipher1.as_mut() *= cipher2.as_ref();
+        *cipher1.as_mut() %= &self.ns1;
+    }
+
+    pub fn mul_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        cipher
+            .as_mut()
+            .pow_mod_mut(plain.as_ref(), &self.ns1)
+            .unwrap();
+    }
+
+    pub fn share_combine(&self, shares: &[PartialDecryption]) -> Result<Plaintext> {
+        let ids: Vec<_> = shares.iter().map(|share| share.id).collect();
+        util::check_evaluation_points(&ids)?;
+        let bases: Vec<&Integer> = shares.iter().map(|share| &share.val).collect();
+        let (lambdas, scale) = util::lagrange_coefficients(&self.delta, &ids);
+        let exps: Vec<Integer> = lambdas.into_iter().map(|lambda| lambda * 2).collect();
+        let cprime = util::multi_pow_mod_par(&bases, &exps, &self.ns1)
+            .ok_or_else(|| anyhow!("partial decryption is not invertible"))?;
+        let t = self.dlog(&cprime);
+        let mut rop: Integer = t * &self.combine_shares_constant % &self.ns;
+        if scale != 1 {
+            let scale_inv = scale
+                .invert(&self.ns)
+                .map_err(|_| anyhow!("evaluation points are not invertible mod n"))?;
+            rop *= scale_inv;
+            rop %= &self.ns;
+        }
+        Ok(rop.into())
+    }
+
+    /// Computes i mod n^s from a = (n+1)^i mod n^{s+1} using the recursive
+    /// algorithm from the Damgård-Jurik paper, which extracts i mod n^j from
+    /// L(a mod n^{j+1}) = (a mod n^{j+1} - 1) / n for j = 1..s.
+    fn dlog(&self, a: &Integer) -> Integer {
+        let mut i = Integer::new();
+        let mut nj = Integer::from(1);
+        for j in 1..=self.s {
+            // nj = n^j
+            nj *= &self.n;
+            let nj1 = (&nj * &self.n).complete();
+            let mut t1 = Integer::from(a % &nj1) - 1;
+            t1 /= &self.n;
+            let mut t2 = i.clone();
+            let mut n_pow = Integer::from(1);
+            let mut k_fac = Integer::from(1);
+            for k in 2..=j {
+                i -= 1;
+                t2 *= &i;
+                t2 %= &nj;
+                // n_pow = n^{k-1}, k_fac = k!
+                n_pow *= &self.n;
+                k_fac *= k;
+                let k_fac_inv = k_fac.clone().invert(&nj).unwrap();
+                let tmp = (&t2 * &n_pow).complete() * k_fac_inv;
+                t1 -= tmp;
+                t1 %= &nj;
+            }
+            if t1 < 0 {
+                t1 += &nj;
+            }
+            i = t1;
+        }
+        i
+    }
+}
+
+impl PrivateKey {
+    pub fn share(
+        self,
+        server_indices: &[u32],
+        rand_state: &mut dyn MutRandState,
+    ) -> Vec<PrivateKeyShare> {
+        assert_eq!(
+            server_indices.len(),
+            self.w as usize,
+            "share() must be called with w unique indices"
+        );
+        let poly = Polynomial::new(&self, rand_state);
+        par::map_collect(server_indices, |_, idx| poly.compute(*idx))
+    }
+}
+
+impl<'a> Polynomial<'a> {
+    pub fn new<'b>(sk: &'a PrivateKey, rand: &'b mut dyn MutRandState) -> Self {
+        let mut coefficients = vec![sk.nsm.clone(); sk.w as usize];
+        coefficients[0] = sk.d.clone();
+        for coeff in coefficients.iter_mut().skip(1) {
+            coeff.random_below_mut(rand);
+        }
+        Self { sk, coefficients }
+    }
+
+    pub fn compute(&self, x: u32) -> PrivateKeyShare {
+        let mut rop = self.coefficients[0].clone();
+        for (i, coeff) in self.coefficients.iter().enumerate().skip(1) {
+            let mut tmp = Integer::u_pow_u(x + 1, i.try_into().unwrap()).complete();
+            tmp *= coeff;
+            rop += tmp;
+            rop %= &self.sk.nsm;
+        }
+        PrivateKeyShare::new(rop, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::damgard_jurik::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_invalid_threshold() {
+        assert!(generate_key_pair(128, 1, 3, 0).is_err());
+        assert!(generate_key_pair(128, 1, 3, 4).is_err());
+        assert!(generate_key_pair(128, 1, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_large_plaintext() {
+        let (pk, sk) = generate_key_pair(128, 3, 3, 3).unwrap();
+        let mut rand = RandState::new();
+        // larger than n but smaller than n^3
+        let m: Integer = Integer::from(pk.n.square_ref()) + 42;
+        let c = pk.encrypt(m.clone().into(), &mut rand);
+        let key_shares = sk.share(&[0, 1, 2], &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|key_share| key_share.share_decrypt(&pk, c.clone()))
+            .collect();
+        let combined = pk.share_combine(&shares).unwrap();
+        assert_eq!(combined, m);
+    }
+
+    #[test]
+    fn test_homomorphic_ops_lower_threshold() {
+        let (pk, sk) = generate_key_pair(128, 2, 3, 2).unwrap();
+        let mut rand = RandState::new();
+        let mut c = pk.encrypt(10.into(), &mut rand);
+        let c2 = pk.encrypt(5.into(), &mut rand);
+        pk.add_encrypted(&mut c, &c2);
+        pk.mul_plain(&mut c, &3.into());
+        pk.add_plain(&mut c, &1.into());
+        pk.reencrypt(&mut c, &mut rand);
+        let key_shares = sk.share(&[0, 2], &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|key_share| key_share.share_decrypt(&pk, c.clone()))
+            .collect();
+        let combined = pk.share_combine(&shares).unwrap();
+        assert_eq!(combined, 46);
+    }
+}
@@ -0,0 +1,409 @@
+//! Trusted dealer for threshold Paillier keys.
+//!
+//! The dealer generates the key pair and deals one key share to each of the l
+//! decryption servers. With the `sealed` feature, [`Dealer::deal_sealed`] encrypts
+//! each share to the X25519 transport key of its server, so shares never travel in
+//! plaintext: an ephemeral X25519 key agreement yields the ChaCha20-Poly1305 key
+//! SHA3-256(label || ephemeral pk || recipient pk || shared secret) which encrypts
+//! the versioned encoding of the share.
+//!
+//! Dealing to many servers can be interrupted. [`Dealer::deal_to`] deals the shares
+//! one by one from a polynomial that is sampled once, and with the `sealed` feature
+//! [`Dealer::checkpoint`] encrypts the dealer's state including the polynomial under a
+//! passphrase, see [`crate::sealed`]. [`Dealer::resume`] continues with the same
+//! polynomial, so shares dealt before and after the interruption fit together.
+//!
+//! ```
+//! # #[cfg(feature = "sealed")] {
+//! use pht_crypto::dealer::Dealer;
+//! use pht_crypto::sealed::KdfParams;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let mut dealer = Dealer::new(256, 3, 2).unwrap();
+//! let first = dealer.deal_to(0, &mut rand).unwrap();
+//! // cheap parameters to keep the example fast, use KdfParams::default() in production
+//! let params = KdfParams { m_cost: 1024, t_cost: 1, p_cost: 1 };
+//! let checkpoint = dealer.checkpoint(b"passphrase", params, &mut rand).unwrap();
+//!
+//! let mut dealer = Dealer::resume(&checkpoint, b"passphrase").unwrap();
+//! assert_eq!(dealer.remaining(), [1, 2]);
+//! let second = dealer.deal_to(1, &mut rand).unwrap();
+//!
+//! let pk = dealer.public_key();
+//! let c = pk.encrypt(7.into(), &mut rand);
+//! let partials = [first.share_decrypt(pk, c.clone()), second.share_decrypt(pk, c)];
+//! assert_eq!(pk.share_combine(&partials).unwrap(), 7);
+//! # }
+//! ```
+
+use crate::paillier::{self, Polynomial, PrivateKey, PrivateKeyShare, PublicKey};
+#[cfg(feature = "sealed")]
+use crate::rand::random_bytes;
+#[cfg(feature = "sealed")]
+use crate::sealed::{self, KdfParams, SealedKey};
+use crate::threshold_rsa::{self, SigningShare, VerificationKey};
+#[cfg(feature = "sealed")]
+use crate::wire::Versioned;
+#[cfg(feature = "sealed")]
+use anyhow::anyhow;
+use anyhow::{ensure, Result};
+#[cfg(feature = "sealed")]
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+#[cfg(feature = "sealed")]
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rug::rand::MutRandState;
+use rug::Integer;
+#[cfg(feature = "sealed")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "sealed")]
+use sha3::{Digest, Sha3_256};
+use std::collections::BTreeSet;
+#[cfg(feature = "sealed")]
+use std::convert::TryInto;
+#[cfg(feature = "sealed")]
+use x25519_dalek::{PublicKey as TransportPublicKey, StaticSecret};
+#[cfg(feature = "sealed")]
+use zeroize::Zeroizing;
+
+#[cfg(feature = "sealed")]
+const KDF_LABEL: &[u8] = b"pht-crypto/dealer/sealed-share";
+
+pub struct Dealer {
+    pk: PublicKey,
+    sk: PrivateKey,
+    /// Statistical security parameter if the shares are dealt over the integers
+    security_bits: Option<u32>,
+    /// Coefficients of the polynomial of [`Dealer::deal_to`], empty until the first
+    /// share is dealt
+    coefficients: Vec<Integer>,
+    /// Zero based indices of the servers dealt to with [`Dealer::deal_to`]
+    dealt: BTreeSet<u32>,
+}
+
+/// The state of a [`Dealer`], only ever stored sealed under a passphrase
+#[cfg(feature = "sealed")]
+#[derive(Serialize, Deserialize)]
+pub(crate) struct DealerCheckpoint {
+    pk: PublicKey,
+    sk: PrivateKey,
+    security_bits: Option<u32>,
+    #[serde(with = "crate::util::serde_integer_vec")]
+    coefficients: Vec<Integer>,
+    dealt: BTreeSet<u32>,
+}
+
+/// A key share encrypted to the transport key of its server
+#[cfg(feature = "sealed")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedShare {
+    /// Zero based index of the receiving server
+    server: u32,
+    ephemeral: [u8; 32],
+    ciphertext: Vec<u8>,
+}
+
+impl Dealer {
+    /// Generates a fresh `bits` bit key for `threshold` of `decryption_servers` servers
+    pub fn new(bits: usize, decryption_servers: u32, threshold: u32) -> Result<Self> {
+        let (pk, sk) = paillier::generate_key_pair(bits, decryption_servers, threshold)?;
+        Ok(Self::from_key_pair(pk, sk))
+    }
+
+    pub fn from_key_pair(pk: PublicKey, sk: PrivateKey) -> Self {
+        Self {
+            pk,
+            sk,
+            security_bits: None,
+            coefficients: Vec::new(),
+            dealt: BTreeSet::new(),
+        }
+    }
+
+    /// Deals shares of a polynomial over the integers, see
+    /// [`Polynomial::new_statistical`]
+    pub fn with_statistical_hiding(mut self, security_bits: u32) -> Self {
+        self.security_bits = Some(security_bits);
+        self
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.pk
+    }
+
+    /// Deals the shares of all l servers, the i-th share belongs to server i. Every
+    /// call samples a new polynomial, independent of [`Dealer::deal_to`].
+    pub fn deal(&self, rand: &mut dyn MutRandState) -> Vec<PrivateKeyShare> {
+        trace_span!("deal", servers = self.pk.l);
+        let poly = self.sample_polynomial(rand);
+        (0..self.pk.l).map(|i| poly.compute(i)).collect()
+    }
+
+    /// Deals threshold RSA signing shares for the same modulus, see
+    /// [`threshold_rsa::deal`]
+    pub fn deal_signing_shares(
+        &self,
+        rand: &mut dyn MutRandState,
+    ) -> Result<(VerificationKey, Vec<SigningShare>)> {
+        threshold_rsa::deal(&self.pk, &self.sk, rand)
+    }
+
+    fn sample_polynomial(&self, rand: &mut dyn MutRandState) -> Polynomial<'_> {
+        match self.security_bits {
+            Some(bits) => Polynomial::new_statistical(&self.sk, bits, rand),
+            None => Polynomial::new(&self.sk, rand),
+        }
+    }
+
+    /// Deals the share of the server with zero based index `server` and records it as
+    /// dealt. All shares of this dealer, also after [`Dealer::resume`], come from the
+    /// same polynomial, so dealing a share again yields the same share.
+    pub fn deal_to(&mut self, server: u32, rand: &mut dyn MutRandState) -> Result<PrivateKeyShare> {
+        ensure!(
+            server < self.pk.l,
+            "server index {} is out of range",
+            server
+        );
+        trace_span!("deal_to", server);
+        if self.coefficients.is_empty() {
+            self.coefficients = self.sample_polynomial(rand).coefficients;
+        }
+        let poly = Polynomial {
+            sk: &self.sk,
+            coefficients: self.coefficients.clone(),
+            reduce: self.security_bits.is_none(),
+        };
+        self.dealt.insert(server);
+        Ok(poly.compute(server))
+    }
+
+    /// Zero based indices of the servers which were not dealt to with
+    /// [`Dealer::deal_to`] yet
+    pub fn remaining(&self) -> Vec<u32> {
+        (0..self.pk.l)
+            .filter(|server| !self.dealt.contains(server))
+            .collect()
+    }
+}
+
+#[cfg(feature = "sealed")]
+impl Dealer {
+    /// Like [`Dealer::deal_to`] but encrypts the share to the server's transport key
+    pub fn deal_sealed_to(
+        &mut self,
+        server: u32,
+        recipient: &TransportPublicKey,
+        rand: &mut dyn MutRandState,
+    ) -> Result<SealedShare> {
+        let share = self.deal_to(server, rand)?;
+        SealedShare::seal(server, &share, recipient, rand)
+    }
+
+    /// Encrypts the dealer's state under `passphrase`. The checkpoint contains the
+    /// private key and the polynomial, anyone who can unseal it can forge every share.
+    pub fn checkpoint(
+        &self,
+        passphrase: &[u8],
+        params: KdfParams,
+        rand: &mut dyn MutRandState,
+    ) -> Result<SealedKey> {
+        let checkpoint = DealerCheckpoint {
+            pk: self.pk.clone(),
+            sk: self.sk.clone(),
+            security_bits: self.security_bits,
+            coefficients: self.coefficients.clone(),
+            dealt: self.dealt.clone(),
+        };
+        sealed::seal(&checkpoint, passphrase, params, rand)
+    }
+
+    /// Restores a dealer from a checkpoint
+    pub fn resume(sealed: &SealedKey, passphrase: &[u8]) -> Result<Self> {
+        let checkpoint: DealerCheckpoint = sealed::unseal(sealed, passphrase)?;
+        ensure!(
+            checkpoint.coefficients.is_empty()
+                || checkpoint.coefficients.len() == checkpoint.pk.w as usize,
+            "checkpoint has an invalid polynomial"
+        );
+        Ok(Self {
+            pk: checkpoint.pk,
+            sk: checkpoint.sk,
+            security_bits: checkpoint.security_bits,
+            coefficients: checkpoint.coefficients,
+            dealt: checkpoint.dealt,
+        })
+    }
+
+    /// Deals the shares of all l servers, each encrypted to the corresponding
+    /// transport key in `recipients`
+    pub fn deal_sealed(
+        &self,
+        recipients: &[TransportPublicKey],
+        rand: &mut dyn MutRandState,
+    ) -> Result<Vec<SealedShare>> {
+        ensure!(
+            recipients.len() == self.pk.l as usize,
+            "expected {} recipients",
+            self.pk.l
+        );
+        self.deal(rand)
+            .iter()
+            .zip(recipients)
+            .enumerate()
+            .map(|(server, (share, recipient))| {
+                SealedShare::seal(server as u32, share, recipient, rand)
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "sealed")]
+impl SealedShare {
+    fn seal(
+        server: u32,
+        share: &PrivateKeyShare,
+        recipient: &TransportPublicKey,
+        rand: &mut dyn MutRandState,
+    ) -> Result<Self> {
+        let secret: [u8; 32] = random_bytes(32, rand).try_into().unwrap();
+        let ephemeral = StaticSecret::from(secret);
+        let ephemeral_pk = TransportPublicKey::from(&ephemeral);
+        let cipher = share_cipher(
+            &ephemeral.diffie_hellman(recipient).to_bytes(),
+            &ephemeral_pk,
+            recipient,
+        );
+        let plaintext = Zeroizing::new(share.to_versioned_bytes());
+        let ciphertext = cipher
+            .encrypt(
+                &Nonce::default(),
+                Payload {
+                    msg: &plaintext,
+                    aad: &server.to_be_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("encryption failed"))?;
+        Ok(Self {
+            server,
+            ephemeral: ephemeral_pk.to_bytes(),
+            ciphertext,
+        })
+    }
+
+    /// Zero based index of the server this share is meant for
+    pub fn server(&self) -> u32 {
+        self.server
+    }
+
+    /// Decrypts the share with the server's transport secret key
+    pub fn open(&self, secret: &StaticSecret) -> Result<PrivateKeyShare> {
+        let ephemeral_pk = TransportPublicKey::from(self.ephemeral);
+        let recipient = TransportPublicKey::from(secret);
+        let cipher = share_cipher(
+            &secret.diffie_hellman(&ephemeral_pk).to_bytes(),
+            &ephemeral_pk,
+            &recipient,
+        );
+        let plaintext = cipher
+            .decrypt(
+                &Nonce::default(),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: &self.server.to_be_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("share is not sealed to this key or was tampered with"))?;
+        let share = PrivateKeyShare::from_versioned_bytes(&Zeroizing::new(plaintext))?;
+        ensure!(share.i == self.server + 1, "share index does not match");
+        Ok(share)
+    }
+}
+
+/// Every ephemeral key is used for a single share, so a constant nonce is safe
+#[cfg(feature = "sealed")]
+fn share_cipher(
+    shared_secret: &[u8; 32],
+    ephemeral: &TransportPublicKey,
+    recipient: &TransportPublicKey,
+) -> ChaCha20Poly1305 {
+    let mut hasher = Sha3_256::new();
+    hasher.update(KDF_LABEL);
+    hasher.update(ephemeral.as_bytes());
+    hasher.update(recipient.as_bytes());
+    hasher.update(shared_secret);
+    let key: Zeroizing<[u8; 32]> = Zeroizing::new(hasher.finalize().into());
+    ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+}
+
+#[cfg(all(test, feature = "sealed"))]
+mod tests {
+    use super::Dealer;
+    use crate::paillier::PrivateKey;
+    use crate::rand::random_bytes;
+    use crate::sealed::KdfParams;
+    use rug::rand::RandState;
+    use std::convert::TryInto;
+    use x25519_dalek::{PublicKey, StaticSecret};
+
+    #[test]
+    fn test_deal_sealed() {
+        let mut rand = RandState::new();
+        let dealer = Dealer::new(256, 3, 2).unwrap();
+        let secrets: Vec<_> = (0..3)
+            .map(|_| {
+                let bytes: [u8; 32] = random_bytes(32, &mut rand).try_into().unwrap();
+                StaticSecret::from(bytes)
+            })
+            .collect();
+        let recipients: Vec<_> = secrets.iter().map(PublicKey::from).collect();
+
+        let sealed = dealer.deal_sealed(&recipients, &mut rand).unwrap();
+        assert!(dealer.deal_sealed(&recipients[..2], &mut rand).is_err());
+        assert!(sealed[0].open(&secrets[1]).is_err());
+        let shares: Vec<_> = sealed
+            .iter()
+            .zip(&secrets)
+            .map(|(s, secret)| s.open(secret).unwrap())
+            .collect();
+
+        let pk = dealer.public_key();
+        let c = pk.encrypt(7.into(), &mut rand);
+        let partials: Vec<_> = shares[1..]
+            .iter()
+            .map(|s| s.share_decrypt(pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&partials).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_resume_dealing() {
+        let mut rand = RandState::new();
+        let params = KdfParams {
+            m_cost: 1024,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        let mut dealer = Dealer::new(256, 3, 2).unwrap().with_statistical_hiding(40);
+        let sealed = dealer.checkpoint(b"passphrase", params, &mut rand).unwrap();
+        assert!(Dealer::resume(&sealed, b"wrong").is_err());
+        assert!(PrivateKey::unseal(&sealed, b"passphrase").is_err());
+        let mut fresh = Dealer::resume(&sealed, b"passphrase").unwrap();
+        assert_eq!(fresh.remaining(), [0, 1, 2]);
+        assert!(fresh.deal_to(3, &mut rand).is_err());
+
+        let first = dealer.deal_to(2, &mut rand).unwrap();
+        let sealed = dealer.checkpoint(b"passphrase", params, &mut rand).unwrap();
+        let mut resumed = Dealer::resume(&sealed, b"passphrase").unwrap();
+        assert_eq!(resumed.remaining(), [0, 1]);
+        assert_eq!(resumed.deal_to(2, &mut rand).unwrap().si, first.si);
+        let second = resumed.deal_to(0, &mut rand).unwrap();
+
+        let pk = resumed.public_key();
+        let c = pk.encrypt(9.into(), &mut rand);
+        let partials = [
+            first.share_decrypt(pk, c.clone()),
+            second.share_decrypt(pk, c),
+        ];
+        assert_eq!(pk.share_combine(&partials).unwrap(), 9);
+    }
+}
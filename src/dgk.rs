@@ -0,0 +1,366 @@
+//! DGK encryption and the DGK secure comparison protocol.
+//!
+//! DGK has a small plaintext space Z_u for a small prime u, but allows the owner of
+//! the private key to check whether a ciphertext encrypts zero with a single
+//! exponentiation. This makes it the basis for efficient two-party comparisons of
+//! private integers, which are needed for e.g. encrypted median, min or argmax
+//! computations.
+//!
+//! Source: Damgård, Geisler, Krøigaard "Efficient and Secure Comparison for On-Line
+//! Auctions" and "A correction to 'Efficient and secure comparison for on-line auctions'"
+//!
+//! # Comparison
+//! Party B owns the key pair and the input y, party A the input x. Both inputs are
+//! `bits` bit unsigned integers.
+//! 1. B sends the bitwise encryption of y with [`request_comparison`].
+//! 2. A answers with [`respond_comparison`] and keeps its share δ_A of the result.
+//! 3. B computes its share δ_B with [`PrivateKey::evaluate_comparison`].
+//!
+//! Afterwards δ_A xor δ_B = [x < y], while neither party learns anything else. If A
+//! should obtain the result encrypted under B's key instead, B sends Enc(δ_B) to A which
+//! calls [`combine_comparison`].
+
+use crate::rand::{generate_prime, random_in_mult_group, UnitCheck};
+use crate::{util, Ciphertext, Plaintext};
+use anyhow::{anyhow, ensure, Result};
+use rug::integer::IsPrime;
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Bit length of the secret subgroup orders v_p and v_q
+const SUBGROUP_BITS: u32 = 160;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PublicKey {
+    /// Modulus of the key. n = p * q
+    #[serde(with = "crate::util::serde_integer")]
+    n: Integer,
+    /// Element of order u * v_p * v_q
+    #[serde(with = "crate::util::serde_integer")]
+    g: Integer,
+    /// Element of order v_p * v_q
+    #[serde(with = "crate::util::serde_integer")]
+    h: Integer,
+    /// Small prime plaintext modulus
+    #[serde(with = "crate::util::serde_integer")]
+    u: Integer,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrivateKey {
+    p: Integer,
+    vp: Integer,
+    /// Precomputation: g^{v_p * m} mod p -> m for m in Z_u
+    decryption_table: HashMap<Integer, u64>,
+}
+
+/// Generates a key pair with a `bits` bit modulus that can be used to compare
+/// integers of up to `plaintext_bits` bits.
+pub fn generate_key_pair(
+    bits: usize,
+    plaintext_bits: u32,
+    rand: &mut dyn MutRandState,
+) -> Result<(PublicKey, PrivateKey)> {
+    ensure!(
+        bits / 2 > SUBGROUP_BITS as usize + 64,
+        "modulus is too small for the subgroup size"
+    );
+    // the comparison computes values up to 3 * (plaintext_bits + 1) + 2 which must not wrap
+    let u = Integer::from(3 * (plaintext_bits + 1) + 3).next_prime();
+    let (p, vp) = generate_dgk_prime(bits / 2, &u, rand)?;
+    let (q, vq) = loop {
+        let (q, vq) = generate_dgk_prime(bits / 2, &u, rand)?;
+        if q != p {
+            break (q, vq);
+        }
+    };
+    let n = (&p * &q).complete();
+
+    let uvp = (&u * &vp).complete();
+    let uvq = (&u * &vq).complete();
+    let g_p = element_of_order(&p, &[&u, &vp], rand);
+    let g_q = element_of_order(&q, &[&u, &vq], rand);
+    let g = util::crt2(&g_p, &p, &g_q, &q);
+    let h_p = element_of_order(&p, &[&vp], rand);
+    let h_q = element_of_order(&q, &[&vq], rand);
+    let h = util::crt2(&h_p, &p, &h_q, &q);
+    debug_assert!(g.pow_mod_ref(&uvp, &p).map(Integer::from) == Some(Integer::from(1)));
+    debug_assert!(g.pow_mod_ref(&uvq, &q).map(Integer::from) == Some(Integer::from(1)));
+
+    let base = g_p.pow_mod(&vp, &p).unwrap();
+    let mut decryption_table = HashMap::new();
+    let mut acc = Integer::from(1);
+    for m in 0..u.to_u64().unwrap() {
+        decryption_table.insert(acc.clone(), m);
+        acc *= &base;
+        acc %= &p;
+    }
+
+    let pk = PublicKey { n, g, h, u };
+    let sk = PrivateKey {
+        p,
+        vp,
+        decryption_table,
+    };
+    Ok((pk, sk))
+}
+
+/// Generates a prime p = 2 * u * v * r + 1 of about `bits` bits with a random
+/// SUBGROUP_BITS bit prime v and returns (p, v).
+fn generate_dgk_prime(
+    bits: usize,
+    u: &Integer,
+    rand: &mut dyn MutRandState,
+) -> Result<(Integer, Integer)> {
+    let v = generate_prime(SUBGROUP_BITS as usize)?;
+    let uv2: Integer = Integer::from(u * &v) * 2;
+    let r_bits = bits as u32 - uv2.significant_bits();
+    loop {
+        let mut r = Integer::from(Integer::random_bits(r_bits, rand));
+        r.set_bit(r_bits - 1, true);
+        let p: Integer = Integer::from(&uv2 * &r) + 1;
+        if p.is_probably_prime(30) != IsPrime::No {
+            break Ok((p, v));
+        }
+    }
+}
+
+/// Finds an element of Z*_p whose order is the product of the given distinct primes,
+/// which must all divide p - 1.
+fn element_of_order(p: &Integer, primes: &[&Integer], rand: &mut dyn MutRandState) -> Integer {
+    let order: Integer = primes.iter().map(|x| (*x).clone()).product();
+    let cofactor = Integer::from(p - 1) / &order;
+    loop {
+        let x = random_in_mult_group(p, UnitCheck::Skip, rand);
+        let y = x.pow_mod(&cofactor, p).unwrap();
+        let full_order = primes.iter().all(|prime| {
+            let e = Integer::from(&order / *prime);
+            y.pow_mod_ref(&e, p).map(Integer::from) != Some(Integer::from(1))
+        });
+        if full_order {
+            break y;
+        }
+    }
+}
+
+impl PublicKey {
+    /// The plaintext modulus u
+    pub fn plaintext_modulus(&self) -> &Integer {
+        &self.u
+    }
+
+    /// Encrypts m mod u as g^m * h^r mod n with a 2.5 * 160 bit random r
+    pub fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Ciphertext {
+        let m = Integer::from(m.as_ref() % &self.u);
+        let mut rop = self.g.pow_mod_ref(&m, &self.n).unwrap().complete();
+        rop *= self.blinding_factor(rand);
+        rop %= &self.n;
+        rop.into()
+    }
+
+    pub fn reencrypt(&self, cipher: &mut Ciphertext, rand: &mut dyn MutRandState) {
+        let cipher = cipher.as_mut();
+        *cipher *= self.blinding_factor(rand);
+        *cipher %= &self.n;
+    }
+
+    pub fn add_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        let m = Integer::from(plain.as_ref() % &self.u);
+        let cipher = cipher.as_mut();
+        *cipher *= Integer::from(self.g.pow_mod_ref(&m, &self.n).unwrap());
+        *cipher %= &self.n;
+    }
+
+    pub fn add_encrypted(&self, cipher1: &mut Ciphertext, cipher2: &Ciphertext) {
+        *cipher1.as_mut() *= cipher2.as_ref();
+        *cipher1.as_mut() %= &self.n;
+    }
+
+    pub fn mul_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        let k = Integer::from(plain.as_ref() % &self.u);
+        cipher.as_mut().pow_mod_mut(&k, &self.n).unwrap();
+    }
+
+    /// Computes Enc(-m) from Enc(m)
+    fn negate(&self, cipher: &Ciphertext) -> Ciphertext {
+        cipher.as_ref().clone().invert(&self.n).unwrap().into()
+    }
+
+    fn blinding_factor(&self, rand: &mut dyn MutRandState) -> Integer {
+        let r = Integer::from(Integer::random_bits(SUBGROUP_BITS * 5 / 2, rand));
+        self.h.pow_mod_ref(&r, &self.n).unwrap().into()
+    }
+}
+
+impl PrivateKey {
+    /// Checks whether `cipher` encrypts 0, which is the case iff c^{v_p} = 1 mod p.
+    pub fn is_zero(&self, cipher: &Ciphertext) -> bool {
+        cipher
+            .as_ref()
+            .pow_mod_ref(&self.vp, &self.p)
+            .map(Integer::from)
+            == Some(Integer::from(1))
+    }
+
+    pub fn decrypt(&self, cipher: &Ciphertext) -> Result<Plaintext> {
+        let c = util::secure_pow_mod(cipher.as_ref(), &self.vp, &self.p);
+        self.decryption_table
+            .get(&c)
+            .map(|m| Plaintext::from(*m))
+            .ok_or_else(|| anyhow!("invalid ciphertext"))
+    }
+
+    /// Computes the share δ_B of the comparison result from A's response. δ_B is true
+    /// iff one of the response ciphertexts encrypts 0.
+    pub fn evaluate_comparison(&self, response: &ComparisonResponse) -> bool {
+        response.ciphers.iter().any(|c| self.is_zero(c))
+    }
+}
+
+/// Bitwise encryption of B's input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonRequest {
+    /// Encryptions of the bits of 2y, least significant first
+    bits: Vec<Ciphertext>,
+}
+
+/// Blinded and permuted ciphertexts of A, one of which encrypts 0 depending on the result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComparisonResponse {
+    ciphers: Vec<Ciphertext>,
+}
+
+/// Computes B's first message, the bitwise encryption of its `bits` bit input `y`.
+pub fn request_comparison(
+    pk: &PublicKey,
+    y: &Integer,
+    bits: u32,
+    rand: &mut dyn MutRandState,
+) -> Result<ComparisonRequest> {
+    ensure!(
+        *y >= 0 && y.significant_bits() <= bits,
+        "input does not fit in {} bits",
+        bits
+    );
+    // compare 2x + 1 with 2y so the inputs are never equal
+    let y = Integer::from(y << 1);
+    let bits = (0..=bits)
+        .map(|i| pk.encrypt(u8::from(y.get_bit(i)).into(), rand))
+        .collect();
+    Ok(ComparisonRequest { bits })
+}
+
+/// Computes A's answer to B's `request` for its `bits` bit input `x` and returns it
+/// together with A's share δ_A of the result.
+pub fn respond_comparison(
+    pk: &PublicKey,
+    x: &Integer,
+    bits: u32,
+    request: &ComparisonRequest,
+    rand: &mut dyn MutRandState,
+) -> Result<(ComparisonResponse, bool)> {
+    ensure!(
+        *x >= 0 && x.significant_bits() <= bits,
+        "input does not fit in {} bits",
+        bits
+    );
+    ensure!(
+        request.bits.len() == bits as usize + 1,
+        "request has the wrong number of bits"
+    );
+    let x: Integer = Integer::from(x << 1) + 1;
+    // with s = 1 a zero appears iff x < y, with s = -1 iff x > y
+    let delta_a = Integer::from(Integer::random_bits(1, rand)) == 1;
+    let s: i32 = if delta_a { -1 } else { 1 };
+
+    // Enc(x_j xor y_j) for every bit j
+    let xors: Vec<_> = request
+        .bits
+        .iter()
+        .enumerate()
+        .map(|(j, y_j)| {
+            if x.get_bit(j as u32) {
+                let mut c = pk.negate(y_j);
+                pk.add_plain(&mut c, &1.into());
+                c
+            } else {
+                y_j.clone()
+            }
+        })
+        .collect();
+
+    let mut ciphers = Vec::with_capacity(request.bits.len());
+    // Enc(3 * sum_{j > i} (x_j xor y_j))
+    let mut suffix: Ciphertext = Integer::from(1).into();
+    for i in (0..request.bits.len()).rev() {
+        // Enc(s + x_i - y_i + 3 * sum_{j > i} (x_j xor y_j))
+        let mut c = pk.negate(&request.bits[i]);
+        let x_i = i32::from(x.get_bit(i as u32));
+        pk.add_plain(&mut c, &(s + x_i).into());
+        let mut tmp = suffix.clone();
+        pk.mul_plain(&mut tmp, &3.into());
+        pk.add_encrypted(&mut c, &tmp);
+        // blind the non zero values and re-randomize
+        let r: Integer = Integer::from(Integer::from(&pk.u - 1).random_below_ref(rand)) + 1;
+        pk.mul_plain(&mut c, &r.into());
+        pk.reencrypt(&mut c, rand);
+        ciphers.push(c);
+
+        pk.add_encrypted(&mut suffix, &xors[i]);
+    }
+    // Fisher-Yates shuffle, so B does not learn the position of the first differing bit
+    for i in (1..ciphers.len()).rev() {
+        let j = Integer::from(i + 1).random_below(rand).to_usize().unwrap();
+        ciphers.swap(i, j);
+    }
+    Ok((ComparisonResponse { ciphers }, delta_a))
+}
+
+/// Computes Enc([x < y]) = Enc(δ_A xor δ_B) from A's share `delta_a` and Enc(δ_B).
+pub fn combine_comparison(pk: &PublicKey, delta_a: bool, delta_b: &Ciphertext) -> Ciphertext {
+    if delta_a {
+        let mut c = pk.negate(delta_b);
+        pk.add_plain(&mut c, &1.into());
+        c
+    } else {
+        delta_b.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{combine_comparison, generate_key_pair, request_comparison, respond_comparison};
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 8, &mut rand).unwrap();
+        let mut c = pk.encrypt(5.into(), &mut rand);
+        pk.add_plain(&mut c, &2.into());
+        pk.mul_plain(&mut c, &3.into());
+        assert_eq!(sk.decrypt(&c).unwrap(), 21);
+        assert!(!sk.is_zero(&c));
+        assert!(sk.is_zero(&pk.encrypt(0.into(), &mut rand)));
+    }
+
+    #[test]
+    fn test_comparison() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 8, &mut rand).unwrap();
+        for (x, y) in [(3, 200), (200, 3), (17, 17), (0, 0), (0, 255), (255, 254)] {
+            let request = request_comparison(&pk, &Integer::from(y), 8, &mut rand).unwrap();
+            let (response, delta_a) =
+                respond_comparison(&pk, &Integer::from(x), 8, &request, &mut rand).unwrap();
+            let delta_b = sk.evaluate_comparison(&response);
+            assert_eq!(delta_a ^ delta_b, x < y, "comparing {} < {}", x, y);
+
+            let enc_delta_b = pk.encrypt(u8::from(delta_b).into(), &mut rand);
+            let result = combine_comparison(&pk, delta_a, &enc_delta_b);
+            assert_eq!(sk.decrypt(&result).unwrap(), u8::from(x < y));
+        }
+    }
+}
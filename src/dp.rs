@@ -0,0 +1,359 @@
+//! Distributed differentially private decryption.
+//!
+//! Before releasing partial decryptions, every participating decryption server
+//! homomorphically adds its own encrypted sample of discrete Laplace (two-sided
+//! geometric) noise to the ciphertext. As every server adds noise of the full scale,
+//! the total noise is the sum of w samples.
+//!
+//! Servers first broadcast hash commitments to their noise ciphertexts and only
+//! reveal them once the commitments of all participants are in, together with a
+//! proof of plaintext knowledge bound to the server id and the session. So the noise
+//! of colluding servers is independent of that of an honest server, and the output
+//! is differentially private as long as one of the participating servers is honest.
+//! The proofs don't bound the noise though, so colluding servers can still ruin the
+//! accuracy of the result.
+//!
+//! The sampler is the exact one from Canonne, Kamath, Steinke "The Discrete Gaussian
+//! for Differential Privacy" and uses only integer arithmetic.
+
+use crate::commit::{commit_hash, HashCommitment};
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::proofs::{
+    prove_plaintext_knowledge, verify_plaintext_knowledge, PlaintextKnowledgeProof,
+};
+use crate::transcript::Transcript;
+use crate::Ciphertext;
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const NOISE_LABEL: &[u8] = b"pht-crypto/dp/noise";
+
+/// Discrete Laplace distribution with P(x) ∝ exp(-|x| / scale) and a rational
+/// scale = num / den. For ε-DP of a query with sensitivity Δ use scale = Δ / ε.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DiscreteLaplace {
+    num: u64,
+    den: u64,
+}
+
+impl DiscreteLaplace {
+    pub fn new(num: u64, den: u64) -> Result<Self> {
+        ensure!(num > 0 && den > 0, "scale must be positive");
+        Ok(Self { num, den })
+    }
+
+    pub fn sample(&self, rand: &mut dyn MutRandState) -> Integer {
+        let t = Integer::from(self.num);
+        loop {
+            let u = Integer::from(t.random_below_ref(rand));
+            if !bernoulli_exp(&u, &t, rand) {
+                continue;
+            }
+            let mut v = Integer::new();
+            while bernoulli_exp(&Integer::from(1), &Integer::from(1), rand) {
+                v += 1;
+            }
+            let x = u + v * &t;
+            let y = x / self.den;
+            let negative = bernoulli(&Integer::from(1), &Integer::from(2), rand);
+            if negative && y == 0 {
+                continue;
+            }
+            break if negative { -y } else { y };
+        }
+    }
+}
+
+/// Samples Bernoulli(num / den)
+fn bernoulli(num: &Integer, den: &Integer, rand: &mut dyn MutRandState) -> bool {
+    Integer::from(den.random_below_ref(rand)) < *num
+}
+
+/// Samples Bernoulli(exp(-num / den)) for num >= 0
+fn bernoulli_exp(num: &Integer, den: &Integer, rand: &mut dyn MutRandState) -> bool {
+    let one = Integer::from(1);
+    let mut num = num.clone();
+    while num > *den {
+        if !bernoulli_exp(&one, &one, rand) {
+            return false;
+        }
+        num -= den;
+    }
+    // exp(-γ) for γ in [0, 1]
+    let mut k = Integer::from(1);
+    loop {
+        if !bernoulli(&num, &Integer::from(den * &k), rand) {
+            break k.is_odd();
+        }
+        k += 1;
+    }
+}
+
+/// Hash commitment of a decryption server to its encrypted noise sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoiseCommitment {
+    id: u32,
+    commitment: HashCommitment,
+}
+
+/// Encrypted noise sample of a decryption server, opening its [`NoiseCommitment`],
+/// with a proof that the server knows the sample
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoiseShare {
+    id: u32,
+    cipher: Ciphertext,
+    proof: PlaintextKnowledgeProof,
+    nonce: [u8; 32],
+}
+
+/// State of one decryption server while decrypting `cipher` with added noise.
+///
+/// 1. Every participating server broadcasts [`NoisyDecryptionSession::commit_noise`].
+/// 2. After receiving the commitments of all other participants via
+///    [`NoisyDecryptionSession::add_noise_commitment`], each server broadcasts
+///    [`NoisyDecryptionSession::noise_share`].
+/// 3. After receiving the noise shares of all committed participants via
+///    [`NoisyDecryptionSession::add_noise_share`], each server releases
+///    [`NoisyDecryptionSession::share_decrypt`].
+/// 4. [`NoisyDecryptionSession::combine`] yields the noisy plaintext.
+pub struct NoisyDecryptionSession<'a> {
+    pk: &'a PublicKey,
+    key_share: &'a PrivateKeyShare,
+    cipher: Ciphertext,
+    session: Vec<u8>,
+    own_share: Option<NoiseShare>,
+    revealed: bool,
+    commitments: BTreeMap<u32, HashCommitment>,
+    noise_shares: BTreeMap<u32, Ciphertext>,
+}
+
+impl<'a> NoisyDecryptionSession<'a> {
+    /// `session` must be unique to this decryption, e.g. a round id, as the noise
+    /// proofs are bound to it.
+    pub fn new(
+        pk: &'a PublicKey,
+        key_share: &'a PrivateKeyShare,
+        cipher: Ciphertext,
+        session: &[u8],
+    ) -> Self {
+        Self {
+            pk,
+            key_share,
+            cipher,
+            session: session.to_vec(),
+            own_share: None,
+            revealed: false,
+            commitments: BTreeMap::new(),
+            noise_shares: BTreeMap::new(),
+        }
+    }
+
+    /// Samples this server's noise and returns the commitment to broadcast
+    pub fn commit_noise(
+        &mut self,
+        distribution: &DiscreteLaplace,
+        rand: &mut dyn MutRandState,
+    ) -> Result<NoiseCommitment> {
+        ensure!(self.own_share.is_none(), "noise was already committed");
+        let noise = distribution.sample(rand);
+        let noise = if noise < 0 { noise + &self.pk.n } else { noise };
+        let id = self.key_share.i;
+        let (cipher, r) = self.pk.encrypt_with_randomness(noise.clone().into(), rand);
+        let proof = prove_plaintext_knowledge(
+            self.pk,
+            &mut self.transcript(id),
+            &cipher,
+            &noise.into(),
+            &r,
+            rand,
+        );
+        let (commitment, nonce) = commit_hash(&cipher.digest(), rand);
+        self.commitments.insert(id, commitment);
+        self.own_share = Some(NoiseShare {
+            id,
+            cipher,
+            proof,
+            nonce,
+        });
+        Ok(NoiseCommitment { id, commitment })
+    }
+
+    /// Adds the noise commitment of another participating server. Fails once the
+    /// own noise share was revealed.
+    pub fn add_noise_commitment(&mut self, commitment: NoiseCommitment) -> Result<()> {
+        ensure!(!self.revealed, "noise shares are already being revealed");
+        ensure!(
+            !self.commitments.contains_key(&commitment.id),
+            "server {} already sent a noise commitment",
+            commitment.id
+        );
+        self.commitments
+            .insert(commitment.id, commitment.commitment);
+        Ok(())
+    }
+
+    /// Returns the own noise share to broadcast. Fails unless the commitments of at
+    /// least w servers in total were added, after which no further commitments are
+    /// accepted.
+    pub fn noise_share(&mut self) -> Result<NoiseShare> {
+        let share = self
+            .own_share
+            .clone()
+            .ok_or_else(|| anyhow!("noise was not committed"))?;
+        ensure!(
+            self.commitments.len() >= self.pk.w as usize,
+            "at least {} noise commitments are needed",
+            self.pk.w
+        );
+        self.revealed = true;
+        self.noise_shares.insert(share.id, share.cipher.clone());
+        Ok(share)
+    }
+
+    /// Adds the noise share of another participating server after checking that it
+    /// opens the server's commitment and that the server knows its noise
+    pub fn add_noise_share(&mut self, share: NoiseShare) -> Result<()> {
+        let commitment = self
+            .commitments
+            .get(&share.id)
+            .ok_or_else(|| anyhow!("server {} did not commit to its noise", share.id))?;
+        ensure!(
+            !self.noise_shares.contains_key(&share.id),
+            "server {} already sent a noise share",
+            share.id
+        );
+        ensure!(
+            commitment.verify(&share.cipher.digest(), &share.nonce),
+            "noise share of server {} does not open its commitment",
+            share.id
+        );
+        ensure!(
+            verify_plaintext_knowledge(
+                self.pk,
+                &mut self.transcript(share.id),
+                &share.cipher,
+                &share.proof
+            ),
+            "invalid noise proof of server {}",
+            share.id
+        );
+        self.noise_shares.insert(share.id, share.cipher);
+        Ok(())
+    }
+
+    /// The ciphertext with the noise of all participants added
+    pub fn noisy_cipher(&self) -> Ciphertext {
+        let mut cipher = self.cipher.clone();
+        for noise in self.noise_shares.values() {
+            self.pk.add_encrypted(&mut cipher, noise);
+        }
+        cipher
+    }
+
+    /// Partially decrypts the noisy ciphertext. Fails unless the noise shares of all
+    /// committed servers, including the own one, were added.
+    pub fn share_decrypt(&self) -> Result<PartialDecryption> {
+        ensure!(self.revealed, "own noise share was not revealed");
+        ensure!(
+            self.noise_shares.len() == self.commitments.len(),
+            "noise shares of committed servers are missing"
+        );
+        Ok(self.key_share.share_decrypt(self.pk, self.noisy_cipher()))
+    }
+
+    /// Combines the partial decryptions of the participants into the noisy result,
+    /// interpreting values above n / 2 as negative.
+    pub fn combine(&self, partials: &[PartialDecryption]) -> Result<Integer> {
+        let ids: Vec<_> = partials.iter().map(|p| p.id).collect();
+        ensure!(
+            ids.iter().all(|id| self.noise_shares.contains_key(id)),
+            "partial decryption of a server without noise share"
+        );
+        let m: Integer = self.pk.share_combine(partials)?.into();
+        let half = Integer::from(&self.pk.n >> 1);
+        Ok(if m > half { m - &self.pk.n } else { m })
+    }
+
+    /// Transcript binding the noise proof of server `id` to this session
+    fn transcript(&self, id: u32) -> Transcript {
+        let mut transcript = Transcript::new(NOISE_LABEL);
+        transcript.append_message(b"session", &self.session);
+        transcript.append_message(b"cipher", &self.cipher.digest());
+        transcript.append_u64(b"server", id.into());
+        transcript
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiscreteLaplace, NoisyDecryptionSession};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_discrete_laplace() {
+        let mut rand = RandState::new();
+        let dist = DiscreteLaplace::new(3, 1).unwrap();
+        let samples: Vec<Integer> = (0..2000).map(|_| dist.sample(&mut rand)).collect();
+        let sum: Integer = samples.iter().sum();
+        let abs_sum: Integer = samples.iter().map(|x| x.clone().abs()).sum();
+        // mean 0, E|X| = 2e^{-1/3} / (1 - e^{-2/3}) ≈ 2.92
+        assert!(sum.abs() < 400);
+        assert!(abs_sum > 2000 * 2 && abs_sum < 2000 * 4);
+    }
+
+    #[test]
+    fn test_noisy_decryption() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 3, 2).unwrap();
+        let key_shares = sk.share(&[0, 2], &mut rand);
+        let cipher = pk.encrypt(1000.into(), &mut rand);
+        let dist = DiscreteLaplace::new(1, 1).unwrap();
+
+        let mut sessions: Vec<_> = key_shares
+            .iter()
+            .map(|s| NoisyDecryptionSession::new(&pk, s, cipher.clone(), b"round 1"))
+            .collect();
+        let commitments: Vec<_> = sessions
+            .iter_mut()
+            .map(|s| s.commit_noise(&dist, &mut rand).unwrap())
+            .collect();
+        assert!(sessions[0].noise_share().is_err());
+        sessions[0]
+            .add_noise_commitment(commitments[1].clone())
+            .unwrap();
+        sessions[1]
+            .add_noise_commitment(commitments[0].clone())
+            .unwrap();
+        let noise: Vec<_> = sessions
+            .iter_mut()
+            .map(|s| s.noise_share().unwrap())
+            .collect();
+        assert!(sessions[0]
+            .add_noise_commitment(commitments[1].clone())
+            .is_err());
+        assert!(sessions[0].share_decrypt().is_err());
+
+        // a share bound to another session or server is rejected
+        let mut other =
+            NoisyDecryptionSession::new(&pk, &key_shares[1], cipher.clone(), b"round 2");
+        other.add_noise_commitment(commitments[0].clone()).unwrap();
+        assert!(other.add_noise_share(noise[0].clone()).is_err());
+        let mut forged = noise[1].clone();
+        forged.id = noise[0].id;
+        assert!(sessions[1].add_noise_share(forged).is_err());
+
+        sessions[0].add_noise_share(noise[1].clone()).unwrap();
+        sessions[1].add_noise_share(noise[0].clone()).unwrap();
+        let partials: Vec<_> = sessions
+            .iter()
+            .map(|s| s.share_decrypt().unwrap())
+            .collect();
+        let result = sessions[0].combine(&partials).unwrap();
+        assert!((result - 1000i32).abs() < 50);
+    }
+}
@@ -0,0 +1,345 @@
+//! Threshold exponential ElGamal over the subgroup of quadratic residues modulo a
+//! safe prime p = 2q + 1. Plaintexts are encoded in the exponent, Enc(m) = (g^r, g^m * h^r),
+//! which makes the scheme additively homomorphic. Decryption recovers g^m and needs a
+//! discrete logarithm, so only plaintexts in a small range [0, bound) can be decoded,
+//! which is done with baby-step giant-step. This is well suited for counters.
+
+use crate::par;
+use crate::rand::generate_safe_prime;
+use crate::{util, Plaintext};
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use subtle::{Choice, ConstantTimeEq};
+
+/// Default upper bound (exclusive) for plaintexts that can be decoded
+pub const DEFAULT_DECODE_BOUND: u64 = 1 << 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Ciphertext {
+    /// g^r mod p
+    #[serde(with = "crate::util::serde_integer")]
+    a: Integer,
+    /// g^m * h^r mod p
+    #[serde(with = "crate::util::serde_integer")]
+    b: Integer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivateKeyShare {
+    i: u32,
+    /// Polynomial evaluation at i
+    #[serde(with = "crate::util::serde_integer")]
+    xi: Integer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartialDecryption {
+    /// a^{x_i} mod p
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+    id: u32,
+    /// The second component g^m * h^r of the decrypted ciphertext, needed to combine
+    #[serde(with = "crate::util::serde_integer")]
+    b: Integer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PublicKey {
+    /// The number of servers req to successfully decrypt
+    w: u32,
+    /// The number of decryption servers in total
+    l: u32,
+    /// Safe prime modulus p = 2q + 1
+    #[serde(with = "crate::util::serde_integer")]
+    p: Integer,
+    /// Prime order q of the group of quadratic residues mod p
+    #[serde(with = "crate::util::serde_integer")]
+    q: Integer,
+    /// Generator of the group of quadratic residues mod p
+    #[serde(with = "crate::util::serde_integer")]
+    g: Integer,
+    /// h = g^x mod p
+    #[serde(with = "crate::util::serde_integer")]
+    h: Integer,
+    /// Plaintexts in [0, decode_bound) can be decrypted
+    decode_bound: u64,
+}
+
+/// Compares the secret exponent in constant time
+#[derive(Debug, Clone, Serialize, Deserialize, Eq)]
+pub struct PrivateKey {
+    /// The number of servers req to decrypt
+    w: u32,
+    /// The number of decryption servers in total
+    l: u32,
+    #[serde(with = "crate::util::serde_integer")]
+    q: Integer,
+    /// The secret exponent x in Z_q
+    #[serde(with = "crate::util::serde_integer")]
+    x: Integer,
+}
+
+impl ConstantTimeEq for PrivateKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        let public = (self.w, self.l, &self.q) == (other.w, other.l, &other.q);
+        Choice::from(u8::from(public)) & util::ct_eq_integer(&self.x, &other.x)
+    }
+}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+/// Generates a key pair for a group modulo a `bits` bit safe prime.
+pub fn generate_key_pair(
+    bits: usize,
+    decryption_servers: u32,
+    threshold: u32,
+    rand: &mut dyn MutRandState,
+) -> Result<(PublicKey, PrivateKey)> {
+    ensure!(
+        threshold >= 1 && threshold <= decryption_servers,
+        "threshold must be in [1, decryption_servers]"
+    );
+    let (p, q) = generate_safe_prime(bits)?;
+    let g = loop {
+        let mut g = Integer::from(p.random_below_ref(rand));
+        g.square_mut();
+        g %= &p;
+        if g > 1 {
+            break g;
+        }
+    };
+    let x = Integer::from(q.random_below_ref(rand));
+    let h = g.pow_mod_ref(&x, &p).unwrap().into();
+    let pk = PublicKey {
+        w: threshold,
+        l: decryption_servers,
+        p,
+        q: q.clone(),
+        g,
+        h,
+        decode_bound: DEFAULT_DECODE_BOUND,
+    };
+    let sk = PrivateKey {
+        w: threshold,
+        l: decryption_servers,
+        q,
+        x,
+    };
+    Ok((pk, sk))
+}
+
+impl PublicKey {
+    /// Sets the exclusive upper bound of plaintexts that can be decoded by
+    /// [`PublicKey::share_combine`]. Decoding takes O(sqrt(bound)) time and memory.
+    pub fn with_decode_bound(mut self, bound: u64) -> Self {
+        self.decode_bound = bound;
+        self
+    }
+
+    pub fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Ciphertext {
+        let r = Integer::from(self.q.random_below_ref(rand));
+        let a = self.g.pow_mod_ref(&r, &self.p).unwrap().into();
+        let mut b = self.h.clone().pow_mod(&r, &self.p).unwrap();
+        b *= self.g_pow(m.as_ref());
+        b %= &self.p;
+        Ciphertext { a, b }
+    }
+
+    pub fn reencrypt(&self, cipher: &mut Ciphertext, rand: &mut dyn MutRandState) {
+        let r = Integer::from(self.q.random_below_ref(rand));
+        cipher.a *= Integer::from(self.g.pow_mod_ref(&r, &self.p).unwrap());
+        cipher.a %= &self.p;
+        cipher.b *= Integer::from(self.h.pow_mod_ref(&r, &self.p).unwrap());
+        cipher.b %= &self.p;
+    }
+
+    pub fn add_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        cipher.b *= self.g_pow(plain.as_ref());
+        cipher.b %= &self.p;
+    }
+
+    pub fn add_encrypted(&self, cipher1: &mut Ciphertext, cipher2: &Ciphertext) {
+        cipher1.a *= &cipher2.a;
+        cipher1.a %= &self.p;
+        cipher1.b *= &cipher2.b;
+        cipher1.b %= &self.p;
+    }
+
+    pub fn mul_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        let k = Integer::from(plain.as_ref() % &self.q);
+        cipher.a.pow_mod_mut(&k, &self.p).unwrap();
+        cipher.b.pow_mod_mut(&k, &self.p).unwrap();
+    }
+
+    /// Combines the partial decryptions of at least w distinct shares
+    pub fn share_combine(&self, shares: &[PartialDecryption]) -> Result<Plaintext> {
+        ensure!(
+            shares.len() >= self.w as usize,
+            "at least {} partial decryptions are needed",
+            self.w
+        );
+        let ids: Vec<_> = shares.iter().map(|share| share.id).collect();
+        util::check_evaluation_points(&ids)?;
+        // the group order q is prime, so the lagrange coefficients can be computed mod q
+        let powers = par::map_collect(shares, |i, si| {
+            let lambda = self.lagrange_coefficient(&ids, i)?;
+            Ok(Integer::from(si.val.pow_mod_ref(&lambda, &self.p).unwrap()))
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+        let ax = powers
+            .into_iter()
+            .fold(Integer::from(1), |a, b| (a * b) % &self.p);
+        let ax_inv = ax
+            .invert(&self.p)
+            .map_err(|_| anyhow!("invalid partial decryptions"))?;
+        let b = &shares
+            .first()
+            .ok_or_else(|| anyhow!("no partial decryptions"))?
+            .b;
+        ensure!(
+            shares.iter().all(|share| share.b == *b),
+            "partial decryptions are for different ciphertexts"
+        );
+        let gm = ax_inv * b % &self.p;
+        let m = self.discrete_log(&gm, self.decode_bound)?;
+        Ok(m.into())
+    }
+
+    /// Finds m in [0, bound) with g^m = target mod p using baby-step giant-step.
+    pub fn discrete_log(&self, target: &Integer, bound: u64) -> Result<u64> {
+        let step = (bound as f64).sqrt().ceil() as u64 + 1;
+        let mut baby_steps = HashMap::with_capacity(step as usize);
+        let mut acc = Integer::from(1);
+        for j in 0..step {
+            baby_steps.entry(acc.clone()).or_insert(j);
+            acc *= &self.g;
+            acc %= &self.p;
+        }
+        // acc = g^step
+        let giant = acc
+            .invert(&self.p)
+            .map_err(|_| anyhow!("generator is not invertible"))?;
+        let mut gamma = target.clone();
+        for i in 0..step {
+            if let Some(j) = baby_steps.get(&gamma) {
+                let m = i * step + j;
+                if m < bound {
+                    return Ok(m);
+                }
+            }
+            gamma *= &giant;
+            gamma %= &self.p;
+        }
+        Err(anyhow!("plaintext is not in the range [0, {})", bound))
+    }
+
+    /// g^m mod p for possibly negative m
+    fn g_pow(&self, m: &Integer) -> Integer {
+        let m = Integer::from(m % &self.q);
+        self.g.pow_mod_ref(&m, &self.p).unwrap().into()
+    }
+
+    /// λ_{0,i} = prod_{j != i} id_j / (id_j - id_i) mod q. Fails unless the ids are
+    /// distinct mod q.
+    fn lagrange_coefficient(&self, ids: &[u32], i: usize) -> Result<Integer> {
+        let mut num = Integer::from(1);
+        let mut den = Integer::from(1);
+        for (j, id) in ids.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            num *= *id;
+            den *= *id as i64 - ids[i] as i64;
+        }
+        let den = den
+            .invert(&self.q)
+            .map_err(|_| anyhow!("evaluation points are not distinct mod q"))?;
+        Ok(num * den % &self.q)
+    }
+}
+
+impl PrivateKeyShare {
+    /// Computes the partial decryption a^{x_i} mod p in constant time
+    pub fn share_decrypt(&self, pk: &PublicKey, cipher: Ciphertext) -> PartialDecryption {
+        PartialDecryption {
+            val: util::secure_pow_mod(&cipher.a, &self.xi, &pk.p),
+            id: self.i,
+            b: cipher.b,
+        }
+    }
+}
+
+impl PrivateKey {
+    /// Shamir shares the secret exponent among the servers with the given zero
+    /// based `server_indices`.
+    pub fn share(
+        self,
+        server_indices: &[u32],
+        rand_state: &mut dyn MutRandState,
+    ) -> Vec<PrivateKeyShare> {
+        assert_eq!(
+            server_indices.len(),
+            self.w as usize,
+            "share() must be called with w unique indices"
+        );
+        let mut coefficients = vec![self.x.clone()];
+        coefficients
+            .extend((1..self.w).map(|_| Integer::from(self.q.random_below_ref(rand_state))));
+        server_indices
+            .iter()
+            .map(|idx| {
+                // horner evaluation at idx + 1
+                let x = idx + 1;
+                let xi = coefficients
+                    .iter()
+                    .rev()
+                    .fold(Integer::new(), |acc, coeff| (acc * x + coeff) % &self.q);
+                PrivateKeyShare { i: x, xi }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_threshold_elgamal() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 3, 2, &mut rand).unwrap();
+        let pk = pk.with_decode_bound(1 << 16);
+        let mut c = pk.encrypt(20.into(), &mut rand);
+        let c2 = pk.encrypt(1.into(), &mut rand);
+        pk.add_encrypted(&mut c, &c2);
+        pk.mul_plain(&mut c, &2.into());
+        pk.reencrypt(&mut c, &mut rand);
+        let key_shares = sk.share(&[0, 2], &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|key_share| key_share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&shares).unwrap(), 42);
+
+        let c = pk.encrypt((1 << 16).into(), &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|key_share| key_share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert!(pk.share_combine(&shares).is_err());
+
+        // too few and duplicate shares
+        assert!(pk.share_combine(&shares[..1]).is_err());
+        let duplicates = vec![shares[0].clone(), shares[0].clone()];
+        assert!(pk.share_combine(&duplicates).is_err());
+    }
+}
@@ -0,0 +1,639 @@
+//! C ABI of the threshold Paillier scheme (feature `ffi`).
+//!
+//! Keys, key shares, ciphertexts and partial decryptions are passed as opaque handles,
+//! which are created by the library and must be released with the matching
+//! `pht_*_free` function. Every handle can be serialized into a [`PhtBuffer`] with the
+//! versioned encoding of [`crate::wire`] and parsed again, so values can be stored or
+//! sent between processes. Plaintexts are unsigned big endian byte strings.
+//!
+//! Every function returns a [`PhtStatus`]. On failure, [`pht_last_error_message`]
+//! describes the error. Outputs are only written on success. Panics are caught at the
+//! boundary and reported as [`PhtStatus::Panic`].
+//!
+//! The header `include/pht_crypto.h` is generated with
+//! `cbindgen --config cbindgen.toml --output include/pht_crypto.h`. Build the library
+//! with `cargo rustc --release --features ffi --crate-type cdylib` or `staticlib`.
+
+use crate::dealer::Dealer;
+use crate::paillier::{self, PartialDecryption, PrivateKey, PrivateKeyShare, PublicKey};
+use crate::wire::Versioned;
+use crate::{rng, Ciphertext, Plaintext};
+use anyhow::anyhow;
+use rug::integer::Order;
+use rug::Integer;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Result of every function of the C ABI
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhtStatus {
+    Ok = 0,
+    /// A required pointer was null
+    NullPointer = 1,
+    /// An argument could not be parsed or is out of range
+    InvalidArgument = 2,
+    /// The operation failed, e.g. partial decryptions of different ciphertexts were
+    /// combined
+    Failed = 3,
+    /// The library panicked, which is a bug
+    Panic = 4,
+}
+
+pub struct PhtPublicKey(PublicKey);
+pub struct PhtPrivateKey(PrivateKey);
+pub struct PhtKeyShare(PrivateKeyShare);
+pub struct PhtCiphertext(Ciphertext);
+pub struct PhtPartialDecryption(PartialDecryption);
+
+/// Bytes allocated by the library, released with [`pht_buffer_free`]
+#[repr(C)]
+pub struct PhtBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl From<Vec<u8>> for PhtBuffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        let mut bytes = bytes.into_boxed_slice();
+        let buffer = PhtBuffer {
+            data: bytes.as_mut_ptr(),
+            len: bytes.len(),
+        };
+        std::mem::forget(bytes);
+        buffer
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+struct Error {
+    status: PhtStatus,
+    message: String,
+}
+
+impl Error {
+    fn null(name: &str) -> Self {
+        Self {
+            status: PhtStatus::NullPointer,
+            message: format!("{} is null", name),
+        }
+    }
+
+    fn invalid(err: anyhow::Error) -> Self {
+        Self {
+            status: PhtStatus::InvalidArgument,
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for Error {
+    fn from(err: anyhow::Error) -> Self {
+        Self {
+            status: PhtStatus::Failed,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Runs `f`, records its error and catches panics
+fn guard(f: impl FnOnce() -> Result<(), Error>) -> PhtStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return PhtStatus::Ok,
+        Ok(Err(err)) => (err.status, err.message),
+        Err(_) => (PhtStatus::Panic, "panic in pht-crypto".to_string()),
+    };
+    let message = CString::new(message.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    status
+}
+
+unsafe fn arg<'a, T>(ptr: *const T, name: &str) -> Result<&'a T, Error> {
+    ptr.as_ref().ok_or_else(|| Error::null(name))
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Error> {
+    if len == 0 {
+        return Ok(&[]);
+    }
+    if data.is_null() {
+        return Err(Error::null("data"));
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+unsafe fn write<T>(out: *mut T, value: T) -> Result<(), Error> {
+    if out.is_null() {
+        return Err(Error::null("out"));
+    }
+    out.write(value);
+    Ok(())
+}
+
+unsafe fn write_handle<T>(out: *mut *mut T, value: T) -> Result<(), Error> {
+    if out.is_null() {
+        return Err(Error::null("out"));
+    }
+    out.write(Box::into_raw(Box::new(value)));
+    Ok(())
+}
+
+unsafe fn free<T>(handle: *mut T) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+fn plaintext_bytes(plaintext: &Plaintext) -> Vec<u8> {
+    plaintext.as_ref().to_digits(Order::MsfBe)
+}
+
+/// The message of the last error on the calling thread, or null if there was none.
+/// The string is valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn pht_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+/// Releases a buffer returned by the library
+///
+/// # Safety
+/// `buffer` must have been returned by the library and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pht_buffer_free(buffer: PhtBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Generates a key pair with a `bits` bit modulus for `threshold` of
+/// `decryption_servers` servers
+///
+/// # Safety
+/// `pk_out` and `sk_out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_generate_key_pair(
+    bits: u32,
+    decryption_servers: u32,
+    threshold: u32,
+    pk_out: *mut *mut PhtPublicKey,
+    sk_out: *mut *mut PhtPrivateKey,
+) -> PhtStatus {
+    guard(|| {
+        if pk_out.is_null() || sk_out.is_null() {
+            return Err(Error::null("out"));
+        }
+        let (pk, sk) = paillier::generate_key_pair(bits as usize, decryption_servers, threshold)
+            .map_err(Error::invalid)?;
+        write_handle(pk_out, PhtPublicKey(pk))?;
+        write_handle(sk_out, PhtPrivateKey(sk))
+    })
+}
+
+/// Deals the key shares of all decryption servers. `shares_out` must have room for
+/// exactly as many handles as there are servers, the i-th share belongs to server i.
+///
+/// # Safety
+/// `pk` and `sk` must be valid handles, `shares_out` must be valid for `shares_len`
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_deal_key_shares(
+    pk: *const PhtPublicKey,
+    sk: *const PhtPrivateKey,
+    shares_out: *mut *mut PhtKeyShare,
+    shares_len: usize,
+) -> PhtStatus {
+    guard(|| {
+        let pk = &arg(pk, "pk")?.0;
+        let sk = &arg(sk, "sk")?.0;
+        if shares_len != pk.decryption_servers() as usize {
+            return Err(Error::invalid(anyhow!(
+                "expected room for {} shares",
+                pk.decryption_servers()
+            )));
+        }
+        if shares_out.is_null() {
+            return Err(Error::null("shares_out"));
+        }
+        let shares = Dealer::from_key_pair(pk.clone(), sk.clone()).deal(&mut rng::secure());
+        for (i, share) in shares.into_iter().enumerate() {
+            write_handle(shares_out.add(i), PhtKeyShare(share))?;
+        }
+        Ok(())
+    })
+}
+
+/// Encrypts the unsigned big endian plaintext `data`, which must be less than n
+///
+/// # Safety
+/// `pk` must be a valid handle, `data` must be valid for `len` reads and `out` for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_encrypt(
+    pk: *const PhtPublicKey,
+    data: *const u8,
+    len: usize,
+    out: *mut *mut PhtCiphertext,
+) -> PhtStatus {
+    guard(|| {
+        let pk = &arg(pk, "pk")?.0;
+        let m = Integer::from_digits(bytes(data, len)?, Order::MsfBe);
+        if m >= *pk.modulus() {
+            return Err(Error::invalid(anyhow!("plaintext must be less than n")));
+        }
+        write_handle(out, PhtCiphertext(pk.encrypt_default(m.into())))
+    })
+}
+
+/// Encrypts `m`
+///
+/// # Safety
+/// `pk` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_encrypt_u64(
+    pk: *const PhtPublicKey,
+    m: u64,
+    out: *mut *mut PhtCiphertext,
+) -> PhtStatus {
+    guard(|| {
+        let pk = &arg(pk, "pk")?.0;
+        write_handle(out, PhtCiphertext(pk.encrypt_default(m.into())))
+    })
+}
+
+/// Encrypts the sum of the plaintexts of `a` and `b`
+///
+/// # Safety
+/// All handles must be valid and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_add(
+    pk: *const PhtPublicKey,
+    a: *const PhtCiphertext,
+    b: *const PhtCiphertext,
+    out: *mut *mut PhtCiphertext,
+) -> PhtStatus {
+    guard(|| {
+        let pk = &arg(pk, "pk")?.0;
+        let mut sum = arg(a, "a")?.0.clone();
+        pk.add_encrypted(&mut sum, &arg(b, "b")?.0);
+        write_handle(out, PhtCiphertext(sum))
+    })
+}
+
+/// Decrypts `cipher` with the full private key into an unsigned big endian buffer
+///
+/// # Safety
+/// All handles must be valid and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_decrypt(
+    sk: *const PhtPrivateKey,
+    cipher: *const PhtCiphertext,
+    out: *mut PhtBuffer,
+) -> PhtStatus {
+    guard(|| {
+        let plaintext = arg(sk, "sk")?.0.decrypt(&arg(cipher, "cipher")?.0);
+        write(out, plaintext_bytes(&plaintext).into())
+    })
+}
+
+/// Computes the partial decryption of `cipher` with `share`
+///
+/// # Safety
+/// All handles must be valid and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_share_decrypt(
+    pk: *const PhtPublicKey,
+    share: *const PhtKeyShare,
+    cipher: *const PhtCiphertext,
+    out: *mut *mut PhtPartialDecryption,
+) -> PhtStatus {
+    guard(|| {
+        let pk = &arg(pk, "pk")?.0;
+        let cipher = arg(cipher, "cipher")?.0.clone();
+        let partial = arg(share, "share")?.0.share_decrypt(pk, cipher);
+        write_handle(out, PhtPartialDecryption(partial))
+    })
+}
+
+/// Combines the partial decryptions of at least w servers into an unsigned big
+/// endian buffer
+///
+/// # Safety
+/// `pk` must be a valid handle, `partials` must point to `count` valid handles and
+/// `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_share_combine(
+    pk: *const PhtPublicKey,
+    partials: *const *const PhtPartialDecryption,
+    count: usize,
+    out: *mut PhtBuffer,
+) -> PhtStatus {
+    guard(|| {
+        let pk = &arg(pk, "pk")?.0;
+        if partials.is_null() {
+            return Err(Error::null("partials"));
+        }
+        if count < pk.threshold() as usize {
+            return Err(Error::invalid(anyhow!(
+                "at least {} partial decryptions are needed",
+                pk.threshold()
+            )));
+        }
+        let partials = std::slice::from_raw_parts(partials, count)
+            .iter()
+            .map(|partial| Ok(arg(*partial, "partial")?.0.clone()))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let plaintext = pk.share_combine(&partials)?;
+        write(out, plaintext_bytes(&plaintext).into())
+    })
+}
+
+/// Serializes the public key with the versioned encoding
+///
+/// # Safety
+/// `pk` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_public_key_to_bytes(
+    pk: *const PhtPublicKey,
+    out: *mut PhtBuffer,
+) -> PhtStatus {
+    guard(|| write(out, arg(pk, "pk")?.0.to_versioned_bytes().into()))
+}
+
+/// Parses a public key serialized with [`pht_public_key_to_bytes`]
+///
+/// # Safety
+/// `data` must be valid for `len` reads and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_public_key_from_bytes(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut PhtPublicKey,
+) -> PhtStatus {
+    guard(|| {
+        let pk = PublicKey::from_versioned_bytes(bytes(data, len)?).map_err(Error::invalid)?;
+        write_handle(out, PhtPublicKey(pk))
+    })
+}
+
+/// Releases a public key, null is ignored
+///
+/// # Safety
+/// `pk` must be null or a handle which has not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pht_public_key_free(pk: *mut PhtPublicKey) {
+    free(pk)
+}
+
+/// Serializes the private key with the versioned encoding
+///
+/// # Safety
+/// `sk` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_private_key_to_bytes(
+    sk: *const PhtPrivateKey,
+    out: *mut PhtBuffer,
+) -> PhtStatus {
+    guard(|| write(out, arg(sk, "sk")?.0.to_versioned_bytes().into()))
+}
+
+/// Parses a private key serialized with [`pht_private_key_to_bytes`]
+///
+/// # Safety
+/// `data` must be valid for `len` reads and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_private_key_from_bytes(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut PhtPrivateKey,
+) -> PhtStatus {
+    guard(|| {
+        let sk = PrivateKey::from_versioned_bytes(bytes(data, len)?).map_err(Error::invalid)?;
+        write_handle(out, PhtPrivateKey(sk))
+    })
+}
+
+/// Releases a private key, null is ignored
+///
+/// # Safety
+/// `sk` must be null or a handle which has not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pht_private_key_free(sk: *mut PhtPrivateKey) {
+    free(sk)
+}
+
+/// Serializes the key share with the versioned encoding
+///
+/// # Safety
+/// `share` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_key_share_to_bytes(
+    share: *const PhtKeyShare,
+    out: *mut PhtBuffer,
+) -> PhtStatus {
+    guard(|| write(out, arg(share, "share")?.0.to_versioned_bytes().into()))
+}
+
+/// Parses a key share serialized with [`pht_key_share_to_bytes`]
+///
+/// # Safety
+/// `data` must be valid for `len` reads and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_key_share_from_bytes(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut PhtKeyShare,
+) -> PhtStatus {
+    guard(|| {
+        let share =
+            PrivateKeyShare::from_versioned_bytes(bytes(data, len)?).map_err(Error::invalid)?;
+        write_handle(out, PhtKeyShare(share))
+    })
+}
+
+/// Releases a key share, null is ignored
+///
+/// # Safety
+/// `share` must be null or a handle which has not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pht_key_share_free(share: *mut PhtKeyShare) {
+    free(share)
+}
+
+/// Serializes the ciphertext with the versioned encoding
+///
+/// # Safety
+/// `cipher` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_ciphertext_to_bytes(
+    cipher: *const PhtCiphertext,
+    out: *mut PhtBuffer,
+) -> PhtStatus {
+    guard(|| write(out, arg(cipher, "cipher")?.0.to_versioned_bytes().into()))
+}
+
+/// Parses a ciphertext serialized with [`pht_ciphertext_to_bytes`]
+///
+/// # Safety
+/// `data` must be valid for `len` reads and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_ciphertext_from_bytes(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut PhtCiphertext,
+) -> PhtStatus {
+    guard(|| {
+        let cipher = Ciphertext::from_versioned_bytes(bytes(data, len)?).map_err(Error::invalid)?;
+        write_handle(out, PhtCiphertext(cipher))
+    })
+}
+
+/// Releases a ciphertext, null is ignored
+///
+/// # Safety
+/// `cipher` must be null or a handle which has not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pht_ciphertext_free(cipher: *mut PhtCiphertext) {
+    free(cipher)
+}
+
+/// Serializes the partial decryption with the versioned encoding
+///
+/// # Safety
+/// `partial` must be a valid handle and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_partial_decryption_to_bytes(
+    partial: *const PhtPartialDecryption,
+    out: *mut PhtBuffer,
+) -> PhtStatus {
+    guard(|| write(out, arg(partial, "partial")?.0.to_versioned_bytes().into()))
+}
+
+/// Parses a partial decryption serialized with [`pht_partial_decryption_to_bytes`]
+///
+/// # Safety
+/// `data` must be valid for `len` reads and `out` for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pht_partial_decryption_from_bytes(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut PhtPartialDecryption,
+) -> PhtStatus {
+    guard(|| {
+        let partial =
+            PartialDecryption::from_versioned_bytes(bytes(data, len)?).map_err(Error::invalid)?;
+        write_handle(out, PhtPartialDecryption(partial))
+    })
+}
+
+/// Releases a partial decryption, null is ignored
+///
+/// # Safety
+/// `partial` must be null or a handle which has not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn pht_partial_decryption_free(partial: *mut PhtPartialDecryption) {
+    free(partial)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_threshold_decryption_over_ffi() {
+        unsafe {
+            let mut pk = ptr::null_mut();
+            let mut sk = ptr::null_mut();
+            assert_eq!(
+                pht_generate_key_pair(128, 3, 2, &mut pk, &mut sk),
+                PhtStatus::Ok
+            );
+            let mut shares = [ptr::null_mut(); 3];
+            assert_eq!(
+                pht_deal_key_shares(pk, sk, shares.as_mut_ptr(), 2),
+                PhtStatus::InvalidArgument
+            );
+            assert!(!pht_last_error_message().is_null());
+            assert_eq!(
+                pht_deal_key_shares(pk, sk, shares.as_mut_ptr(), 3),
+                PhtStatus::Ok
+            );
+
+            let (mut a, mut b, mut sum) = (ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+            assert_eq!(pht_encrypt_u64(pk, 20, &mut a), PhtStatus::Ok);
+            assert_eq!(pht_encrypt(pk, [1, 0].as_ptr(), 2, &mut b), PhtStatus::Ok);
+            assert_eq!(pht_add(pk, a, b, &mut sum), PhtStatus::Ok);
+
+            // the ciphertext travels as bytes to the servers
+            let mut buffer = PhtBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(pht_ciphertext_to_bytes(sum, &mut buffer), PhtStatus::Ok);
+            let mut received = ptr::null_mut();
+            assert_eq!(
+                pht_ciphertext_from_bytes(buffer.data, buffer.len, &mut received),
+                PhtStatus::Ok
+            );
+            pht_buffer_free(buffer);
+
+            let mut partials = [ptr::null_mut(); 2];
+            for (partial, share) in partials.iter_mut().zip(&shares[1..]) {
+                assert_eq!(
+                    pht_share_decrypt(pk, *share, received, partial),
+                    PhtStatus::Ok
+                );
+            }
+            let mut plaintext = PhtBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            let partial_ptrs = [partials[0] as *const _, partials[1] as *const _];
+            assert_eq!(
+                pht_share_combine(pk, partial_ptrs.as_ptr(), 1, &mut plaintext),
+                PhtStatus::InvalidArgument
+            );
+            assert_eq!(
+                pht_share_combine(pk, partial_ptrs.as_ptr(), 2, &mut plaintext),
+                PhtStatus::Ok
+            );
+            assert_eq!(
+                std::slice::from_raw_parts(plaintext.data, plaintext.len),
+                [1, 20]
+            );
+            pht_buffer_free(plaintext);
+
+            let mut invalid = ptr::null_mut();
+            assert_eq!(
+                pht_public_key_from_bytes([1, 2, 3].as_ptr(), 3, &mut invalid),
+                PhtStatus::InvalidArgument
+            );
+            let message = CStr::from_ptr(pht_last_error_message());
+            assert_eq!(message.to_str().unwrap(), "missing versioned header");
+            assert_eq!(
+                pht_add(pk, a, ptr::null(), &mut sum),
+                PhtStatus::NullPointer
+            );
+
+            partials
+                .iter()
+                .for_each(|p| pht_partial_decryption_free(*p));
+            shares.iter().for_each(|s| pht_key_share_free(*s));
+            [a, b, sum, received]
+                .iter()
+                .for_each(|c| pht_ciphertext_free(*c));
+            pht_private_key_free(sk);
+            pht_public_key_free(pk);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Encrypted histograms / frequency tables with threshold Paillier.
+//!
+//! Each station counts its records per category in an encrypted [`Histogram`], the
+//! histograms are merged bin-wise and all bins are threshold-decrypted in one batch.
+//! Incrementing a bin only changes that bin's ciphertext, so a station has to
+//! [`Histogram::rerandomize`] its histogram before publishing it.
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::par::prelude::*;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Histogram {
+    bins: Vec<Ciphertext>,
+}
+
+impl Histogram {
+    /// Creates a histogram of `bins` fresh encryptions of 0
+    pub fn new(pk: &PublicKey, bins: usize, rand: &mut dyn MutRandState) -> Self {
+        Self {
+            bins: (0..bins).map(|_| pk.encrypt(0.into(), rand)).collect(),
+        }
+    }
+
+    /// Encrypts the plaintext `counts`
+    pub fn from_counts(pk: &PublicKey, counts: &[u64], rand: &mut dyn MutRandState) -> Self {
+        Self {
+            bins: counts
+                .iter()
+                .map(|c| pk.encrypt((*c).into(), rand))
+                .collect(),
+        }
+    }
+
+    pub fn bins(&self) -> &[Ciphertext] {
+        &self.bins
+    }
+
+    pub fn len(&self) -> usize {
+        self.bins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    /// Adds an encryption of 1 to `bin`
+    pub fn increment(
+        &mut self,
+        bin: usize,
+        pk: &PublicKey,
+        rand: &mut dyn MutRandState,
+    ) -> Result<()> {
+        ensure!(bin < self.bins.len(), "bin {} is out of range", bin);
+        let one = pk.encrypt(1.into(), rand);
+        pk.add_encrypted(&mut self.bins[bin], &one);
+        Ok(())
+    }
+
+    /// Adds `other` bin-wise to this histogram
+    pub fn merge(&mut self, pk: &PublicKey, other: &Histogram) -> Result<()> {
+        ensure!(
+            self.bins.len() == other.bins.len(),
+            "histograms have a different number of bins"
+        );
+        for (bin, other) in self.bins.iter_mut().zip(&other.bins) {
+            pk.add_encrypted(bin, other);
+        }
+        Ok(())
+    }
+
+    /// Reencrypts all bins so the histogram can't be linked to earlier versions
+    pub fn rerandomize(&mut self, pk: &PublicKey, rand: &mut dyn MutRandState) {
+        for bin in &mut self.bins {
+            pk.reencrypt(bin, rand);
+        }
+    }
+
+    /// Partially decrypts all bins with a server's key share
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> Vec<PartialDecryption> {
+        self.bins
+            .par_iter()
+            .map(|bin| key_share.share_decrypt(pk, bin.clone()))
+            .collect()
+    }
+
+    /// Combines the partial decryptions of at least w servers, one vector per server,
+    /// into the counts of all bins.
+    pub fn combine(
+        &self,
+        pk: &PublicKey,
+        partials: &[Vec<PartialDecryption>],
+    ) -> Result<Vec<Plaintext>> {
+        ensure!(
+            partials.iter().all(|p| p.len() == self.bins.len()),
+            "partial decryptions don't match the number of bins"
+        );
+        (0..self.bins.len())
+            .into_par_iter()
+            .map(|k| {
+                let shares: Vec<_> = partials.iter().map(|p| p[k].clone()).collect();
+                pk.share_combine(&shares)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Histogram;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_histogram() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 3, 2).unwrap();
+        let key_shares = sk.share(&[1, 2], &mut rand);
+
+        let mut station1 = Histogram::new(&pk, 4, &mut rand);
+        for bin in [0, 2, 2, 3] {
+            station1.increment(bin, &pk, &mut rand).unwrap();
+        }
+        assert!(station1.increment(4, &pk, &mut rand).is_err());
+        station1.rerandomize(&pk, &mut rand);
+        let station2 = Histogram::from_counts(&pk, &[5, 0, 1, 0], &mut rand);
+
+        let mut total = station1;
+        total.merge(&pk, &station2).unwrap();
+        assert!(total
+            .merge(&pk, &Histogram::new(&pk, 3, &mut rand))
+            .is_err());
+
+        let partials: Vec<_> = key_shares
+            .iter()
+            .map(|s| total.share_decrypt(&pk, s))
+            .collect();
+        let counts = total.combine(&pk, &partials).unwrap();
+        assert_eq!(counts, [6, 0, 3, 1]);
+    }
+}
@@ -0,0 +1,133 @@
+//! Hybrid encryption of large payloads: a fresh 256 bit key is encrypted with threshold
+//! Paillier and the payload with ChaCha20-Poly1305 under that key. The payload can
+//! therefore only be decrypted after w servers released their partial decryptions of
+//! the key, while its size is only increased by the 16 byte tag and one ciphertext.
+//!
+//! ```
+//! use pht_crypto::hybrid::Envelope;
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 2, 2).unwrap();
+//! let key_shares = sk.share(&[0, 1], &mut rand);
+//!
+//! let envelope = Envelope::seal(&pk, b"model update", &mut rand).unwrap();
+//! let partials: Vec<_> = key_shares
+//!     .iter()
+//!     .map(|share| envelope.share_decrypt(&pk, share))
+//!     .collect();
+//! assert_eq!(envelope.open(&pk, &partials).unwrap(), b"model update");
+//! ```
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::Ciphertext;
+use anyhow::{anyhow, ensure, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rug::integer::Order;
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+const KEY_BYTES: usize = 32;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Paillier encryption of the symmetric key
+    key: Ciphertext,
+    /// ChaCha20-Poly1305 encryption of the payload, including the tag
+    payload: Vec<u8>,
+}
+
+impl Envelope {
+    pub fn seal(pk: &PublicKey, payload: &[u8], rand: &mut dyn MutRandState) -> Result<Self> {
+        ensure!(
+            pk.n.significant_bits() as usize > KEY_BYTES * 8,
+            "modulus is too small to encrypt the key"
+        );
+        let key = Integer::from(Integer::random_bits(KEY_BYTES as u32 * 8, rand));
+        let encrypted_key = pk.encrypt(key.clone().into(), rand);
+        let payload = cipher(&key)
+            .encrypt(
+                &nonce(),
+                Payload {
+                    msg: payload,
+                    aad: &encrypted_key.as_ref().to_digits::<u8>(Order::MsfBe),
+                },
+            )
+            .map_err(|_| anyhow!("payload encryption failed"))?;
+        Ok(Self {
+            key: encrypted_key,
+            payload,
+        })
+    }
+
+    pub fn encrypted_key(&self) -> &Ciphertext {
+        &self.key
+    }
+
+    /// Partially decrypts the symmetric key with a server's key share
+    pub fn share_decrypt(&self, pk: &PublicKey, key_share: &PrivateKeyShare) -> PartialDecryption {
+        key_share.share_decrypt(pk, self.key.clone())
+    }
+
+    /// Combines the partial decryptions of at least w servers to recover the key and
+    /// decrypts the payload.
+    pub fn open(&self, pk: &PublicKey, partials: &[PartialDecryption]) -> Result<Vec<u8>> {
+        let key: Integer = pk.share_combine(partials)?.into();
+        ensure!(
+            key.significant_bits() as usize <= KEY_BYTES * 8,
+            "decrypted key is out of range"
+        );
+        cipher(&key)
+            .decrypt(
+                &nonce(),
+                Payload {
+                    msg: &self.payload,
+                    aad: &self.key.as_ref().to_digits::<u8>(Order::MsfBe),
+                },
+            )
+            .map_err(|_| anyhow!("payload authentication failed"))
+    }
+}
+
+fn cipher(key: &Integer) -> ChaCha20Poly1305 {
+    let digits = key.to_digits::<u8>(Order::MsfBe);
+    let mut bytes = [0; KEY_BYTES];
+    bytes[KEY_BYTES - digits.len()..].copy_from_slice(&digits);
+    ChaCha20Poly1305::new(Key::from_slice(&bytes))
+}
+
+/// Every key encrypts exactly one payload, so a constant nonce is safe
+fn nonce() -> Nonce {
+    Nonce::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Envelope;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 1, 1).unwrap();
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+        let payload = vec![42; 100_000];
+
+        let envelope = Envelope::seal(&pk, &payload, &mut rand).unwrap();
+        let partials = [envelope.share_decrypt(&pk, &key_share)];
+        assert_eq!(envelope.open(&pk, &partials).unwrap(), payload);
+
+        let mut tampered = envelope.clone();
+        tampered.payload[7] ^= 1;
+        assert!(tampered.open(&pk, &partials).is_err());
+
+        let mut reencrypted = envelope;
+        pk.reencrypt(&mut reencrypted.key, &mut rand);
+        let partials = [reencrypted.share_decrypt(&pk, &key_share)];
+        assert!(reencrypted.open(&pk, &partials).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Conversions from and to the keys and ciphertexts of the
+//! [kzen-paillier](https://github.com/ZenGo-X/rust-paillier) crate.
+//!
+//! Both crates use g = n + 1 and the same ciphertext space, so ciphertexts can be
+//! moved between them as they are. kzen-paillier keys are not threshold keys: a
+//! converted [`EncryptionKey`] becomes a [`PublicKey`] for 1 of 1 servers, use
+//! [`PublicKey::from_modulus`] for other parameters. A [`DecryptionKey`] consists of
+//! the primes of the modulus, which need not be safe primes, so a [`PrivateKey`] can
+//! only be converted if it knows its factors, see [`PrivateKey::factors`], or from the
+//! [`ModulusFactors`] returned by key generation.
+//!
+//! ```
+//! use kzen_paillier::{BigInt, Decrypt, DecryptionKey, Paillier, RawCiphertext};
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//! use std::convert::TryFrom;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let dk = DecryptionKey::try_from(&sk).unwrap();
+//! let c = pk.encrypt(42.into(), &mut rand);
+//! let m: BigInt = Paillier::decrypt(&dk, RawCiphertext::from(&c)).into();
+//! assert_eq!(m, BigInt::from(42));
+//! ```
+
+use crate::paillier::{self, ModulusFactors, PrivateKey, PublicKey};
+use crate::Ciphertext;
+use anyhow::{anyhow, ensure, Error, Result};
+use curv::arithmetic::Converter;
+use kzen_paillier::{BigInt, DecryptionKey, EncryptionKey, RawCiphertext};
+use rug::integer::Order;
+use rug::Integer;
+use std::convert::TryFrom;
+
+impl From<&PublicKey> for EncryptionKey {
+    fn from(pk: &PublicKey) -> Self {
+        EncryptionKey::from(&to_kzen(&pk.n))
+    }
+}
+
+impl TryFrom<&EncryptionKey> for PublicKey {
+    type Error = Error;
+
+    /// A key for 1 of 1 servers
+    fn try_from(ek: &EncryptionKey) -> Result<Self> {
+        PublicKey::from_modulus(from_kzen(&ek.n)?, 1, 1)
+    }
+}
+
+impl From<&ModulusFactors> for DecryptionKey {
+    fn from(factors: &ModulusFactors) -> Self {
+        DecryptionKey {
+            p: to_kzen(&factors.p),
+            q: to_kzen(&factors.q),
+        }
+    }
+}
+
+impl TryFrom<&PrivateKey> for DecryptionKey {
+    type Error = Error;
+
+    fn try_from(sk: &PrivateKey) -> Result<Self> {
+        let factors = sk
+            .factors()
+            .ok_or_else(|| anyhow!("private key does not know the factors of its modulus"))?;
+        Ok(DecryptionKey::from(factors))
+    }
+}
+
+impl TryFrom<&DecryptionKey> for PrivateKey {
+    type Error = Error;
+
+    /// A key for 1 of 1 servers
+    fn try_from(dk: &DecryptionKey) -> Result<Self> {
+        let (p, q) = (from_kzen(&dk.p)?, from_kzen(&dk.q)?);
+        ensure!(p != q, "primes must be distinct");
+        ensure!(
+            p > 2 && q > 2 && p.is_odd() && q.is_odd(),
+            "primes must be odd"
+        );
+        let (p1, q1) = (Integer::from(&p >> 1), Integer::from(&q >> 1));
+        let n = Integer::from(&p * &q);
+        ensure!(
+            Integer::from(&p1 * &q1).gcd(&n) == 1,
+            "modulus is not coprime to (p - 1)(q - 1) / 4"
+        );
+        let (_, sk, _) = paillier::key_pair_from_primes(p, p1, q, q1, 1, 1)?;
+        Ok(sk)
+    }
+}
+
+impl From<&Ciphertext> for RawCiphertext<'static> {
+    fn from(cipher: &Ciphertext) -> Self {
+        RawCiphertext::from(to_kzen(&cipher.val))
+    }
+}
+
+impl TryFrom<RawCiphertext<'_>> for Ciphertext {
+    type Error = Error;
+
+    fn try_from(cipher: RawCiphertext<'_>) -> Result<Self> {
+        Ok(from_kzen(&cipher.0)?.into())
+    }
+}
+
+fn to_kzen(x: &Integer) -> BigInt {
+    BigInt::from_bytes(&x.to_digits::<u8>(Order::MsfBe))
+}
+
+fn from_kzen(x: &BigInt) -> Result<Integer> {
+    ensure!(*x >= BigInt::from(0), "value is negative");
+    Ok(Integer::from_digits(&x.to_bytes(), Order::MsfBe))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paillier::{generate_key_pair, PrivateKey, PublicKey};
+    use crate::Ciphertext;
+    use kzen_paillier::{
+        BigInt, Decrypt, DecryptionKey, Encrypt, EncryptionKey, KeyGeneration, Paillier,
+        RawCiphertext, RawPlaintext,
+    };
+    use rug::rand::RandState;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let ek = EncryptionKey::from(&pk);
+        let dk = DecryptionKey::try_from(&sk).unwrap();
+        assert_eq!(PublicKey::try_from(&ek).unwrap(), pk);
+        assert_eq!(PrivateKey::try_from(&dk).unwrap(), sk);
+
+        let c = pk.encrypt(42.into(), &mut rand);
+        let m: BigInt = Paillier::decrypt(&dk, RawCiphertext::from(&c)).into();
+        assert_eq!(m, BigInt::from(42));
+        let imported = Ciphertext::try_from(RawCiphertext::from(&c)).unwrap();
+        assert_eq!(imported.as_ref(), c.as_ref());
+        let c = Paillier::encrypt(&ek, RawPlaintext::from(BigInt::from(7)));
+        assert_eq!(sk.decrypt(&Ciphertext::try_from(c).unwrap()), 7);
+        assert!(Ciphertext::try_from(RawCiphertext::from(BigInt::from(-1))).is_err());
+
+        // kzen-paillier keys aren't built from safe primes
+        let (ek, dk) = Paillier::keypair_with_modulus_size(512).keys();
+        let (pk, sk) = (
+            PublicKey::try_from(&ek).unwrap(),
+            PrivateKey::try_from(&dk).unwrap(),
+        );
+        let c = Paillier::encrypt(&ek, RawPlaintext::from(BigInt::from(99)));
+        let c = Ciphertext::try_from(c).unwrap();
+        assert_eq!(sk.decrypt(&c), 99);
+        let without_factors =
+            PrivateKey::from_parts(sk.n.clone(), 1, 1, sk.d.clone(), sk.nm.clone()).unwrap();
+        assert!(DecryptionKey::try_from(&without_factors).is_err());
+        let partial = sk.share(&[0], &mut rand).remove(0).share_decrypt(&pk, c);
+        assert_eq!(pk.share_combine(&[partial]).unwrap(), 99);
+    }
+}
@@ -0,0 +1,164 @@
+//! Interoperability with the threshold Paillier (`pcs_t`) implementation of
+//! [libhcs](https://github.com/tiehuis/libhcs), which this crate's threshold scheme
+//! follows.
+//!
+//! libhcs exports its values as JSON objects whose integers are lowercase hex strings
+//! without prefix (`mpz_get_str(.., 16, ..)`), and ciphertexts as bare hex strings:
+//!
+//! ```text
+//! public key:  {"n": "<hex>", "l": <servers>, "w": <threshold>}
+//! private key: {"n": "<hex>", "d": "<hex>", "nm": "<hex>", "l": <servers>, "w": <threshold>}
+//! auth server: {"i": <one based index>, "si": "<hex>"}
+//! ```
+//!
+//! The precomputed values libhcs additionally keeps in memory (g, n^2, Δ, ...) are not
+//! exported and are recomputed on import. Keys and shares can thus be moved between
+//! deployments one server at a time.
+
+use crate::paillier::{PrivateKey, PrivateKeyShare, PublicKey};
+use crate::proofs::in_mult_group;
+use crate::Ciphertext;
+use anyhow::{anyhow, ensure, Result};
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct HcsPublicKey {
+    pub n: String,
+    pub l: u32,
+    pub w: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct HcsPrivateKey {
+    pub n: String,
+    pub d: String,
+    pub nm: String,
+    pub l: u32,
+    pub w: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct HcsAuthServer {
+    pub i: u32,
+    pub si: String,
+}
+
+fn to_hex(x: &Integer) -> String {
+    x.to_string_radix(16)
+}
+
+fn from_hex(s: &str) -> Result<Integer> {
+    let s = s.trim_start_matches("0x");
+    let x = Integer::from_str_radix(s, 16).map_err(|e| anyhow!("invalid hex integer: {}", e))?;
+    ensure!(x >= 0, "integer must not be negative");
+    Ok(x)
+}
+
+impl From<&PublicKey> for HcsPublicKey {
+    fn from(pk: &PublicKey) -> Self {
+        Self {
+            n: to_hex(&pk.n),
+            l: pk.l,
+            w: pk.w,
+        }
+    }
+}
+
+impl HcsPublicKey {
+    pub fn to_public_key(&self) -> Result<PublicKey> {
+        PublicKey::from_modulus(from_hex(&self.n)?, self.l, self.w)
+    }
+}
+
+impl From<&PrivateKey> for HcsPrivateKey {
+    fn from(sk: &PrivateKey) -> Self {
+        Self {
+            n: to_hex(&sk.n),
+            d: to_hex(&sk.d),
+            nm: to_hex(&sk.nm),
+            l: sk.l,
+            w: sk.w,
+        }
+    }
+}
+
+impl HcsPrivateKey {
+    pub fn to_private_key(&self) -> Result<PrivateKey> {
+        PrivateKey::from_parts(
+            from_hex(&self.n)?,
+            self.l,
+            self.w,
+            from_hex(&self.d)?,
+            from_hex(&self.nm)?,
+        )
+    }
+}
+
+impl From<&PrivateKeyShare> for HcsAuthServer {
+    fn from(share: &PrivateKeyShare) -> Self {
+        Self {
+            i: share.i,
+            si: to_hex(&share.si),
+        }
+    }
+}
+
+impl HcsAuthServer {
+    pub fn to_private_key_share(&self) -> Result<PrivateKeyShare> {
+        ensure!(self.i > 0, "server index must be positive");
+        Ok(PrivateKeyShare {
+            i: self.i,
+            si: from_hex(&self.si)?,
+            key_fingerprint: None,
+        })
+    }
+}
+
+/// Exports a ciphertext as a hex string
+pub fn export_ciphertext(cipher: &Ciphertext) -> String {
+    to_hex(cipher.as_ref())
+}
+
+/// Imports a hex ciphertext and checks that it is in Z*_{n^2}
+pub fn import_ciphertext(pk: &PublicKey, hex: &str) -> Result<Ciphertext> {
+    let c = from_hex(hex)?;
+    ensure!(
+        in_mult_group(&c, &pk.n, &pk.n2),
+        "ciphertext is not in Z*_n^2"
+    );
+    Ok(c.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export_ciphertext, import_ciphertext, HcsAuthServer, HcsPrivateKey, HcsPublicKey};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+
+        let json = serde_json::to_string(&HcsPublicKey::from(&pk)).unwrap();
+        let imported: HcsPublicKey = serde_json::from_str(&json).unwrap();
+        assert_eq!(imported.to_public_key().unwrap(), pk);
+        let imported_sk = HcsPrivateKey::from(&sk).to_private_key().unwrap();
+        assert_eq!(imported_sk, sk);
+
+        // migrate one server while the other one keeps its share
+        let shares = sk.share(&[0, 1], &mut rand);
+        let migrated = HcsAuthServer::from(&shares[0])
+            .to_private_key_share()
+            .unwrap();
+        let c = pk.encrypt(99.into(), &mut rand);
+        let c = import_ciphertext(&pk, &export_ciphertext(&c)).unwrap();
+        let partials = [
+            migrated.share_decrypt(&pk, c.clone()),
+            shares[1].share_decrypt(&pk, c),
+        ];
+        assert_eq!(pk.share_combine(&partials).unwrap(), 99);
+        assert!(import_ciphertext(&pk, "xyz").is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! Conversions from and to the key and ciphertext formats of other Paillier
+//! implementations, so this crate can interoperate with their existing clients.
+
+#[cfg(feature = "kzen")]
+pub mod kzen;
+pub mod libhcs;
+#[cfg(feature = "python_paillier")]
+pub mod python_paillier;
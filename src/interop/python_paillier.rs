@@ -0,0 +1,250 @@
+//! Interoperability with [python-paillier](https://github.com/data61/python-paillier).
+//!
+//! python-paillier uses the same g = n + 1 variant of Paillier, so its clients can
+//! encrypt under the modulus of a threshold key of this crate. This module reads and
+//! writes
+//! - the JWK public key of `pheutil`: `{"kty": "DAJ", "alg": "PAI-GN1", "n": ...}` with n
+//!   in unpadded base64url,
+//! - the serialized `EncryptedNumber`: `{"v": "<ciphertext in decimal>", "e": exponent}`,
+//! - the `EncodedNumber` convention: a value x is encoded as the integer
+//!   round(x * 16^{-e}), negative integers m as n + m, with |m| at most n / 3 - 1.
+
+use crate::paillier::PublicKey;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{anyhow, ensure, Result};
+use base64::engine::general_purpose::{URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use rug::integer::Order;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// Base of the exponent of python-paillier's `EncodedNumber`
+pub const BASE: u32 = 16;
+/// Bits of precision of an f64 mantissa
+const FLOAT_MANTISSA_BITS: i32 = 53;
+
+/// Public key in the JWK format of `pheutil`
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PublicKeyJwk {
+    pub kty: String,
+    pub alg: String,
+    pub key_ops: Vec<String>,
+    pub n: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+}
+
+/// Serialized `EncryptedNumber`
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct EncryptedNumber {
+    /// Ciphertext as decimal string
+    pub v: String,
+    /// Exponent to base 16 of the encoded plaintext
+    pub e: i32,
+}
+
+impl PublicKeyJwk {
+    pub fn from_public_key(pk: &PublicKey) -> Self {
+        Self {
+            kty: "DAJ".into(),
+            alg: "PAI-GN1".into(),
+            key_ops: vec!["encrypt".into()],
+            n: URL_SAFE_NO_PAD.encode(pk.n.to_digits::<u8>(Order::MsfBe)),
+            kid: None,
+        }
+    }
+
+    /// Converts the key into a threshold public key. python-paillier keys contain only
+    /// n, the threshold parameters have to be supplied.
+    pub fn to_public_key(&self, decryption_servers: u32, threshold: u32) -> Result<PublicKey> {
+        ensure!(
+            self.kty == "DAJ" && self.alg == "PAI-GN1",
+            "not a python-paillier public key"
+        );
+        let trimmed = self.n.trim_end_matches('=');
+        let bytes = URL_SAFE_NO_PAD
+            .decode(trimmed)
+            .or_else(|_| URL_SAFE.decode(&self.n))
+            .map_err(|e| anyhow!("invalid base64 modulus: {}", e))?;
+        PublicKey::from_modulus(
+            Integer::from_digits(&bytes, Order::MsfBe),
+            decryption_servers,
+            threshold,
+        )
+    }
+}
+
+impl EncryptedNumber {
+    pub fn new(cipher: &Ciphertext, exponent: i32) -> Self {
+        Self {
+            v: cipher.as_ref().to_string(),
+            e: exponent,
+        }
+    }
+
+    /// Parses the ciphertext and returns it together with its exponent
+    pub fn to_ciphertext(&self, pk: &PublicKey) -> Result<(Ciphertext, i32)> {
+        let c = Integer::from_str_radix(&self.v, 10)
+            .map_err(|e| anyhow!("invalid ciphertext: {}", e))?;
+        ensure!(
+            crate::proofs::in_mult_group(&c, &pk.n, &pk.n2),
+            "ciphertext is not in Z*_n^2"
+        );
+        Ok((c.into(), self.e))
+    }
+}
+
+/// The largest absolute value of an encoded integer: n / 3 - 1
+fn max_int(pk: &PublicKey) -> Integer {
+    Integer::from(&pk.n / 3) - 1
+}
+
+/// Encodes the integer `value` with exponent 0
+pub fn encode_int(pk: &PublicKey, value: &Integer) -> Result<Plaintext> {
+    ensure!(
+        Integer::from(value.abs_ref()) <= max_int(pk),
+        "value is too large to encode"
+    );
+    let m = if *value < 0 {
+        Integer::from(&pk.n + value)
+    } else {
+        value.clone()
+    };
+    Ok(m.into())
+}
+
+/// Encodes `value` like python-paillier's `EncodedNumber.encode` without explicit
+/// precision, i.e. with the smallest exponent that represents it exactly. Returns the
+/// plaintext and the exponent.
+pub fn encode_f64(pk: &PublicKey, value: f64) -> Result<(Plaintext, i32)> {
+    ensure!(value.is_finite(), "value must be finite");
+    let (mantissa, exp2) = decompose(value);
+    // exponent of the least significant bit, in base 2 and base 16
+    let lsb_exponent = frexp_exponent(value) - FLOAT_MANTISSA_BITS;
+    let exponent = lsb_exponent.div_euclid(4);
+    // int_rep = round(mantissa * 2^{exp2 - 4 * exponent})
+    let shift = exp2 - 4 * exponent;
+    let int_rep = if shift >= 0 {
+        Integer::from(mantissa) << shift as u32
+    } else {
+        round_half_even_shr(Integer::from(mantissa), (-shift) as u32)
+    };
+    Ok((encode_int(pk, &int_rep)?, exponent))
+}
+
+/// Decodes an encoded plaintext with the given exponent
+pub fn decode_f64(pk: &PublicKey, plain: &Plaintext, exponent: i32) -> Result<f64> {
+    let m = decode_int(pk, plain)?;
+    let (mantissa, exp) = m.to_f64_exp();
+    Ok(mantissa * 2f64.powi(exp as i32) * f64::from(BASE).powi(exponent))
+}
+
+/// Decodes an encoded plaintext with exponent 0 into a signed integer
+pub fn decode_int(pk: &PublicKey, plain: &Plaintext) -> Result<Integer> {
+    let m = plain.as_ref();
+    let max_int = max_int(pk);
+    if *m <= max_int {
+        Ok(m.clone())
+    } else if *m >= Integer::from(&pk.n - &max_int) {
+        Ok(Integer::from(m - &pk.n))
+    } else {
+        Err(anyhow!("overflow detected in decrypted number"))
+    }
+}
+
+/// Splits a finite f64 into an integer mantissa and a power of two
+fn decompose(value: f64) -> (i64, i32) {
+    if value == 0.0 {
+        return (0, 0);
+    }
+    let bits = value.to_bits();
+    let sign = if bits >> 63 == 0 { 1 } else { -1 };
+    let exponent = ((bits >> 52) & 0x7ff) as i32;
+    let fraction = (bits & 0xf_ffff_ffff_ffff) as i64;
+    if exponent == 0 {
+        // subnormal
+        (sign * fraction, -1074)
+    } else {
+        (sign * (fraction | 1 << 52), exponent - 1075)
+    }
+}
+
+/// The exponent e of Python's `math.frexp`, value = f * 2^e with 0.5 <= |f| < 1
+fn frexp_exponent(value: f64) -> i32 {
+    let (mantissa, exp2) = decompose(value);
+    if mantissa == 0 {
+        return 0;
+    }
+    let bits = 64 - mantissa.unsigned_abs().leading_zeros() as i32;
+    exp2 + bits
+}
+
+/// Computes round(x / 2^shift) rounding ties to even, like Python's `round`
+fn round_half_even_shr(x: Integer, shift: u32) -> Integer {
+    let negative = x < 0;
+    let x = x.abs();
+    let quotient = Integer::from(&x >> shift);
+    let remainder = x.keep_bits(shift);
+    let half = Integer::from(1) << (shift - 1);
+    let rounded = match remainder.cmp(&half) {
+        std::cmp::Ordering::Greater => quotient + 1,
+        std::cmp::Ordering::Equal if quotient.is_odd() => quotient + 1,
+        _ => quotient,
+    };
+    if negative {
+        -rounded
+    } else {
+        rounded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_f64, decode_int, encode_f64, encode_int, EncryptedNumber, PublicKeyJwk};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_encoding() {
+        let (pk, _) = generate_key_pair(256, 1, 1).unwrap();
+        // python-paillier: EncodedNumber.encode(pk, 1.5) has exponent -13 and
+        // encoding 1.5 * 16^13
+        let (m, e) = encode_f64(&pk, 1.5).unwrap();
+        assert_eq!(e, -13);
+        assert_eq!(m, Integer::from(3) << 51);
+        for value in [0.0, 1.5, -2.25, 1e-7, -123456.789, 3.0] {
+            let (m, e) = encode_f64(&pk, value).unwrap();
+            assert_eq!(decode_f64(&pk, &m, e).unwrap(), value);
+        }
+        let m = encode_int(&pk, &Integer::from(-5)).unwrap();
+        assert_eq!(m, Integer::from(&pk.n - 5));
+        assert_eq!(decode_int(&pk, &m).unwrap(), -5);
+        assert!(decode_int(&pk, &Integer::from(&pk.n / 2).into()).is_err());
+    }
+
+    #[test]
+    fn test_json_formats() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+
+        let json = serde_json::to_string(&PublicKeyJwk::from_public_key(&pk)).unwrap();
+        assert!(json.contains(r#""alg":"PAI-GN1""#));
+        let jwk: PublicKeyJwk = serde_json::from_str(&json).unwrap();
+        assert_eq!(jwk.to_public_key(2, 2).unwrap(), pk);
+
+        // a python client encrypting -0.5
+        let (m, e) = encode_f64(&pk, -0.5).unwrap();
+        let number = EncryptedNumber::new(&pk.encrypt(m, &mut rand), e);
+        let json = serde_json::to_string(&number).unwrap();
+        let number: EncryptedNumber = serde_json::from_str(&json).unwrap();
+        let (c, e) = number.to_ciphertext(&pk).unwrap();
+        let partials: Vec<_> = key_shares
+            .iter()
+            .map(|s| s.share_decrypt(&pk, c.clone()))
+            .collect();
+        let m = pk.share_combine(&partials).unwrap();
+        assert_eq!(decode_f64(&pk, &m, e).unwrap(), -0.5);
+    }
+}
@@ -0,0 +1,169 @@
+//! JNI facade for encrypt-only clients on the JVM (feature `jni`).
+//!
+//! The native methods of `pht.crypto.PhtCrypto` in `java/pht/crypto/PhtCrypto.java`
+//! take and return byte arrays only: public keys in the versioned encoding of
+//! [`crate::wire`], ciphertexts in its canonical fixed size encoding and plaintexts as
+//! unsigned big endian bytes. The ciphertexts can thus be decoded with
+//! [`Ciphertext::from_bytes`] and decrypted by the Rust committee. Invalid input throws
+//! an `IllegalArgumentException`.
+//!
+//! Every call parses the public key, so encrypt many values with `encryptBatch`.
+
+use crate::paillier::PublicKey;
+use crate::wire::{self, Versioned};
+use crate::Ciphertext;
+use anyhow::{anyhow, ensure, Result};
+use jni::objects::{JByteArray, JClass, JLongArray};
+use jni::sys::{jbyteArray, jint, jlong};
+use jni::JNIEnv;
+use rug::integer::Order;
+use rug::Integer;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+fn encrypt(pk: &[u8], value: i64) -> Result<Vec<u8>> {
+    let pk = PublicKey::from_versioned_bytes(pk)?;
+    Ok(pk.encrypt_default(value.into()).to_bytes(&pk))
+}
+
+fn encrypt_bytes(pk: &[u8], value: &[u8]) -> Result<Vec<u8>> {
+    let pk = PublicKey::from_versioned_bytes(pk)?;
+    let m = Integer::from_digits(value, Order::MsfBe);
+    ensure!(m < *pk.modulus(), "value must be less than n");
+    Ok(pk.encrypt_default(m.into()).to_bytes(&pk))
+}
+
+fn encrypt_batch(pk: &[u8], values: &[i64]) -> Result<Vec<u8>> {
+    let pk = PublicKey::from_versioned_bytes(pk)?;
+    let ciphers: Vec<_> = values
+        .iter()
+        .map(|value| pk.encrypt_default((*value).into()))
+        .collect();
+    Ok(wire::encode_ciphertexts(&pk, &ciphers))
+}
+
+fn add_encrypted(pk: &[u8], a: &[u8], b: &[u8]) -> Result<Vec<u8>> {
+    let pk = PublicKey::from_versioned_bytes(pk)?;
+    let mut sum = Ciphertext::from_bytes(&pk, a)?;
+    pk.add_encrypted(&mut sum, &Ciphertext::from_bytes(&pk, b)?);
+    Ok(sum.to_bytes(&pk))
+}
+
+/// Runs `f` and converts its result to a Java byte array. Errors and panics throw an
+/// `IllegalArgumentException` and return null.
+fn to_java<'local>(
+    env: &mut JNIEnv<'local>,
+    f: impl FnOnce(&mut JNIEnv<'local>) -> Result<Vec<u8>>,
+) -> jbyteArray {
+    let result = catch_unwind(AssertUnwindSafe(|| f(env)))
+        .unwrap_or_else(|_| Err(anyhow!("panic in pht-crypto")))
+        .and_then(|bytes| Ok(env.byte_array_from_slice(&bytes)?));
+    match result {
+        Ok(array) => array.into_raw(),
+        Err(err) => {
+            // fails only if an exception is pending already, which is thrown instead
+            let _ = env.throw_new("java/lang/IllegalArgumentException", err.to_string());
+            std::ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_pht_crypto_PhtCrypto_encrypt<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    public_key: JByteArray<'local>,
+    value: jlong,
+) -> jbyteArray {
+    to_java(&mut env, |env| {
+        encrypt(&env.convert_byte_array(&public_key)?, value)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_pht_crypto_PhtCrypto_encryptBytes<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    public_key: JByteArray<'local>,
+    value: JByteArray<'local>,
+) -> jbyteArray {
+    to_java(&mut env, |env| {
+        let pk = env.convert_byte_array(&public_key)?;
+        encrypt_bytes(&pk, &env.convert_byte_array(&value)?)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_pht_crypto_PhtCrypto_encryptBatch<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    public_key: JByteArray<'local>,
+    values: JLongArray<'local>,
+) -> jbyteArray {
+    to_java(&mut env, |env| {
+        let pk = env.convert_byte_array(&public_key)?;
+        let mut buf = vec![0; env.get_array_length(&values)? as usize];
+        env.get_long_array_region(&values, 0, &mut buf)?;
+        encrypt_batch(&pk, &buf)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_pht_crypto_PhtCrypto_addEncrypted<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    public_key: JByteArray<'local>,
+    a: JByteArray<'local>,
+    b: JByteArray<'local>,
+) -> jbyteArray {
+    to_java(&mut env, |env| {
+        let pk = env.convert_byte_array(&public_key)?;
+        add_encrypted(
+            &pk,
+            &env.convert_byte_array(&a)?,
+            &env.convert_byte_array(&b)?,
+        )
+    })
+}
+
+/// Length of an encoded ciphertext in bytes, or -1 with an exception if the key is
+/// invalid
+#[no_mangle]
+pub extern "system" fn Java_pht_crypto_PhtCrypto_ciphertextLength<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    public_key: JByteArray<'local>,
+) -> jint {
+    let len = env
+        .convert_byte_array(&public_key)
+        .map_err(anyhow::Error::from)
+        .and_then(|pk| PublicKey::from_versioned_bytes(&pk))
+        .map(|pk| pk.ciphertext_len() as jint);
+    len.unwrap_or_else(|err| {
+        let _ = env.throw_new("java/lang/IllegalArgumentException", err.to_string());
+        -1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_encrypted, encrypt, encrypt_batch, encrypt_bytes};
+    use crate::paillier::generate_key_pair;
+    use crate::wire::{decode_ciphertexts, Versioned};
+    use crate::Ciphertext;
+
+    #[test]
+    fn test_java_facade() {
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let pk_bytes = pk.to_versioned_bytes();
+        let a = encrypt(&pk_bytes, 40).unwrap();
+        let b = encrypt_bytes(&pk_bytes, &[2]).unwrap();
+        let sum = add_encrypted(&pk_bytes, &a, &b).unwrap();
+        assert_eq!(sum.len(), pk.ciphertext_len());
+        assert_eq!(sk.decrypt(&Ciphertext::from_bytes(&pk, &sum).unwrap()), 42);
+
+        let batch = decode_ciphertexts(&pk, &encrypt_batch(&pk_bytes, &[1, -1]).unwrap()).unwrap();
+        assert_eq!(sk.decrypt(&batch[1]), pk.modulus().clone() - 1);
+        assert!(encrypt(&pk_bytes[1..], 1).is_err());
+        assert!(add_encrypted(&pk_bytes, &a, &b[1..]).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Joye-Libert private stream aggregation. A trusted dealer generates a modulus N and
+//! a key for each of the `u` users as well as an aggregator key, such that all keys
+//! sum up to 0. Per round t the users encrypt their values with
+//! c_i = (1 + x_i * N) * H(t)^{sk_i} mod N^2 and the aggregator, and only the
+//! aggregator, can decrypt the sum of all users' values of that round. Individual
+//! values, partial sums and sums of other rounds stay hidden. Encryption costs a single
+//! exponentiation and no interaction between the users is needed.
+//!
+//! Source: Joye, Libert "A Scalable Scheme for Privacy-Preserving Aggregation of
+//! Time-Series Data"
+
+use crate::rand::generate_modulus_safe_primes;
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/joye-libert/round";
+
+/// Public parameters of the aggregation, the modulus N.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PublicParams {
+    #[serde(with = "crate::util::serde_integer")]
+    n: Integer,
+    /// Precomputation: n^2
+    #[serde(with = "crate::util::serde_integer")]
+    n2: Integer,
+}
+
+/// Secret encryption key of a single user.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserKey {
+    #[serde(with = "crate::util::serde_integer")]
+    sk: Integer,
+}
+
+/// Secret key of the aggregator, the negated sum of all user keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregatorKey {
+    #[serde(with = "crate::util::serde_integer")]
+    sk: Integer,
+}
+
+/// Generates a `bits` bit modulus and the keys for `users` users and the aggregator.
+/// The factorization of the modulus is discarded.
+pub fn setup(
+    bits: usize,
+    users: usize,
+    rand: &mut dyn MutRandState,
+) -> Result<(PublicParams, AggregatorKey, Vec<UserKey>)> {
+    ensure!(users > 0, "at least one user is required");
+    let (p, _, q, _) = generate_modulus_safe_primes(bits)?;
+    let n = p * q;
+    let n2 = n.clone().square();
+    let user_keys: Vec<_> = (0..users)
+        .map(|_| UserKey {
+            sk: Integer::from(n2.random_below_ref(rand)),
+        })
+        .collect();
+    let sk = -user_keys.iter().map(|key| &key.sk).sum::<Integer>();
+    Ok((PublicParams { n, n2 }, AggregatorKey { sk }, user_keys))
+}
+
+impl PublicParams {
+    /// Hashes the round identifier to H(t) in Z*_{N^2}
+    fn hash_round(&self, round: u64) -> Integer {
+        let mut transcript = Transcript::new(LABEL);
+        transcript.append_integer(b"n", &self.n);
+        transcript.append_u64(b"round", round);
+        let bits = self.n2.significant_bits() + 128;
+        transcript.challenge_integer(b"hash", bits) % &self.n2
+    }
+}
+
+impl UserKey {
+    /// Encrypts the value `x` of this user for the given `round`. A user must only
+    /// encrypt a single value per round.
+    pub fn encrypt(&self, pp: &PublicParams, x: &Plaintext, round: u64) -> Ciphertext {
+        let mut rop: Integer = Integer::from(x.as_ref() * &pp.n) + 1;
+        rop *= pp.hash_round(round).pow_mod(&self.sk, &pp.n2).unwrap();
+        rop %= &pp.n2;
+        rop.into()
+    }
+}
+
+impl AggregatorKey {
+    /// Decrypts the sum mod N of the values of `round`. The result is only
+    /// meaningful if `ciphers` contains exactly one ciphertext of every user.
+    pub fn aggregate(&self, pp: &PublicParams, ciphers: &[Ciphertext], round: u64) -> Plaintext {
+        let mut v = pp.hash_round(round).pow_mod(&self.sk, &pp.n2).unwrap();
+        for c in ciphers {
+            v *= c.as_ref();
+            v %= &pp.n2;
+        }
+        let rop: Integer = (v - 1) / &pp.n;
+        rop.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::setup;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_aggregation() {
+        let mut rand = RandState::new();
+        let (pp, aggregator, users) = setup(128, 3, &mut rand).unwrap();
+        let ciphers: Vec<_> = users
+            .iter()
+            .zip([10, 20, 12])
+            .map(|(user, x)| user.encrypt(&pp, &x.into(), 7))
+            .collect();
+        assert_eq!(aggregator.aggregate(&pp, &ciphers, 7), 42);
+        assert_ne!(aggregator.aggregate(&pp, &ciphers, 8), 42);
+        assert_ne!(aggregator.aggregate(&pp, &ciphers[..2], 7), 30);
+    }
+}
@@ -0,0 +1,374 @@
+//! Configurable generation of paillier key pairs.
+//!
+//! [`crate::paillier::generate_key_pair`] always uses safe primes, which the
+//! threshold variant needs for the proofs about the modulus and key shares in
+//! [`crate::proofs`]. Encryption and threshold decryption are correct for any
+//! primes however, and standard primes are found orders of magnitude faster. For
+//! development and test environments [`KeygenParams`] allows to trade the former
+//! for the latter:
+//!
+//! ```
+//! use pht_crypto::keygen::{KeygenParams, PrimeKind};
+//!
+//! let (pk, sk) = KeygenParams::new(512, 3, 2)
+//!     .with_min_bits(512)
+//!     .with_prime_kind(PrimeKind::Standard)
+//!     .with_miller_rabin_rounds(20)
+//!     .generate()
+//!     .unwrap();
+//! ```
+//!
+//! # Background generation
+//! Generating a key with safe primes of 3072 bits or more takes minutes.
+//! [`KeygenParams::spawn`] runs the generation on a background thread and returns a
+//! [`KeygenHandle`] to check for the result without blocking, to await it from an
+//! async runtime or to cancel it.
+//!
+//! # Progress
+//! A callback set with [`KeygenParams::with_progress`] receives [`KeygenEvent`]s
+//! while the primes are searched, e.g. to show that a long generation is alive:
+//!
+//! ```
+//! use pht_crypto::keygen::{KeygenEvent, KeygenParams};
+//! use std::sync::mpsc::channel;
+//!
+//! let (sender, receiver) = channel();
+//! let handle = KeygenParams::new(256, 1, 1)
+//!     .with_min_bits(256)
+//!     .with_progress(move |event| sender.send(event).unwrap())
+//!     .spawn();
+//! let found = receiver
+//!     .iter()
+//!     .filter(|event| matches!(event, KeygenEvent::PrimeFound { .. }))
+//!     .take(2)
+//!     .count();
+//! assert_eq!(found, 2);
+//! handle.wait().unwrap();
+//! ```
+
+use crate::paillier::{self, ModulusFactors, PrivateKey, PublicKey};
+use crate::rand::{PrimeSearch, SearchControl, MILLER_RABIN_ROUNDS};
+use anyhow::{anyhow, ensure, Result};
+use std::fmt::{self, Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// Kind of the prime factors of the modulus
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PrimeKind {
+    /// Primes p = 2 * p' + 1 with p' prime, as needed by the proofs of the threshold
+    /// scheme
+    Safe,
+    /// Random primes, only for development and testing
+    Standard,
+}
+
+/// Progress of the prime search during the key generation. The primes p and q of the
+/// modulus are searched one after another and identified by the index `prime` 0 and 1.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KeygenEvent {
+    /// Sent periodically with the number of candidates tested so far for the prime
+    CandidatesTested { prime: usize, candidates: u64 },
+    /// The prime was found after testing `candidates` candidates
+    PrimeFound { prime: usize, candidates: u64 },
+}
+
+#[derive(Clone)]
+struct Progress(Arc<dyn Fn(KeygenEvent) + Send + Sync>);
+
+impl Debug for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Progress")
+    }
+}
+
+/// Smallest modulus size accepted by [`KeygenParams`] unless lowered with
+/// [`KeygenParams::with_min_bits`]
+pub const DEFAULT_MIN_BITS: usize = 2048;
+
+/// Parameters of the key generation, constructed with [`KeygenParams::new`] and
+/// adjusted with the `with_*` methods.
+#[derive(Debug, Clone)]
+pub struct KeygenParams {
+    bits: usize,
+    decryption_servers: u32,
+    threshold: u32,
+    prime_kind: PrimeKind,
+    miller_rabin_rounds: u32,
+    primes_3_mod_4: bool,
+    min_bits: usize,
+    progress: Option<Progress>,
+}
+
+impl KeygenParams {
+    /// Parameters for a modulus of exactly `bits` bits with `decryption_servers` key
+    /// shares of which `threshold` are needed to decrypt. Defaults to safe primes, 40
+    /// Miller-Rabin rounds, no further restrictions on the primes and a minimum size
+    /// of [`DEFAULT_MIN_BITS`].
+    pub fn new(bits: usize, decryption_servers: u32, threshold: u32) -> Self {
+        Self {
+            bits,
+            decryption_servers,
+            threshold,
+            prime_kind: PrimeKind::Safe,
+            miller_rabin_rounds: MILLER_RABIN_ROUNDS,
+            primes_3_mod_4: false,
+            min_bits: DEFAULT_MIN_BITS,
+            progress: None,
+        }
+    }
+
+    pub fn with_prime_kind(mut self, prime_kind: PrimeKind) -> Self {
+        self.prime_kind = prime_kind;
+        self
+    }
+
+    /// Number of Miller-Rabin rounds each prime (and (p - 1) / 2 for safe primes) must
+    /// pass. A composite passes with probability at most 4^-rounds.
+    pub fn with_miller_rabin_rounds(mut self, rounds: u32) -> Self {
+        self.miller_rabin_rounds = rounds;
+        self
+    }
+
+    /// Whether both primes must be 3 mod 4, i.e. n must be a Blum integer. Safe
+    /// primes always are.
+    pub fn with_primes_3_mod_4(mut self, primes_3_mod_4: bool) -> Self {
+        self.primes_3_mod_4 = primes_3_mod_4;
+        self
+    }
+
+    /// The security floor: generating a modulus of less than `min_bits` bits fails.
+    /// Only lower it for tests.
+    pub fn with_min_bits(mut self, min_bits: usize) -> Self {
+        self.min_bits = min_bits;
+        self
+    }
+
+    /// Calls `progress` with the progress of the prime search. It is called from the
+    /// threads of the rayon pool and must not block for long.
+    pub fn with_progress(mut self, progress: impl Fn(KeygenEvent) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Progress(Arc::new(progress)));
+        self
+    }
+
+    fn prime_search(&self) -> PrimeSearch {
+        // the bit length is set by PrimeSearch::find_modulus_primes
+        let search = match self.prime_kind {
+            PrimeKind::Safe => PrimeSearch::safe(0),
+            PrimeKind::Standard => PrimeSearch::standard(0),
+        };
+        PrimeSearch {
+            rounds: self.miller_rabin_rounds,
+            three_mod_four: self.primes_3_mod_4,
+            ..search
+        }
+    }
+
+    pub fn generate(&self) -> Result<(PublicKey, PrivateKey)> {
+        let (pk, sk, _factors) = self.generate_with_factors()?;
+        Ok((pk, sk))
+    }
+
+    /// Like [`KeygenParams::generate`] but additionally returns the prime factors of
+    /// the modulus.
+    pub fn generate_with_factors(&self) -> Result<(PublicKey, PrivateKey, ModulusFactors)> {
+        self.generate_until(&AtomicBool::new(false))
+    }
+
+    fn generate_until(
+        &self,
+        cancelled: &AtomicBool,
+    ) -> Result<(PublicKey, PrivateKey, ModulusFactors)> {
+        ensure!(
+            self.bits >= self.min_bits,
+            "modulus of {} bits is below the minimum of {} bits",
+            self.bits,
+            self.min_bits
+        );
+        ensure!(self.bits >= 16, "modulus must have at least 16 bits");
+        ensure!(
+            self.miller_rabin_rounds >= 1,
+            "at least one Miller-Rabin round is required"
+        );
+        let control = SearchControl {
+            cancelled: Some(cancelled),
+            progress: self.progress.as_ref().map(|progress| &*progress.0 as _),
+        };
+        let (p, p1, q, q1) = self
+            .prime_search()
+            .find_modulus_primes(self.bits as u32, &control)
+            .ok_or_else(|| anyhow!("key generation was cancelled"))?;
+        paillier::key_pair_from_primes(p, p1, q, q1, self.decryption_servers, self.threshold)
+    }
+
+    /// Starts the key generation on a background thread. The prime candidates are
+    /// still tested on the rayon pool.
+    pub fn spawn(&self) -> KeygenHandle {
+        let shared = Arc::new(Shared {
+            cancelled: AtomicBool::new(false),
+            state: Mutex::new(State {
+                result: None,
+                waker: None,
+            }),
+        });
+        let params = self.clone();
+        let background = Arc::clone(&shared);
+        thread::spawn(move || {
+            let result = params.generate_until(&background.cancelled);
+            let mut state = background.state.lock().expect("poisoned lock");
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        KeygenHandle { shared }
+    }
+}
+
+type KeygenResult = Result<(PublicKey, PrivateKey, ModulusFactors)>;
+
+struct Shared {
+    cancelled: AtomicBool,
+    state: Mutex<State>,
+}
+
+struct State {
+    result: Option<KeygenResult>,
+    waker: Option<Waker>,
+}
+
+/// Handle of a key generation started with [`KeygenParams::spawn`]. As a [`Future`]
+/// it resolves to the key pair and its factors, so it can be awaited from any async
+/// runtime without blocking a worker thread. Dropping the handle cancels the
+/// generation.
+pub struct KeygenHandle {
+    shared: Arc<Shared>,
+}
+
+impl KeygenHandle {
+    /// Returns the result if the generation has finished, without blocking. The
+    /// result is only returned once.
+    pub fn try_result(&mut self) -> Option<KeygenResult> {
+        self.shared
+            .state
+            .lock()
+            .expect("poisoned lock")
+            .result
+            .take()
+    }
+
+    /// Stops the generation. The result will be an error unless it had already
+    /// finished.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Blocks the current thread until the generation has finished
+    pub fn wait(mut self) -> KeygenResult {
+        loop {
+            if let Some(result) = self.try_result() {
+                break result;
+            }
+            let thread = thread::current();
+            let waker = Arc::new(ThreadWaker(thread)).into();
+            {
+                let mut state = self.shared.state.lock().expect("poisoned lock");
+                if state.result.is_some() {
+                    continue;
+                }
+                state.waker = Some(waker);
+            }
+            thread::park();
+        }
+    }
+}
+
+impl Future for KeygenHandle {
+    type Output = KeygenResult;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<KeygenResult> {
+        let mut state = self.shared.state.lock().expect("poisoned lock");
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for KeygenHandle {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+struct ThreadWaker(thread::Thread);
+
+impl std::task::Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeygenParams, PrimeKind};
+    use rug::integer::IsPrime;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_keygen_params() {
+        let mut rand = RandState::new();
+        let (pk, sk, factors) = KeygenParams::new(256, 3, 2)
+            .with_min_bits(256)
+            .with_prime_kind(PrimeKind::Standard)
+            .with_primes_3_mod_4(true)
+            .generate_with_factors()
+            .unwrap();
+        for p in [&factors.p, &factors.q] {
+            assert_eq!(p.mod_u(4), 3);
+            assert_ne!(p.is_probably_prime(30), IsPrime::No);
+        }
+        let key_shares = sk.share(&[0, 2], &mut rand);
+        let cipher = pk.encrypt(42.into(), &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, cipher.clone()))
+            .collect();
+        let m: Integer = pk.share_combine(&shares).unwrap().into();
+        assert_eq!(m, 42);
+
+        let (pk, _, factors) = KeygenParams::new(127, 1, 1)
+            .with_min_bits(127)
+            .generate_with_factors()
+            .unwrap();
+        assert_eq!(pk.bit_length(), 127);
+        let p1 = Integer::from(&factors.p >> 1);
+        assert_ne!(p1.is_probably_prime(30), IsPrime::No);
+
+        assert!(KeygenParams::new(256, 1, 1)
+            .with_min_bits(256)
+            .with_miller_rabin_rounds(0)
+            .generate()
+            .is_err());
+        assert!(KeygenParams::new(1024, 1, 1).generate().is_err());
+    }
+
+    #[test]
+    fn test_keygen_handle() {
+        let handle = KeygenParams::new(256, 1, 1).with_min_bits(256).spawn();
+        let (pk, _, factors) = handle.wait().unwrap();
+        assert_eq!(*pk.modulus(), Integer::from(&factors.p * &factors.q));
+
+        let handle = KeygenParams::new(8192, 1, 1).spawn();
+        handle.cancel();
+        assert!(handle.wait().is_err());
+    }
+}
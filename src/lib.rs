@@ -1,25 +1,184 @@
 #![doc = include_str!("../README.md")]
 
+/// Enters an `INFO` span named `$name` with the given fields until the end of the
+/// enclosing block if the `trace` feature is enabled, see the `trace` module.
+#[cfg_attr(not(feature = "gmp"), allow(unused_macros))]
+macro_rules! trace_span {
+    ($name:literal $(, $($field:tt)*)?) => {
+        #[cfg(feature = "trace")]
+        let _timed = crate::trace::Timed::enter(tracing::info_span!($name $(, $($field)*)?));
+    };
+}
+
+#[cfg(feature = "trace")]
+mod trace;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "gmp")]
 use rug::Integer;
+#[cfg(feature = "gmp")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "gmp")]
 use std::cmp::Ordering;
+#[cfg(feature = "gmp")]
+use std::convert::TryFrom;
+#[cfg(feature = "gmp")]
+use subtle::{Choice, ConstantTimeEq};
+
+pub mod backend;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
+#[cfg(feature = "gmp")]
+pub mod aggregation;
+#[cfg(feature = "gmp")]
+pub mod arith;
+#[cfg(feature = "asn1")]
+pub mod asn1;
+#[cfg(feature = "gmp")]
+pub mod audit;
+#[cfg(feature = "gmp")]
+pub mod bigint;
+#[cfg(feature = "gmp")]
+pub mod blinding;
+#[cfg(feature = "gmp")]
+pub mod bounded;
+#[cfg(feature = "gmp")]
+pub mod bytes;
+#[cfg(feature = "gmp")]
+pub mod cbor;
+#[cfg(feature = "gmp")]
+pub mod commit;
+#[cfg(feature = "gmp")]
+pub mod context;
+#[cfg(feature = "gmp")]
+pub mod coordinator;
+#[cfg(feature = "gmp")]
+pub mod counter;
+#[cfg(feature = "gmp")]
+pub mod custody;
+#[cfg(feature = "gmp")]
+pub mod damgard_jurik;
+#[cfg(feature = "gmp")]
+pub mod dealer;
+#[cfg(feature = "gmp")]
+pub mod dgk;
+#[cfg(feature = "gmp")]
+pub mod dp;
+#[cfg(feature = "gmp")]
+pub mod elgamal;
+#[cfg(feature = "gmp")]
+pub mod histogram;
+#[cfg(feature = "hybrid")]
+pub mod hybrid;
+#[cfg(feature = "gmp")]
+pub mod interop;
+#[cfg(feature = "jni")]
+pub mod java;
+#[cfg(feature = "gmp")]
+pub mod joye_libert;
+#[cfg(feature = "gmp")]
+pub mod keygen;
+#[cfg(feature = "gmp")]
+pub mod matrix;
+#[cfg(feature = "gmp")]
+pub mod metrics;
+#[cfg(feature = "gmp")]
+pub mod mixnet;
+#[cfg(feature = "uniffi")]
+pub mod mobile;
+#[cfg(feature = "gmp")]
+pub mod okamoto_uchiyama;
+#[cfg(feature = "gmp")]
+pub mod packing;
+#[cfg(feature = "gmp")]
 pub mod paillier;
+#[cfg(feature = "gmp")]
+mod par;
+#[cfg(feature = "gmp")]
+pub mod pht;
+#[cfg(feature = "gmp")]
+pub mod policy;
+#[cfg(feature = "gmp")]
+pub mod pool;
+#[cfg(feature = "gmp")]
+pub mod prepared;
+#[cfg(feature = "gmp")]
+pub mod proofs;
+#[cfg(feature = "proto")]
+pub mod proto;
+#[cfg(feature = "gmp")]
+pub mod protocols;
+#[cfg(feature = "gmp")]
 mod rand;
+#[cfg(feature = "gmp")]
+pub mod repair;
+#[cfg(feature = "gmp")]
+pub mod rng;
+#[cfg(feature = "gmp")]
+pub mod rotation;
+#[cfg(feature = "sealed")]
+pub mod sealed;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "signed")]
+pub mod signed;
+#[cfg(feature = "gmp")]
+pub mod stats;
+#[cfg(feature = "test_vectors")]
+pub mod test_vectors;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "gmp")]
+pub mod threshold_rsa;
+#[cfg(feature = "gmp")]
+pub mod traits;
+#[cfg(feature = "gmp")]
+pub mod transcript;
+#[cfg(feature = "gmp")]
 mod util;
+#[cfg(feature = "gmp")]
+pub mod vector;
+#[cfg(feature = "gmp")]
+pub mod wire;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A ciphertext of the homomorphic schemes.
+///
+/// `==` and [`Hash`] compare the representation, not the plaintext: two encryptions
+/// of the same value are unequal, and so are a ciphertext and an unreduced copy of
+/// it. They are meant for deduplication, as map keys and in transcripts. Use
+/// [`Ciphertext::to_bytes`] for a canonical encoding under a key.
+#[cfg(feature = "gmp")]
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub struct Ciphertext {
     #[serde(with = "crate::util::serde_integer")]
     val: Integer,
 }
 
+/// A plaintext of the homomorphic schemes. Its comparisons with `==` and `<` take
+/// variable time, use [`Plaintext::ct_eq`] to compare secrets like decrypted tags.
+#[cfg(feature = "gmp")]
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Plaintext {
     #[serde(with = "crate::util::serde_integer")]
     val: Integer,
 }
 
+/// The random value r used to blind a [`Ciphertext`]. Needed by the encryptor
+/// to prove statements about its ciphertexts, it must be kept secret otherwise.
+#[cfg(feature = "gmp")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Randomness {
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+}
+
+#[cfg(feature = "gmp")]
 macro_rules! impl_from {
     ($target:ty; $($from:ty)+) => {
         $(
@@ -34,6 +193,7 @@ macro_rules! impl_from {
     };
 }
 
+#[cfg(feature = "gmp")]
 macro_rules! impl_partial_eq_ord_plaintext {
     ($($rhs:ty)+) => {
         $(
@@ -52,43 +212,159 @@ macro_rules! impl_partial_eq_ord_plaintext {
     }
 }
 
+#[cfg(feature = "gmp")]
+macro_rules! impl_try_from_plaintext {
+    ($($target:ty)+) => {
+        $(
+            impl TryFrom<&Plaintext> for $target {
+                type Error = anyhow::Error;
+
+                fn try_from(p: &Plaintext) -> anyhow::Result<Self> {
+                    <$target>::try_from(&p.val).map_err(|_| {
+                        anyhow::anyhow!("plaintext does not fit into {}", stringify!($target))
+                    })
+                }
+            }
+
+            impl TryFrom<Plaintext> for $target {
+                type Error = anyhow::Error;
+
+                fn try_from(p: Plaintext) -> anyhow::Result<Self> {
+                    <$target>::try_from(&p)
+                }
+            }
+        )+
+    }
+}
+
 // Damn coherence and lack of specialisation...
+#[cfg(feature = "gmp")]
 impl_from!(Ciphertext; bool i128 i16 i32 i64 i8 isize u128 u16 u32 u64 u8 usize Integer &Integer);
+#[cfg(feature = "gmp")]
 impl_from!(Plaintext; bool i128 i16 i32 i64 i8 isize u128 u16 u32 u64 u8 usize Integer &Integer);
+#[cfg(feature = "gmp")]
+impl_from!(Randomness; Integer &Integer);
+#[cfg(feature = "gmp")]
 impl_partial_eq_ord_plaintext!(f32 f64 i128 i16 i32 i64 i8 isize u128 u16 u32 u64 u8 usize Integer);
+#[cfg(feature = "gmp")]
+impl_try_from_plaintext!(i128 i16 i32 i64 i8 isize u128 u16 u32 u64 u8 usize);
 
+#[cfg(feature = "gmp")]
+impl Plaintext {
+    /// Compares the plaintexts in time which only depends on their sizes
+    pub fn ct_eq(&self, other: &Plaintext) -> Choice {
+        util::ct_eq_integer(&self.val, &other.val)
+    }
+
+    /// Decodes a fixed point value m * 2^exponent, where `exponent` is usually the
+    /// negated number of fractional bits used for encoding.
+    pub fn to_f64(&self, exponent: i32) -> f64 {
+        let (mantissa, exp) = self.val.to_f64_exp();
+        let exp = i64::from(exp) + i64::from(exponent);
+        let exp = exp.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+        mantissa * 2f64.powi(exp)
+    }
+}
+
+#[cfg(feature = "gmp")]
 impl From<Ciphertext> for Integer {
     fn from(c: Ciphertext) -> Self {
         c.val
     }
 }
 
+#[cfg(feature = "gmp")]
 impl From<Plaintext> for Integer {
     fn from(c: Plaintext) -> Self {
         c.val
     }
 }
 
+#[cfg(feature = "gmp")]
+impl From<Randomness> for Integer {
+    fn from(r: Randomness) -> Self {
+        r.val
+    }
+}
+
+#[cfg(feature = "gmp")]
 impl AsRef<Integer> for Ciphertext {
     fn as_ref(&self) -> &Integer {
         &self.val
     }
 }
 
+#[cfg(feature = "gmp")]
 impl AsRef<Integer> for Plaintext {
     fn as_ref(&self) -> &Integer {
         &self.val
     }
 }
 
+#[cfg(feature = "gmp")]
+impl AsRef<Integer> for Randomness {
+    fn as_ref(&self) -> &Integer {
+        &self.val
+    }
+}
+
+#[cfg(feature = "gmp")]
+impl ConstantTimeEq for Plaintext {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        Plaintext::ct_eq(self, other)
+    }
+}
+
+#[cfg(feature = "gmp")]
 impl AsMut<Integer> for Ciphertext {
     fn as_mut(&mut self) -> &mut Integer {
         &mut self.val
     }
 }
 
+#[cfg(feature = "gmp")]
 impl AsMut<Integer> for Plaintext {
     fn as_mut(&mut self) -> &mut Integer {
         &mut self.val
     }
 }
+
+#[cfg(all(test, feature = "gmp"))]
+mod tests {
+    use crate::paillier::generate_key_pair;
+    use crate::{Ciphertext, Plaintext};
+    use rug::rand::RandState;
+    use rug::Integer;
+    use std::collections::HashSet;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_ciphertext_eq_hash() {
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let c1 = pk.encrypt(1.into(), &mut rand);
+        let c2 = pk.encrypt(1.into(), &mut rand);
+        assert_ne!(c1, c2);
+        let unique: HashSet<Ciphertext> = vec![c1.clone(), c2.clone(), c1.clone()]
+            .into_iter()
+            .collect();
+        assert_eq!(unique.len(), 2);
+        assert_eq!(Ciphertext::from_bytes(&pk, &c1.to_bytes(&pk)).unwrap(), c1);
+    }
+
+    #[test]
+    fn test_plaintext_conversions() {
+        let p = Plaintext::from(300);
+        assert_eq!(u64::try_from(&p).unwrap(), 300);
+        assert_eq!(i16::try_from(p.clone()).unwrap(), 300);
+        assert!(u8::try_from(&p).is_err());
+        assert!(u32::try_from(Plaintext::from(-1)).is_err());
+        assert!(u128::try_from(Plaintext::from(Integer::from(1) << 128)).is_err());
+
+        assert_eq!(Plaintext::from(3).to_f64(-1), 1.5);
+        assert_eq!(Plaintext::from(5).to_f64(2), 20.0);
+
+        assert!(bool::from(Plaintext::from(7).ct_eq(&Plaintext::from(7))));
+        assert!(!bool::from(Plaintext::from(7).ct_eq(&Plaintext::from(-7))));
+    }
+}
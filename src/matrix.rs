@@ -0,0 +1,318 @@
+//! Matrices of ciphertexts multiplied with plaintext matrices.
+//!
+//! Every entry of a product is a homomorphic linear combination, which is computed
+//! with one multi-exponentiation, and the entries are computed in parallel with the
+//! `parallel` feature. This suffices for the inference of linear models on encrypted
+//! features, `A_enc * B_plain`, and for stations applying their plaintext data to
+//! encrypted parameters, `A_plain * B_enc`. The products are deterministic functions of
+//! their inputs, [`EncryptedMatrix::reencrypt`] them before publishing.
+//!
+//! ```
+//! use pht_crypto::matrix::{EncryptedMatrix, PlaintextMatrix};
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+//!
+//! let a = PlaintextMatrix::from_rows(vec![vec![1.into(), 2.into()], vec![3.into(), 4.into()]])
+//!     .unwrap();
+//! let b = PlaintextMatrix::from_rows(vec![vec![5.into()], vec![6.into()]]).unwrap();
+//! let a_enc = EncryptedMatrix::encrypt(&pk, &a, &mut rand);
+//!
+//! let product = a_enc.mul_plain(&pk, &b).unwrap();
+//! assert_eq!(product.decrypt(&sk), PlaintextMatrix::new(2, 1, vec![17.into(), 39.into()]).unwrap());
+//! let product = EncryptedMatrix::plain_mul(&pk, &a, &a_enc).unwrap();
+//! assert_eq!(product.decrypt(&sk).entry(1, 1), &22);
+//! ```
+
+use crate::paillier::{PartialDecryption, PrivateKey, PrivateKeyShare, PublicKey};
+use crate::par::map_collect;
+use crate::util::multi_pow_mod;
+use crate::vector::{CiphertextVec, PlaintextVec};
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+
+/// A rows x cols matrix of plaintexts, stored row by row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaintextMatrix {
+    rows: usize,
+    cols: usize,
+    entries: Vec<Plaintext>,
+}
+
+/// A rows x cols matrix of ciphertexts, stored row by row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedMatrix {
+    rows: usize,
+    cols: usize,
+    entries: CiphertextVec,
+}
+
+impl PlaintextMatrix {
+    /// Creates a matrix from its `entries` in row-major order
+    pub fn new(rows: usize, cols: usize, entries: Vec<Plaintext>) -> Result<Self> {
+        ensure!(
+            rows.checked_mul(cols) == Some(entries.len()),
+            "expected {} x {} entries",
+            rows,
+            cols
+        );
+        Ok(Self {
+            rows,
+            cols,
+            entries,
+        })
+    }
+
+    /// Fails if the rows differ in length
+    pub fn from_rows(rows: Vec<Vec<Plaintext>>) -> Result<Self> {
+        let cols = rows.first().map_or(0, Vec::len);
+        ensure!(
+            rows.iter().all(|row| row.len() == cols),
+            "rows differ in length"
+        );
+        Self::new(rows.len(), cols, rows.into_iter().flatten().collect())
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Panics if the position is out of bounds
+    pub fn entry(&self, row: usize, col: usize) -> &Plaintext {
+        assert!(row < self.rows && col < self.cols, "position out of bounds");
+        &self.entries[row * self.cols + col]
+    }
+
+    /// The entries reduced mod n, to be used as exponents
+    fn exponents(&self, pk: &PublicKey) -> Vec<Integer> {
+        self.entries
+            .iter()
+            .map(|m| m.as_ref().modulo_ref(&pk.n).complete())
+            .collect()
+    }
+}
+
+impl EncryptedMatrix {
+    pub fn encrypt(pk: &PublicKey, plain: &PlaintextMatrix, rand: &mut dyn MutRandState) -> Self {
+        let entries = PlaintextVec::from(plain.entries.clone());
+        Self {
+            rows: plain.rows,
+            cols: plain.cols,
+            entries: CiphertextVec::encrypt(pk, &entries, rand),
+        }
+    }
+
+    /// Wraps ciphertexts in row-major order
+    pub fn from_ciphertexts(rows: usize, cols: usize, entries: CiphertextVec) -> Result<Self> {
+        ensure!(
+            rows.checked_mul(cols) == Some(entries.len()),
+            "expected {} x {} entries",
+            rows,
+            cols
+        );
+        Ok(Self {
+            rows,
+            cols,
+            entries,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Panics if the position is out of bounds
+    pub fn entry(&self, row: usize, col: usize) -> &Ciphertext {
+        assert!(row < self.rows && col < self.cols, "position out of bounds");
+        &self.entries.as_slice()[row * self.cols + col]
+    }
+
+    /// The entries in row-major order
+    pub fn entries(&self) -> &CiphertextVec {
+        &self.entries
+    }
+
+    /// Enc(A * B) for this matrix A and the plaintext matrix `b`
+    pub fn mul_plain(&self, pk: &PublicKey, b: &PlaintextMatrix) -> Result<Self> {
+        ensure!(
+            self.cols == b.rows,
+            "can't multiply {} x {} and {} x {} matrices",
+            self.rows,
+            self.cols,
+            b.rows,
+            b.cols
+        );
+        let exps = b.exponents(pk);
+        let a = self.entries.as_slice();
+        let entries = linear_combinations(pk, self.rows, b.cols, |i, j| {
+            let bases = (0..self.cols)
+                .map(|k| a[i * self.cols + k].as_ref())
+                .collect();
+            let exps = (0..self.cols)
+                .map(|k| exps[k * b.cols + j].clone())
+                .collect();
+            (bases, exps)
+        });
+        Self::from_ciphertexts(self.rows, b.cols, entries)
+    }
+
+    /// Enc(A * B) for the plaintext matrix `a` and the encrypted matrix `b`
+    pub fn plain_mul(pk: &PublicKey, a: &PlaintextMatrix, b: &EncryptedMatrix) -> Result<Self> {
+        ensure!(
+            a.cols == b.rows,
+            "can't multiply {} x {} and {} x {} matrices",
+            a.rows,
+            a.cols,
+            b.rows,
+            b.cols
+        );
+        let exps = a.exponents(pk);
+        let entries = b.entries.as_slice();
+        let product = linear_combinations(pk, a.rows, b.cols, |i, j| {
+            let bases = (0..a.cols)
+                .map(|k| entries[k * b.cols + j].as_ref())
+                .collect();
+            let exps = exps[i * a.cols..(i + 1) * a.cols].to_vec();
+            (bases, exps)
+        });
+        Self::from_ciphertexts(a.rows, b.cols, product)
+    }
+
+    /// Enc(A * x) for this matrix A and the plaintext column vector `x`
+    pub fn mul_vec(&self, pk: &PublicKey, x: &PlaintextVec) -> Result<CiphertextVec> {
+        let x = PlaintextMatrix::new(x.len(), 1, x.as_slice().to_vec())?;
+        Ok(self.mul_plain(pk, &x)?.entries)
+    }
+
+    /// Re-randomizes all entries
+    pub fn reencrypt(&mut self, pk: &PublicKey, rand: &mut dyn MutRandState) {
+        self.entries.reencrypt(pk, rand);
+    }
+
+    pub fn decrypt(&self, sk: &PrivateKey) -> PlaintextMatrix {
+        PlaintextMatrix {
+            rows: self.rows,
+            cols: self.cols,
+            entries: self.entries.decrypt(sk).into_inner(),
+        }
+    }
+
+    /// Partial decryptions of all entries in row-major order
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> Vec<PartialDecryption> {
+        self.entries.share_decrypt(pk, key_share)
+    }
+
+    /// Combines the partial decryptions of at least w servers, one vector per server
+    /// as returned by [`EncryptedMatrix::share_decrypt`]
+    pub fn share_combine(
+        &self,
+        pk: &PublicKey,
+        partials: &[Vec<PartialDecryption>],
+    ) -> Result<PlaintextMatrix> {
+        let entries = self.entries.share_combine(pk, partials)?;
+        PlaintextMatrix::new(self.rows, self.cols, entries.into_inner())
+    }
+}
+
+/// Computes the rows x cols entries prod_k bases[k]^exps[k], with the bases and
+/// exponents of every entry given by `terms`
+fn linear_combinations<'a, F>(pk: &PublicKey, rows: usize, cols: usize, terms: F) -> CiphertextVec
+where
+    F: Fn(usize, usize) -> (Vec<&'a Integer>, Vec<Integer>) + Sync + Send,
+{
+    let positions: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|i| (0..cols).map(move |j| (i, j)))
+        .collect();
+    map_collect(&positions, |_, &(i, j)| {
+        let (bases, exps) = terms(i, j);
+        // the exponents are reduced mod n, so no base has to be inverted
+        Ciphertext::from(multi_pow_mod(&bases, &exps, &pk.n2).unwrap())
+    })
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncryptedMatrix, PlaintextMatrix};
+    use crate::paillier::generate_key_pair;
+    use crate::vector::PlaintextVec;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    fn matrix(rows: usize, cols: usize, f: impl Fn(usize, usize) -> i64) -> PlaintextMatrix {
+        let entries = (0..rows * cols)
+            .map(|pos| f(pos / cols, pos % cols).into())
+            .collect();
+        PlaintextMatrix::new(rows, cols, entries).unwrap()
+    }
+
+    #[test]
+    fn test_matrix_products() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let key_share = sk.clone().share(&[0], &mut rand).remove(0);
+        let a = matrix(3, 4, |i, j| i as i64 - 2 * j as i64);
+        let b = matrix(4, 2, |i, j| (i * j) as i64 + 1);
+        let expected = matrix(3, 2, |i, j| {
+            (0..4)
+                .map(|k| (i as i64 - 2 * k as i64) * ((k * j) as i64 + 1))
+                .sum()
+        });
+
+        let a_enc = EncryptedMatrix::encrypt(&pk, &a, &mut rand);
+        let b_enc = EncryptedMatrix::encrypt(&pk, &b, &mut rand);
+        // maps [n/2, n) to the negative numbers
+        let centered = |m: &Integer| {
+            if *m > Integer::from(pk.modulus() >> 1) {
+                Integer::from(m - pk.modulus())
+            } else {
+                m.clone()
+            }
+        };
+        let decode = |m: &EncryptedMatrix| {
+            let partials = vec![m.share_decrypt(&pk, &key_share)];
+            let plain = m.share_combine(&pk, &partials).unwrap();
+            let entries = plain
+                .entries
+                .iter()
+                .map(|e| centered(e.as_ref()).into())
+                .collect();
+            PlaintextMatrix::new(plain.rows(), plain.cols(), entries).unwrap()
+        };
+        assert_eq!(decode(&a_enc.mul_plain(&pk, &b).unwrap()), expected);
+        assert_eq!(
+            decode(&EncryptedMatrix::plain_mul(&pk, &a, &b_enc).unwrap()),
+            expected
+        );
+        assert!(a_enc.mul_plain(&pk, &a).is_err());
+        assert!(EncryptedMatrix::plain_mul(&pk, &b, &b_enc).is_err());
+
+        let x: PlaintextVec = vec![1, 1, 1, 1].into_iter().collect();
+        let ax = a_enc.mul_vec(&pk, &x).unwrap();
+        let ax: Vec<_> = ax
+            .decrypt(&sk)
+            .as_slice()
+            .iter()
+            .map(|m| centered(m.as_ref()))
+            .collect();
+        assert_eq!(ax, [-12, -8, -4]);
+        assert!(PlaintextMatrix::from_rows(vec![vec![1.into()], vec![]]).is_err());
+    }
+}
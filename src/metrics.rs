@@ -0,0 +1,78 @@
+//! Names of the metrics recorded with the `metrics` feature.
+//!
+//! The metrics are recorded with the [`metrics`](https://docs.rs/metrics) crate, so
+//! any of its exporters, e.g. for Prometheus, can publish them. Without the feature
+//! nothing is recorded. A spike of [`INVALID_PARTIAL_DECRYPTIONS`] or
+//! [`PROOF_VERIFICATION_FAILURES`] hints at a faulty or malicious server, a growing
+//! [`RANDOMIZER_POOL_MISSES`] at a refill thread which can't keep up.
+
+/// Counter of encrypted plaintexts
+pub const ENCRYPTIONS: &str = "pht_crypto_encryptions_total";
+/// Histogram of the time [`crate::paillier::PublicKey::share_combine`] takes, in
+/// seconds
+pub const COMBINE_SECONDS: &str = "pht_crypto_share_combine_seconds";
+/// Counter of partial decryptions rejected by [`crate::coordinator::Coordinator`] or
+/// because of their metadata
+pub const INVALID_PARTIAL_DECRYPTIONS: &str = "pht_crypto_invalid_partial_decryptions_total";
+/// Counter of proofs which failed to verify, labeled with the kind of `proof`
+pub const PROOF_VERIFICATION_FAILURES: &str = "pht_crypto_proof_verification_failures_total";
+/// Counter of randomizers which had to be computed because the
+/// [`crate::pool::RandomizerPool`] was empty
+pub const RANDOMIZER_POOL_MISSES: &str = "pht_crypto_randomizer_pool_misses_total";
+/// Gauge of the randomizers left in a [`crate::pool::RandomizerPool`] after one was
+/// taken
+pub const RANDOMIZER_POOL_SIZE: &str = "pht_crypto_randomizer_pool_size";
+
+#[cfg(feature = "metrics")]
+pub(crate) use recorded::*;
+#[cfg(not(feature = "metrics"))]
+pub(crate) use unrecorded::*;
+
+#[cfg(feature = "metrics")]
+mod recorded {
+    use std::time::Duration;
+
+    pub(crate) fn encryptions(count: usize) {
+        ::metrics::counter!(super::ENCRYPTIONS).increment(count as u64);
+    }
+
+    pub(crate) fn combine_duration(duration: Duration) {
+        ::metrics::histogram!(super::COMBINE_SECONDS).record(duration.as_secs_f64());
+    }
+
+    pub(crate) fn invalid_partial_decryption() {
+        ::metrics::counter!(super::INVALID_PARTIAL_DECRYPTIONS).increment(1);
+    }
+
+    /// Records a failure if `valid` is false and returns it
+    pub(crate) fn proof_verified(proof: &'static str, valid: bool) -> bool {
+        if !valid {
+            ::metrics::counter!(super::PROOF_VERIFICATION_FAILURES, "proof" => proof).increment(1);
+        }
+        valid
+    }
+
+    pub(crate) fn randomizers_taken(missing: usize, left: usize) {
+        if missing > 0 {
+            ::metrics::counter!(super::RANDOMIZER_POOL_MISSES).increment(missing as u64);
+        }
+        ::metrics::gauge!(super::RANDOMIZER_POOL_SIZE).set(left as f64);
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+mod unrecorded {
+    use std::time::Duration;
+
+    pub(crate) fn encryptions(_count: usize) {}
+
+    pub(crate) fn combine_duration(_duration: Duration) {}
+
+    pub(crate) fn invalid_partial_decryption() {}
+
+    pub(crate) fn proof_verified(_proof: &'static str, valid: bool) -> bool {
+        valid
+    }
+
+    pub(crate) fn randomizers_taken(_missing: usize, _left: usize) {}
+}
@@ -0,0 +1,265 @@
+//! Re-encryption mixnet for ciphertexts. A mix node re-randomizes and permutes a batch
+//! of ciphertexts and proves that the output batch decrypts to a permutation of the
+//! plaintexts of the input batch, without revealing the permutation.
+//!
+//! The shuffle proof is a Fiat-Shamir transformed cut-and-choose proof using shadow
+//! mixes (Sako-Kilian): the prover shuffles the input [`SHUFFLE_ROUNDS`] more times
+//! and, depending on a challenge bit, opens the link of each shadow mix either to
+//! the input or to the output batch. Interactively, a cheating prover is caught with
+//! probability 1 - 2^{-SHUFFLE_ROUNDS}. As the challenge bits come from a hash, a
+//! prover trying 2^t shadow mixes can cheat with probability about
+//! 2^{t - SHUFFLE_ROUNDS}, so the rounds match the bit length of the Fiat-Shamir
+//! challenges of the other proofs.
+
+use crate::paillier::PublicKey;
+use crate::par::prelude::*;
+use crate::proofs::{challenge, in_mult_group, CHALLENGE_BITS};
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::transcript::Transcript;
+use crate::Ciphertext;
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// Number of shadow mixes in a [`ShuffleProof`], one per challenge bit. The soundness
+/// error is 2^-SHUFFLE_ROUNDS per hash evaluation of a cheating prover.
+pub const SHUFFLE_ROUNDS: usize = CHALLENGE_BITS as usize;
+
+const LABEL: &[u8] = b"pht-crypto/mixnet/shuffle";
+
+/// Secret permutation and randomness of a shuffle: `output[i] = input[permutation[i]] * r_i^n`
+#[derive(Debug, Clone)]
+pub struct ShuffleWitness {
+    permutation: Vec<usize>,
+    randomness: Vec<Integer>,
+}
+
+/// A shadow mix of the input and the opening of its link to either the input or the output
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+struct ShadowMix {
+    #[serde(with = "crate::util::serde_integer_vec")]
+    shadow: Vec<Integer>,
+    permutation: Vec<usize>,
+    #[serde(with = "crate::util::serde_integer_vec")]
+    randomness: Vec<Integer>,
+}
+
+/// Non-interactive proof that a batch of ciphertexts is a re-encrypted permutation of another
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ShuffleProof {
+    rounds: Vec<ShadowMix>,
+}
+
+/// Re-randomizes and permutes `input` with a uniformly random permutation.
+pub fn shuffle(
+    pk: &PublicKey,
+    input: &[Ciphertext],
+    rand: &mut dyn MutRandState,
+) -> (Vec<Ciphertext>, ShuffleWitness) {
+    let permutation = random_permutation(input.len(), rand);
+    let randomness: Vec<_> = (0..input.len())
+        .map(|_| random_in_mult_group(&pk.n, UnitCheck::Skip, rand))
+        .collect();
+    let input: Vec<_> = input.iter().map(|c| c.as_ref().clone()).collect();
+    let output = apply_shuffle(pk, &input, &permutation, &randomness)
+        .into_iter()
+        .map(Ciphertext::from)
+        .collect();
+    (
+        output,
+        ShuffleWitness {
+            permutation,
+            randomness,
+        },
+    )
+}
+
+/// Proves that `output` was computed from `input` by [`shuffle`] returning `witness`.
+pub fn prove_shuffle(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    input: &[Ciphertext],
+    output: &[Ciphertext],
+    witness: &ShuffleWitness,
+    rand: &mut dyn MutRandState,
+) -> ShuffleProof {
+    let input: Vec<_> = input.iter().map(|c| c.as_ref().clone()).collect();
+    let mut rounds: Vec<_> = (0..SHUFFLE_ROUNDS)
+        .map(|_| {
+            let permutation = random_permutation(input.len(), rand);
+            let randomness: Vec<_> = (0..input.len())
+                .map(|_| random_in_mult_group(&pk.n, UnitCheck::Skip, rand))
+                .collect();
+            let shadow = apply_shuffle(pk, &input, &permutation, &randomness);
+            ShadowMix {
+                shadow,
+                permutation,
+                randomness,
+            }
+        })
+        .collect();
+
+    let e = shuffle_challenge(pk, transcript, &input, output, &rounds);
+    let mut inverse = vec![0; witness.permutation.len()];
+    for (i, &p) in witness.permutation.iter().enumerate() {
+        inverse[p] = i;
+    }
+    for (j, round) in rounds.iter_mut().enumerate() {
+        if !e.get_bit(j as u32) {
+            continue;
+        }
+        // open the link output -> shadow instead: shadow[i] = output[k] * (rho_i / r_k)^n
+        // where output[k] holds the same input as shadow[i]
+        for i in 0..round.permutation.len() {
+            let k = inverse[round.permutation[i]];
+            let r_inv = witness.randomness[k].clone().invert(&pk.n).unwrap();
+            round.randomness[i] *= r_inv;
+            round.randomness[i] %= &pk.n;
+            round.permutation[i] = k;
+        }
+    }
+    ShuffleProof { rounds }
+}
+
+/// Verifies that `output` is a re-encrypted permutation of `input`.
+pub fn verify_shuffle(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    input: &[Ciphertext],
+    output: &[Ciphertext],
+    proof: &ShuffleProof,
+) -> bool {
+    if input.len() != output.len() || proof.rounds.len() != SHUFFLE_ROUNDS {
+        return false;
+    }
+    let ciphers_valid = input
+        .iter()
+        .chain(output)
+        .all(|c| in_mult_group(c.as_ref(), &pk.n, &pk.n2));
+    if !ciphers_valid {
+        return false;
+    }
+    let input: Vec<_> = input.iter().map(|c| c.as_ref().clone()).collect();
+    let output_raw: Vec<_> = output.iter().map(|c| c.as_ref().clone()).collect();
+    let e = shuffle_challenge(pk, transcript, &input, output, &proof.rounds);
+    proof.rounds.par_iter().enumerate().all(|(j, round)| {
+        if round.shadow.len() != input.len()
+            || round.randomness.len() != input.len()
+            || !is_permutation(&round.permutation)
+            || !round
+                .randomness
+                .iter()
+                .all(|r| in_mult_group(r, &pk.n, &pk.n))
+        {
+            return false;
+        }
+        let source = if e.get_bit(j as u32) {
+            &output_raw
+        } else {
+            &input
+        };
+        apply_shuffle(pk, source, &round.permutation, &round.randomness) == round.shadow
+    })
+}
+
+/// Computes `ciphers[permutation[i]] * randomness[i]^n mod n^2` for every i
+fn apply_shuffle(
+    pk: &PublicKey,
+    ciphers: &[Integer],
+    permutation: &[usize],
+    randomness: &[Integer],
+) -> Vec<Integer> {
+    (0..permutation.len())
+        .into_par_iter()
+        .map(|i| {
+            let mut c = randomness[i].clone().pow_mod(&pk.n, &pk.n2).unwrap();
+            c *= &ciphers[permutation[i]];
+            c %= &pk.n2;
+            c
+        })
+        .collect()
+}
+
+fn shuffle_challenge(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    input: &[Integer],
+    output: &[Ciphertext],
+    rounds: &[ShadowMix],
+) -> Integer {
+    let mut values = vec![&pk.n];
+    values.extend(input);
+    values.extend(output.iter().map(|c| c.as_ref()));
+    values.extend(rounds.iter().flat_map(|round| &round.shadow));
+    challenge(transcript, LABEL, &values)
+}
+
+/// Fisher-Yates shuffle of 0..len
+fn random_permutation(len: usize, rand: &mut dyn MutRandState) -> Vec<usize> {
+    let mut permutation: Vec<_> = (0..len).collect();
+    for i in (1..len).rev() {
+        let j = Integer::from(i + 1).random_below(rand).to_usize().unwrap();
+        permutation.swap(i, j);
+    }
+    permutation
+}
+
+fn is_permutation(permutation: &[usize]) -> bool {
+    let mut seen = vec![false; permutation.len()];
+    permutation
+        .iter()
+        .all(|&p| p < seen.len() && !std::mem::replace(&mut seen[p], true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_shuffle, shuffle, verify_shuffle};
+    use crate::paillier::generate_key_pair;
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_shuffle() {
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let input: Vec<_> = (0..5).map(|m| pk.encrypt(m.into(), &mut rand)).collect();
+        let (output, witness) = shuffle(&pk, &input, &mut rand);
+        let proof = prove_shuffle(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &input,
+            &output,
+            &witness,
+            &mut rand,
+        );
+        assert!(verify_shuffle(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &input,
+            &output,
+            &proof
+        ));
+
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+        let mut plaintexts: Vec<i32> = output
+            .iter()
+            .map(|c| {
+                let share = key_share.share_decrypt(&pk, c.clone());
+                let m: rug::Integer = pk.share_combine(&[share]).unwrap().into();
+                m.to_i32().unwrap()
+            })
+            .collect();
+        plaintexts.sort_unstable();
+        assert_eq!(plaintexts, vec![0, 1, 2, 3, 4]);
+
+        let mut tampered = output.clone();
+        pk.add_plain(&mut tampered[0], &1.into());
+        assert!(!verify_shuffle(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &input,
+            &tampered,
+            &proof
+        ));
+    }
+}
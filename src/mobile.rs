@@ -0,0 +1,159 @@
+//! Bindings for iOS and Android client apps (feature `uniffi`).
+//!
+//! Clients only hold the public key of a study. They encrypt their responses locally
+//! with a [`StudyKey`], optionally re-randomize or add them, and submit the canonical
+//! encoding of [`crate::wire`] to the aggregator. The bindings are exported with UniFFI
+//! proc macros, generate the Kotlin or Swift sources from the built library with
+//! `uniffi-bindgen generate --library target/release/libpht_crypto.so --language kotlin
+//! --out-dir out` of UniFFI 0.28.
+
+use crate::paillier::PublicKey;
+use crate::wire::{self, Versioned};
+use crate::{rng, Ciphertext};
+use rug::integer::Order;
+use rug::Integer;
+use std::fmt;
+use std::sync::Arc;
+
+/// Errors surfaced to the client
+#[derive(Debug, uniffi::Error)]
+pub enum MobileError {
+    /// Bytes or values passed in are invalid
+    Invalid { message: String },
+}
+
+impl fmt::Display for MobileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MobileError::Invalid { message } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for MobileError {}
+
+impl From<anyhow::Error> for MobileError {
+    fn from(err: anyhow::Error) -> Self {
+        MobileError::Invalid {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// The public key of a study
+#[derive(Debug, uniffi::Object)]
+pub struct StudyKey {
+    pk: PublicKey,
+}
+
+/// A response encrypted under a [`StudyKey`]
+#[derive(Debug, uniffi::Object)]
+pub struct EncryptedValue {
+    cipher: Ciphertext,
+}
+
+#[uniffi::export]
+impl StudyKey {
+    /// Parses a public key in the versioned encoding of [`crate::wire`]
+    #[uniffi::constructor]
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Arc<Self>, MobileError> {
+        let pk = PublicKey::from_versioned_bytes(&bytes)?;
+        Ok(Arc::new(Self { pk }))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.pk.to_versioned_bytes()
+    }
+
+    /// Fingerprint of the key, for displaying it to the user
+    pub fn fingerprint(&self) -> Vec<u8> {
+        self.pk.fingerprint().to_vec()
+    }
+
+    /// Encrypts `value`, negative values are encoded as n + value
+    pub fn encrypt(&self, value: i64) -> Arc<EncryptedValue> {
+        Arc::new(EncryptedValue {
+            cipher: self.pk.encrypt_default(value.into()),
+        })
+    }
+
+    /// Encrypts the unsigned big endian value `bytes`, which must be less than n
+    pub fn encrypt_bytes(&self, bytes: Vec<u8>) -> Result<Arc<EncryptedValue>, MobileError> {
+        let m = Integer::from_digits(&bytes, Order::MsfBe);
+        if m >= *self.pk.modulus() {
+            return Err(MobileError::Invalid {
+                message: "value must be less than n".to_string(),
+            });
+        }
+        Ok(Arc::new(EncryptedValue {
+            cipher: self.pk.encrypt_default(m.into()),
+        }))
+    }
+
+    /// Encrypts all `values`, e.g. the answers of a questionnaire
+    pub fn encrypt_all(&self, values: Vec<i64>) -> Vec<Arc<EncryptedValue>> {
+        values
+            .into_iter()
+            .map(|value| self.encrypt(value))
+            .collect()
+    }
+
+    /// A fresh encryption of the same value, which can't be linked to `value`
+    pub fn rerandomize(&self, value: Arc<EncryptedValue>) -> Arc<EncryptedValue> {
+        let mut cipher = value.cipher.clone();
+        self.pk.reencrypt(&mut cipher, &mut rng::secure());
+        Arc::new(EncryptedValue { cipher })
+    }
+
+    /// Encrypts the sum of the values of `a` and `b`
+    pub fn add(&self, a: Arc<EncryptedValue>, b: Arc<EncryptedValue>) -> Arc<EncryptedValue> {
+        let mut cipher = a.cipher.clone();
+        self.pk.add_encrypted(&mut cipher, &b.cipher);
+        Arc::new(EncryptedValue { cipher })
+    }
+
+    /// Canonical fixed size encoding of `value` for submission
+    pub fn encode(&self, value: Arc<EncryptedValue>) -> Vec<u8> {
+        value.cipher.to_bytes(&self.pk)
+    }
+
+    /// Concatenated canonical encodings of `values`, see [`wire::encode_ciphertexts`]
+    pub fn encode_all(&self, values: Vec<Arc<EncryptedValue>>) -> Vec<u8> {
+        let ciphers: Vec<_> = values.iter().map(|value| value.cipher.clone()).collect();
+        wire::encode_ciphertexts(&self.pk, &ciphers)
+    }
+
+    /// Parses a canonically encoded value
+    pub fn decode(&self, bytes: Vec<u8>) -> Result<Arc<EncryptedValue>, MobileError> {
+        let cipher = Ciphertext::from_bytes(&self.pk, &bytes)?;
+        Ok(Arc::new(EncryptedValue { cipher }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StudyKey;
+    use crate::paillier::generate_key_pair;
+    use crate::wire::{decode_ciphertexts, Versioned};
+
+    #[test]
+    fn test_client_encryption() {
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let key = StudyKey::from_bytes(pk.to_versioned_bytes()).unwrap();
+        assert!(StudyKey::from_bytes(vec![0; 8]).is_err());
+        assert_eq!(key.fingerprint(), pk.fingerprint());
+
+        let answers = key.encrypt_all(vec![3, 4]);
+        let rerandomized = key.rerandomize(answers[0].clone());
+        assert_ne!(rerandomized.cipher, answers[0].cipher);
+        let sum = key.add(rerandomized, answers[1].clone());
+        let decoded = key.decode(key.encode(sum)).unwrap();
+        assert_eq!(sk.decrypt(&decoded.cipher), 7);
+
+        let submitted = decode_ciphertexts(&pk, &key.encode_all(answers)).unwrap();
+        assert_eq!(sk.decrypt(&submitted[1]), 4);
+        let big = key.encrypt_bytes(vec![1, 0]).unwrap();
+        assert_eq!(sk.decrypt(&big.cipher), 256);
+        assert!(key.encrypt_bytes(vec![0xff; 17]).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! Okamoto-Uchiyama encryption with modulus n = p^2 * q. Ciphertexts are only as
+//! large as n, compared to n^2 for paillier, at the cost of a plaintext space of
+//! only Z_p and no threshold decryption.
+//!
+//! Source: Okamoto, Uchiyama "A New Public-Key Cryptosystem as Secure as Factoring"
+
+use crate::rand::{generate_prime, random_in_mult_group, UnitCheck};
+use crate::{util, Ciphertext, Plaintext};
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PublicKey {
+    /// Modulus of the key. n = p^2 * q
+    #[serde(with = "crate::util::serde_integer")]
+    n: Integer,
+    /// Random g with g^{p-1} mod p^2 of order p
+    #[serde(with = "crate::util::serde_integer")]
+    g: Integer,
+    /// Precomputation: g^n mod n
+    #[serde(with = "crate::util::serde_integer")]
+    h: Integer,
+    /// Plaintexts must be smaller than 2^plaintext_bits < p
+    plaintext_bits: u32,
+}
+
+/// Compares the secrets in constant time
+#[derive(Debug, Clone, Serialize, Deserialize, Eq)]
+pub struct PrivateKey {
+    #[serde(with = "crate::util::serde_integer")]
+    p: Integer,
+    /// Precomputation: p^2
+    #[serde(with = "crate::util::serde_integer")]
+    p2: Integer,
+    /// Precomputation: L(g^{p-1} mod p^2)^{-1} mod p
+    #[serde(with = "crate::util::serde_integer")]
+    lg_inv: Integer,
+}
+
+impl ConstantTimeEq for PrivateKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        // p^2 is determined by p
+        util::ct_eq_integer(&self.p, &other.p) & util::ct_eq_integer(&self.lg_inv, &other.lg_inv)
+    }
+}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+/// Generates a key pair with a modulus of about `bits` bits using primes of `bits / 3` bits.
+pub fn generate_key_pair(
+    bits: usize,
+    rand: &mut dyn MutRandState,
+) -> Result<(PublicKey, PrivateKey)> {
+    let (p, q) = loop {
+        let p = generate_prime(bits / 3)?;
+        let q = generate_prime(bits / 3)?;
+        if p != q {
+            break (p, q);
+        }
+    };
+    let p2 = p.clone().square();
+    let n = (&p2 * &q).complete();
+    let p1 = Integer::from(&p - 1);
+    let (g, lg) = loop {
+        let g = random_in_mult_group(&n, UnitCheck::Gcd, rand);
+        let gp = g.pow_mod_ref(&p1, &p2).unwrap().into();
+        let lg = l_function(gp, &p);
+        if lg != 0 {
+            break (g, lg);
+        }
+    };
+    let lg_inv = lg.invert(&p).map_err(|_| anyhow!("No inverse"))?;
+    let h = g.pow_mod_ref(&n, &n).unwrap().into();
+    let plaintext_bits = p.significant_bits() - 1;
+    let pk = PublicKey {
+        n,
+        g,
+        h,
+        plaintext_bits,
+    };
+    let sk = PrivateKey { p, p2, lg_inv };
+    Ok((pk, sk))
+}
+
+/// L(x) = (x - 1) / p mod p
+fn l_function(x: Integer, p: &Integer) -> Integer {
+    let rop: Integer = (x - 1) / p;
+    rop % p
+}
+
+impl PublicKey {
+    /// Plaintexts, including results of homomorphic operations, must be smaller
+    /// than 2^plaintext_bits to be decryptable.
+    pub fn plaintext_bits(&self) -> u32 {
+        self.plaintext_bits
+    }
+
+    pub fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Ciphertext {
+        let r = Integer::from(self.n.random_below_ref(rand));
+        let mut rop = self.g.pow_mod_ref(m.as_ref(), &self.n).unwrap().complete();
+        rop *= Integer::from(self.h.pow_mod_ref(&r, &self.n).unwrap());
+        rop %= &self.n;
+        rop.into()
+    }
+
+    pub fn reencrypt(&self, cipher: &mut Ciphertext, rand: &mut dyn MutRandState) {
+        let r = Integer::from(self.n.random_below_ref(rand));
+        let cipher = cipher.as_mut();
+        *cipher *= Integer::from(self.h.pow_mod_ref(&r, &self.n).unwrap());
+        *cipher %= &self.n;
+    }
+
+    pub fn add_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        let cipher = cipher.as_mut();
+        *cipher *= Integer::from(self.g.pow_mod_ref(plain.as_ref(), &self.n).unwrap());
+        *cipher %= &self.n;
+    }
+
+    pub fn add_encrypted(&self, cipher1: &mut Ciphertext, cipher2: &Ciphertext) {
+        *cipher1.as_mut() *= cipher2.as_ref();
+        *cipher1.as_mut() %= &self.n;
+    }
+
+    pub fn mul_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+        cipher
+            .as_mut()
+            .pow_mod_mut(plain.as_ref(), &self.n)
+            .unwrap();
+    }
+}
+
+impl PrivateKey {
+    /// Decrypts `cipher` to m = L(c^{p-1} mod p^2) / L(g^{p-1} mod p^2) mod p.
+    pub fn decrypt(&self, cipher: &Ciphertext) -> Result<Plaintext> {
+        let c = cipher.as_ref();
+        ensure!(*c > 0, "invalid ciphertext");
+        let p1 = Integer::from(&self.p - 1);
+        let cp = util::secure_pow_mod(c, &p1, &self.p2);
+        let rop = l_function(cp, &self.p) * &self.lg_inv % &self.p;
+        Ok(rop.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_okamoto_uchiyama() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(192, &mut rand).unwrap();
+        let mut c = pk.encrypt(10.into(), &mut rand);
+        let c2 = pk.encrypt(4.into(), &mut rand);
+        pk.add_encrypted(&mut c, &c2);
+        pk.mul_plain(&mut c, &3.into());
+        pk.reencrypt(&mut c, &mut rand);
+        assert_eq!(sk.decrypt(&c).unwrap(), 42);
+        pk.add_plain(&mut c, &1.into());
+        assert_eq!(sk.decrypt(&c).unwrap(), 43);
+    }
+}
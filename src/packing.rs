@@ -0,0 +1,392 @@
+//! Packing of multiple small values into a single Paillier plaintext.
+//!
+//! A [`Packer`] places `slots` values into consecutive `bits_per_slot` bit wide slots of
+//! one plaintext, i.e. sum_i v_i * 2^{i * bits_per_slot}. Adding packed ciphertexts
+//! adds the slots element-wise, as long as no slot overflows into the next one. If the
+//! values have at most `value_bits` bits, up to 2^{bits_per_slot - value_bits} packed
+//! plaintexts can be summed safely. [`PackedCiphertext`] counts the summands to enforce
+//! this bound.
+//!
+//! ```
+//! use pht_crypto::packing::Packer;
+//! use pht_crypto::paillier::generate_key_pair;
+//! use rug::{rand::RandState, Integer};
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 1, 1).unwrap();
+//! let key_share = sk.share(&[0], &mut rand).remove(0);
+//!
+//! // 16 bit values with room for 2^8 additions
+//! let packer = Packer::new(24, 8).unwrap().with_value_bits(16).unwrap();
+//! let a: Vec<Integer> = (0..8).map(Integer::from).collect();
+//! let b: Vec<Integer> = (0..8).map(|i| Integer::from(i * 100)).collect();
+//! let mut sum = packer.encrypt(&pk, &a, &mut rand).unwrap();
+//! packer.add(&pk, &mut sum, &packer.encrypt(&pk, &b, &mut rand).unwrap()).unwrap();
+//!
+//! let partial = key_share.share_decrypt(&pk, sum.cipher().clone());
+//! let unpacked = packer.unpack(&pk.share_combine(&[partial]).unwrap());
+//! assert_eq!(unpacked[3], 303);
+//! ```
+//!
+//! # CRT packing
+//! A [`CrtPacker`] instead places the slots of a [`SlotVector`] modulo pairwise coprime
+//! moduli m_i into one plaintext via the Chinese remainder theorem. Each slot then
+//! behaves like an element of Z_{m_i}: additions and slot-wise multiplications wrap
+//! modulo m_i without affecting other slots, as long as the underlying integer stays
+//! below n. With M = prod m_i this allows n / M summands or one slot-wise
+//! multiplication if M^2 < n.
+
+use crate::paillier::{PartialDecryption, PublicKey};
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Packer {
+    bits_per_slot: u32,
+    slots: usize,
+    value_bits: u32,
+}
+
+/// Packed ciphertext together with the number of packed plaintexts summed in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackedCiphertext {
+    cipher: Ciphertext,
+    summands: u64,
+}
+
+impl PackedCiphertext {
+    pub fn cipher(&self) -> &Ciphertext {
+        &self.cipher
+    }
+
+    pub fn summands(&self) -> u64 {
+        self.summands
+    }
+}
+
+impl Packer {
+    /// Creates a packer for `slots` slots of `bits_per_slot` bits. Without a call to
+    /// [`Packer::with_value_bits`] values may use the whole slot and no additions are
+    /// possible. Fails if the slots are empty.
+    pub fn new(bits_per_slot: u32, slots: usize) -> Result<Self> {
+        ensure!(bits_per_slot > 0, "slots must not be empty");
+        Ok(Self {
+            bits_per_slot,
+            slots,
+            value_bits: bits_per_slot,
+        })
+    }
+
+    /// Restricts values to `value_bits` bits, leaving the remaining bits of each slot as
+    /// headroom for additions.
+    pub fn with_value_bits(mut self, value_bits: u32) -> Result<Self> {
+        ensure!(
+            value_bits <= self.bits_per_slot,
+            "values don't fit into a slot"
+        );
+        ensure!(
+            self.bits_per_slot - value_bits < 64,
+            "headroom must be less than 64 bits"
+        );
+        self.value_bits = value_bits;
+        Ok(self)
+    }
+
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// The number of packed plaintexts that can be summed without overflowing a slot
+    pub fn max_summands(&self) -> u64 {
+        1 << (self.bits_per_slot - self.value_bits)
+    }
+
+    /// Checks whether all slots fit into the plaintext space of `pk`
+    pub fn fits(&self, pk: &PublicKey) -> bool {
+        (self.slots as u64) * u64::from(self.bits_per_slot) < u64::from(pk.n.significant_bits())
+    }
+
+    /// Packs `values` into one plaintext. Missing trailing values are 0.
+    pub fn pack(&self, values: &[Integer]) -> Result<Plaintext> {
+        ensure!(values.len() <= self.slots, "too many values");
+        let mut rop = Integer::new();
+        for v in values.iter().rev() {
+            ensure!(
+                *v >= 0 && v.significant_bits() <= self.value_bits,
+                "value {} does not fit into {} bits",
+                v,
+                self.value_bits
+            );
+            rop <<= self.bits_per_slot;
+            rop += v;
+        }
+        Ok(rop.into())
+    }
+
+    /// Splits a plaintext into its slots
+    pub fn unpack(&self, plain: &Plaintext) -> Vec<Integer> {
+        let mut plain = plain.as_ref().clone();
+        (0..self.slots)
+            .map(|_| {
+                let slot = Integer::from(plain.keep_bits_ref(self.bits_per_slot));
+                plain >>= self.bits_per_slot;
+                slot
+            })
+            .collect()
+    }
+
+    pub fn encrypt(
+        &self,
+        pk: &PublicKey,
+        values: &[Integer],
+        rand: &mut dyn MutRandState,
+    ) -> Result<PackedCiphertext> {
+        ensure!(self.fits(pk), "slots don't fit into the plaintext space");
+        Ok(PackedCiphertext {
+            cipher: pk.encrypt(self.pack(values)?, rand),
+            summands: 1,
+        })
+    }
+
+    /// Adds `other` slot-wise to `cipher`. Fails if the sum could overflow a slot.
+    pub fn add(
+        &self,
+        pk: &PublicKey,
+        cipher: &mut PackedCiphertext,
+        other: &PackedCiphertext,
+    ) -> Result<()> {
+        let summands = cipher.summands.saturating_add(other.summands);
+        ensure!(
+            summands <= self.max_summands(),
+            "sum of {} packed plaintexts could overflow a slot",
+            summands
+        );
+        pk.add_encrypted(&mut cipher.cipher, &other.cipher);
+        cipher.summands = summands;
+        Ok(())
+    }
+}
+
+/// Plaintext vector whose i-th slot is an element of Z_{m_i}
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SlotVector {
+    #[serde(with = "crate::util::serde_integer_vec")]
+    values: Vec<Integer>,
+}
+
+impl SlotVector {
+    pub fn new(values: Vec<Integer>) -> Self {
+        Self { values }
+    }
+
+    pub fn values(&self) -> &[Integer] {
+        &self.values
+    }
+}
+
+impl From<Vec<Integer>> for SlotVector {
+    fn from(values: Vec<Integer>) -> Self {
+        Self::new(values)
+    }
+}
+
+impl From<SlotVector> for Vec<Integer> {
+    fn from(v: SlotVector) -> Self {
+        v.values
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CrtPacker {
+    #[serde(with = "crate::util::serde_integer_vec")]
+    moduli: Vec<Integer>,
+    /// Precomputation: M = prod m_i
+    #[serde(with = "crate::util::serde_integer")]
+    modulus: Integer,
+    /// Precomputation: e_i = 1 mod m_i and e_i = 0 mod m_j for j != i
+    #[serde(with = "crate::util::serde_integer_vec")]
+    basis: Vec<Integer>,
+}
+
+impl CrtPacker {
+    /// Creates a packer for slots modulo the pairwise coprime `moduli`
+    pub fn new(moduli: Vec<Integer>) -> Result<Self> {
+        ensure!(!moduli.is_empty(), "at least one slot is needed");
+        ensure!(
+            moduli.iter().all(|m| *m > 1),
+            "moduli must be greater than 1"
+        );
+        for (i, m_i) in moduli.iter().enumerate() {
+            for m_j in &moduli[i + 1..] {
+                ensure!(
+                    Integer::from(m_i.gcd_ref(m_j)) == 1,
+                    "moduli must be pairwise coprime"
+                );
+            }
+        }
+        let modulus: Integer = moduli.iter().product();
+        let basis = moduli
+            .iter()
+            .map(|m_i| {
+                let rest = Integer::from(&modulus / m_i);
+                let inv = rest.clone().invert(m_i).unwrap();
+                rest * inv
+            })
+            .collect();
+        Ok(Self {
+            moduli,
+            modulus,
+            basis,
+        })
+    }
+
+    pub fn moduli(&self) -> &[Integer] {
+        &self.moduli
+    }
+
+    /// Checks whether M = prod m_i fits into the plaintext space of `pk`
+    pub fn fits(&self, pk: &PublicKey) -> bool {
+        self.modulus < pk.n
+    }
+
+    /// The number of encoded vectors that can be summed before the sum wraps mod n
+    pub fn max_summands(&self, pk: &PublicKey) -> Integer {
+        Integer::from(&pk.n - 1) / Integer::from(&self.modulus - 1)
+    }
+
+    /// Encodes `v` as the unique x mod M with x = v_i mod m_i. Missing trailing slots
+    /// are 0.
+    pub fn encode(&self, v: &SlotVector) -> Result<Plaintext> {
+        ensure!(v.values.len() <= self.moduli.len(), "too many slots");
+        let mut rop = Integer::new();
+        for ((value, m_i), e_i) in v.values.iter().zip(&self.moduli).zip(&self.basis) {
+            let mut value = Integer::from(value % m_i);
+            if value < 0 {
+                value += m_i;
+            }
+            rop += value * e_i;
+        }
+        rop %= &self.modulus;
+        Ok(rop.into())
+    }
+
+    /// Decodes a plaintext into its slots by reducing it modulo every m_i
+    pub fn decode(&self, plain: &Plaintext) -> SlotVector {
+        let plain = plain.as_ref();
+        let values = self
+            .moduli
+            .iter()
+            .map(|m_i| {
+                let mut v = Integer::from(plain % m_i);
+                if v < 0 {
+                    v += m_i;
+                }
+                v
+            })
+            .collect();
+        SlotVector { values }
+    }
+
+    pub fn encrypt(
+        &self,
+        pk: &PublicKey,
+        v: &SlotVector,
+        rand: &mut dyn MutRandState,
+    ) -> Result<Ciphertext> {
+        ensure!(self.fits(pk), "slots don't fit into the plaintext space");
+        Ok(pk.encrypt(self.encode(v)?, rand))
+    }
+
+    /// Multiplies every slot of `cipher` with the corresponding slot of `scalars`.
+    /// Requires that the encrypted value times M is less than n, e.g. M^2 < n for a
+    /// fresh encryption.
+    pub fn mul_slots(
+        &self,
+        pk: &PublicKey,
+        cipher: &mut Ciphertext,
+        scalars: &SlotVector,
+    ) -> Result<()> {
+        ensure!(
+            Integer::from(self.modulus.square_ref()) < pk.n,
+            "slot-wise multiplication could wrap mod n"
+        );
+        pk.mul_plain(cipher, &self.encode(scalars)?);
+        Ok(())
+    }
+
+    /// Combines the partial decryptions of at least w servers and decodes the result
+    pub fn share_combine(
+        &self,
+        pk: &PublicKey,
+        partials: &[PartialDecryption],
+    ) -> Result<SlotVector> {
+        Ok(self.decode(&pk.share_combine(partials)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrtPacker, Packer, SlotVector};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_pack_unpack() {
+        let packer = Packer::new(10, 4).unwrap().with_value_bits(8).unwrap();
+        assert_eq!(packer.max_summands(), 4);
+        let values: Vec<Integer> = [255, 0, 17, 3].iter().map(|v| Integer::from(*v)).collect();
+        let packed = packer.pack(&values).unwrap();
+        assert_eq!(packer.unpack(&packed), values);
+        assert_eq!(
+            packer.unpack(&packer.pack(&values[..2]).unwrap()),
+            [255, 0, 0, 0]
+        );
+        assert!(packer.pack(&[Integer::from(256)]).is_err());
+        assert!(packer.pack(&[Integer::from(-1)]).is_err());
+        assert!(Packer::new(8, 2).unwrap().with_value_bits(9).is_err());
+        assert!(Packer::new(0, 2).is_err());
+    }
+
+    #[test]
+    fn test_crt_slots() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 1, 1).unwrap();
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+        let moduli: Vec<Integer> = [251, 256, 255, 253]
+            .iter()
+            .map(|m| Integer::from(*m))
+            .collect();
+        assert!(CrtPacker::new(vec![Integer::from(4), Integer::from(6)]).is_err());
+        let packer = CrtPacker::new(moduli).unwrap();
+
+        let a = SlotVector::from(vec![
+            Integer::from(250),
+            Integer::from(3),
+            Integer::from(-1),
+        ]);
+        let b = SlotVector::from(vec![
+            Integer::from(2),
+            Integer::from(255),
+            Integer::from(7),
+            Integer::from(1),
+        ]);
+        let mut c = packer.encrypt(&pk, &a, &mut rand).unwrap();
+        pk.add_encrypted(&mut c, &packer.encrypt(&pk, &b, &mut rand).unwrap());
+        let scalars = SlotVector::from(vec![
+            Integer::from(1),
+            Integer::from(2),
+            Integer::from(3),
+            Integer::from(100),
+        ]);
+        packer.mul_slots(&pk, &mut c, &scalars).unwrap();
+
+        let partial = key_share.share_decrypt(&pk, c);
+        let result = packer.share_combine(&pk, &[partial]).unwrap();
+        // slot-wise (a + b) * scalars, wrapping mod m_i
+        assert_eq!(result.values(), &[1, 4, 18, 100]);
+    }
+}
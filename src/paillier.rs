@@ -1,115 +1,361 @@
-use crate::rand::{generate_safe_prime, random_in_mult_group};
-use crate::{util, Ciphertext, Plaintext};
-use anyhow::{anyhow, Result};
+use crate::rand::{
+    generate_modulus_safe_primes, random_in_mult_group, UnitCheck, MILLER_RABIN_ROUNDS,
+};
+use crate::{metrics, util, Ciphertext, Plaintext, Randomness};
+use anyhow::{anyhow, ensure, Result};
+use rug::integer::IsPrime;
 use rug::rand::MutRandState;
 use rug::{Assign, Complete, Integer};
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
 
-use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use std::convert::TryInto;
-use std::thread;
+use crate::par;
+use std::convert::{TryFrom, TryInto};
+use std::time::Instant;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrivateKeyShare {
-    i: u32,
+    /// Evaluation point, never 0
+    #[serde(deserialize_with = "crate::util::deserialize_point")]
+    pub(crate) i: u32,
     /// Polynomial evaluation at i
     #[serde(with = "crate::util::serde_integer")]
-    si: Integer,
+    pub(crate) si: Integer,
+    /// [`PublicKey::fingerprint`] of the shared key, set when the share is dealt
+    #[serde(default)]
+    pub(crate) key_fingerprint: Option<[u8; 32]>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PartialDecryption {
     #[serde(with = "crate::util::serde_integer")]
-    val: Integer,
-    id: u32,
+    pub(crate) val: Integer,
+    /// Evaluation point of the share, never 0
+    #[serde(deserialize_with = "crate::util::deserialize_point")]
+    pub(crate) id: u32,
+    /// [`Ciphertext::digest`] of the decrypted ciphertext, if known
+    #[serde(default)]
+    pub(crate) cipher_digest: Option<[u8; 32]>,
+    /// [`PublicKey::fingerprint`] of the key, if known
+    #[serde(default)]
+    pub(crate) key_fingerprint: Option<[u8; 32]>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct PublicKey {
     /// The number of servers req to successfully decrypt
-    w: u32,
+    pub(crate) w: u32,
     /// The number of decryption servers in total
-    l: u32,
+    pub(crate) l: u32,
     /// Modulus of the key. n = p * q
     #[serde(with = "crate::util::serde_integer")]
-    n: Integer,
+    pub(crate) n: Integer,
     /// Precomputation: n + 1
     #[serde(with = "crate::util::serde_integer")]
-    g: Integer,
+    pub(crate) g: Integer,
     /// Precomputation: n^2
     #[serde(with = "crate::util::serde_integer")]
-    n2: Integer,
+    pub(crate) n2: Integer,
     /// Precomputation: l!
     #[serde(with = "crate::util::serde_integer")]
-    delta: Integer,
+    pub(crate) delta: Integer,
     /// Precomputation (4*delta^2)^{-1} mod n
     #[serde(with = "crate::util::serde_integer")]
-    combine_shares_constant: Integer,
+    pub(crate) combine_shares_constant: Integer,
 }
 
+/// Serialized form of a [`PublicKey`] without the precomputations, which are
+/// recomputed on conversion back. Use `#[serde(with = "serde_compact")]` to store a
+/// `PublicKey` field in this form.
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CompactPublicKey {
+    w: u32,
+    l: u32,
+    #[serde(with = "crate::util::serde_integer")]
+    n: Integer,
+}
+
+impl From<&PublicKey> for CompactPublicKey {
+    fn from(pk: &PublicKey) -> Self {
+        Self {
+            w: pk.w,
+            l: pk.l,
+            n: pk.n.clone(),
+        }
+    }
+}
+
+impl TryFrom<CompactPublicKey> for PublicKey {
+    type Error = anyhow::Error;
+
+    fn try_from(pk: CompactPublicKey) -> Result<Self> {
+        PublicKey::from_modulus(pk.n, pk.l, pk.w)
+    }
+}
+
+/// Ser/de of a [`PublicKey`] as a [`CompactPublicKey`]
+pub mod serde_compact {
+    use super::{CompactPublicKey, PublicKey};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::convert::TryFrom;
+
+    pub fn serialize<S: Serializer>(pk: &PublicKey, serializer: S) -> Result<S::Ok, S::Error> {
+        CompactPublicKey::from(pk).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PublicKey, D::Error> {
+        let compact = CompactPublicKey::deserialize(deserializer)?;
+        PublicKey::try_from(compact).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrivateKey {
     /// The number of servers req to decrypt
-    w: u32,
+    pub(crate) w: u32,
     /// The number of decryption servers in total
-    l: u32,
+    pub(crate) l: u32,
     /// d = 0 mod m and d = 1 mod n^2
     #[serde(with = "crate::util::serde_integer")]
-    d: Integer,
+    pub(crate) d: Integer,
     /// Modulus of the key: p * q
     #[serde(with = "crate::util::serde_integer")]
-    n: Integer,
+    pub(crate) n: Integer,
     /// Precomputation: n^2
     #[serde(with = "crate::util::serde_integer")]
-    n2: Integer,
+    pub(crate) n2: Integer,
     /// Precomputation: n * m
     #[serde(with = "crate::util::serde_integer")]
-    nm: Integer,
+    pub(crate) nm: Integer,
+    /// The prime factors of n if known, enabling the CRT fast paths. Always serialized,
+    /// as non self-describing formats like bincode can't skip fields.
+    #[serde(default)]
+    pub(crate) factors: Option<ModulusFactors>,
 }
 
+// Compares the secrets in constant time. The factors are a cache determined by n, so
+// keys decoded from formats without them still compare equal.
+impl ConstantTimeEq for PrivateKey {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        let public = (self.w, self.l, &self.n) == (other.w, other.l, &other.n);
+        Choice::from(u8::from(public))
+            & util::ct_eq_integer(&self.d, &other.d)
+            & util::ct_eq_integer(&self.nm, &other.nm)
+    }
+}
+
+impl PartialEq for PrivateKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for PrivateKey {}
+
+/// Default statistical security parameter of [`Polynomial::new_statistical`]
+pub const DEFAULT_STATISTICAL_SECURITY: u32 = 80;
+
 pub struct Polynomial<'a> {
-    sk: &'a PrivateKey,
-    coefficients: Vec<Integer>,
+    pub(crate) sk: &'a PrivateKey,
+    pub(crate) coefficients: Vec<Integer>,
+    /// Shares are reduced mod n * m unless the polynomial is evaluated over the integers
+    pub(crate) reduce: bool,
+}
+
+/// Feldman commitments to the coefficients of a sharing [`Polynomial`]. These
+/// are published by the dealer so that every server can check its share with
+/// [`PrivateKeyShare::verify_against`].
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PolynomialCommitments {
+    /// Random generator of the squares in Z*_{n^2}
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) v: Integer,
+    /// v^{a_j} mod n^2 for every coefficient a_j
+    #[serde(with = "crate::util::serde_integer_vec")]
+    pub(crate) commitments: Vec<Integer>,
 }
 
+/// The secret safe prime factors p and q of the modulus n = p * q. They are needed to
+/// prove properties of the modulus, see [`crate::proofs::prove_modulus`], and speed up
+/// exponentiations mod n^2 by the CRT. Compares in constant time.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq)]
+pub struct ModulusFactors {
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) p: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) q: Integer,
+}
+
+impl ConstantTimeEq for ModulusFactors {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        util::ct_eq_integer(&self.p, &other.p) & util::ct_eq_integer(&self.q, &other.q)
+    }
+}
+
+impl PartialEq for ModulusFactors {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl ModulusFactors {
+    /// Precomputes the moduli p^2 and q^2 for repeated exponentiations mod n^2
+    pub(crate) fn crt(&self) -> Crt<'_> {
+        let p2 = self.p.clone().square();
+        let q2 = self.q.clone().square();
+        let p2_inv = p2.invert_ref(&q2).unwrap().into();
+        Crt {
+            factors: self,
+            p2,
+            q2,
+            p2_inv,
+        }
+    }
+}
+
+/// Exponentiation mod n^2 as two half size exponentiations mod p^2 and q^2
+pub(crate) struct Crt<'a> {
+    factors: &'a ModulusFactors,
+    p2: Integer,
+    q2: Integer,
+    /// (p^2)^-1 mod q^2
+    p2_inv: Integer,
+}
+
+impl Crt<'_> {
+    /// base^exp mod n^2 for a non-negative exponent
+    pub(crate) fn pow_mod(&self, base: &Integer, exp: &Integer) -> Integer {
+        let xp = Self::pow_mod_prime_square(base, exp, &self.factors.p, &self.p2);
+        let xq = Self::pow_mod_prime_square(base, exp, &self.factors.q, &self.q2);
+        self.combine(xp, xq)
+    }
+
+    /// base^exp mod n^2 for a secret exponent. Both half size exponentiations run in
+    /// constant time with the exponent blinded by a random multiple of the group order.
+    pub(crate) fn secure_pow_mod(
+        &self,
+        base: &Integer,
+        exp: &Integer,
+        rand: &mut dyn MutRandState,
+    ) -> Integer {
+        let xp = Self::secure_pow_mod_prime_square(base, exp, &self.factors.p, &self.p2, rand);
+        let xq = Self::secure_pow_mod_prime_square(base, exp, &self.factors.q, &self.q2, rand);
+        self.combine(xp, xq)
+    }
+
+    /// The x mod n^2 with x = xp mod p^2 and x = xq mod q^2
+    fn combine(&self, xp: Integer, xq: Integer) -> Integer {
+        // x = xp + p^2 * ((xq - xp) * (p^2)^-1 mod q^2)
+        let mut t = xq - &xp;
+        t *= &self.p2_inv;
+        t.modulo_mut(&self.q2);
+        t * &self.p2 + xp
+    }
+
+    fn pow_mod_prime_square(base: &Integer, exp: &Integer, p: &Integer, p2: &Integer) -> Integer {
+        let base = Integer::from(base % p2);
+        if base.is_divisible(p) {
+            return base.pow_mod(exp, p2).unwrap();
+        }
+        // the order of Z*_{p^2} is p * (p - 1)
+        let order = Integer::from(p - 1) * p;
+        let exp = Integer::from(exp % &order);
+        base.pow_mod(&exp, p2).unwrap()
+    }
+
+    fn secure_pow_mod_prime_square(
+        base: &Integer,
+        exp: &Integer,
+        p: &Integer,
+        p2: &Integer,
+        rand: &mut dyn MutRandState,
+    ) -> Integer {
+        if base.is_divisible(p) {
+            return util::secure_pow_mod(base, exp, p2);
+        }
+        let order = Integer::from(p - 1) * p;
+        let exp = util::blind_exponent(&Integer::from(exp % &order), &order, rand);
+        util::secure_pow_mod(base, &exp, p2)
+    }
+}
+
+/// Generates a key pair whose modulus has exactly `bits` bits. No minimum size is
+/// enforced, use [`crate::keygen::KeygenParams`] to reject insecure sizes.
 pub fn generate_key_pair(
     bits: usize,
     decryption_servers: u32,
     threshold: u32,
 ) -> Result<(PublicKey, PrivateKey)> {
-    let bits = bits / 2;
-    let (mut t1, mut t2, mut t3, t4) = loop {
-        let handle = thread::spawn(move || generate_safe_prime(bits));
-        let (t3, t4) = generate_safe_prime(bits)?;
-        let (t1, t2) = handle.join().expect("joining thread")?;
-        if t1 != t3 {
-            break (t1, t2, t3, t4);
-        }
+    let (pk, sk, _factors) = generate_key_pair_with_factors(bits, decryption_servers, threshold)?;
+    Ok((pk, sk))
+}
+
+/// Like [`generate_key_pair`] but additionally returns the prime factors of the modulus.
+pub fn generate_key_pair_with_factors(
+    bits: usize,
+    decryption_servers: u32,
+    threshold: u32,
+) -> Result<(PublicKey, PrivateKey, ModulusFactors)> {
+    trace_span!("generate_key_pair", bits, decryption_servers, threshold);
+    let (p, p1, q, q1) = generate_modulus_safe_primes(bits)?;
+    key_pair_from_primes(p, p1, q, q1, decryption_servers, threshold)
+}
+
+/// Creates a key pair from externally supplied safe primes `p` and `q`, e.g. generated
+/// by an HSM or taken from certified test vectors. Fails unless p and q are distinct
+/// safe primes of at least 8 bits whose lengths differ by at most one bit.
+pub fn generate_key_pair_from_primes(
+    p: Integer,
+    q: Integer,
+    decryption_servers: u32,
+    threshold: u32,
+) -> Result<(PublicKey, PrivateKey)> {
+    ensure!(p != q, "primes must be distinct");
+    let (p_bits, q_bits) = (p.significant_bits(), q.significant_bits());
+    ensure!(p_bits.min(q_bits) >= 8, "primes must have at least 8 bits");
+    ensure!(
+        p_bits.abs_diff(q_bits) <= 1,
+        "primes of {} and {} bits are unbalanced",
+        p_bits,
+        q_bits
+    );
+    let p1 = safe_prime_half(&p)?;
+    let q1 = safe_prime_half(&q)?;
+    let (pk, sk, _) = key_pair_from_primes(p, p1, q, q1, decryption_servers, threshold)?;
+    Ok((pk, sk))
+}
+
+/// Returns (p - 1) / 2 if p is a safe prime
+fn safe_prime_half(p: &Integer) -> Result<Integer> {
+    let is_prime = |x: &Integer| x.is_probably_prime(MILLER_RABIN_ROUNDS) != IsPrime::No;
+    ensure!(p.is_odd() && is_prime(p), "{} is not prime", p);
+    let p1 = Integer::from(p >> 1);
+    ensure!(is_prime(&p1), "{} is not a safe prime", p);
+    Ok(p1)
+}
+
+/// Derives the key pair from the primes p = 2 * p1 + 1 and q = 2 * q1 + 1. p1 and q1
+/// are prime for safe primes, which is not needed for correctness of the scheme.
+pub(crate) fn key_pair_from_primes(
+    mut t1: Integer,
+    mut t2: Integer,
+    mut t3: Integer,
+    t4: Integer,
+    decryption_servers: u32,
+    threshold: u32,
+) -> Result<(PublicKey, PrivateKey, ModulusFactors)> {
+    let factors = ModulusFactors {
+        p: t1.clone(),
+        q: t3.clone(),
     };
     let n = t1.clone() * &t3;
     let n2 = n.clone().square();
-    let g = n.clone() + 1;
     t3 = t2.clone() * t4;
     let nm = n.clone() * &t3;
     t1.assign(1);
     t2.assign(0);
     let d = util::crt2(&t1, &n, &t2, &t3);
-    let delta = Integer::factorial(decryption_servers).complete();
-    let mut combine_shares_constant = delta.clone().square();
-    combine_shares_constant *= 4;
-    if combine_shares_constant.invert_mut(&n).is_err() {
-        return Err(anyhow!("No inverse"));
-    }
-
-    let pk = PublicKey {
-        w: threshold,
-        l: decryption_servers,
-        n: n.clone(),
-        g,
-        n2: n2.clone(),
-        delta,
-        combine_shares_constant,
-    };
+    let pk = PublicKey::from_modulus(n.clone(), decryption_servers, threshold)?;
 
     let sk = PrivateKey {
         w: threshold,
@@ -118,52 +364,221 @@ pub fn generate_key_pair(
         n,
         n2,
         nm,
+        factors: Some(factors.clone()),
     };
 
-    Ok((pk, sk))
+    Ok((pk, sk, factors))
 }
 
 impl PrivateKeyShare {
     pub fn new(si: Integer, i: u32) -> Self {
         // i + 1 needed for zero indexed servers
-        Self { i: i + 1, si }
+        let i = i.checked_add(1).expect("server index is out of range");
+        Self {
+            i,
+            si,
+            key_fingerprint: None,
+        }
     }
 
+    /// A share f(point) of the sharing polynomial f at an arbitrary nonzero point,
+    /// unlike [`PrivateKeyShare::new`] which takes the zero based server index.
+    ///
+    /// Panics if `point` is 0, as f(0) is the secret.
+    pub fn at_point(si: Integer, point: u32) -> Self {
+        assert_ne!(point, 0, "evaluation point 0 is the secret");
+        Self {
+            i: point,
+            si,
+            key_fingerprint: None,
+        }
+    }
+
+    /// Zero based index of the server, i.e. the evaluation point minus one
+    pub fn index(&self) -> u32 {
+        self.i - 1
+    }
+
+    /// Evaluation point of the share
+    pub fn point(&self) -> u32 {
+        self.i
+    }
+
+    /// Fingerprint of the key this share was dealt for, `None` for shares created
+    /// with [`PrivateKeyShare::new`] or [`PrivateKeyShare::at_point`]
+    pub fn key_fingerprint(&self) -> Option<&[u8; 32]> {
+        self.key_fingerprint.as_ref()
+    }
+
+    /// Computes the partial decryption c^{2 * Δ * s_i} mod n^2 in constant time. The
+    /// exponent can't be blinded, as share holders don't know the order of Z*_{n^2}.
     pub fn share_decrypt(&self, pk: &PublicKey, cipher: Ciphertext) -> PartialDecryption {
         let exponent = self.si.clone() * &pk.delta * 2;
-        let share = cipher.val.pow_mod(&exponent, &pk.n2).unwrap();
+        let share = util::secure_pow_mod(&cipher.val, &exponent, &pk.n2);
         PartialDecryption {
             val: share,
             id: self.i,
+            cipher_digest: Some(cipher.digest()),
+            key_fingerprint: Some(pk.fingerprint()),
+        }
+    }
+
+    /// Checks that this share lies on the polynomial the dealer committed to,
+    /// i.e. that v^{s_i} = prod_j C_j^{i^j} mod n^2.
+    pub fn verify_against(&self, pk: &PublicKey, commitments: &PolynomialCommitments) -> bool {
+        match commitments.evaluate(pk, self.i) {
+            Some(rhs) => util::secure_pow_mod(&commitments.v, &self.si, &pk.n2) == rhs,
+            None => false,
         }
     }
 }
 
+impl PartialDecryption {
+    /// Zero based index of the server, i.e. the evaluation point minus one
+    pub fn server_id(&self) -> u32 {
+        self.id - 1
+    }
+
+    /// Evaluation point of the share which computed this partial decryption
+    pub fn point(&self) -> u32 {
+        self.id
+    }
+
+    /// Digest of the ciphertext this partial decryption was computed for. `None` if
+    /// it was decoded from an encoding without metadata, like CBOR.
+    pub fn cipher_digest(&self) -> Option<&[u8; 32]> {
+        self.cipher_digest.as_ref()
+    }
+
+    /// Fingerprint of the key this partial decryption was computed with, `None` if
+    /// unknown
+    pub fn key_fingerprint(&self) -> Option<&[u8; 32]> {
+        self.key_fingerprint.as_ref()
+    }
+}
+
+impl PolynomialCommitments {
+    /// v^{f(point)} mod n^2 computed from the public commitments, or `None` if there
+    /// are no commitments or one is not invertible
+    pub(crate) fn evaluate(&self, pk: &PublicKey, point: u32) -> Option<Integer> {
+        if self.commitments.is_empty() {
+            return None;
+        }
+        let mut acc = Integer::from(1);
+        let mut x = Integer::from(1);
+        for c in &self.commitments {
+            acc *= Integer::from(c.pow_mod_ref(&x, &pk.n2)?);
+            acc %= &pk.n2;
+            x *= point;
+        }
+        Some(acc)
+    }
+}
+
 impl PublicKey {
+    /// Creates the public key for modulus `n` with `threshold` of `decryption_servers`
+    /// servers needed for decryption, recomputing all precomputations.
+    pub fn from_modulus(n: Integer, decryption_servers: u32, threshold: u32) -> Result<Self> {
+        ensure!(n > 1 && n.is_odd(), "modulus must be odd");
+        ensure!(
+            threshold >= 1 && threshold <= decryption_servers,
+            "threshold must be in [1, decryption_servers]"
+        );
+        let n2 = n.clone().square();
+        let g = n.clone() + 1;
+        let delta = Integer::factorial(decryption_servers).complete();
+        let mut combine_shares_constant = delta.clone().square();
+        combine_shares_constant *= 4;
+        if combine_shares_constant.invert_mut(&n).is_err() {
+            return Err(anyhow!("No inverse"));
+        }
+        Ok(PublicKey {
+            w: threshold,
+            l: decryption_servers,
+            n,
+            g,
+            n2,
+            delta,
+            combine_shares_constant,
+        })
+    }
+
+    /// The modulus n
+    pub fn modulus(&self) -> &Integer {
+        &self.n
+    }
+
+    /// The number of bits of the modulus n
+    pub fn bit_length(&self) -> u32 {
+        self.n.significant_bits()
+    }
+
+    /// The number of servers needed to decrypt
+    pub fn threshold(&self) -> u32 {
+        self.w
+    }
+
+    /// The number of decryption servers in total
+    pub fn decryption_servers(&self) -> u32 {
+        self.l
+    }
+
     pub fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Ciphertext {
-        let m = m.into();
-        // TODO is random_in_mult_group needed? Other implementations just choose 0 < r < n
-        // https://crypto.stackexchange.com/questions/62371/paillier-encryption-problem-when-q-or-p-divides-r
-        let mut r = random_in_mult_group(&self.n, rand);
-        let mut rop = self.g.clone().pow_mod(&m, &self.n2).unwrap();
-        r.pow_mod_mut(&self.n, &self.n2).unwrap();
-        rop *= r;
+        self.encrypt_with_randomness(m, rand).0
+    }
+
+    /// Encrypts `m` and additionally returns the randomness r used for the
+    /// ciphertext g^m * r^n mod n^2. This is needed to prove statements about the
+    /// ciphertext, e.g. with [`crate::proofs::prove_plaintext_knowledge`].
+    pub fn encrypt_with_randomness(
+        &self,
+        m: Plaintext,
+        rand: &mut dyn MutRandState,
+    ) -> (Ciphertext, Randomness) {
+        let r = random_in_mult_group(&self.n, UnitCheck::Skip, rand);
+        let c = self.encrypt_raw(m.as_ref(), &r);
+        metrics::encryptions(1);
+        (c.into(), r.into())
+    }
+
+    /// Computes g^m mod n^2 without an exponentiation: as g = n + 1, the binomial
+    /// theorem gives g^m = 1 + m * n mod n^2 for every integer m.
+    pub(crate) fn g_pow(&self, m: &Integer) -> Integer {
+        let mut rop: Integer = Integer::from(m * &self.n) + 1;
+        rop.modulo_mut(&self.n2);
+        rop
+    }
+
+    /// Computes g^m * r^n mod n^2 for the given randomness r
+    pub(crate) fn encrypt_raw(&self, m: &Integer, r: &Integer) -> Integer {
+        let mut rop = self.g_pow(m);
+        rop *= Integer::from(r.pow_mod_ref(&self.n, &self.n2).unwrap());
         rop %= &self.n2;
-        rop.into()
+        rop
     }
 
     pub fn reencrypt(&self, cipher: &mut Ciphertext, rand: &mut dyn MutRandState) {
+        self.reencrypt_with_randomness(cipher, rand);
+    }
+
+    /// Re-randomizes `cipher` by multiplying it with s^n mod n^2 and returns s. This
+    /// is needed to prove the correct re-encryption with
+    /// [`crate::proofs::prove_reencryption`].
+    pub fn reencrypt_with_randomness(
+        &self,
+        cipher: &mut Ciphertext,
+        rand: &mut dyn MutRandState,
+    ) -> Randomness {
         let cipher = cipher.as_mut();
-        let mut tmp = random_in_mult_group(&self.n, rand);
-        tmp.pow_mod_mut(&self.n, &self.n2).unwrap();
-        *cipher *= tmp;
+        let s = random_in_mult_group(&self.n, UnitCheck::Skip, rand);
+        *cipher *= Integer::from(s.pow_mod_ref(&self.n, &self.n2).unwrap());
         *cipher %= &self.n2;
+        s.into()
     }
 
     pub fn add_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
         let cipher = cipher.as_mut();
-        let tmp = self.g.clone().pow_mod(plain.as_ref(), &self.n2).unwrap();
-        *cipher *= tmp;
+        *cipher *= self.g_pow(plain.as_ref());
         *cipher %= &self.n2;
     }
 
@@ -179,32 +594,306 @@ impl PublicKey {
             .unwrap();
     }
 
+    /// Enc(a * m + b) for the plaintext m of `cipher`, computed as c^a * g^b with a
+    /// single reduction mod n^2. Without re-randomization the result is linkable to
+    /// `cipher`, see [`PublicKey::affine_randomized`].
+    pub fn affine(&self, cipher: &Ciphertext, a: &Plaintext, b: &Plaintext) -> Ciphertext {
+        let mut rop = self.affine_unreduced(cipher, a, b);
+        rop %= &self.n2;
+        rop.into()
+    }
+
+    /// Like [`PublicKey::affine`] but additionally multiplies with a fresh r^n
+    pub fn affine_randomized(
+        &self,
+        cipher: &Ciphertext,
+        a: &Plaintext,
+        b: &Plaintext,
+        rand: &mut dyn MutRandState,
+    ) -> Ciphertext {
+        let r = random_in_mult_group(&self.n, UnitCheck::Skip, rand);
+        let mut rop = self.affine_unreduced(cipher, a, b);
+        rop *= Integer::from(r.pow_mod_ref(&self.n, &self.n2).unwrap());
+        rop %= &self.n2;
+        rop.into()
+    }
+
+    /// c^(a mod n) * g^b, the product not reduced mod n^2
+    fn affine_unreduced(&self, cipher: &Ciphertext, a: &Plaintext, b: &Plaintext) -> Integer {
+        let a = a.as_ref().modulo_ref(&self.n).complete();
+        let mut rop = Integer::from(cipher.as_ref().pow_mod_ref(&a, &self.n2).unwrap());
+        // g^b = 1 + b * n mod n^2
+        let b = b.as_ref().modulo_ref(&self.n).complete();
+        rop *= b * &self.n + 1u32;
+        rop
+    }
+
+    /// Divides the plaintext of `cipher` by `k` mod n, i.e. multiplies it with
+    /// k^-1 mod n. The result is the exact quotient only if k divides the plaintext,
+    /// e.g. to average a sum whose participants are known to contribute multiples.
+    /// Fails if k is not invertible mod n.
+    pub fn div_plain(&self, cipher: &mut Ciphertext, k: &Plaintext) -> Result<()> {
+        let k_inv = k
+            .as_ref()
+            .invert_ref(&self.n)
+            .ok_or_else(|| anyhow!("divisor is not invertible mod n"))?;
+        self.mul_plain(cipher, &Integer::from(k_inv).into());
+        Ok(())
+    }
+
+    /// Combines the partial decryptions of at least w shares, which may have been
+    /// dealt at arbitrary evaluation points. Fails if their metadata shows that they
+    /// were computed with another key or for different ciphertexts.
     pub fn share_combine(&self, shares: &[PartialDecryption]) -> Result<Plaintext> {
-        let cprime: Integer = shares
-            .par_iter()
-            .enumerate()
-            .map(|(i, si)| {
-                let mut lambda = self.delta.clone();
-                for (j, sj) in shares.iter().enumerate() {
-                    if i == j {
-                        continue;
-                    }
-                    assert_ne!(si.id, sj.id, "`share_combine` must be passed unique shares");
-                    let v = si.id as i64 - sj.id as i64;
-                    lambda *= -(sj.id as i64);
-                    lambda /= v;
-                }
-                let lambda2 = lambda * 2;
-                si.val.clone().pow_mod(&lambda2, &self.n2).unwrap()
-            })
-            .reduce(|| Integer::from(1), |a, b| (a * b) % &self.n2);
+        trace_span!("share_combine", shares = shares.len());
+        let start = Instant::now();
+        let ids: Vec<_> = shares.iter().map(|share| share.id).collect();
+        util::check_evaluation_points(&ids)?;
+        if let Err(err) = self.check_partials(shares) {
+            metrics::invalid_partial_decryption();
+            return Err(err);
+        }
+        let bases: Vec<&Integer> = shares.iter().map(|share| &share.val).collect();
+        let (lambdas, scale) = util::lagrange_coefficients(&self.delta, &ids);
+        let exps: Vec<Integer> = lambdas.into_iter().map(|lambda| lambda * 2).collect();
+        let cprime = util::multi_pow_mod_par(&bases, &exps, &self.n2)
+            .ok_or_else(|| anyhow!("partial decryption is not invertible"))?;
+        let plaintext = self.decode_combined(cprime, &scale);
+        metrics::combine_duration(start.elapsed());
+        plaintext
+    }
+
+    /// Checks the metadata of partial decryptions, as far as it is known
+    fn check_partials(&self, shares: &[PartialDecryption]) -> Result<()> {
+        if shares.iter().any(|share| share.key_fingerprint.is_some()) {
+            let fingerprint = self.fingerprint();
+            ensure!(
+                shares
+                    .iter()
+                    .filter_map(|share| share.key_fingerprint.as_ref())
+                    .all(|f| *f == fingerprint),
+                "partial decryption was computed with another key"
+            );
+        }
+        let mut digests = shares
+            .iter()
+            .filter_map(|share| share.cipher_digest.as_ref());
+        if let Some(first) = digests.next() {
+            ensure!(
+                digests.all(|digest| digest == first),
+                "partial decryptions of different ciphertexts"
+            );
+        }
+        Ok(())
+    }
+
+    /// Recovers the plaintext from the product c' of the partial decryptions raised to
+    /// their Lagrange coefficients, which were multiplied by `scale`
+    fn decode_combined(&self, cprime: Integer, scale: &Integer) -> Result<Plaintext> {
         let t = (cprime - 1) / &self.n;
-        let rop: Integer = t * &self.combine_shares_constant % &self.n;
+        let mut rop: Integer = t * &self.combine_shares_constant % &self.n;
+        if *scale != 1 {
+            let scale_inv = scale
+                .invert_ref(&self.n)
+                .ok_or_else(|| anyhow!("evaluation points are not invertible mod n"))?;
+            rop *= Integer::from(scale_inv);
+            rop %= &self.n;
+        }
         Ok(rop.into())
     }
+
+    /// Starts combining the partial decryptions of the servers `server_indices`, as
+    /// passed to [`PrivateKey::share`], one at a time as they arrive. Each share is
+    /// exponentiated when it is added, so [`IncrementalCombine::finish`] is cheap.
+    ///
+    /// Unlike for [`PublicKey::share_combine`] the servers must be known upfront, as
+    /// the Lagrange coefficient of every share depends on all servers taking part.
+    pub fn combine_incremental(&self, server_indices: &[u32]) -> Result<IncrementalCombine<'_>> {
+        ensure!(
+            server_indices.len() >= self.w as usize,
+            "at least {} servers are needed to decrypt",
+            self.w
+        );
+        let ids: Vec<u32> = server_indices.iter().map(|idx| idx + 1).collect();
+        let mut sorted = ids.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        ensure!(sorted.len() == ids.len(), "server indices must be unique");
+        let (lambdas, scale) = util::lagrange_coefficients(&self.delta, &ids);
+        Ok(IncrementalCombine {
+            pk: self,
+            received: vec![false; ids.len()],
+            ids,
+            lambdas,
+            scale,
+            product: Integer::from(1),
+            cipher_digest: None,
+        })
+    }
+}
+
+/// Running product of the partial decryptions of a fixed set of servers, see
+/// [`PublicKey::combine_incremental`]
+#[derive(Debug, Clone)]
+pub struct IncrementalCombine<'a> {
+    pub(crate) pk: &'a PublicKey,
+    /// Share ids, i.e. server index + 1
+    ids: Vec<u32>,
+    lambdas: Vec<Integer>,
+    /// Common factor of the Lagrange coefficients beyond Δ
+    scale: Integer,
+    received: Vec<bool>,
+    product: Integer,
+    /// Digest of the ciphertext of the first added share which had one
+    cipher_digest: Option<[u8; 32]>,
+}
+
+impl IncrementalCombine<'_> {
+    /// Raises `share` to its Lagrange coefficient and multiplies it into the running
+    /// product. Fails for shares of other servers and for duplicates.
+    pub fn add(&mut self, share: &PartialDecryption) -> Result<()> {
+        let pos = self
+            .ids
+            .iter()
+            .position(|id| *id == share.id)
+            .ok_or_else(|| anyhow!("share with id {} was not expected", share.id))?;
+        // the expected ids are server indices + 1
+        let server = self.ids[pos] - 1;
+        ensure!(
+            !self.received[pos],
+            "share of server {} was already added",
+            server
+        );
+        self.pk.check_partials(std::slice::from_ref(share))?;
+        if let Some(digest) = &share.cipher_digest {
+            ensure!(
+                *self.cipher_digest.get_or_insert(*digest) == *digest,
+                "share of server {} is for another ciphertext",
+                server
+            );
+        }
+        let lambda2 = Integer::from(&self.lambdas[pos] * 2);
+        let power = share.val.pow_mod_ref(&lambda2, &self.pk.n2);
+        let power = Integer::from(power.ok_or_else(|| anyhow!("share is not invertible"))?);
+        self.product *= power;
+        self.product %= &self.pk.n2;
+        self.received[pos] = true;
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.received.iter().all(|received| *received)
+    }
+
+    /// The indices of the servers whose shares are still missing
+    pub fn missing(&self) -> Vec<u32> {
+        self.ids
+            .iter()
+            .zip(&self.received)
+            .filter(|(_, received)| !**received)
+            .map(|(id, _)| id - 1)
+            .collect()
+    }
+
+    /// Returns the plaintext once the shares of all servers were added
+    pub fn finish(self) -> Result<Plaintext> {
+        ensure!(
+            self.is_complete(),
+            "missing shares of servers {:?}",
+            self.missing()
+        );
+        self.pk.decode_combined(self.product, &self.scale)
+    }
 }
 
 impl PrivateKey {
+    /// Reassembles a private key from its encoded parts, recomputing n^2
+    pub(crate) fn from_parts(n: Integer, l: u32, w: u32, d: Integer, nm: Integer) -> Result<Self> {
+        ensure!(n > 1 && n.is_odd(), "modulus must be odd");
+        ensure!(
+            w >= 1 && w <= l,
+            "threshold must be in [1, decryption_servers]"
+        );
+        ensure!(Integer::from(&nm % &n) == 0, "nm must be a multiple of n");
+        let n2 = n.clone().square();
+        Ok(Self {
+            w,
+            l,
+            d,
+            n,
+            n2,
+            nm,
+            factors: None,
+        })
+    }
+
+    /// The prime factors of the modulus, if the key was generated by this crate and
+    /// not decoded from a format without them.
+    pub fn factors(&self) -> Option<&ModulusFactors> {
+        self.factors.as_ref()
+    }
+
+    /// The number of servers needed to decrypt
+    pub fn threshold(&self) -> u32 {
+        self.w
+    }
+
+    /// The number of decryption servers in total
+    pub fn decryption_servers(&self) -> u32 {
+        self.l
+    }
+
+    /// c^e mod n^2, via the CRT if the factors are known
+    fn pow_mod_n2(&self, base: &Integer, exp: &Integer) -> Integer {
+        match &self.factors {
+            Some(factors) => factors.crt().pow_mod(base, exp),
+            None => base.pow_mod_ref(exp, &self.n2).unwrap().into(),
+        }
+    }
+
+    /// c^e mod n^2 for a secret exponent e in constant time, via the CRT if the factors
+    /// are known. The exponent is blinded by a random multiple of the group order.
+    pub(crate) fn secure_pow_mod_n2(
+        &self,
+        base: &Integer,
+        exp: &Integer,
+        rand: &mut dyn MutRandState,
+    ) -> Integer {
+        match &self.factors {
+            Some(factors) => factors.crt().secure_pow_mod(base, exp, rand),
+            None => {
+                // the squares of Z*_{n^2} have order n * m
+                let order = Integer::from(&self.nm << 1);
+                let exp = util::blind_exponent(exp, &order, rand);
+                util::secure_pow_mod(base, &exp, &self.n2)
+            }
+        }
+    }
+
+    /// Encrypts `m` like [`PublicKey::encrypt`], computing r^n mod n^2 by the CRT if the
+    /// factors are known.
+    pub fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Ciphertext {
+        let r = random_in_mult_group(&self.n, UnitCheck::Skip, rand);
+        // g^m = (n + 1)^m = 1 + m * n mod n^2
+        let mut gm: Integer = Integer::from(m.as_ref() * &self.n) + 1;
+        gm.modulo_mut(&self.n2);
+        let rn = self.pow_mod_n2(&r, &self.n);
+        (gm * rn % &self.n2).into()
+    }
+
+    /// Decrypts `cipher` without the decryption servers. As d is a multiple of only
+    /// lambda / 2, this computes c^{2d} = 1 + 2 * m * n mod n^2. Uses the CRT if the
+    /// factors are known. The exponentiation runs in constant time with a blinded
+    /// exponent.
+    pub fn decrypt(&self, cipher: &Ciphertext) -> Plaintext {
+        let exp = Integer::from(&self.d << 1);
+        let c = self.secure_pow_mod_n2(&cipher.val, &exp, &mut crate::rng::secure());
+        let t: Integer = (c - 1) / &self.n;
+        let two_inv = Integer::from(2).invert(&self.n).unwrap();
+        let m: Integer = t * two_inv % &self.n;
+        m.into()
+    }
+
     pub fn share(
         self,
         server_indices: &[u32],
@@ -215,11 +904,89 @@ impl PrivateKey {
             self.w as usize,
             "share() must be called with w unique indices"
         );
+        trace_span!("share", shares = server_indices.len());
         let poly = Polynomial::new(&self, rand_state);
-        server_indices
-            .par_iter()
-            .map(|idx| poly.compute(*idx))
-            .collect()
+        par::map_collect(server_indices, |_, idx| poly.compute(*idx))
+    }
+
+    /// Deals one share at each of the nonzero, unique evaluation `points`. Any w of
+    /// them can decrypt. Points larger than l make combining slightly more expensive.
+    pub fn share_at_points(
+        &self,
+        points: &[u32],
+        rand_state: &mut dyn MutRandState,
+    ) -> Result<Vec<PrivateKeyShare>> {
+        util::check_evaluation_points(points)?;
+        ensure!(
+            points.len() >= self.w as usize,
+            "at least {} shares are needed to decrypt",
+            self.w
+        );
+        trace_span!("share", shares = points.len());
+        let poly = Polynomial::new(self, rand_state);
+        Ok(par::map_collect(points, |_, point| poly.evaluate(*point)))
+    }
+
+    /// Deals `weights[k]` shares to shareholder k for a weighted threshold: a set of
+    /// shareholders can decrypt if their weights sum to at least w. The shares are
+    /// dealt at the consecutive points 1, 2, .. and every shareholder partially
+    /// decrypts with all of its shares.
+    pub fn share_weighted(
+        &self,
+        weights: &[u32],
+        rand_state: &mut dyn MutRandState,
+    ) -> Result<Vec<Vec<PrivateKeyShare>>> {
+        let total = weights
+            .iter()
+            .try_fold(0u32, |total, weight| total.checked_add(*weight))
+            .ok_or_else(|| anyhow!("total weight is too large"))?;
+        let points: Vec<u32> = (1..=total).collect();
+        let mut shares = self.share_at_points(&points, rand_state)?.into_iter();
+        Ok(weights
+            .iter()
+            .map(|weight| shares.by_ref().take(*weight as usize).collect())
+            .collect())
+    }
+
+    /// Like [`PrivateKey::share`] but samples the sharing polynomial with
+    /// [`Polynomial::new_statistical`], so the shares reveal nothing about m.
+    pub fn share_statistical(
+        self,
+        server_indices: &[u32],
+        security_bits: u32,
+        rand_state: &mut dyn MutRandState,
+    ) -> Vec<PrivateKeyShare> {
+        assert_eq!(
+            server_indices.len(),
+            self.w as usize,
+            "share_statistical() must be called with w unique indices"
+        );
+        let poly = Polynomial::new_statistical(&self, security_bits, rand_state);
+        par::map_collect(server_indices, |_, idx| poly.compute(*idx))
+    }
+
+    /// Like [`PrivateKey::share`] but additionally returns Feldman commitments
+    /// to the sharing polynomial which the servers can verify their shares against.
+    /// Accepts any number of at least w unique indices, so all l servers can be dealt
+    /// a share.
+    pub fn share_verifiable(
+        self,
+        server_indices: &[u32],
+        rand_state: &mut dyn MutRandState,
+    ) -> (Vec<PrivateKeyShare>, PolynomialCommitments) {
+        let mut unique = server_indices.to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+        assert!(
+            unique.len() == server_indices.len()
+                && server_indices.len() >= self.w as usize
+                && server_indices.len() <= self.l as usize,
+            "share_verifiable() must be called with at least w unique indices"
+        );
+        let poly = Polynomial::new(&self, rand_state);
+        let commitments = poly.commit(rand_state);
+        let shares = par::map_collect(server_indices, |_, idx| poly.compute(*idx));
+        (shares, commitments)
     }
 }
 
@@ -230,26 +997,88 @@ impl<'a> Polynomial<'a> {
         for coeff in coefficients.iter_mut().skip(1) {
             coeff.random_below_mut(rand);
         }
-        Self { sk, coefficients }
+        Self {
+            sk,
+            coefficients,
+            reduce: true,
+        }
+    }
+
+    /// Samples the coefficients over the integers from [0, Δ^2 * n * m * 2^security_bits)
+    /// with Δ = l! like in the threshold schemes of Shoup and Damgård-Jurik. Shares
+    /// reduced mod n * m would tell the servers a multiple of m. Here, the shares are
+    /// not reduced and any w - 1 of them are statistically independent of the secret
+    /// up to a distance of about 2^-security_bits.
+    pub fn new_statistical<'b>(
+        sk: &'a PrivateKey,
+        security_bits: u32,
+        rand: &'b mut dyn MutRandState,
+    ) -> Self {
+        let delta = Integer::from(Integer::factorial(sk.l));
+        let bound: Integer = (delta.square() * &sk.nm) << security_bits;
+        let mut coefficients = vec![bound; sk.w as usize];
+        coefficients[0] = sk.d.clone();
+        for coeff in coefficients.iter_mut().skip(1) {
+            coeff.random_below_mut(rand);
+        }
+        Self {
+            sk,
+            coefficients,
+            reduce: false,
+        }
     }
 
+    /// The share of the server with zero based index `x`, evaluated at x + 1
     pub fn compute(&self, x: u32) -> PrivateKeyShare {
+        self.evaluate(x + 1)
+    }
+
+    /// The share at the nonzero evaluation point `point`, see
+    /// [`PrivateKeyShare::at_point`]
+    pub fn evaluate(&self, point: u32) -> PrivateKeyShare {
         let mut rop = self.coefficients[0].clone();
         for (i, coeff) in self.coefficients.iter().enumerate().skip(1) {
-            let mut tmp = Integer::u_pow_u(x + 1, i.try_into().unwrap()).complete();
+            let mut tmp = Integer::u_pow_u(point, i.try_into().unwrap()).complete();
             tmp *= coeff;
             rop += tmp;
-            rop %= &self.sk.nm;
+            if self.reduce {
+                rop %= &self.sk.nm;
+            }
         }
-        PrivateKeyShare::new(rop, x)
+        let mut share = PrivateKeyShare::at_point(rop, point);
+        share.key_fingerprint = Some(self.sk.fingerprint());
+        share
+    }
+
+    /// Computes Feldman commitments v^{a_j} mod n^2 to the coefficients of
+    /// this polynomial for a random generator v of the squares in Z*_{n^2}.
+    pub fn commit(&self, rand: &mut dyn MutRandState) -> PolynomialCommitments {
+        let n2 = &self.sk.n2;
+        let mut v = random_in_mult_group(n2, UnitCheck::Gcd, rand);
+        v.square_mut();
+        v %= n2;
+        // the coefficients are secret, v is in Z*_{n^2}
+        let crt = self.sk.factors.as_ref().map(ModulusFactors::crt);
+        let commitments = par::map_collect(&self.coefficients, |_, coeff| match &crt {
+            Some(crt) => crt.secure_pow_mod(&v, coeff, &mut crate::rng::secure()),
+            None => util::secure_pow_mod(&v, coeff, n2),
+        });
+        PolynomialCommitments { v, commitments }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::paillier::{generate_key_pair, Polynomial};
+    use crate::paillier::{
+        generate_key_pair, generate_key_pair_from_primes, generate_key_pair_with_factors,
+        CompactPublicKey, PartialDecryption, Polynomial, PrivateKeyShare, PublicKey,
+        DEFAULT_STATISTICAL_SECURITY,
+    };
+    use std::convert::TryFrom;
 
+    use rug::integer::IsPrime;
     use rug::rand::RandState;
+    use rug::Integer;
 
     use rand::seq::SliceRandom;
 
@@ -266,6 +1095,135 @@ mod tests {
         assert_eq!(combined, 5);
     }
 
+    #[test]
+    fn test_exact_bit_length() {
+        for bits in [127, 128] {
+            let (pk, _) = generate_key_pair(bits, 1, 1).unwrap();
+            assert_eq!(pk.bit_length(), bits as u32);
+        }
+    }
+
+    #[test]
+    fn test_key_pair_from_primes() {
+        let (_, _, factors) = generate_key_pair_with_factors(128, 2, 2).unwrap();
+        let (p, q) = (factors.p, factors.q);
+        let (pk, sk) = generate_key_pair_from_primes(p.clone(), q.clone(), 2, 2).unwrap();
+        assert_eq!(*pk.modulus(), Integer::from(&p * &q));
+        let mut rand = RandState::new();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+        let c = pk.encrypt(42.into(), &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&shares).unwrap(), 42);
+
+        assert!(generate_key_pair_from_primes(p.clone(), p.clone(), 1, 1).is_err());
+        let mut not_safe = Integer::from(&p + 2).next_prime();
+        while Integer::from(&not_safe >> 1).is_probably_prime(30) != IsPrime::No {
+            not_safe.next_prime_mut();
+        }
+        assert!(generate_key_pair_from_primes(p.clone(), not_safe, 1, 1).is_err());
+        assert!(generate_key_pair_from_primes(p.clone(), Integer::from(&q + 2), 1, 1).is_err());
+        assert!(generate_key_pair_from_primes(p, Integer::from(23), 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_crt_fast_paths() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+        let mut without_factors = sk.clone();
+        without_factors.factors = None;
+        for key in [&sk, &without_factors] {
+            let c = key.encrypt(42.into(), &mut rand);
+            assert_eq!(sk.decrypt(&c), 42);
+            assert_eq!(without_factors.decrypt(&c), 42);
+        }
+        let c = pk.encrypt(7.into(), &mut rand);
+        assert_eq!(sk.decrypt(&c), 7);
+
+        let (shares, commitments) = sk.share_verifiable(&[0, 1], &mut rand);
+        assert!(shares.iter().all(|s| s.verify_against(&pk, &commitments)));
+    }
+
+    #[test]
+    fn test_g_pow_shortcut() {
+        let (pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let mut exponents = vec![Integer::new(), Integer::from(1), Integer::from(-5)];
+        exponents.push(Integer::from(pk.n.random_below_ref(&mut rand)));
+        exponents.push(Integer::from(&pk.n2 + 3));
+        for m in &exponents {
+            let generic = pk.g.clone().pow_mod(m, &pk.n2).unwrap();
+            assert_eq!(pk.g_pow(m), generic);
+        }
+    }
+
+    #[test]
+    fn test_combine_incremental() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 5, 3).unwrap();
+        let key_shares = sk.share(&[0, 1, 2], &mut rand);
+        let c = pk.encrypt(42.into(), &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, c.clone()))
+            .collect();
+
+        assert!(pk.combine_incremental(&[0, 1]).is_err());
+        assert!(pk.combine_incremental(&[0, 1, 1]).is_err());
+        let mut combine = pk.combine_incremental(&[2, 0, 1]).unwrap();
+        combine.add(&shares[1]).unwrap();
+        assert!(combine.add(&shares[1]).is_err());
+        assert_eq!(combine.missing(), vec![2, 0]);
+        assert!(combine.clone().finish().is_err());
+        combine.add(&shares[2]).unwrap();
+        combine.add(&shares[0]).unwrap();
+        assert!(combine.is_complete());
+        assert_eq!(combine.finish().unwrap(), 42);
+
+        let mut combine = pk.combine_incremental(&[0, 1, 3]).unwrap();
+        assert!(combine.add(&shares[2]).is_err());
+        let mut zero = shares[0].clone();
+        zero.id = 0;
+        assert!(combine.add(&zero).is_err());
+    }
+
+    #[test]
+    fn test_partial_decryption_metadata() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 2, 2).unwrap();
+        let (other_pk, _) = generate_key_pair(128, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+        let c1 = pk.encrypt(1.into(), &mut rand);
+        let c2 = pk.encrypt(2.into(), &mut rand);
+        let p1 = key_shares[0].share_decrypt(&pk, c1.clone());
+        let p2 = key_shares[1].share_decrypt(&pk, c2);
+        assert_eq!(p2.server_id(), 1);
+        assert_eq!(p1.cipher_digest(), Some(&c1.digest()));
+        assert_eq!(p1.key_fingerprint(), Some(&pk.fingerprint()));
+        assert!(pk.share_combine(&[p1.clone(), p2.clone()]).is_err());
+        let mut combine = pk.combine_incremental(&[0, 1]).unwrap();
+        combine.add(&p1).unwrap();
+        assert!(combine.add(&p2).is_err());
+
+        let p2 = key_shares[1].share_decrypt(&pk, c1.clone());
+        let foreign = key_shares[1].share_decrypt(&other_pk, c1);
+        assert!(pk.share_combine(&[p1.clone(), foreign]).is_err());
+        // partial decryptions without metadata are accepted
+        let mut stripped = p2.clone();
+        stripped.cipher_digest = None;
+        stripped.key_fingerprint = None;
+        assert_eq!(pk.share_combine(&[p1.clone(), stripped]).unwrap(), 1);
+        assert_eq!(pk.share_combine(&[p1.clone(), p2]).unwrap(), 1);
+
+        let mut zero = p1;
+        zero.id = 0;
+        let bytes = bincode::serialize(&zero).unwrap();
+        assert!(bincode::deserialize::<PartialDecryption>(&bytes).is_err());
+        assert!(PartialDecryption::from_cbor_legacy(&zero.to_cbor()).is_err());
+    }
+
     #[test]
     fn test_multiple_server() {
         let (pk, sk) = generate_key_pair(128, 3, 3).unwrap();
@@ -296,6 +1254,74 @@ mod tests {
         assert_eq!(combined, 10);
     }
 
+    #[test]
+    fn test_verifiable_shares() {
+        let (pk, sk) = generate_key_pair(128, 3, 3).unwrap();
+        let mut rand = RandState::new();
+        let (mut key_shares, commitments) = sk.share_verifiable(&[0, 1, 2], &mut rand);
+        assert!(key_shares
+            .iter()
+            .all(|share| share.verify_against(&pk, &commitments)));
+        key_shares[1].si += 1;
+        assert!(!key_shares[1].verify_against(&pk, &commitments));
+    }
+
+    #[test]
+    fn test_arbitrary_points_and_weights() {
+        let (pk, sk) = generate_key_pair(128, 3, 3).unwrap();
+        let mut rand = RandState::new();
+        let c = pk.encrypt(10.into(), &mut rand);
+        assert!(sk.share_at_points(&[1, 0, 5], &mut rand).is_err());
+        assert!(sk.share_at_points(&[1, 5, 5], &mut rand).is_err());
+        assert!(sk.share_at_points(&[1, 5], &mut rand).is_err());
+
+        let shares = sk
+            .share_at_points(&[7, 1_000, u32::MAX, 2], &mut rand)
+            .unwrap();
+        let mut secret = shares[0].clone();
+        secret.i = 0;
+        let bytes = bincode::serialize(&secret).unwrap();
+        assert!(bincode::deserialize::<PrivateKeyShare>(&bytes).is_err());
+        let partials: Vec<_> = shares
+            .iter()
+            .map(|key_share| key_share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&partials[1..]).unwrap(), 10);
+        assert_eq!(pk.share_combine(&partials).unwrap(), 10);
+        let mut duplicate = partials[..2].to_vec();
+        duplicate.push(partials[0].clone());
+        assert!(pk.share_combine(&duplicate).is_err());
+
+        // a heavyweight holder with 2 of the 3 needed shares and two light ones
+        let holders = sk.share_weighted(&[2, 1, 1], &mut rand).unwrap();
+        assert_eq!(holders.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1, 1]);
+        let partials: Vec<_> = holders[0]
+            .iter()
+            .chain(&holders[2])
+            .map(|key_share| key_share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&partials).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_statistical_shares() {
+        let (pk, sk) = generate_key_pair(128, 3, 2).unwrap();
+        let mut rand = RandState::new();
+        let poly = Polynomial::new_statistical(&sk, DEFAULT_STATISTICAL_SECURITY, &mut rand);
+        let commitments = poly.commit(&mut rand);
+        let share = poly.compute(1);
+        assert!(share.si > sk.nm);
+        assert!(share.verify_against(&pk, &commitments));
+
+        let c = pk.encrypt(10.into(), &mut rand);
+        let shares: Vec<_> = sk
+            .share_statistical(&[0, 2], DEFAULT_STATISTICAL_SECURITY, &mut rand)
+            .iter()
+            .map(|key_share| key_share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&shares).unwrap(), 10);
+    }
+
     #[test]
     fn test_multiple_server_lower_threshold() {
         let (pk, sk) = generate_key_pair(128, 3, 2).unwrap();
@@ -310,4 +1336,53 @@ mod tests {
         let combined = pk.share_combine(&shares).unwrap();
         assert_eq!(combined, 10);
     }
+
+    #[test]
+    fn test_affine() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let c = pk.encrypt(5.into(), &mut rand);
+        let affine = pk.affine(&c, &3.into(), &(-1).into());
+        assert_eq!(Integer::from(sk.decrypt(&affine)), 14);
+        let randomized = pk.affine_randomized(&c, &(-2).into(), &20.into(), &mut rand);
+        assert_eq!(Integer::from(sk.decrypt(&randomized)), 10);
+        assert_ne!(
+            pk.affine_randomized(&c, &3.into(), &(-1).into(), &mut rand)
+                .as_ref(),
+            affine.as_ref()
+        );
+    }
+
+    #[test]
+    fn test_div_plain() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut c = pk.encrypt(42.into(), &mut rand);
+        pk.div_plain(&mut c, &6.into()).unwrap();
+        assert_eq!(Integer::from(sk.decrypt(&c)), 7);
+        pk.div_plain(&mut c, &(-7).into()).unwrap();
+        assert_eq!(Integer::from(sk.decrypt(&c)), pk.n.clone() - 1);
+        assert!(pk.div_plain(&mut c, &0.into()).is_err());
+        assert!(pk.div_plain(&mut c, &pk.n.clone().into()).is_err());
+    }
+
+    #[test]
+    fn test_compact_public_key() {
+        let (pk, _) = generate_key_pair(256, 3, 2).unwrap();
+        let compact = CompactPublicKey::from(&pk);
+        let full_len = bincode::serialize(&pk).unwrap().len();
+        let compact_len = bincode::serialize(&compact).unwrap().len();
+        assert!(compact_len * 2 < full_len);
+        assert_eq!(PublicKey::try_from(compact).unwrap(), pk);
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Wrapper {
+            #[serde(with = "crate::paillier::serde_compact")]
+            pk: PublicKey,
+        }
+        let bytes = bincode::serialize(&Wrapper { pk: pk.clone() }).unwrap();
+        assert_eq!(bytes.len(), compact_len);
+        let wrapper: Wrapper = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(wrapper.pk, pk);
+    }
 }
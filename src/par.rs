@@ -0,0 +1,76 @@
+//! Data parallelism with rayon if the `parallel` feature is enabled. Without it the
+//! same adapters run sequentially, so small deployments don't need a thread pool.
+//!
+//! Import the adapters with `use crate::par::prelude::*;` and use them like rayon's.
+
+/// Inputs with fewer items are processed on the calling thread even with the
+/// `parallel` feature, as handing a few items to the thread pool costs more latency
+/// than it saves.
+#[cfg(feature = "parallel")]
+pub(crate) const MIN_PARALLEL_LEN: usize = 8;
+
+pub(crate) mod prelude {
+    #[cfg(feature = "parallel")]
+    pub(crate) use rayon::prelude::*;
+
+    #[cfg(not(feature = "parallel"))]
+    pub(crate) use super::sequential::*;
+}
+
+/// Maps `f` over the items and their indices, in parallel if there are at least
+/// [`MIN_PARALLEL_LEN`] of them.
+pub(crate) fn map_collect<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &T) -> R + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    if items.len() >= MIN_PARALLEL_LEN {
+        use rayon::prelude::*;
+        return items.par_iter().enumerate().map(|(i, x)| f(i, x)).collect();
+    }
+    items.iter().enumerate().map(|(i, x)| f(i, x)).collect()
+}
+
+/// Sequential stand-ins for the rayon adapters used in the crate
+#[cfg(not(feature = "parallel"))]
+mod sequential {
+    pub(crate) trait IntoParallelRefIterator<'a> {
+        type Iter: Iterator;
+
+        fn par_iter(&'a self) -> Self::Iter;
+    }
+
+    impl<'a, T: 'a> IntoParallelRefIterator<'a> for [T] {
+        type Iter = std::slice::Iter<'a, T>;
+
+        fn par_iter(&'a self) -> Self::Iter {
+            self.iter()
+        }
+    }
+
+    pub(crate) trait IntoParallelIterator {
+        type Iter: Iterator;
+
+        fn into_par_iter(self) -> Self::Iter;
+    }
+
+    impl<I: IntoIterator> IntoParallelIterator for I {
+        type Iter = I::IntoIter;
+
+        fn into_par_iter(self) -> Self::Iter {
+            self.into_iter()
+        }
+    }
+
+    pub(crate) trait ParallelSlice<T> {
+        fn par_chunks(&self, chunk_size: usize) -> std::slice::Chunks<'_, T>;
+    }
+
+    impl<T> ParallelSlice<T> for [T] {
+        fn par_chunks(&self, chunk_size: usize) -> std::slice::Chunks<'_, T> {
+            self.chunks(chunk_size)
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Federated analytics in the style of the Personal Health Train.
+//!
+//! A [`Train`] travels from institution to institution, the [`Station`]s, carrying the
+//! public key of the decryption committee and one encrypted accumulator per
+//! statistic. Every station adds its local results with [`Station::contribute`] and
+//! re-randomizes the accumulators, so the next station can't tell which values
+//! changed. After the last station, [`Train::finish`] yields a [`ResultEnvelope`],
+//! which only the committee can decrypt. No station learns the results of another.
+//!
+//! The train is routed by an untrusted party, so a station doesn't encrypt under the
+//! key the train carries but checks it against the [`PublicKey::fingerprint`] of the
+//! committee it trusts.
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::pht::{Station, Train};
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 2, 2).unwrap();
+//! let key_shares = sk.share(&[0, 1], &mut rand);
+//!
+//! // count of patients and sum of their ages
+//! let mut train = Train::new(1, pk.clone(), 2, &mut rand)
+//!     .with_route(vec!["aachen".into(), "göttingen".into()]);
+//! Station::new("aachen", pk.fingerprint(), vec![12.into(), 600.into()])
+//!     .contribute(&mut train, &mut rand)
+//!     .unwrap();
+//! Station::new("göttingen", pk.fingerprint(), vec![30.into(), 1500.into()])
+//!     .contribute(&mut train, &mut rand)
+//!     .unwrap();
+//! let envelope = train.finish().unwrap();
+//!
+//! let partials: Vec<_> = key_shares
+//!     .iter()
+//!     .map(|share| envelope.share_decrypt(&pk, share))
+//!     .collect();
+//! let results = envelope.decrypt(&pk, &partials).unwrap();
+//! assert_eq!(results, [42, 2100]);
+//! ```
+
+use crate::paillier::{self, PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::proofs::in_mult_group;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use serde::{Deserialize, Serialize};
+
+/// The encrypted accumulators on their way through the stations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Train {
+    id: u64,
+    #[serde(with = "paillier::serde_compact")]
+    pk: PublicKey,
+    /// Stations in the order they must be visited, any order if empty
+    route: Vec<String>,
+    visited: Vec<String>,
+    accumulators: Vec<Ciphertext>,
+}
+
+/// An institution holding local results, one per accumulator of the train
+#[derive(Debug, Clone)]
+pub struct Station {
+    id: String,
+    /// Fingerprint of the committee key the station trusts
+    committee: [u8; 32],
+    results: Vec<Plaintext>,
+}
+
+/// The accumulators of a finished train, to be decrypted by the committee
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultEnvelope {
+    train: u64,
+    key_fingerprint: [u8; 32],
+    stations: Vec<String>,
+    accumulators: Vec<Ciphertext>,
+}
+
+impl Train {
+    /// Creates train `id` with `len` accumulators, each a fresh encryption of 0
+    pub fn new(id: u64, pk: PublicKey, len: usize, rand: &mut dyn MutRandState) -> Self {
+        let accumulators = (0..len).map(|_| pk.encrypt(0.into(), rand)).collect();
+        Self {
+            id,
+            pk,
+            route: Vec::new(),
+            visited: Vec::new(),
+            accumulators,
+        }
+    }
+
+    /// Requires the stations to be visited in the order of `route`
+    pub fn with_route(mut self, route: Vec<String>) -> Self {
+        self.route = route;
+        self
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.pk
+    }
+
+    pub fn visited(&self) -> &[String] {
+        &self.visited
+    }
+
+    /// The station to visit next, if the train has a route which is not completed
+    pub fn next_station(&self) -> Option<&str> {
+        self.route.get(self.visited.len()).map(String::as_str)
+    }
+
+    /// Ends the journey. Fails if no station contributed or the route is incomplete.
+    pub fn finish(self) -> Result<ResultEnvelope> {
+        ensure!(!self.visited.is_empty(), "no station contributed");
+        ensure!(
+            self.visited.len() >= self.route.len(),
+            "stations {:?} were not visited",
+            &self.route[self.visited.len()..]
+        );
+        Ok(ResultEnvelope {
+            train: self.id,
+            key_fingerprint: self.pk.fingerprint(),
+            stations: self.visited,
+            accumulators: self.accumulators,
+        })
+    }
+}
+
+impl Station {
+    /// Creates station `id` which only contributes to trains carrying the committee
+    /// key with fingerprint `committee`
+    pub fn new(id: impl Into<String>, committee: [u8; 32], results: Vec<Plaintext>) -> Self {
+        Self {
+            id: id.into(),
+            committee,
+            results,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Adds the local results to the accumulators of `train` and re-randomizes them.
+    /// Every station may contribute once, in the order of the route if there is one.
+    /// Fails if the train carries another key than the trusted committee key.
+    pub fn contribute(&self, train: &mut Train, rand: &mut dyn MutRandState) -> Result<()> {
+        ensure!(
+            train.pk.fingerprint() == self.committee,
+            "train carries another key than the committee key"
+        );
+        ensure!(
+            self.results.len() == train.accumulators.len(),
+            "train carries {} accumulators but station {} has {} results",
+            train.accumulators.len(),
+            self.id,
+            self.results.len()
+        );
+        ensure!(
+            !train.visited.contains(&self.id),
+            "station {} already contributed",
+            self.id
+        );
+        if !train.route.is_empty() {
+            ensure!(
+                train.next_station() == Some(self.id.as_str()),
+                "station {} is not next on the route",
+                self.id
+            );
+        }
+        let pk = &train.pk;
+        ensure!(
+            train
+                .accumulators
+                .iter()
+                .all(|acc| in_mult_group(acc.as_ref(), &pk.n, &pk.n2)),
+            "train carries an invalid accumulator"
+        );
+        for (acc, result) in train.accumulators.iter_mut().zip(&self.results) {
+            pk.add_plain(acc, result);
+            pk.reencrypt(acc, rand);
+        }
+        train.visited.push(self.id.clone());
+        Ok(())
+    }
+}
+
+impl ResultEnvelope {
+    pub fn train(&self) -> u64 {
+        self.train
+    }
+
+    /// The stations which contributed, in the order they were visited
+    pub fn stations(&self) -> &[String] {
+        &self.stations
+    }
+
+    pub fn accumulators(&self) -> &[Ciphertext] {
+        &self.accumulators
+    }
+
+    /// Partial decryptions of all accumulators by a committee member
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> Vec<PartialDecryption> {
+        self.accumulators
+            .iter()
+            .map(|acc| key_share.share_decrypt(pk, acc.clone()))
+            .collect()
+    }
+
+    /// Combines the partial decryptions of at least w committee members, one vector
+    /// per member as returned by [`ResultEnvelope::share_decrypt`]
+    pub fn decrypt(
+        &self,
+        pk: &PublicKey,
+        partials: &[Vec<PartialDecryption>],
+    ) -> Result<Vec<Plaintext>> {
+        ensure!(
+            self.key_fingerprint == pk.fingerprint(),
+            "results are encrypted under another key"
+        );
+        ensure!(
+            partials.len() >= pk.w as usize,
+            "at least {} partial decryptions are needed",
+            pk.w
+        );
+        ensure!(
+            partials
+                .iter()
+                .all(|member| member.len() == self.accumulators.len()),
+            "expected {} partial decryptions per member",
+            self.accumulators.len()
+        );
+        (0..self.accumulators.len())
+            .map(|pos| {
+                let shares: Vec<_> = partials.iter().map(|member| member[pos].clone()).collect();
+                pk.share_combine(&shares)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Station, Train};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_train_validation() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let (other_pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+        let mut train = Train::new(3, pk.clone(), 2, &mut rand).with_route(vec![
+            "a".into(),
+            "b".into(),
+            "c".into(),
+        ]);
+        assert!(train.clone().finish().is_err());
+
+        let committee = pk.fingerprint();
+        let a = Station::new("a", committee, vec![1.into(), 2.into()]);
+        let b = Station::new("b", committee, vec![3.into(), 4.into()]);
+        assert!(b.contribute(&mut train, &mut rand).is_err());
+        let before = train.accumulators.clone();
+        a.contribute(&mut train, &mut rand).unwrap();
+        assert!(a.contribute(&mut train, &mut rand).is_err());
+        assert_ne!(before[0].as_ref(), train.accumulators[0].as_ref());
+        assert!(Station::new("b", committee, vec![1.into()])
+            .contribute(&mut train, &mut rand)
+            .is_err());
+        b.contribute(&mut train, &mut rand).unwrap();
+        assert_eq!(train.next_station(), Some("c"));
+        assert!(train.clone().finish().is_err());
+
+        let mut tampered = train.clone();
+        tampered.accumulators[1] = pk.n2.clone().into();
+        let c = Station::new("c", committee, vec![5.into(), 6.into()]);
+        assert!(c.contribute(&mut tampered, &mut rand).is_err());
+
+        // the router swapped in its own key to decrypt the contribution of c
+        let mut swapped = train.clone();
+        swapped.pk = other_pk.clone();
+        assert!(c.contribute(&mut swapped, &mut rand).is_err());
+        assert_eq!(swapped.visited(), ["a", "b"]);
+
+        c.contribute(&mut train, &mut rand).unwrap();
+        let envelope = train.finish().unwrap();
+        assert_eq!(envelope.stations(), ["a", "b", "c"]);
+        let partials = vec![envelope.share_decrypt(&pk, &key_share)];
+        assert_eq!(envelope.decrypt(&pk, &partials).unwrap(), [9, 12]);
+        assert!(envelope.decrypt(&other_pk, &partials).is_err());
+        assert!(envelope.decrypt(&pk, &[partials[0][..1].to_vec()]).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+//! Decryption policies beyond a flat w out of l threshold.
+//!
+//! A [`Policy`] is a tree of threshold gates over named parties, e.g. "2 hospitals and
+//! 1 regulator". [`PrivateKey::share_policy`] compiles it into nested secret sharings:
+//! the decryption exponent is shared among the children of the root gate, and the
+//! share of every child gate is shared again among its own children. A set of
+//! partial decryptions can only be combined with [`PublicKey::share_combine_policy`]
+//! if the parties they come from satisfy the policy.
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::policy::Policy;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 1, 1).unwrap();
+//! let policy = Policy::all(vec![
+//!     Policy::threshold(2, vec![
+//!         Policy::party("hospital-a"),
+//!         Policy::party("hospital-b"),
+//!         Policy::party("hospital-c"),
+//!     ]),
+//!     Policy::any(vec![Policy::party("regulator")]),
+//! ]);
+//! let shares = sk.share_policy(&policy, &mut rand).unwrap();
+//!
+//! let c = pk.encrypt(42.into(), &mut rand);
+//! let partials: Vec<_> = shares
+//!     .iter()
+//!     .filter(|share| share.party() != "hospital-b")
+//!     .map(|share| share.share_decrypt(&pk, &c))
+//!     .collect();
+//! assert_eq!(pk.share_combine_policy(&policy, &partials).unwrap(), 42);
+//! assert!(pk.share_combine_policy(&policy, &partials[..2]).is_err());
+//! ```
+
+use crate::paillier::{PrivateKey, PublicKey};
+use crate::{util, Ciphertext, Plaintext};
+use anyhow::{anyhow, bail, ensure, Result};
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// An access structure for decryption
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Policy {
+    /// A single party identified by its name
+    Party(String),
+    /// Satisfied if at least `threshold` of the children are
+    Threshold {
+        threshold: u32,
+        children: Vec<Policy>,
+    },
+}
+
+impl Policy {
+    pub fn party(name: impl Into<String>) -> Self {
+        Policy::Party(name.into())
+    }
+
+    pub fn threshold(threshold: u32, children: Vec<Policy>) -> Self {
+        Policy::Threshold {
+            threshold,
+            children,
+        }
+    }
+
+    /// Satisfied if all children are
+    pub fn all(children: Vec<Policy>) -> Self {
+        Self::threshold(children.len() as u32, children)
+    }
+
+    /// Satisfied if any child is
+    pub fn any(children: Vec<Policy>) -> Self {
+        Self::threshold(1, children)
+    }
+
+    /// The names of all parties in the order of the tree
+    pub fn parties(&self) -> Vec<&str> {
+        match self {
+            Policy::Party(name) => vec![name.as_str()],
+            Policy::Threshold { children, .. } => {
+                children.iter().flat_map(Policy::parties).collect()
+            }
+        }
+    }
+
+    /// Checks that every gate has children and a threshold between 1 and their number,
+    /// and that the party names are unique
+    pub fn validate(&self) -> Result<()> {
+        self.validate_gates()?;
+        let parties = self.parties();
+        let unique: HashSet<&str> = parties.iter().copied().collect();
+        ensure!(unique.len() == parties.len(), "party names must be unique");
+        Ok(())
+    }
+
+    fn validate_gates(&self) -> Result<()> {
+        if let Policy::Threshold {
+            threshold,
+            children,
+        } = self
+        {
+            ensure!(
+                *threshold >= 1 && *threshold as usize <= children.len(),
+                "threshold {} is invalid for {} children",
+                threshold,
+                children.len()
+            );
+            children.iter().try_for_each(Policy::validate_gates)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the `parties` together satisfy the policy
+    pub fn is_satisfied_by<'a>(&self, parties: impl IntoIterator<Item = &'a str>) -> bool {
+        let parties: HashSet<&str> = parties.into_iter().collect();
+        self.satisfied(&parties)
+    }
+
+    fn satisfied(&self, parties: &HashSet<&str>) -> bool {
+        match self {
+            Policy::Party(name) => parties.contains(name.as_str()),
+            Policy::Threshold {
+                threshold,
+                children,
+            } => {
+                children
+                    .iter()
+                    .filter(|child| child.satisfied(parties))
+                    .count()
+                    >= *threshold as usize
+            }
+        }
+    }
+}
+
+/// The key share of one party of a [`Policy`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyShare {
+    party: String,
+    #[serde(with = "crate::util::serde_integer")]
+    si: Integer,
+}
+
+/// A partial decryption by one party of a [`Policy`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PolicyPartialDecryption {
+    party: String,
+    /// c^{2 * s_i} mod n^2
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+}
+
+impl PolicyShare {
+    pub fn party(&self) -> &str {
+        &self.party
+    }
+
+    /// Computes the partial decryption c^{2 * s_i} mod n^2 in constant time
+    pub fn share_decrypt(&self, pk: &PublicKey, cipher: &Ciphertext) -> PolicyPartialDecryption {
+        let exponent = Integer::from(&self.si << 1);
+        PolicyPartialDecryption {
+            party: self.party.clone(),
+            val: util::secure_pow_mod(cipher.as_ref(), &exponent, &pk.n2),
+        }
+    }
+}
+
+impl PolicyPartialDecryption {
+    pub fn party(&self) -> &str {
+        &self.party
+    }
+}
+
+impl PrivateKey {
+    /// Deals one share to every party of `policy`. The children of a gate receive the
+    /// evaluations at 1, 2, .. of a random polynomial mod n * m whose degree is the
+    /// threshold of the gate minus one and whose constant term is the gate's share.
+    pub fn share_policy(
+        &self,
+        policy: &Policy,
+        rand: &mut dyn MutRandState,
+    ) -> Result<Vec<PolicyShare>> {
+        policy.validate()?;
+        let mut shares = Vec::new();
+        self.share_node(policy, self.d.clone(), &mut shares, rand);
+        Ok(shares)
+    }
+
+    fn share_node(
+        &self,
+        node: &Policy,
+        secret: Integer,
+        shares: &mut Vec<PolicyShare>,
+        rand: &mut dyn MutRandState,
+    ) {
+        match node {
+            Policy::Party(name) => shares.push(PolicyShare {
+                party: name.clone(),
+                si: secret,
+            }),
+            Policy::Threshold {
+                threshold,
+                children,
+            } => {
+                let mut coefficients = vec![secret];
+                coefficients
+                    .extend((1..*threshold).map(|_| Integer::from(self.nm.random_below_ref(rand))));
+                for (x, child) in (1u32..).zip(children) {
+                    // Horner's method
+                    let share = coefficients
+                        .iter()
+                        .rev()
+                        .fold(Integer::new(), |acc, coeff| (acc * x + coeff) % &self.nm);
+                    self.share_node(child, share, shares, rand);
+                }
+            }
+        }
+    }
+}
+
+impl PublicKey {
+    /// Combines partial decryptions of parties which satisfy `policy`. Fails for
+    /// unknown or duplicate parties and if the parties don't satisfy the policy.
+    pub fn share_combine_policy(
+        &self,
+        policy: &Policy,
+        partials: &[PolicyPartialDecryption],
+    ) -> Result<Plaintext> {
+        policy.validate()?;
+        let known: HashSet<&str> = policy.parties().into_iter().collect();
+        let mut received = HashMap::new();
+        for partial in partials {
+            ensure!(
+                known.contains(partial.party.as_str()),
+                "party {} is not part of the policy",
+                partial.party
+            );
+            if received
+                .insert(partial.party.as_str(), &partial.val)
+                .is_some()
+            {
+                bail!(
+                    "received two partial decryptions of party {}",
+                    partial.party
+                );
+            }
+        }
+        let (cprime, factor) = self
+            .combine_node(policy, &received)?
+            .ok_or_else(|| anyhow!("partial decryptions do not satisfy the policy"))?;
+        // c' = c^{2 * factor * d} = 1 + 2 * factor * m * n mod n^2
+        let t = (cprime - 1) / &self.n;
+        let two_factor: Integer = factor << 1;
+        let inv = two_factor
+            .invert(&self.n)
+            .map_err(|_| anyhow!("policy factor is not invertible mod n"))?;
+        let rop: Integer = t * inv % &self.n;
+        Ok(rop.into())
+    }
+
+    /// Returns c^{2 * factor * s} mod n^2 for the share s of `node` together with the
+    /// factor, or `None` if the received partials don't satisfy the node
+    fn combine_node(
+        &self,
+        node: &Policy,
+        received: &HashMap<&str, &Integer>,
+    ) -> Result<Option<(Integer, Integer)>> {
+        let (threshold, children) = match node {
+            Policy::Party(name) => {
+                return Ok(received
+                    .get(name.as_str())
+                    .map(|val| ((*val).clone(), Integer::from(1))))
+            }
+            Policy::Threshold {
+                threshold,
+                children,
+            } => (*threshold as usize, children),
+        };
+        let mut ids = Vec::with_capacity(threshold);
+        let mut values = Vec::with_capacity(threshold);
+        for (x, child) in (1u32..).zip(children) {
+            if ids.len() == threshold {
+                break;
+            }
+            if let Some(value) = self.combine_node(child, received)? {
+                ids.push(x);
+                values.push(value);
+            }
+        }
+        if ids.len() < threshold {
+            return Ok(None);
+        }
+        // bring all children to the common factor before interpolating
+        let common = values
+            .iter()
+            .fold(Integer::from(1), |acc, (_, factor)| acc.lcm(factor));
+        let delta = Integer::from(Integer::factorial(children.len() as u32));
+        let (lambdas, scale) = util::lagrange_coefficients(&delta, &ids);
+        let exps: Vec<Integer> = lambdas
+            .into_iter()
+            .zip(&values)
+            .map(|(lambda, (_, factor))| lambda * common.div_exact_ref(factor).complete())
+            .collect();
+        let bases: Vec<&Integer> = values.iter().map(|(val, _)| val).collect();
+        let combined = util::multi_pow_mod(&bases, &exps, &self.n2)
+            .ok_or_else(|| anyhow!("partial decryption is not invertible"))?;
+        Ok(Some((combined, common * delta * scale)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Policy;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_nested_policy() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        // (2 of 3 hospitals and a regulator) or all of the auditors
+        let policy = Policy::any(vec![
+            Policy::all(vec![
+                Policy::threshold(
+                    2,
+                    vec![
+                        Policy::party("h1"),
+                        Policy::party("h2"),
+                        Policy::party("h3"),
+                    ],
+                ),
+                Policy::any(vec![Policy::party("r1"), Policy::party("r2")]),
+            ]),
+            Policy::all(vec![Policy::party("a1"), Policy::party("a2")]),
+        ]);
+        let shares = sk.share_policy(&policy, &mut rand).unwrap();
+        assert_eq!(shares.len(), 7);
+        let c = pk.encrypt(42.into(), &mut rand);
+        let decrypt = |parties: &[&str]| {
+            let partials: Vec<_> = shares
+                .iter()
+                .filter(|share| parties.contains(&share.party()))
+                .map(|share| share.share_decrypt(&pk, &c))
+                .collect();
+            let result = pk.share_combine_policy(&policy, &partials);
+            assert_eq!(
+                result.is_ok(),
+                policy.is_satisfied_by(parties.iter().copied())
+            );
+            result
+        };
+        assert_eq!(decrypt(&["h1", "h3", "r2"]).unwrap(), 42);
+        assert_eq!(decrypt(&["h2", "h3", "h1", "r1", "r2"]).unwrap(), 42);
+        assert_eq!(decrypt(&["a1", "a2"]).unwrap(), 42);
+        assert_eq!(decrypt(&["a1", "h2", "h3", "r1"]).unwrap(), 42);
+        assert!(decrypt(&["h1", "h2", "h3"]).is_err());
+        assert!(decrypt(&["h1", "r1", "a1"]).is_err());
+
+        let mut partials: Vec<_> = shares.iter().map(|s| s.share_decrypt(&pk, &c)).collect();
+        partials.push(partials[0].clone());
+        assert!(pk.share_combine_policy(&policy, &partials).is_err());
+    }
+
+    #[test]
+    fn test_invalid_policies() {
+        let (_, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        for policy in [
+            Policy::threshold(0, vec![Policy::party("a")]),
+            Policy::threshold(2, vec![Policy::party("a")]),
+            Policy::all(vec![]),
+            Policy::all(vec![Policy::party("a"), Policy::party("a")]),
+        ] {
+            assert!(sk.share_policy(&policy, &mut rand).is_err());
+        }
+        let single = Policy::party("a");
+        assert_eq!(sk.share_policy(&single, &mut rand).unwrap().len(), 1);
+    }
+}
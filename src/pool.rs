@@ -0,0 +1,307 @@
+//! Precomputed randomizers for low latency encryption.
+//!
+//! The cost of [`PublicKey::encrypt`] is dominated by r^n mod n^2, which does not
+//! depend on the plaintext. A [`RandomizerPool`] computes these blinding factors ahead
+//! of time, either explicitly with [`RandomizerPool::fill`] or on a background thread
+//! with [`RandomizerPool::spawn_refill`], so that [`PublicKey::encrypt_with_pool`] only
+//! needs two multiplications. A randomizer is removed from the pool when it is used
+//! and is thus never used twice. Relays re-randomizing many ciphertexts at once use
+//! [`PublicKey::reencrypt_batch_with_pool`].
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::pool::RandomizerPool;
+//! use rug::rand::RandState;
+//! use std::sync::Arc;
+//!
+//! let (pk, _sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let mut rand = RandState::new();
+//! let pool = Arc::new(RandomizerPool::new(&pk));
+//! pool.fill(16, &mut rand);
+//! // keeps 16 randomizers available until dropped
+//! let _refill = pool.spawn_refill(16);
+//! let cipher = pk.encrypt_with_pool(42.into(), &pool, &mut rand).unwrap();
+//! ```
+
+use crate::metrics;
+use crate::paillier::PublicKey;
+use crate::par::prelude::*;
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::rng;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+
+/// A pool of precomputed r^n mod n^2 for the public key it was created for
+#[derive(Debug)]
+pub struct RandomizerPool {
+    n: Integer,
+    n2: Integer,
+    randomizers: Mutex<Vec<Integer>>,
+    /// Notified when a randomizer is taken or a refill is stopped
+    taken: Condvar,
+}
+
+impl RandomizerPool {
+    /// Creates an empty pool for `pk`
+    pub fn new(pk: &PublicKey) -> Self {
+        Self {
+            n: pk.n.clone(),
+            n2: pk.n2.clone(),
+            randomizers: Mutex::new(Vec::new()),
+            taken: Condvar::new(),
+        }
+    }
+
+    /// The number of randomizers available
+    pub fn len(&self) -> usize {
+        self.randomizers.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Computes `count` randomizers in parallel and adds them to the pool
+    pub fn fill(&self, count: usize, rand: &mut dyn MutRandState) {
+        let rs: Vec<Integer> = (0..count)
+            .map(|_| random_in_mult_group(&self.n, UnitCheck::Skip, rand))
+            .collect();
+        let mut randomizers: Vec<Integer> = rs
+            .into_par_iter()
+            .map(|r| r.pow_mod(&self.n, &self.n2).unwrap())
+            .collect();
+        self.randomizers.lock().unwrap().append(&mut randomizers);
+    }
+
+    /// Removes a randomizer from the pool
+    fn take(&self) -> Option<Integer> {
+        let mut randomizers = self.randomizers.lock().unwrap();
+        let rn = randomizers.pop();
+        metrics::randomizers_taken(rn.is_none() as usize, randomizers.len());
+        if rn.is_some() {
+            self.taken.notify_all();
+        }
+        rn
+    }
+
+    /// Removes up to `count` randomizers from the pool
+    fn take_many(&self, count: usize) -> Vec<Integer> {
+        let mut randomizers = self.randomizers.lock().unwrap();
+        let keep = randomizers.len().saturating_sub(count);
+        let taken = randomizers.split_off(keep);
+        metrics::randomizers_taken(count - taken.len(), keep);
+        if !taken.is_empty() {
+            self.taken.notify_all();
+        }
+        taken
+    }
+
+    /// Spawns a thread which tops the pool up to `target` randomizers whenever one is
+    /// taken, until the returned handle is dropped. The thread draws its randomness
+    /// from [`rng::secure`].
+    pub fn spawn_refill(self: &Arc<Self>, target: usize) -> RefillHandle {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let pool = Arc::clone(self);
+        let thread_stopped = Arc::clone(&stopped);
+        let thread = std::thread::spawn(move || {
+            let mut rand = rng::secure();
+            loop {
+                let missing = {
+                    let mut randomizers = pool.randomizers.lock().unwrap();
+                    while randomizers.len() >= target && !thread_stopped.load(Ordering::Acquire) {
+                        randomizers = pool.taken.wait(randomizers).unwrap();
+                    }
+                    if thread_stopped.load(Ordering::Acquire) {
+                        return;
+                    }
+                    target - randomizers.len()
+                };
+                pool.fill(missing, &mut rand);
+            }
+        });
+        RefillHandle {
+            pool: Arc::clone(self),
+            stopped,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Handle of a refill thread started with [`RandomizerPool::spawn_refill`]. Dropping
+/// it stops the thread.
+#[derive(Debug)]
+pub struct RefillHandle {
+    pool: Arc<RandomizerPool>,
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for RefillHandle {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+        // notify while holding the lock so the thread can't miss the wake up between
+        // checking the flag and waiting
+        let guard = self.pool.randomizers.lock().unwrap();
+        self.pool.taken.notify_all();
+        drop(guard);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl PublicKey {
+    /// Encrypts `m` with a randomizer from `pool`. If the pool is empty, the
+    /// randomizer is computed from `rand` like in [`PublicKey::encrypt`]. Fails if
+    /// `pool` was created for another key.
+    pub fn encrypt_with_pool(
+        &self,
+        m: Plaintext,
+        pool: &RandomizerPool,
+        rand: &mut dyn MutRandState,
+    ) -> Result<Ciphertext> {
+        ensure!(pool.n == self.n, "randomizer pool of a different key");
+        Ok(match pool.take() {
+            Some(rn) => {
+                metrics::encryptions(1);
+                let mut c = self.g_pow(m.as_ref());
+                c *= rn;
+                c %= &self.n2;
+                c.into()
+            }
+            None => self.encrypt(m, rand),
+        })
+    }
+
+    /// Re-randomizes all `ciphers` in parallel, e.g. at a relay to unlink incoming
+    /// from outgoing ciphertexts
+    pub fn reencrypt_batch(&self, ciphers: &mut [Ciphertext], rand: &mut dyn MutRandState) {
+        self.reencrypt_batch_with(ciphers, Vec::new(), rand);
+    }
+
+    /// Like [`PublicKey::reencrypt_batch`] but takes the randomizers from `pool` as
+    /// long as it has some. Fails if `pool` was created for another key.
+    pub fn reencrypt_batch_with_pool(
+        &self,
+        ciphers: &mut [Ciphertext],
+        pool: &RandomizerPool,
+        rand: &mut dyn MutRandState,
+    ) -> Result<()> {
+        ensure!(pool.n == self.n, "randomizer pool of a different key");
+        let pooled = pool.take_many(ciphers.len());
+        self.reencrypt_batch_with(ciphers, pooled, rand);
+        Ok(())
+    }
+
+    fn reencrypt_batch_with(
+        &self,
+        ciphers: &mut [Ciphertext],
+        pooled: Vec<Integer>,
+        rand: &mut dyn MutRandState,
+    ) {
+        trace_span!(
+            "reencrypt_batch",
+            len = ciphers.len(),
+            pooled = pooled.len()
+        );
+        // the randomness is drawn sequentially, the exponentiations run in parallel
+        let mut pooled = pooled.into_iter();
+        let jobs: Vec<(&mut Ciphertext, Integer, bool)> = ciphers
+            .iter_mut()
+            .map(|c| match pooled.next() {
+                Some(rn) => (c, rn, true),
+                None => (
+                    c,
+                    random_in_mult_group(&self.n, UnitCheck::Skip, rand),
+                    false,
+                ),
+            })
+            .collect();
+        jobs.into_par_iter().for_each(|(c, r, precomputed)| {
+            let rn = if precomputed {
+                r
+            } else {
+                r.pow_mod(&self.n, &self.n2).unwrap()
+            };
+            *c.as_mut() *= rn;
+            *c.as_mut() %= &self.n2;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RandomizerPool;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_encrypt_with_pool() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let pool = Arc::new(RandomizerPool::new(&pk));
+        pool.fill(2, &mut rand);
+        assert_eq!(pool.len(), 2);
+
+        let c1 = pk.encrypt_with_pool(42.into(), &pool, &mut rand).unwrap();
+        let c2 = pk.encrypt_with_pool(42.into(), &pool, &mut rand).unwrap();
+        assert!(pool.is_empty());
+        // falls back to a fresh randomizer
+        let c3 = pk.encrypt_with_pool(42.into(), &pool, &mut rand).unwrap();
+        assert_ne!(c1.as_ref(), c2.as_ref());
+        for c in [c1, c2, c3] {
+            assert_eq!(Integer::from(sk.decrypt(&c)), 42);
+        }
+
+        let refill = pool.spawn_refill(4);
+        let start = Instant::now();
+        while pool.len() < 4 {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let c = pk.encrypt_with_pool(7.into(), &pool, &mut rand).unwrap();
+
+        let (other_pk, _) = generate_key_pair(256, 1, 1).unwrap();
+        assert!(other_pk
+            .encrypt_with_pool(7.into(), &pool, &mut rand)
+            .is_err());
+        let mut ciphers = vec![c.clone()];
+        assert!(other_pk
+            .reencrypt_batch_with_pool(&mut ciphers, &pool, &mut rand)
+            .is_err());
+        assert_eq!(Integer::from(sk.decrypt(&c)), 7);
+        drop(refill);
+        assert!(pool.len() <= 4);
+    }
+
+    #[test]
+    fn test_reencrypt_batch() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let original: Vec<_> = (0..20).map(|m| pk.encrypt(m.into(), &mut rand)).collect();
+        let mut ciphers = original.clone();
+        pk.reencrypt_batch(&mut ciphers, &mut rand);
+
+        let pool = RandomizerPool::new(&pk);
+        pool.fill(12, &mut rand);
+        let mut pooled = original.clone();
+        pk.reencrypt_batch_with_pool(&mut pooled, &pool, &mut rand)
+            .unwrap();
+        assert!(pool.is_empty());
+
+        for (m, c) in original.iter().enumerate() {
+            assert_ne!(c.as_ref(), ciphers[m].as_ref());
+            assert_ne!(c.as_ref(), pooled[m].as_ref());
+            assert_eq!(Integer::from(sk.decrypt(&ciphers[m])), m);
+            assert_eq!(Integer::from(sk.decrypt(&pooled[m])), m);
+        }
+    }
+}
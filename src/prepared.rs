@@ -0,0 +1,112 @@
+//! Fixed-base exponentiation for ciphertexts which are multiplied by many plaintexts,
+//! e.g. the entries of an encrypted vector in a matrix-vector product.
+//!
+//! A [`PreparedCiphertext`] stores c^(j * 2^(4i)) mod n^2 for all 4 bit digits j and
+//! window positions i. [`PreparedCiphertext::mul_plain`] then needs one multiplication
+//! per nonzero digit of the plaintext and no squarings, which is about four times
+//! faster than [`PublicKey::mul_plain`]. The table of a ciphertext under a 2048 bit key
+//! for 2048 bit plaintexts takes about 4 MB.
+
+use crate::paillier::PublicKey;
+use crate::{Ciphertext, Plaintext};
+use rug::Integer;
+
+/// Bits of the exponent handled by one table lookup
+const WINDOW: u32 = 4;
+
+/// A ciphertext with precomputed powers for fast [`PublicKey::mul_plain`]
+#[derive(Debug, Clone)]
+pub struct PreparedCiphertext {
+    n2: Integer,
+    cipher: Integer,
+    /// table[i][j - 1] = c^(j * 2^(WINDOW * i)) mod n^2 for 1 <= j < 2^WINDOW
+    table: Vec<Vec<Integer>>,
+}
+
+impl PreparedCiphertext {
+    /// Precomputes the powers of `cipher` for plaintexts of up to `exponent_bits` bits.
+    /// Larger or negative plaintexts are still supported but don't profit from the
+    /// table.
+    pub fn new(pk: &PublicKey, cipher: &Ciphertext, exponent_bits: u32) -> Self {
+        let n2 = pk.n2.clone();
+        let windows = exponent_bits.div_ceil(WINDOW);
+        let mut table = Vec::with_capacity(windows as usize);
+        let mut base = Integer::from(cipher.as_ref() % &n2);
+        for _ in 0..windows {
+            let mut row = Vec::with_capacity((1 << WINDOW) - 1);
+            row.push(base.clone());
+            for j in 1..(1 << WINDOW) - 1 {
+                let next = Integer::from(&row[j - 1] * &base) % &n2;
+                row.push(next);
+            }
+            // base^(2^WINDOW) = base^(2^WINDOW - 1) * base
+            base = Integer::from(&row[row.len() - 1] * &base) % &n2;
+            table.push(row);
+        }
+        Self {
+            cipher: cipher.as_ref().clone(),
+            n2,
+            table,
+        }
+    }
+
+    /// The maximal plaintext bit length profiting from the table
+    pub fn exponent_bits(&self) -> u32 {
+        self.table.len() as u32 * WINDOW
+    }
+
+    /// Homomorphically multiplies the plaintext of the ciphertext with `plain`,
+    /// like [`PublicKey::mul_plain`].
+    pub fn mul_plain(&self, plain: &Plaintext) -> Ciphertext {
+        let exp = plain.as_ref();
+        if *exp < 0 || exp.significant_bits() > self.exponent_bits() {
+            let rop: Integer = self.cipher.pow_mod_ref(exp, &self.n2).unwrap().into();
+            return rop.into();
+        }
+        let mut rop = Integer::from(1);
+        for (i, row) in self.table.iter().enumerate() {
+            let offset = i as u32 * WINDOW;
+            let digit = (0..WINDOW).fold(0, |digit, k| {
+                digit | (usize::from(exp.get_bit(offset + k)) << k)
+            });
+            if digit != 0 {
+                rop *= &row[digit - 1];
+                rop %= &self.n2;
+            }
+        }
+        rop.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PreparedCiphertext;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_prepared_mul_plain() {
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let cipher = pk.encrypt(3.into(), &mut rand);
+        let prepared = PreparedCiphertext::new(&pk, &cipher, 128);
+        assert_eq!(prepared.exponent_bits(), 128);
+
+        let mut plains = vec![
+            Integer::new(),
+            Integer::from(1),
+            Integer::from(15),
+            Integer::from(16),
+            Integer::from(-7),
+            Integer::from(&pk.n2 + 1),
+        ];
+        plains.push(Integer::from(pk.n.random_below_ref(&mut rand)));
+        for plain in plains {
+            let plain = plain.into();
+            let mut expected = cipher.clone();
+            pk.mul_plain(&mut expected, &plain);
+            assert_eq!(prepared.mul_plain(&plain).as_ref(), expected.as_ref());
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::metrics;
+use crate::paillier::PublicKey;
+use crate::proofs::{challenge, in_mult_group, CHALLENGE_BITS};
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Plaintext, Randomness};
+use anyhow::{bail, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/bit";
+
+/// Disjunctive proof that a ciphertext encrypts either 0 or 1. For j in {0, 1}
+/// this proves that c * g^{-j} is an n-th power for at least one j, simulating
+/// the branch for which this does not hold.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BitProof {
+    #[serde(with = "crate::util::serde_integer_vec")]
+    pub(crate) a: Vec<Integer>,
+    #[serde(with = "crate::util::serde_integer_vec")]
+    pub(crate) e: Vec<Integer>,
+    #[serde(with = "crate::util::serde_integer_vec")]
+    pub(crate) z: Vec<Integer>,
+}
+
+/// c * g^{-j} mod n^2 for both possible plaintexts j
+fn candidates(pk: &PublicKey, c: &Integer) -> Option<[Integer; 2]> {
+    let g_inv = pk.g.clone().invert(&pk.n2).ok()?;
+    let u1 = (c * g_inv) % &pk.n2;
+    Some([c.clone(), u1])
+}
+
+/// Proves that the encryption of `m` with randomness `r` encrypts either 0 or 1,
+/// e.g. for encrypted votes or flags. Fails if `m` is not a bit.
+pub fn prove_bit(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    m: &Plaintext,
+    r: &Randomness,
+    rand: &mut dyn MutRandState,
+) -> Result<BitProof> {
+    let bit = match m.as_ref().to_u8() {
+        Some(0) => false,
+        Some(1) => true,
+        _ => bail!("plaintext must be 0 or 1"),
+    };
+    let c = pk.encrypt_raw(m.as_ref(), r.as_ref());
+    Ok(prove_bit_raw(pk, transcript, &c, bit, r.as_ref(), rand))
+}
+
+/// Verifies that `cipher` encrypts either 0 or 1.
+pub fn verify_bit(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    proof: &BitProof,
+) -> bool {
+    let valid = verify_bit_raw(pk, transcript, cipher.as_ref(), proof);
+    metrics::proof_verified("bit", valid)
+}
+
+pub(crate) fn prove_bit_raw(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    c: &Integer,
+    bit: bool,
+    r: &Integer,
+    rand: &mut dyn MutRandState,
+) -> BitProof {
+    let u = candidates(pk, c).expect("ciphertext must be invertible mod n^2");
+    let (real, fake) = if bit { (1, 0) } else { (0, 1) };
+    let modulus = Integer::from(1) << CHALLENGE_BITS;
+
+    let mut a = vec![Integer::new(), Integer::new()];
+    let mut e = vec![Integer::new(), Integer::new()];
+    let mut z = vec![Integer::new(), Integer::new()];
+
+    // simulate the branch we can not prove
+    e[fake] = Integer::from(modulus.random_below_ref(rand));
+    z[fake] = random_in_mult_group(&pk.n, UnitCheck::Skip, rand);
+    let u_e = u[fake].clone().pow_mod(&e[fake], &pk.n2).unwrap();
+    a[fake] = z[fake].clone().pow_mod(&pk.n, &pk.n2).unwrap();
+    a[fake] *= u_e.invert(&pk.n2).unwrap();
+    a[fake] %= &pk.n2;
+
+    let s = random_in_mult_group(&pk.n, UnitCheck::Skip, rand);
+    a[real] = s.clone().pow_mod(&pk.n, &pk.n2).unwrap();
+
+    let e_total = challenge(transcript, LABEL, &[&pk.n, c, &a[0], &a[1]]);
+    e[real] = e_total - &e[fake];
+    if e[real] < 0 {
+        e[real] += &modulus;
+    }
+    z[real] = r.clone().pow_mod(&e[real], &pk.n).unwrap();
+    z[real] *= s;
+    z[real] %= &pk.n;
+
+    BitProof { a, e, z }
+}
+
+pub(crate) fn verify_bit_raw(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    c: &Integer,
+    proof: &BitProof,
+) -> bool {
+    if proof.a.len() != 2 || proof.e.len() != 2 || proof.z.len() != 2 {
+        return false;
+    }
+    if !in_mult_group(c, &pk.n, &pk.n2) {
+        return false;
+    }
+    let u = match candidates(pk, c) {
+        Some(u) => u,
+        None => return false,
+    };
+    let modulus = Integer::from(1) << CHALLENGE_BITS;
+    let e_total = challenge(transcript, LABEL, &[&pk.n, c, &proof.a[0], &proof.a[1]]);
+    if (Integer::from(&proof.e[0] + &proof.e[1]) % &modulus) != e_total {
+        return false;
+    }
+    (0..2).all(|j| {
+        let (a, e, z) = (&proof.a[j], &proof.e[j], &proof.z[j]);
+        if !in_mult_group(a, &pk.n, &pk.n2) || !in_mult_group(z, &pk.n, &pk.n) {
+            return false;
+        }
+        if *e < 0 || *e >= modulus {
+            return false;
+        }
+        // z^n = a * u^e mod n^2
+        let lhs = z.clone().pow_mod(&pk.n, &pk.n2).unwrap();
+        let mut rhs = u[j].clone().pow_mod(e, &pk.n2).unwrap();
+        rhs *= a;
+        rhs %= &pk.n2;
+        lhs == rhs
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_bit, verify_bit};
+    use crate::paillier::generate_key_pair;
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_bit_proof() {
+        let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        for m in [0, 1] {
+            let (c, r) = pk.encrypt_with_randomness(m.into(), &mut rand);
+            let proof =
+                prove_bit(&pk, &mut Transcript::new(b"test"), &m.into(), &r, &mut rand).unwrap();
+            assert!(verify_bit(&pk, &mut Transcript::new(b"test"), &c, &proof));
+        }
+        let (c, r) = pk.encrypt_with_randomness(2.into(), &mut rand);
+        assert!(prove_bit(&pk, &mut Transcript::new(b"test"), &2.into(), &r, &mut rand).is_err());
+        let proof =
+            prove_bit(&pk, &mut Transcript::new(b"test"), &1.into(), &r, &mut rand).unwrap();
+        assert!(!verify_bit(&pk, &mut Transcript::new(b"test"), &c, &proof));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::audit::VerificationKey;
+use crate::metrics;
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::proofs::{challenge, in_mult_group, CHALLENGE_BITS};
+use crate::transcript::Transcript;
+use crate::{util, Ciphertext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/decryption";
+
+/// Proof that a partial decryption c_i = c^{2Δs_i} was computed with the share s_i
+/// behind the verification key v_i = v^{s_i}, following Shoup: it shows
+/// log_{c^{4Δ}}(c_i^2) = log_v(v_i).
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct DecryptionProof {
+    /// Commitment a = c^{4Δr} mod n^2
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) a: Integer,
+    /// Commitment b = v^r mod n^2
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) b: Integer,
+    /// Response z = r + e * s_i over the integers
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) z: Integer,
+}
+
+/// c^{4Δ} mod n^2
+fn base(pk: &PublicKey, cipher: &Ciphertext) -> Integer {
+    let exponent = Integer::from(&pk.delta << 2);
+    cipher
+        .as_ref()
+        .pow_mod_ref(&exponent, &pk.n2)
+        .unwrap()
+        .into()
+}
+
+/// Partially decrypts `cipher` with `key_share` and proves that the result matches
+/// the `verification_key` of the share.
+pub fn prove_decryption(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    key_share: &PrivateKeyShare,
+    verification_key: &VerificationKey,
+    rand: &mut dyn MutRandState,
+) -> Result<(PartialDecryption, DecryptionProof)> {
+    ensure!(
+        verification_key.point() == key_share.point(),
+        "verification key is for another share"
+    );
+    let partial = key_share.share_decrypt(pk, cipher.clone());
+    let base = base(pk, cipher);
+    let v = &verification_key.v;
+    // r hides e * s_i statistically, also for shares larger than n^2
+    let bits = key_share
+        .si
+        .significant_bits()
+        .max(pk.n2.significant_bits())
+        + 2 * CHALLENGE_BITS;
+    let r = Integer::from(Integer::random_bits(bits, rand));
+    let a = util::secure_pow_mod(&base, &r, &pk.n2);
+    let b = util::secure_pow_mod(v, &r, &pk.n2);
+    let squared = partial.val.square_ref().complete() % &pk.n2;
+    let e = challenge(
+        transcript,
+        LABEL,
+        &[&pk.n, &base, &squared, v, verification_key.key(), &a, &b],
+    );
+    let z = r + e * &key_share.si;
+    Ok((partial, DecryptionProof { a, b, z }))
+}
+
+/// Verifies that `partial` is the partial decryption of `cipher` by the share with
+/// `verification_key`.
+pub fn verify_decryption(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    partial: &PartialDecryption,
+    verification_key: &VerificationKey,
+    proof: &DecryptionProof,
+) -> bool {
+    metrics::proof_verified(
+        "decryption",
+        decryption_valid(pk, transcript, cipher, partial, verification_key, proof),
+    )
+}
+
+fn decryption_valid(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    partial: &PartialDecryption,
+    verification_key: &VerificationKey,
+    proof: &DecryptionProof,
+) -> bool {
+    let (v, vk) = (&verification_key.v, verification_key.key());
+    if partial.point() != verification_key.point()
+        || !in_mult_group(cipher.as_ref(), &pk.n, &pk.n2)
+        || !in_mult_group(&partial.val, &pk.n, &pk.n2)
+        || !in_mult_group(v, &pk.n, &pk.n2)
+        || !in_mult_group(vk, &pk.n, &pk.n2)
+        || !in_mult_group(&proof.a, &pk.n, &pk.n2)
+        || !in_mult_group(&proof.b, &pk.n, &pk.n2)
+        || proof.z < 0
+    {
+        return false;
+    }
+    let base = base(pk, cipher);
+    let squared = partial.val.square_ref().complete() % &pk.n2;
+    let e = challenge(
+        transcript,
+        LABEL,
+        &[&pk.n, &base, &squared, v, vk, &proof.a, &proof.b],
+    );
+    // base^z = a * (c_i^2)^e and v^z = b * v_i^e
+    let check = |g: &Integer, commitment: &Integer, h: &Integer| {
+        let lhs = g.pow_mod_ref(&proof.z, &pk.n2).unwrap();
+        let mut rhs = h.pow_mod_ref(&e, &pk.n2).unwrap().complete();
+        rhs *= commitment;
+        rhs %= &pk.n2;
+        Integer::from(lhs) == rhs
+    };
+    check(&base, &proof.a, &squared) && check(v, &proof.b, vk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_decryption, verify_decryption};
+    use crate::audit::VerificationKey;
+    use crate::paillier::{generate_key_pair, PartialDecryption, PrivateKeyShare};
+    use crate::transcript::Transcript;
+    use crate::Ciphertext;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_decryption_proof() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 3, 2).unwrap();
+        let (shares, dealing) = sk.share_audited(&[0, 2], &mut rand);
+        let vk = dealing.verification_key(2).unwrap();
+        let c = pk.encrypt(42.into(), &mut rand);
+        let (partial, proof) = prove_decryption(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &shares[1],
+            &vk,
+            &mut rand,
+        )
+        .unwrap();
+        let verify = |c: &Ciphertext,
+                      partial: &PartialDecryption,
+                      vk: &VerificationKey,
+                      transcript: &[u8]| {
+            verify_decryption(
+                &pk,
+                &mut Transcript::new(transcript),
+                c,
+                partial,
+                vk,
+                &proof,
+            )
+        };
+        assert!(verify(&c, &partial, &vk, b"test"));
+        assert!(!verify(&c, &partial, &vk, b"other"));
+        let other = pk.encrypt(42.into(), &mut rand);
+        assert!(!verify(&other, &partial, &vk, b"test"));
+        let vk0 = dealing.verification_key(0).unwrap();
+        assert!(!verify(&c, &partial, &vk0, b"test"));
+        assert!(prove_decryption(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &shares[0],
+            &vk,
+            &mut rand
+        )
+        .is_err());
+
+        // a share which doesn't match its verification key
+        let wrong = PrivateKeyShare::at_point(shares[1].si.clone() + 1, 3);
+        let (partial, proof) = prove_decryption(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &wrong,
+            &vk,
+            &mut rand,
+        )
+        .unwrap();
+        assert!(!verify_decryption(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &partial,
+            &vk,
+            &proof
+        ));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::metrics;
+use crate::paillier::PublicKey;
+use crate::proofs::nth_root::{prove_nth_root, verify_nth_root, NthRootProof};
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Randomness};
+use anyhow::{anyhow, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/plaintext-equality";
+
+/// Proof that two ciphertexts under the same public key encrypt the same plaintext.
+/// For c1 = g^m * r1^n and c2 = g^m * r2^n the quotient c1 / c2 = (r1 / r2)^n is an
+/// n-th power, which is proven with the witness r1 / r2 mod n.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PlaintextEqualityProof {
+    pub(crate) proof: NthRootProof,
+}
+
+/// c1 * c2^{-1} mod n^2
+fn quotient(pk: &PublicKey, c1: &Ciphertext, c2: &Ciphertext) -> Option<Integer> {
+    let inv = c2.as_ref().clone().invert(&pk.n2).ok()?;
+    Some(inv * c1.as_ref() % &pk.n2)
+}
+
+/// Proves that `c1` and `c2`, encrypted with randomness `r1` and `r2` respectively,
+/// encrypt the same plaintext.
+pub fn prove_eq(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    c1: &Ciphertext,
+    r1: &Randomness,
+    c2: &Ciphertext,
+    r2: &Randomness,
+    rand: &mut dyn MutRandState,
+) -> Result<PlaintextEqualityProof> {
+    let u = quotient(pk, c1, c2).ok_or_else(|| anyhow!("ciphertext is not invertible"))?;
+    let root = r2
+        .as_ref()
+        .clone()
+        .invert(&pk.n)
+        .map_err(|_| anyhow!("randomness is not invertible"))?
+        * r1.as_ref()
+        % &pk.n;
+    let context = [c1.as_ref(), c2.as_ref()];
+    let proof = prove_nth_root(pk, transcript, LABEL, &context, &u, &root, rand);
+    Ok(PlaintextEqualityProof { proof })
+}
+
+/// Verifies that `c1` and `c2` encrypt the same plaintext.
+pub fn verify_eq(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    c1: &Ciphertext,
+    c2: &Ciphertext,
+    proof: &PlaintextEqualityProof,
+) -> bool {
+    metrics::proof_verified("eq", eq_valid(pk, transcript, c1, c2, proof))
+}
+
+fn eq_valid(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    c1: &Ciphertext,
+    c2: &Ciphertext,
+    proof: &PlaintextEqualityProof,
+) -> bool {
+    let u = match quotient(pk, c1, c2) {
+        Some(u) => u,
+        None => return false,
+    };
+    let context = [c1.as_ref(), c2.as_ref()];
+    verify_nth_root(pk, transcript, LABEL, &context, &u, &proof.proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_eq, verify_eq};
+    use crate::paillier::generate_key_pair;
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_equality_proof() {
+        let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let (c1, r1) = pk.encrypt_with_randomness(42.into(), &mut rand);
+        let (c2, r2) = pk.encrypt_with_randomness(42.into(), &mut rand);
+        let proof = prove_eq(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &r1,
+            &c2,
+            &r2,
+            &mut rand,
+        )
+        .unwrap();
+        assert!(verify_eq(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &c2,
+            &proof
+        ));
+        assert!(!verify_eq(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c2,
+            &c1,
+            &proof
+        ));
+
+        let (c3, r3) = pk.encrypt_with_randomness(43.into(), &mut rand);
+        let proof = prove_eq(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &r1,
+            &c3,
+            &r3,
+            &mut rand,
+        )
+        .unwrap();
+        assert!(!verify_eq(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &c3,
+            &proof
+        ));
+    }
+}
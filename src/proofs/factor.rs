@@ -0,0 +1,238 @@
+use crate::metrics;
+use crate::paillier::{ModulusFactors, PublicKey};
+use crate::proofs::RingPedersenParams;
+use crate::transcript::Transcript;
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/no-small-factor";
+
+/// Statistical security parameter ℓ, the factors are proven to be larger than ~2^ℓ
+const ELL: u32 = 256;
+/// Slackness parameter ε of the range check
+const EPSILON: u32 = 512;
+
+/// Proof that the modulus n = p * q has no factors smaller than ~2^ℓ with ℓ = 256
+/// (Π^fac of Canetti et al. "UC Non-Interactive, Proactive, Threshold ECDSA with
+/// Identifiable Aborts"). The proof is relative to the verifier's
+/// [`RingPedersenParams`].
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct NoSmallFactorProof {
+    #[serde(with = "crate::util::serde_integer")]
+    p_commit: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    q_commit: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    a: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    b: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    t: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    sigma: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    z1: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    z2: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    w1: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    w2: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    v: Integer,
+}
+
+/// Uniformly samples from [-bound, bound]
+fn sample_signed(bound: &Integer, rand: &mut dyn MutRandState) -> Integer {
+    let range: Integer = Integer::from(bound << 1) + 1;
+    Integer::from(range.random_below_ref(rand)) - bound
+}
+
+/// Smallest integer larger than the square root of n
+fn sqrt_bound(n: &Integer) -> Integer {
+    n.sqrt_ref().complete() + 1
+}
+
+/// Signed challenge e in [-2^ℓ, 2^ℓ]
+fn factor_challenge(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    params: &RingPedersenParams,
+    proof: &NoSmallFactorProof,
+) -> Integer {
+    transcript.append_message(b"dom-sep", LABEL);
+    for val in [
+        &pk.n,
+        &params.n_hat,
+        &params.s,
+        &params.t,
+        &proof.p_commit,
+        &proof.q_commit,
+        &proof.a,
+        &proof.b,
+        &proof.t,
+        &proof.sigma,
+    ] {
+        transcript.append_integer(b"value", val);
+    }
+    let h = transcript.challenge_integer(b"challenge", ELL + 1);
+    let negative = h.get_bit(0);
+    let e: Integer = h >> 1;
+    if negative {
+        -e
+    } else {
+        e
+    }
+}
+
+/// Proves that the modulus of `pk` has no small factors to the verifier owning `params`.
+pub fn prove_no_small_factor(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    factors: &ModulusFactors,
+    params: &RingPedersenParams,
+    rand: &mut dyn MutRandState,
+) -> Result<NoSmallFactorProof> {
+    let (p, q, n0) = (&factors.p, &factors.q, &pk.n);
+    ensure!(
+        (p * q).complete() == *n0,
+        "factors do not match the modulus"
+    );
+    params.validate()?;
+    let n_hat = &params.n_hat;
+    let sqrt_n0 = sqrt_bound(n0);
+
+    let alpha = sample_signed(&(sqrt_n0.clone() << (ELL + EPSILON)), rand);
+    let beta = sample_signed(&(sqrt_n0 << (ELL + EPSILON)), rand);
+    let mu = sample_signed(&(n_hat.clone() << ELL), rand);
+    let nu = sample_signed(&(n_hat.clone() << ELL), rand);
+    let n0_n_hat = (n0 * n_hat).complete();
+    let sigma = sample_signed(&(n0_n_hat.clone() << ELL), rand);
+    let r = sample_signed(&(n0_n_hat << (ELL + EPSILON)), rand);
+    let x = sample_signed(&(n_hat.clone() << (ELL + EPSILON)), rand);
+    let y = sample_signed(&(n_hat.clone() << (ELL + EPSILON)), rand);
+
+    let commit = |a: &Integer, b: &Integer| {
+        params
+            .commit(a, b)
+            .ok_or_else(|| anyhow!("invalid ring-pedersen parameters"))
+    };
+    let p_commit = commit(p, &mu)?;
+    let q_commit = commit(q, &nu)?;
+    let a = commit(&alpha, &x)?;
+    let b = commit(&beta, &y)?;
+    let mut t = q_commit.clone().pow_mod(&alpha, n_hat).unwrap();
+    t *= params.t.clone().pow_mod(&r, n_hat).unwrap();
+    t %= n_hat;
+
+    let mut proof = NoSmallFactorProof {
+        p_commit,
+        q_commit,
+        a,
+        b,
+        t,
+        sigma,
+        z1: Integer::new(),
+        z2: Integer::new(),
+        w1: Integer::new(),
+        w2: Integer::new(),
+        v: Integer::new(),
+    };
+    let e = factor_challenge(pk, transcript, params, &proof);
+    let sigma_hat: Integer = &proof.sigma - (nu.clone() * p);
+    proof.z1 = alpha + (&e * p).complete();
+    proof.z2 = beta + (&e * q).complete();
+    proof.w1 = x + &e * mu;
+    proof.w2 = y + &e * nu;
+    proof.v = r + e * sigma_hat;
+    Ok(proof)
+}
+
+/// Verifies that the modulus of `pk` has no small factors, using the verifier's own `params`.
+pub fn verify_no_small_factor(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    params: &RingPedersenParams,
+    proof: &NoSmallFactorProof,
+) -> bool {
+    metrics::proof_verified(
+        "no_small_factor",
+        no_small_factor_valid(pk, transcript, params, proof),
+    )
+}
+
+fn no_small_factor_valid(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    params: &RingPedersenParams,
+    proof: &NoSmallFactorProof,
+) -> bool {
+    let n_hat = &params.n_hat;
+    let e = factor_challenge(pk, transcript, params, proof);
+    let range = sqrt_bound(&pk.n) << (ELL + EPSILON);
+    if proof.z1.clone().abs() > range || proof.z2.clone().abs() > range {
+        return false;
+    }
+    let check = || -> Option<bool> {
+        let r = params.commit(&pk.n, &proof.sigma)?;
+        // s^{z1} t^{w1} = A P^e
+        let lhs = params.commit(&proof.z1, &proof.w1)?;
+        let rhs = proof.p_commit.clone().pow_mod(&e, n_hat).ok()? * &proof.a % n_hat;
+        if lhs != rhs {
+            return Some(false);
+        }
+        // s^{z2} t^{w2} = B Q^e
+        let lhs = params.commit(&proof.z2, &proof.w2)?;
+        let rhs = proof.q_commit.clone().pow_mod(&e, n_hat).ok()? * &proof.b % n_hat;
+        if lhs != rhs {
+            return Some(false);
+        }
+        // Q^{z1} t^v = T R^e
+        let mut lhs = proof.q_commit.clone().pow_mod(&proof.z1, n_hat).ok()?;
+        lhs *= params.t.clone().pow_mod(&proof.v, n_hat).ok()?;
+        lhs %= n_hat;
+        let rhs = r.pow_mod(&e, n_hat).ok()? * &proof.t % n_hat;
+        Some(lhs == rhs)
+    };
+    check().unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_no_small_factor, verify_no_small_factor};
+    use crate::paillier::{generate_key_pair, generate_key_pair_with_factors};
+    use crate::proofs::RingPedersenParams;
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_no_small_factor_proof() {
+        let (pk, _sk, factors) = generate_key_pair_with_factors(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let params = RingPedersenParams::generate(128, &mut rand).unwrap();
+        let proof = prove_no_small_factor(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &factors,
+            &params,
+            &mut rand,
+        )
+        .unwrap();
+        assert!(verify_no_small_factor(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &params,
+            &proof
+        ));
+
+        let (other_pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        assert!(!verify_no_small_factor(
+            &other_pk,
+            &mut Transcript::new(b"test"),
+            &params,
+            &proof
+        ));
+    }
+}
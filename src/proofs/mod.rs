@@ -0,0 +1,49 @@
+//! Non-interactive zero-knowledge proofs for statements about paillier ciphertexts.
+//! The proofs are sigma protocols made non-interactive via the Fiat-Shamir heuristic.
+//! Every prover and verifier takes a [`Transcript`] which binds the proof to its
+//! context; verification only succeeds if the verifier's transcript matches the
+//! prover's.
+
+use crate::transcript::Transcript;
+use rug::{Complete, Integer};
+
+mod bit;
+mod decryption;
+mod equality;
+mod factor;
+mod modulus;
+pub(crate) mod nth_root;
+mod plaintext_knowledge;
+pub(crate) mod range;
+mod reencryption;
+mod ring_pedersen;
+
+pub use bit::{prove_bit, verify_bit, BitProof};
+pub use decryption::{prove_decryption, verify_decryption, DecryptionProof};
+pub use equality::{prove_eq, verify_eq, PlaintextEqualityProof};
+pub use factor::{prove_no_small_factor, verify_no_small_factor, NoSmallFactorProof};
+pub use modulus::{prove_modulus, verify_modulus, ModulusProof, MODULUS_PROOF_ROUNDS};
+pub use plaintext_knowledge::{
+    prove_plaintext_knowledge, verify_plaintext_knowledge, PlaintextKnowledgeProof,
+};
+pub use range::{prove_range, verify_range, RangeProof};
+pub use reencryption::{prove_reencryption, verify_reencryption, ReencryptionProof};
+pub use ring_pedersen::RingPedersenParams;
+
+/// Bit length of the Fiat-Shamir challenges
+pub(crate) const CHALLENGE_BITS: u32 = 128;
+
+/// Appends the domain separating `label` and `values` to the transcript and
+/// derives a challenge in [0, 2^CHALLENGE_BITS) from it.
+pub(crate) fn challenge(transcript: &mut Transcript, label: &[u8], values: &[&Integer]) -> Integer {
+    transcript.append_message(b"dom-sep", label);
+    for val in values {
+        transcript.append_integer(b"value", val);
+    }
+    transcript.challenge_integer(b"challenge", CHALLENGE_BITS)
+}
+
+/// Checks that 0 < x < modulus and that x is coprime to n
+pub(crate) fn in_mult_group(x: &Integer, n: &Integer, modulus: &Integer) -> bool {
+    *x > 0 && x < modulus && x.gcd_ref(n).complete() == 1
+}
@@ -0,0 +1,173 @@
+use crate::metrics;
+use crate::paillier::{ModulusFactors, PublicKey};
+use crate::transcript::Transcript;
+use crate::util;
+use anyhow::{anyhow, ensure, Result};
+use rug::integer::IsPrime;
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/paillier-blum-modulus";
+
+/// Number of rounds of the [`ModulusProof`], the soundness error is 2^-MODULUS_PROOF_ROUNDS
+pub const MODULUS_PROOF_ROUNDS: usize = 80;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+struct ModulusProofRound {
+    /// Fourth root of (-1)^a * w^b * y mod n
+    #[serde(with = "crate::util::serde_integer")]
+    x: Integer,
+    a: bool,
+    b: bool,
+    /// N-th root of y mod n
+    #[serde(with = "crate::util::serde_integer")]
+    z: Integer,
+}
+
+/// Proof that the modulus n of a [`PublicKey`] is a Paillier-Blum modulus, i.e.
+/// n = p * q for primes p = q = 3 mod 4 with gcd(n, phi(n)) = 1 (Π^mod of
+/// Canetti et al. "UC Non-Interactive, Proactive, Threshold ECDSA with Identifiable
+/// Aborts"). The challenges y_i are derived from n and the prover's w by hashing.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ModulusProof {
+    /// Random w with Jacobi symbol (w | n) = -1
+    #[serde(with = "crate::util::serde_integer")]
+    w: Integer,
+    rounds: Vec<ModulusProofRound>,
+}
+
+fn challenges(transcript: &mut Transcript, n: &Integer, w: &Integer) -> Vec<Integer> {
+    transcript.append_message(b"dom-sep", LABEL);
+    transcript.append_integer(b"n", n);
+    transcript.append_integer(b"w", w);
+    let bits = n.significant_bits() + 128;
+    (0..MODULUS_PROOF_ROUNDS)
+        .map(|_| transcript.challenge_integer(b"y", bits) % n)
+        .collect()
+}
+
+/// Computes the fourth root of a quadratic residue `a` modulo a prime p = 3 mod 4,
+/// which is a^{((p+1)/4)^2} mod p.
+fn fourth_root(a: &Integer, p: &Integer) -> Integer {
+    let exp: Integer = Integer::from(p + 1) >> 2;
+    let exp = exp.square();
+    a.clone().pow_mod(&exp, p).unwrap()
+}
+
+/// (-1)^a * w^b * y mod n
+fn twist(y: &Integer, w: &Integer, a: bool, b: bool, n: &Integer) -> Integer {
+    let mut rop = y.clone();
+    if b {
+        rop *= w;
+    }
+    rop %= n;
+    if a && rop != 0 {
+        rop = n - rop;
+    }
+    rop
+}
+
+/// Proves that the modulus of `pk` is a Paillier-Blum modulus using its `factors`.
+pub fn prove_modulus(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    factors: &ModulusFactors,
+    rand: &mut dyn MutRandState,
+) -> Result<ModulusProof> {
+    let (p, q, n) = (&factors.p, &factors.q, &pk.n);
+    ensure!((p * q).complete() == *n, "factors do not match the modulus");
+    ensure!(
+        p.mod_u(4) == 3 && q.mod_u(4) == 3,
+        "factors are not congruent 3 mod 4"
+    );
+    let phi = (p - Integer::from(1)) * (q - Integer::from(1));
+    let n_inv = n
+        .clone()
+        .invert(&phi)
+        .map_err(|_| anyhow!("modulus is not coprime to phi(n)"))?;
+    let w = loop {
+        let w = Integer::from(n.random_below_ref(rand));
+        if w.jacobi(n) == -1 {
+            break w;
+        }
+    };
+
+    let rounds = challenges(transcript, n, &w)
+        .into_iter()
+        .map(|y| {
+            let z = y.clone().pow_mod(&n_inv, n).unwrap();
+            for &(a, b) in &[(false, false), (true, false), (false, true), (true, true)] {
+                let y_twisted = twist(&y, &w, a, b, n);
+                if y_twisted.legendre(p) == 1 && y_twisted.legendre(q) == 1 {
+                    let x_p = fourth_root(&(y_twisted.clone() % p), p);
+                    let x_q = fourth_root(&(y_twisted % q), q);
+                    let x = util::crt2(&x_p, p, &x_q, q);
+                    return Ok(ModulusProofRound { x, a, b, z });
+                }
+            }
+            Err(anyhow!("challenge is not coprime to the modulus"))
+        })
+        .collect::<Result<_>>()?;
+    Ok(ModulusProof { w, rounds })
+}
+
+/// Verifies that the modulus of `pk` is a Paillier-Blum modulus.
+pub fn verify_modulus(pk: &PublicKey, transcript: &mut Transcript, proof: &ModulusProof) -> bool {
+    metrics::proof_verified("modulus", modulus_valid(pk, transcript, proof))
+}
+
+fn modulus_valid(pk: &PublicKey, transcript: &mut Transcript, proof: &ModulusProof) -> bool {
+    let n = &pk.n;
+    if n.is_even() || *n <= 3 || n.is_probably_prime(30) != IsPrime::No {
+        return false;
+    }
+    if proof.rounds.len() != MODULUS_PROOF_ROUNDS
+        || proof.w <= 0
+        || proof.w >= *n
+        || proof.w.jacobi(n) != -1
+    {
+        return false;
+    }
+    challenges(transcript, n, &proof.w)
+        .iter()
+        .zip(&proof.rounds)
+        .all(|(y, round)| {
+            if round.x < 0 || round.x >= *n || round.z < 0 || round.z >= *n {
+                return false;
+            }
+            let z_n = round.z.clone().pow_mod(n, n).unwrap();
+            let x_4 = round.x.clone().pow_mod(&Integer::from(4), n).unwrap();
+            z_n == *y && x_4 == twist(y, &proof.w, round.a, round.b, n)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_modulus, verify_modulus};
+    use crate::paillier::{generate_key_pair, generate_key_pair_with_factors};
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_modulus_proof() {
+        let (pk, _sk, factors) = generate_key_pair_with_factors(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let proof = prove_modulus(&pk, &mut Transcript::new(b"test"), &factors, &mut rand).unwrap();
+        assert!(verify_modulus(&pk, &mut Transcript::new(b"test"), &proof));
+
+        let (other_pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        assert!(!verify_modulus(
+            &other_pk,
+            &mut Transcript::new(b"test"),
+            &proof
+        ));
+        assert!(prove_modulus(
+            &other_pk,
+            &mut Transcript::new(b"test"),
+            &factors,
+            &mut rand
+        )
+        .is_err());
+    }
+}
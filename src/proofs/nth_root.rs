@@ -0,0 +1,78 @@
+use crate::paillier::PublicKey;
+use crate::proofs::{challenge, in_mult_group};
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::transcript::Transcript;
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// Proof of knowledge of an n-th root of some u in Z*_{n^2}, i.e. of an x with
+/// u = x^n mod n^2. A ciphertext is an n-th power iff it encrypts 0, which makes
+/// this the building block for proofs about plaintext equality.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct NthRootProof {
+    /// Commitment a = s^n mod n^2
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) a: Integer,
+    /// Response z = s * x^e mod n
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) z: Integer,
+}
+
+/// Proves knowledge of `root` with root^n = u mod n^2. The challenge is bound to
+/// the `label` and the additional `context` values.
+pub(crate) fn prove_nth_root(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    label: &[u8],
+    context: &[&Integer],
+    u: &Integer,
+    root: &Integer,
+    rand: &mut dyn MutRandState,
+) -> NthRootProof {
+    let s = random_in_mult_group(&pk.n, UnitCheck::Skip, rand);
+    let a = s.clone().pow_mod(&pk.n, &pk.n2).unwrap();
+    let e = nth_root_challenge(pk, transcript, label, context, u, &a);
+    let mut z = root.clone().pow_mod(&e, &pk.n).unwrap();
+    z *= s;
+    z %= &pk.n;
+    NthRootProof { a, z }
+}
+
+/// Verifies z^n = a * u^e mod n^2
+pub(crate) fn verify_nth_root(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    label: &[u8],
+    context: &[&Integer],
+    u: &Integer,
+    proof: &NthRootProof,
+) -> bool {
+    if !in_mult_group(u, &pk.n, &pk.n2)
+        || !in_mult_group(&proof.a, &pk.n, &pk.n2)
+        || !in_mult_group(&proof.z, &pk.n, &pk.n)
+    {
+        return false;
+    }
+    let e = nth_root_challenge(pk, transcript, label, context, u, &proof.a);
+    let lhs = proof.z.clone().pow_mod(&pk.n, &pk.n2).unwrap();
+    let mut rhs = u.clone().pow_mod(&e, &pk.n2).unwrap();
+    rhs *= &proof.a;
+    rhs %= &pk.n2;
+    lhs == rhs
+}
+
+fn nth_root_challenge(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    label: &[u8],
+    context: &[&Integer],
+    u: &Integer,
+    a: &Integer,
+) -> Integer {
+    let mut values = vec![&pk.n];
+    values.extend_from_slice(context);
+    values.push(u);
+    values.push(a);
+    challenge(transcript, label, &values)
+}
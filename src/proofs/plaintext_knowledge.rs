@@ -0,0 +1,161 @@
+use crate::metrics;
+use crate::paillier::PublicKey;
+use crate::proofs::{challenge, in_mult_group};
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Plaintext, Randomness};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/plaintext-knowledge";
+
+/// Proof that the creator of a ciphertext c = g^m * r^n mod n^2 knows m and r.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PlaintextKnowledgeProof {
+    /// Commitment a = g^x * s^n mod n^2
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) a: Integer,
+    /// Response z = x + e * m mod n
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) z: Integer,
+    /// Response w = s * r^e mod n
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) w: Integer,
+}
+
+/// Proves knowledge of the plaintext `m` and randomness `r` of `cipher`, as
+/// returned by [`PublicKey::encrypt_with_randomness`].
+pub fn prove_plaintext_knowledge(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    m: &Plaintext,
+    r: &Randomness,
+    rand: &mut dyn MutRandState,
+) -> PlaintextKnowledgeProof {
+    let x = Integer::from(pk.n.random_below_ref(rand));
+    let s = random_in_mult_group(&pk.n, UnitCheck::Skip, rand);
+    let mut a = pk.g_pow(&x);
+    a *= Integer::from(s.pow_mod_ref(&pk.n, &pk.n2).unwrap());
+    a %= &pk.n2;
+
+    let e = challenge(transcript, LABEL, &[&pk.n, cipher.as_ref(), &a]);
+    let z = (x + m.as_ref() * e.clone()) % &pk.n;
+    let mut w = r.as_ref().clone().pow_mod(&e, &pk.n).unwrap();
+    w *= s;
+    w %= &pk.n;
+    PlaintextKnowledgeProof { a, z, w }
+}
+
+/// Verifies that the creator of `cipher` knows its plaintext and randomness.
+/// Because g = n + 1 has order n in Z*_{n^2}, this checks g^z * w^n = a * c^e mod n^2.
+pub fn verify_plaintext_knowledge(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    proof: &PlaintextKnowledgeProof,
+) -> bool {
+    metrics::proof_verified(
+        "plaintext_knowledge",
+        plaintext_knowledge_valid(pk, transcript, cipher, proof),
+    )
+}
+
+fn plaintext_knowledge_valid(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    proof: &PlaintextKnowledgeProof,
+) -> bool {
+    let c = cipher.as_ref();
+    if !in_mult_group(c, &pk.n, &pk.n2)
+        || !in_mult_group(&proof.a, &pk.n, &pk.n2)
+        || !in_mult_group(&proof.w, &pk.n, &pk.n)
+        || proof.z < 0
+        || proof.z >= pk.n
+    {
+        return false;
+    }
+    let e = challenge(transcript, LABEL, &[&pk.n, c, &proof.a]);
+    let mut lhs = pk.g_pow(&proof.z);
+    lhs *= Integer::from(proof.w.pow_mod_ref(&pk.n, &pk.n2).unwrap());
+    lhs %= &pk.n2;
+    let mut rhs = c.clone().pow_mod(&e, &pk.n2).unwrap();
+    rhs *= &proof.a;
+    rhs %= &pk.n2;
+    lhs == rhs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_plaintext_knowledge, verify_plaintext_knowledge};
+    use crate::paillier::generate_key_pair;
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_plaintext_knowledge() {
+        let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let (c, r) = pk.encrypt_with_randomness(42.into(), &mut rand);
+        let proof = prove_plaintext_knowledge(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &42.into(),
+            &r,
+            &mut rand,
+        );
+        assert!(verify_plaintext_knowledge(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &proof
+        ));
+
+        let other = pk.encrypt(42.into(), &mut rand);
+        assert!(!verify_plaintext_knowledge(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &other,
+            &proof
+        ));
+        let wrong = prove_plaintext_knowledge(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &41.into(),
+            &r,
+            &mut rand,
+        );
+        assert!(!verify_plaintext_knowledge(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &wrong
+        ));
+    }
+
+    #[test]
+    fn test_proof_bound_to_transcript() {
+        let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let (c, r) = pk.encrypt_with_randomness(42.into(), &mut rand);
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_u64(b"round", 1);
+        let proof = prove_plaintext_knowledge(&pk, &mut transcript, &c, &42.into(), &r, &mut rand);
+
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_u64(b"round", 1);
+        assert!(verify_plaintext_knowledge(&pk, &mut transcript, &c, &proof));
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_u64(b"round", 2);
+        assert!(!verify_plaintext_knowledge(
+            &pk,
+            &mut transcript,
+            &c,
+            &proof
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::metrics;
+use crate::paillier::PublicKey;
+use crate::proofs::bit::{prove_bit_raw, verify_bit_raw, BitProof};
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Plaintext, Randomness};
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/range";
+
+/// Encryption of a single bit of a decomposed value together with a proof
+/// that it encrypts either 0 or 1.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct EncryptedBit {
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) c: Integer,
+    pub(crate) proof: BitProof,
+}
+
+/// Proof that a ciphertext encrypts a value m with 0 <= m <= B for a public bound B.
+///
+/// With k being the bit length of B, the proof consists of encryptions of the bits
+/// of m and of B - m, each with a proof that it encrypts a bit. The randomness of
+/// the bit encryptions is chosen such that their homomorphic recombination
+/// yields exactly Enc(m) and Enc(B - m). This shows m, B - m in [0, 2^k) and thus
+/// m in [0, B]. The proof size is linear in the bit length of B.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RangeProof {
+    pub(crate) lower: Vec<EncryptedBit>,
+    pub(crate) upper: Vec<EncryptedBit>,
+}
+
+fn range_bits(bound: &Integer) -> u32 {
+    bound.significant_bits().max(1)
+}
+
+/// Encrypts the `bits` lowest bits of `value` such that their weighted product
+/// equals g^value * r^n mod n^2.
+fn decompose(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    value: &Integer,
+    r: &Integer,
+    bits: u32,
+    rand: &mut dyn MutRandState,
+) -> Vec<EncryptedBit> {
+    let mut randomness: Vec<Integer> = (0..bits)
+        .map(|_| random_in_mult_group(&pk.n, UnitCheck::Skip, rand))
+        .collect();
+    // choose r_0 such that prod_i r_i^{2^i} = r mod n
+    let mut acc = Integer::from(1);
+    for (i, r_i) in randomness.iter().enumerate().skip(1) {
+        let exp = Integer::from(1) << i as u32;
+        acc *= r_i.clone().pow_mod(&exp, &pk.n).unwrap();
+        acc %= &pk.n;
+    }
+    randomness[0] = (acc.invert(&pk.n).unwrap() * r) % &pk.n;
+
+    randomness
+        .iter()
+        .enumerate()
+        .map(|(i, r_i)| {
+            let bit = value.get_bit(i as u32);
+            let c = pk.encrypt_raw(&Integer::from(bit), r_i);
+            let proof = prove_bit_raw(pk, transcript, &c, bit, r_i, rand);
+            EncryptedBit { c, proof }
+        })
+        .collect()
+}
+
+/// Checks all bit proofs and that prod_i c_i^{2^i} = target mod n^2
+fn verify_decomposition(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    target: &Integer,
+    bits: &[EncryptedBit],
+    expected_len: u32,
+) -> bool {
+    if bits.len() != expected_len as usize {
+        return false;
+    }
+    let mut acc = Integer::from(1);
+    for (i, bit) in bits.iter().enumerate() {
+        if !verify_bit_raw(pk, transcript, &bit.c, &bit.proof) {
+            return false;
+        }
+        let exp = Integer::from(1) << i as u32;
+        acc *= bit.c.clone().pow_mod(&exp, &pk.n2).unwrap();
+        acc %= &pk.n2;
+    }
+    acc == *target
+}
+
+fn bind_statement(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    bound: &Integer,
+) {
+    transcript.append_message(b"dom-sep", LABEL);
+    transcript.append_integer(b"n", &pk.n);
+    transcript.append_integer(b"cipher", cipher.as_ref());
+    transcript.append_integer(b"bound", bound);
+}
+
+/// g^B * c^{-1} mod n^2, an encryption of B - m
+fn complement(pk: &PublicKey, cipher: &Integer, bound: &Integer) -> Option<Integer> {
+    let inv = cipher.clone().invert(&pk.n2).ok()?;
+    Some(pk.encrypt_raw(bound, &Integer::from(1)) * inv % &pk.n2)
+}
+
+/// Proves that `cipher`, an encryption of `m` with randomness `r`, encrypts a value
+/// in [0, `bound`]. Fails if `m` is not in this range.
+pub fn prove_range(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    m: &Plaintext,
+    r: &Randomness,
+    bound: &Integer,
+    rand: &mut dyn MutRandState,
+) -> Result<RangeProof> {
+    let m = m.as_ref();
+    ensure!(
+        *m >= 0 && m <= bound,
+        "plaintext is not in the range [0, bound]"
+    );
+    ensure!(
+        pk.encrypt_raw(m, r.as_ref()) == *cipher.as_ref(),
+        "ciphertext does not encrypt the plaintext with the given randomness"
+    );
+    let bits = range_bits(bound);
+    let r_inv = r
+        .as_ref()
+        .clone()
+        .invert(&pk.n)
+        .map_err(|_| anyhow!("randomness is not invertible mod n"))?;
+    bind_statement(pk, transcript, cipher, bound);
+    let lower = decompose(pk, transcript, m, r.as_ref(), bits, rand);
+    let upper = decompose(pk, transcript, &(bound - m).into(), &r_inv, bits, rand);
+    Ok(RangeProof { lower, upper })
+}
+
+/// Verifies that `cipher` encrypts a value in [0, `bound`].
+pub fn verify_range(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    bound: &Integer,
+    proof: &RangeProof,
+) -> bool {
+    metrics::proof_verified("range", range_valid(pk, transcript, cipher, bound, proof))
+}
+
+fn range_valid(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    cipher: &Ciphertext,
+    bound: &Integer,
+    proof: &RangeProof,
+) -> bool {
+    if *bound < 0 {
+        return false;
+    }
+    let bits = range_bits(bound);
+    let complement = match complement(pk, cipher.as_ref(), bound) {
+        Some(complement) => complement,
+        None => return false,
+    };
+    bind_statement(pk, transcript, cipher, bound);
+    verify_decomposition(pk, transcript, cipher.as_ref(), &proof.lower, bits)
+        && verify_decomposition(pk, transcript, &complement, &proof.upper, bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_range, verify_range};
+    use crate::paillier::generate_key_pair;
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_range_proof() {
+        let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let bound = Integer::from(100);
+        for m in [0, 42, 100] {
+            let (c, r) = pk.encrypt_with_randomness(m.into(), &mut rand);
+            let proof = prove_range(
+                &pk,
+                &mut Transcript::new(b"test"),
+                &c,
+                &m.into(),
+                &r,
+                &bound,
+                &mut rand,
+            )
+            .unwrap();
+            assert!(verify_range(
+                &pk,
+                &mut Transcript::new(b"test"),
+                &c,
+                &bound,
+                &proof
+            ));
+            assert!(!verify_range(
+                &pk,
+                &mut Transcript::new(b"test"),
+                &c,
+                &Integer::from(50),
+                &proof
+            ));
+        }
+        let (c, r) = pk.encrypt_with_randomness(101.into(), &mut rand);
+        assert!(prove_range(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &101.into(),
+            &r,
+            &bound,
+            &mut rand
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_range_proof_wrong_cipher() {
+        let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let bound = Integer::from(1000);
+        let (c, r) = pk.encrypt_with_randomness(7.into(), &mut rand);
+        let proof = prove_range(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c,
+            &7.into(),
+            &r,
+            &bound,
+            &mut rand,
+        )
+        .unwrap();
+        let other = pk.encrypt(7.into(), &mut rand);
+        assert!(!verify_range(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &other,
+            &bound,
+            &proof
+        ));
+    }
+}
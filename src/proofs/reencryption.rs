@@ -0,0 +1,114 @@
+use crate::metrics;
+use crate::paillier::PublicKey;
+use crate::proofs::nth_root::{prove_nth_root, verify_nth_root, NthRootProof};
+use crate::transcript::Transcript;
+use crate::{Ciphertext, Randomness};
+use anyhow::{anyhow, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+const LABEL: &[u8] = b"pht-crypto/proofs/reencryption";
+
+/// Proof that a ciphertext c' is a re-randomization c' = c * s^n mod n^2 of c and
+/// thus decrypts to the same plaintext. Only the party that performed the
+/// re-encryption and knows s can create it.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ReencryptionProof {
+    pub(crate) proof: NthRootProof,
+}
+
+/// c' * c^{-1} mod n^2
+fn quotient(pk: &PublicKey, original: &Ciphertext, reencrypted: &Ciphertext) -> Option<Integer> {
+    let inv = original.as_ref().clone().invert(&pk.n2).ok()?;
+    Some(inv * reencrypted.as_ref() % &pk.n2)
+}
+
+/// Proves that `reencrypted` was obtained from `original` with
+/// [`PublicKey::reencrypt_with_randomness`] returning `s`.
+pub fn prove_reencryption(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    original: &Ciphertext,
+    reencrypted: &Ciphertext,
+    s: &Randomness,
+    rand: &mut dyn MutRandState,
+) -> Result<ReencryptionProof> {
+    let u = quotient(pk, original, reencrypted)
+        .ok_or_else(|| anyhow!("ciphertext is not invertible"))?;
+    let context = [original.as_ref(), reencrypted.as_ref()];
+    let proof = prove_nth_root(pk, transcript, LABEL, &context, &u, s.as_ref(), rand);
+    Ok(ReencryptionProof { proof })
+}
+
+/// Verifies that `reencrypted` is a re-randomization of `original`.
+pub fn verify_reencryption(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    original: &Ciphertext,
+    reencrypted: &Ciphertext,
+    proof: &ReencryptionProof,
+) -> bool {
+    metrics::proof_verified(
+        "reencryption",
+        reencryption_valid(pk, transcript, original, reencrypted, proof),
+    )
+}
+
+fn reencryption_valid(
+    pk: &PublicKey,
+    transcript: &mut Transcript,
+    original: &Ciphertext,
+    reencrypted: &Ciphertext,
+    proof: &ReencryptionProof,
+) -> bool {
+    let u = match quotient(pk, original, reencrypted) {
+        Some(u) => u,
+        None => return false,
+    };
+    let context = [original.as_ref(), reencrypted.as_ref()];
+    verify_nth_root(pk, transcript, LABEL, &context, &u, &proof.proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{prove_reencryption, verify_reencryption};
+    use crate::paillier::generate_key_pair;
+    use crate::transcript::Transcript;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_reencryption_proof() {
+        let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut rand = RandState::new();
+        let original = pk.encrypt(42.into(), &mut rand);
+        let mut reencrypted = original.clone();
+        let s = pk.reencrypt_with_randomness(&mut reencrypted, &mut rand);
+        let proof = prove_reencryption(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &original,
+            &reencrypted,
+            &s,
+            &mut rand,
+        )
+        .unwrap();
+        assert!(verify_reencryption(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &original,
+            &reencrypted,
+            &proof
+        ));
+
+        let mut shifted = reencrypted.clone();
+        pk.add_plain(&mut shifted, &1.into());
+        assert!(!verify_reencryption(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &original,
+            &shifted,
+            &proof
+        ));
+    }
+}
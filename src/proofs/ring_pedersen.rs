@@ -0,0 +1,62 @@
+use crate::rand::{generate_modulus_safe_primes, random_in_mult_group, UnitCheck};
+use anyhow::{ensure, Result};
+use rug::integer::IsPrime;
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+
+/// Ring-Pedersen parameters (N̂, s, t) with N̂ a product of two safe primes and
+/// s, t random squares in Z*_N̂ with s in the subgroup generated by t. They are
+/// generated by a verifier and used by provers to commit to integers for proofs
+/// like [`crate::proofs::prove_no_small_factor`]. Since the verifier generates
+/// them for its own use, it does not need to be convinced of their validity.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct RingPedersenParams {
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) n_hat: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) s: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub(crate) t: Integer,
+}
+
+impl RingPedersenParams {
+    /// Generates fresh parameters with a modulus of `bits` bits.
+    pub fn generate(bits: usize, rand: &mut dyn MutRandState) -> Result<Self> {
+        let (p, p1, q, q1) = generate_modulus_safe_primes(bits)?;
+        let n_hat = p * q;
+        // the squares in Z*_N̂ have order p1 * q1
+        let order = p1 * q1;
+        let mut t = random_in_mult_group(&n_hat, UnitCheck::Gcd, rand);
+        t.square_mut();
+        t %= &n_hat;
+        let lambda = Integer::from(order.random_below_ref(rand));
+        let s = t.clone().pow_mod(&lambda, &n_hat).unwrap();
+        Ok(Self { n_hat, s, t })
+    }
+
+    /// Checks the parameters received from a verifier for basic sanity. This
+    /// does not prove that s is in the subgroup generated by t.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.n_hat.is_odd() && self.n_hat.is_probably_prime(30) == IsPrime::No,
+            "N̂ must be an odd composite"
+        );
+        for x in [&self.s, &self.t] {
+            ensure!(
+                *x > 1 && *x < self.n_hat && x.gcd_ref(&self.n_hat).complete() == 1,
+                "s and t must be in Z*_N̂"
+            );
+        }
+        ensure!(self.s != self.t, "s and t must be distinct");
+        Ok(())
+    }
+
+    /// Computes the commitment s^a * t^b mod N̂ for possibly negative a, b
+    pub(crate) fn commit(&self, a: &Integer, b: &Integer) -> Option<Integer> {
+        let mut rop = self.s.clone().pow_mod(a, &self.n_hat).ok()?;
+        rop *= self.t.clone().pow_mod(b, &self.n_hat).ok()?;
+        rop %= &self.n_hat;
+        Some(rop)
+    }
+}
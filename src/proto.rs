@@ -0,0 +1,422 @@
+//! Protocol buffer messages (feature `proto`) for keys, ciphertexts, partial
+//! decryptions and proofs, so they can be embedded in gRPC services with a proper
+//! schema. The schema is available in `proto/pht_crypto.proto`; the messages are
+//! compatible with code generated from it for any language.
+//!
+//! Integers are encoded as unsigned big-endian bytes.
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::proto;
+//! use prost::Message;
+//! use rug::rand::RandState;
+//! use std::convert::TryFrom;
+//!
+//! let (pk, _sk) = generate_key_pair(128, 1, 1).unwrap();
+//! let cipher = pk.encrypt(42.into(), &mut RandState::new());
+//!
+//! let bytes = proto::Ciphertext::from(&cipher).encode_to_vec();
+//! let decoded: pht_crypto::Ciphertext = proto::Ciphertext::decode(&bytes[..]).unwrap().into();
+//! assert_eq!(decoded.as_ref(), cipher.as_ref());
+//!
+//! let bytes = proto::PublicKey::from(&pk).encode_to_vec();
+//! let decoded = pht_crypto::paillier::PublicKey::try_from(
+//!     proto::PublicKey::decode(&bytes[..]).unwrap(),
+//! ).unwrap();
+//! assert_eq!(decoded, pk);
+//! ```
+
+use crate::proofs::nth_root;
+use crate::proofs::range;
+use crate::{paillier, proofs};
+use anyhow::{anyhow, ensure, Error, Result};
+use rug::integer::Order;
+use rug::Integer;
+use std::convert::{TryFrom, TryInto};
+
+fn to_bytes(x: &Integer) -> Vec<u8> {
+    x.to_digits(Order::Msf)
+}
+
+fn from_bytes(bytes: &[u8]) -> Integer {
+    Integer::from_digits(bytes, Order::Msf)
+}
+
+fn to_bytes_vec(xs: &[Integer]) -> Vec<Vec<u8>> {
+    xs.iter().map(to_bytes).collect()
+}
+
+fn from_bytes_vec(xs: &[Vec<u8>]) -> Vec<Integer> {
+    xs.iter().map(|x| from_bytes(x)).collect()
+}
+
+fn required<T>(field: Option<T>, name: &str) -> Result<T> {
+    field.ok_or_else(|| anyhow!("Missing field {}", name))
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PublicKey {
+    #[prost(bytes = "vec", tag = "1")]
+    pub n: Vec<u8>,
+    #[prost(uint32, tag = "2")]
+    pub decryption_servers: u32,
+    #[prost(uint32, tag = "3")]
+    pub threshold: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Ciphertext {
+    #[prost(bytes = "vec", tag = "1")]
+    pub value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PartialDecryption {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub cipher_digest: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub key_fingerprint: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PlaintextKnowledgeProof {
+    #[prost(bytes = "vec", tag = "1")]
+    pub a: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub z: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub w: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct NthRootProof {
+    #[prost(bytes = "vec", tag = "1")]
+    pub a: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub z: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PlaintextEqualityProof {
+    #[prost(message, optional, tag = "1")]
+    pub proof: Option<NthRootProof>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ReencryptionProof {
+    #[prost(message, optional, tag = "1")]
+    pub proof: Option<NthRootProof>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BitProof {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub a: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub e: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub z: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EncryptedBit {
+    #[prost(bytes = "vec", tag = "1")]
+    pub c: Vec<u8>,
+    #[prost(message, optional, tag = "2")]
+    pub proof: Option<BitProof>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RangeProof {
+    #[prost(message, repeated, tag = "1")]
+    pub lower: Vec<EncryptedBit>,
+    #[prost(message, repeated, tag = "2")]
+    pub upper: Vec<EncryptedBit>,
+}
+
+impl From<&paillier::PublicKey> for PublicKey {
+    fn from(pk: &paillier::PublicKey) -> Self {
+        Self {
+            n: to_bytes(&pk.n),
+            decryption_servers: pk.l,
+            threshold: pk.w,
+        }
+    }
+}
+
+impl TryFrom<PublicKey> for paillier::PublicKey {
+    type Error = Error;
+
+    fn try_from(pk: PublicKey) -> Result<Self> {
+        paillier::PublicKey::from_modulus(from_bytes(&pk.n), pk.decryption_servers, pk.threshold)
+    }
+}
+
+impl From<&crate::Ciphertext> for Ciphertext {
+    fn from(cipher: &crate::Ciphertext) -> Self {
+        Self {
+            value: to_bytes(&cipher.val),
+        }
+    }
+}
+
+impl From<Ciphertext> for crate::Ciphertext {
+    fn from(cipher: Ciphertext) -> Self {
+        from_bytes(&cipher.value).into()
+    }
+}
+
+impl From<&paillier::PartialDecryption> for PartialDecryption {
+    fn from(share: &paillier::PartialDecryption) -> Self {
+        Self {
+            id: share.id,
+            value: to_bytes(&share.val),
+            cipher_digest: share.cipher_digest.map(Vec::from).unwrap_or_default(),
+            key_fingerprint: share.key_fingerprint.map(Vec::from).unwrap_or_default(),
+        }
+    }
+}
+
+impl TryFrom<PartialDecryption> for paillier::PartialDecryption {
+    type Error = Error;
+
+    fn try_from(share: PartialDecryption) -> Result<Self> {
+        ensure!(share.id > 0, "evaluation point 0 is not a valid share");
+        Ok(Self {
+            id: share.id,
+            val: from_bytes(&share.value),
+            // metadata of another length than a digest is treated as unknown
+            cipher_digest: share.cipher_digest.as_slice().try_into().ok(),
+            key_fingerprint: share.key_fingerprint.as_slice().try_into().ok(),
+        })
+    }
+}
+
+impl From<&proofs::PlaintextKnowledgeProof> for PlaintextKnowledgeProof {
+    fn from(proof: &proofs::PlaintextKnowledgeProof) -> Self {
+        Self {
+            a: to_bytes(&proof.a),
+            z: to_bytes(&proof.z),
+            w: to_bytes(&proof.w),
+        }
+    }
+}
+
+impl From<PlaintextKnowledgeProof> for proofs::PlaintextKnowledgeProof {
+    fn from(proof: PlaintextKnowledgeProof) -> Self {
+        Self {
+            a: from_bytes(&proof.a),
+            z: from_bytes(&proof.z),
+            w: from_bytes(&proof.w),
+        }
+    }
+}
+
+impl From<&nth_root::NthRootProof> for NthRootProof {
+    fn from(proof: &nth_root::NthRootProof) -> Self {
+        Self {
+            a: to_bytes(&proof.a),
+            z: to_bytes(&proof.z),
+        }
+    }
+}
+
+impl From<NthRootProof> for nth_root::NthRootProof {
+    fn from(proof: NthRootProof) -> Self {
+        Self {
+            a: from_bytes(&proof.a),
+            z: from_bytes(&proof.z),
+        }
+    }
+}
+
+impl From<&proofs::PlaintextEqualityProof> for PlaintextEqualityProof {
+    fn from(proof: &proofs::PlaintextEqualityProof) -> Self {
+        Self {
+            proof: Some((&proof.proof).into()),
+        }
+    }
+}
+
+impl TryFrom<PlaintextEqualityProof> for proofs::PlaintextEqualityProof {
+    type Error = Error;
+
+    fn try_from(proof: PlaintextEqualityProof) -> Result<Self> {
+        Ok(Self {
+            proof: required(proof.proof, "proof")?.into(),
+        })
+    }
+}
+
+impl From<&proofs::ReencryptionProof> for ReencryptionProof {
+    fn from(proof: &proofs::ReencryptionProof) -> Self {
+        Self {
+            proof: Some((&proof.proof).into()),
+        }
+    }
+}
+
+impl TryFrom<ReencryptionProof> for proofs::ReencryptionProof {
+    type Error = Error;
+
+    fn try_from(proof: ReencryptionProof) -> Result<Self> {
+        Ok(Self {
+            proof: required(proof.proof, "proof")?.into(),
+        })
+    }
+}
+
+impl From<&proofs::BitProof> for BitProof {
+    fn from(proof: &proofs::BitProof) -> Self {
+        Self {
+            a: to_bytes_vec(&proof.a),
+            e: to_bytes_vec(&proof.e),
+            z: to_bytes_vec(&proof.z),
+        }
+    }
+}
+
+impl From<BitProof> for proofs::BitProof {
+    fn from(proof: BitProof) -> Self {
+        Self {
+            a: from_bytes_vec(&proof.a),
+            e: from_bytes_vec(&proof.e),
+            z: from_bytes_vec(&proof.z),
+        }
+    }
+}
+
+impl From<&range::EncryptedBit> for EncryptedBit {
+    fn from(bit: &range::EncryptedBit) -> Self {
+        Self {
+            c: to_bytes(&bit.c),
+            proof: Some((&bit.proof).into()),
+        }
+    }
+}
+
+impl TryFrom<EncryptedBit> for range::EncryptedBit {
+    type Error = Error;
+
+    fn try_from(bit: EncryptedBit) -> Result<Self> {
+        Ok(Self {
+            c: from_bytes(&bit.c),
+            proof: required(bit.proof, "proof")?.into(),
+        })
+    }
+}
+
+impl From<&proofs::RangeProof> for RangeProof {
+    fn from(proof: &proofs::RangeProof) -> Self {
+        Self {
+            lower: proof.lower.iter().map(Into::into).collect(),
+            upper: proof.upper.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl TryFrom<RangeProof> for proofs::RangeProof {
+    type Error = Error;
+
+    fn try_from(proof: RangeProof) -> Result<Self> {
+        let convert = |bits: Vec<EncryptedBit>| {
+            bits.into_iter()
+                .map(range::EncryptedBit::try_from)
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            lower: convert(proof.lower)?,
+            upper: convert(proof.upper)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paillier::generate_key_pair;
+    use crate::proofs::{prove_eq, prove_range, verify_eq, verify_range};
+    use crate::transcript::Transcript;
+    use prost::Message;
+    use rug::rand::RandState;
+
+    fn roundtrip<M: Message + Default>(msg: M) -> M {
+        M::decode(&msg.encode_to_vec()[..]).unwrap()
+    }
+
+    #[test]
+    fn test_proto_roundtrip() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+
+        let pk2 = paillier::PublicKey::try_from(roundtrip(PublicKey::from(&pk))).unwrap();
+        assert_eq!(pk2, pk);
+
+        let (c1, r1) = pk.encrypt_with_randomness(7.into(), &mut rand);
+        let (c2, r2) = pk.encrypt_with_randomness(7.into(), &mut rand);
+        let cipher: crate::Ciphertext = roundtrip(Ciphertext::from(&c1)).into();
+        assert_eq!(cipher.as_ref(), c1.as_ref());
+
+        let shares: Vec<paillier::PartialDecryption> = key_shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, cipher.clone()))
+            .map(|share| {
+                paillier::PartialDecryption::try_from(roundtrip(PartialDecryption::from(&share)))
+            })
+            .collect::<Result<_>>()
+            .unwrap();
+        let m: Integer = pk.share_combine(&shares).unwrap().into();
+        assert_eq!(m, 7);
+        let mut zero = PartialDecryption::from(&shares[0]);
+        zero.id = 0;
+        assert!(paillier::PartialDecryption::try_from(zero).is_err());
+
+        let proof = prove_eq(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &r1,
+            &c2,
+            &r2,
+            &mut rand,
+        )
+        .unwrap();
+        let proof = proofs::PlaintextEqualityProof::try_from(roundtrip(
+            PlaintextEqualityProof::from(&proof),
+        ))
+        .unwrap();
+        assert!(verify_eq(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &c2,
+            &proof
+        ));
+
+        let proof = prove_range(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &7.into(),
+            &r1,
+            &Integer::from(10),
+            &mut rand,
+        )
+        .unwrap();
+        let proof = proofs::RangeProof::try_from(roundtrip(RangeProof::from(&proof))).unwrap();
+        assert!(verify_range(
+            &pk,
+            &mut Transcript::new(b"test"),
+            &c1,
+            &Integer::from(10),
+            &proof
+        ));
+
+        assert!(proofs::ReencryptionProof::try_from(ReencryptionProof::default()).is_err());
+    }
+}
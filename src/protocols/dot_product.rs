@@ -0,0 +1,114 @@
+//! Two-party secure dot product using threshold Paillier.
+//!
+//! Party A holds x, party B holds y, both vectors over Z_n.
+//! 1. A sends Enc(x_i) for every element with [`request`].
+//! 2. B computes Enc(<x, y> + r) = prod Enc(x_i)^{y_i} * Enc(r) for a uniformly random
+//!    mask r in Z_n, rerandomizes it and keeps -r mod n as its share with [`respond`].
+//! 3. The decryption servers threshold-decrypt [`DotProductResponse::cipher`]. The
+//!    resulting <x, y> + r mod n is A's share.
+//!
+//! The shares of A and B sum to <x, y> mod n, while the masked value leaks nothing about
+//! y to A or the decryption servers. If A should learn the result, B reveals its share
+//! and A calls [`unmask`].
+
+use crate::paillier::PublicKey;
+use crate::proofs::in_mult_group;
+use crate::protocols::reduce;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotProductRequest {
+    ciphers: Vec<Ciphertext>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DotProductResponse {
+    cipher: Ciphertext,
+}
+
+impl DotProductResponse {
+    /// The encrypted masked dot product that has to be threshold-decrypted
+    pub fn cipher(&self) -> &Ciphertext {
+        &self.cipher
+    }
+}
+
+/// Computes A's message, the element-wise encryption of `x`.
+pub fn request(pk: &PublicKey, x: &[Plaintext], rand: &mut dyn MutRandState) -> DotProductRequest {
+    let ciphers = x
+        .iter()
+        .map(|x_i| pk.encrypt(reduce(pk, x_i.as_ref()).into(), rand))
+        .collect();
+    DotProductRequest { ciphers }
+}
+
+/// Computes B's answer for its vector `y` and returns it together with B's share of
+/// the dot product.
+pub fn respond(
+    pk: &PublicKey,
+    request: &DotProductRequest,
+    y: &[Plaintext],
+    rand: &mut dyn MutRandState,
+) -> Result<(DotProductResponse, Plaintext)> {
+    ensure!(
+        request.ciphers.len() == y.len(),
+        "vectors must have the same length"
+    );
+    ensure!(
+        request
+            .ciphers
+            .iter()
+            .all(|c| in_mult_group(c.as_ref(), &pk.n, &pk.n2)),
+        "ciphertext is not in Z*_n^2"
+    );
+    let mask = Integer::from(pk.n.random_below_ref(rand));
+    let mut cipher = pk.encrypt(Plaintext::from(&mask), rand);
+    for (c, y_i) in request.ciphers.iter().zip(y) {
+        let mut term = c.clone();
+        pk.mul_plain(&mut term, &reduce(pk, y_i.as_ref()).into());
+        pk.add_encrypted(&mut cipher, &term);
+    }
+    pk.reencrypt(&mut cipher, rand);
+    let share = reduce(pk, &(-mask));
+    Ok((DotProductResponse { cipher }, share.into()))
+}
+
+/// Computes <x, y> mod n from the decrypted masked value and B's share.
+pub fn unmask(pk: &PublicKey, masked: &Plaintext, share: &Plaintext) -> Plaintext {
+    reduce(pk, &Integer::from(masked.as_ref() + share.as_ref())).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{request, respond, unmask};
+    use crate::paillier::generate_key_pair;
+    use crate::Plaintext;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_dot_product() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 3, 2).unwrap();
+        let key_shares = sk.share(&[0, 2], &mut rand);
+
+        let x: Vec<Plaintext> = vec![3.into(), 0.into(), 7.into(), 11.into()];
+        let y: Vec<Plaintext> = vec![5.into(), 9.into(), (-2).into(), 1.into()];
+        let req = request(&pk, &x, &mut rand);
+        let (resp, share_b) = respond(&pk, &req, &y, &mut rand).unwrap();
+
+        let partials: Vec<_> = key_shares
+            .iter()
+            .map(|s| s.share_decrypt(&pk, resp.cipher().clone()))
+            .collect();
+        let share_a = pk.share_combine(&partials).unwrap();
+        assert_ne!(share_a, 12);
+        assert_eq!(unmask(&pk, &share_a, &share_b), 12);
+
+        let short = request(&pk, &x[..3], &mut rand);
+        assert!(respond(&pk, &short, &y, &mut rand).is_err());
+    }
+}
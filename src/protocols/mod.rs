@@ -0,0 +1,23 @@
+//! Multi-party protocols built on top of the homomorphic operations of the schemes.
+//!
+//! The protocols are written as explicit rounds: every party calls the round functions
+//! in order and broadcasts the returned messages to all other participants. Transport is
+//! left to the caller. Unless stated otherwise, the protocols are secure against
+//! semi-honest parties.
+
+use crate::paillier::PublicKey;
+use rug::Integer;
+
+pub mod dot_product;
+pub mod polynomial;
+pub mod psi;
+pub mod triples;
+
+/// Reduces x into [0, n)
+pub(crate) fn reduce(pk: &PublicKey, x: &Integer) -> Integer {
+    let mut rop = Integer::from(x % &pk.n);
+    if rop < 0 {
+        rop += &pk.n;
+    }
+    rop
+}
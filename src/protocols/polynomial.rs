@@ -0,0 +1,96 @@
+//! Oblivious polynomial evaluation: one party encrypts the coefficients of a polynomial,
+//! another party evaluates it at its private inputs without learning the polynomial.
+//! A degree one polynomial gives oblivious linear function evaluation (OLE).
+
+use crate::paillier::PublicKey;
+use crate::proofs::in_mult_group;
+use crate::protocols::reduce;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// Polynomial over Z_n with encrypted coefficients, lowest degree first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedPolynomial {
+    coefficients: Vec<Ciphertext>,
+}
+
+impl EncryptedPolynomial {
+    /// Encrypts the plaintext `coefficients`, lowest degree first
+    pub fn new(pk: &PublicKey, coefficients: &[Plaintext], rand: &mut dyn MutRandState) -> Self {
+        let coefficients = coefficients
+            .iter()
+            .map(|a| pk.encrypt(reduce(pk, a.as_ref()).into(), rand))
+            .collect();
+        Self { coefficients }
+    }
+
+    /// Wraps already encrypted coefficients, e.g. received from another party, after
+    /// checking that they are valid ciphertexts.
+    pub fn from_ciphertexts(pk: &PublicKey, coefficients: Vec<Ciphertext>) -> Result<Self> {
+        let poly = Self { coefficients };
+        ensure!(poly.is_valid(pk), "invalid polynomial");
+        Ok(poly)
+    }
+
+    /// Checks that there is at least one coefficient and all are valid ciphertexts.
+    /// Must be called on polynomials that were deserialized from untrusted input.
+    pub fn is_valid(&self, pk: &PublicKey) -> bool {
+        !self.coefficients.is_empty()
+            && self
+                .coefficients
+                .iter()
+                .all(|c| in_mult_group(c.as_ref(), &pk.n, &pk.n2))
+    }
+
+    pub fn coefficients(&self) -> &[Ciphertext] {
+        &self.coefficients
+    }
+
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// Computes Enc(P(x)) with Horner's method. The result is not rerandomized, callers
+    /// sending it to the key holders have to blind and reencrypt it.
+    pub fn eval_at(&self, pk: &PublicKey, x: &Plaintext) -> Ciphertext {
+        let x = Plaintext::from(reduce(pk, x.as_ref()));
+        let mut coefficients = self.coefficients.iter().rev();
+        let mut acc = match coefficients.next() {
+            Some(c) => c.clone(),
+            None => return Integer::from(1).into(),
+        };
+        for c in coefficients {
+            pk.mul_plain(&mut acc, &x);
+            pk.add_encrypted(&mut acc, c);
+        }
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EncryptedPolynomial;
+    use crate::paillier::generate_key_pair;
+    use crate::Plaintext;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_eval_at() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 1, 1).unwrap();
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+
+        // P(x) = 4 - 3x + 2x^2
+        let coefficients: Vec<Plaintext> = vec![4.into(), (-3).into(), 2.into()];
+        let poly = EncryptedPolynomial::new(&pk, &coefficients, &mut rand);
+        assert_eq!(poly.degree(), 2);
+        for (x, expected) in [(0, 4), (1, 3), (5, 39)] {
+            let c = poly.eval_at(&pk, &x.into());
+            let partial = key_share.share_decrypt(&pk, c);
+            assert_eq!(pk.share_combine(&[partial]).unwrap(), expected);
+        }
+    }
+}
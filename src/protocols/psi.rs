@@ -0,0 +1,140 @@
+//! Private set intersection cardinality via encrypted polynomial evaluation.
+//!
+//! Source: Freedman, Nissim, Pinkas "Efficient Private Matching and Set Intersection"
+//!
+//! Party A holds the set X, party B the set Y, the elements are integers mod n.
+//! 1. A encrypts the coefficients of P(z) = prod_{x in X} (z - x) with [`encode_set`].
+//! 2. B homomorphically evaluates Enc(r_y * P(y)) for each y in Y and a random r_y,
+//!    shuffles the results and sends them out with [`evaluate`].
+//! 3. The decryption servers decrypt all values with
+//!    [`PsiResponse::share_decrypt`] and [`cardinality`] counts the zeros.
+//!
+//! An evaluation is zero iff y is in X, all other values are uniformly random. Due to
+//! the shuffle only |X ∩ Y| is revealed, but not which elements are in the intersection.
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::protocols::polynomial::EncryptedPolynomial;
+use crate::protocols::reduce;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// B's shuffled and blinded evaluations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PsiResponse {
+    ciphers: Vec<Ciphertext>,
+}
+
+/// Computes A's message for its `set`.
+pub fn encode_set(
+    pk: &PublicKey,
+    set: &[Integer],
+    rand: &mut dyn MutRandState,
+) -> EncryptedPolynomial {
+    // multiply out prod (z - x), coefficients[j] belongs to z^j
+    let mut coefficients = vec![Integer::from(1)];
+    for x in set {
+        let x = reduce(pk, x);
+        let mut next = vec![Integer::new(); coefficients.len() + 1];
+        for (j, a) in coefficients.iter().enumerate() {
+            next[j + 1] += a;
+            next[j] -= Integer::from(a * &x);
+        }
+        coefficients = next.iter().map(|a| reduce(pk, a)).collect();
+    }
+    let coefficients: Vec<Plaintext> = coefficients.into_iter().map(Plaintext::from).collect();
+    EncryptedPolynomial::new(pk, &coefficients, rand)
+}
+
+/// Computes B's response by evaluating A's polynomial on every element of `set`.
+pub fn evaluate(
+    pk: &PublicKey,
+    poly: &EncryptedPolynomial,
+    set: &[Integer],
+    rand: &mut dyn MutRandState,
+) -> Result<PsiResponse> {
+    ensure!(poly.is_valid(pk), "invalid polynomial");
+    ensure!(poly.degree() > 0, "polynomial must have at least one root");
+    let mut ciphers: Vec<_> = set
+        .iter()
+        .map(|y| {
+            let mut acc = poly.eval_at(pk, &Plaintext::from(y));
+            let r: Integer = Integer::from(Integer::from(&pk.n - 1).random_below_ref(rand)) + 1;
+            pk.mul_plain(&mut acc, &r.into());
+            pk.reencrypt(&mut acc, rand);
+            acc
+        })
+        .collect();
+    for i in (1..ciphers.len()).rev() {
+        let j = Integer::from(i + 1).random_below(rand).to_usize().unwrap();
+        ciphers.swap(i, j);
+    }
+    Ok(PsiResponse { ciphers })
+}
+
+impl PsiResponse {
+    /// Computes the partial decryptions of all evaluations with a server's key share
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> Vec<PartialDecryption> {
+        self.ciphers
+            .iter()
+            .map(|c| key_share.share_decrypt(pk, c.clone()))
+            .collect()
+    }
+}
+
+/// Combines the partial decryptions of at least w servers, one vector per server, and
+/// returns the size of the intersection.
+pub fn cardinality(pk: &PublicKey, partials: &[Vec<PartialDecryption>]) -> Result<usize> {
+    ensure!(!partials.is_empty(), "no partial decryptions");
+    let len = partials[0].len();
+    ensure!(
+        partials.iter().all(|p| p.len() == len),
+        "servers decrypted a different number of values"
+    );
+    let mut count = 0;
+    for k in 0..len {
+        let shares: Vec<_> = partials.iter().map(|p| p[k].clone()).collect();
+        if pk.share_combine(&shares)? == 0 {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cardinality, encode_set, evaluate};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_psi_cardinality() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+
+        let set_a: Vec<Integer> = [3, 17, 42, 1000, 7]
+            .iter()
+            .map(|x| Integer::from(*x))
+            .collect();
+        let set_b: Vec<Integer> = [42, 5, 7, 8, 1000, 99]
+            .iter()
+            .map(|x| Integer::from(*x))
+            .collect();
+
+        let poly = encode_set(&pk, &set_a, &mut rand);
+        let resp = evaluate(&pk, &poly, &set_b, &mut rand).unwrap();
+        let partials: Vec<_> = key_shares
+            .iter()
+            .map(|s| resp.share_decrypt(&pk, s))
+            .collect();
+        assert_eq!(cardinality(&pk, &partials).unwrap(), 3);
+    }
+}
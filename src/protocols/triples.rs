@@ -0,0 +1,281 @@
+//! Generation of additively shared multiplication triples (a, b, c = ab) mod M using
+//! threshold Paillier, e.g. as the offline phase of an MPC engine.
+//!
+//! Every participant holds a share of the same threshold key. The protocol is:
+//! 1. Each party P_i samples a_i, b_i in Z_M and broadcasts Enc(a_i) and Enc(b_i).
+//! 2. Everyone computes Enc(a) = Enc(sum a_i). P_i broadcasts the rerandomized
+//!    Enc(a * b_i) and Enc(f_i) for a random mask f_i in [0, 2^κ * l^2 * M^2).
+//! 3. Everyone computes Enc(ab + sum f_i) and broadcasts a partial decryption of it.
+//! 4. The decryption d is range checked. The party with the lowest id sets
+//!    c_i = d - f_i mod M, all others c_i = -f_i mod M.
+//!
+//! As the masks are 2^κ times larger than the masked value, d statistically hides ab
+//! with distance at most 2^-κ. All arithmetic happens over the integers, so n must be
+//! large enough that d never wraps mod n.
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::proofs::in_mult_group;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// Statistical security parameter κ of the masks
+pub const STATISTICAL_SECURITY: u32 = 40;
+
+/// A party's additive share of a multiplication triple mod M
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct TripleShare {
+    #[serde(with = "crate::util::serde_integer")]
+    pub a: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub b: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub c: Integer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Round1Message {
+    id: u32,
+    a: Vec<Ciphertext>,
+    b: Vec<Ciphertext>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Round2Message {
+    id: u32,
+    ab: Vec<Ciphertext>,
+    masks: Vec<Ciphertext>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Round3Message {
+    partials: Vec<PartialDecryption>,
+}
+
+/// State of one party in the triple generation
+pub struct TripleGenerator<'a> {
+    pk: &'a PublicKey,
+    key_share: &'a PrivateKeyShare,
+    modulus: Integer,
+    a: Vec<Integer>,
+    b: Vec<Integer>,
+    masks: Vec<Integer>,
+    parties: Vec<u32>,
+    masked: Vec<Ciphertext>,
+}
+
+impl<'a> TripleGenerator<'a> {
+    /// Starts the generation of `count` triples mod `modulus` and returns the first
+    /// message of this party.
+    pub fn new(
+        pk: &'a PublicKey,
+        key_share: &'a PrivateKeyShare,
+        modulus: Integer,
+        count: usize,
+        rand: &mut dyn MutRandState,
+    ) -> Result<(Self, Round1Message)> {
+        ensure!(modulus > 1, "modulus must be greater than 1");
+        ensure!(
+            pk.n > max_decryption(&modulus, pk.l),
+            "Paillier modulus is too small for the triple modulus"
+        );
+        let mut sample = || -> Vec<Integer> {
+            (0..count)
+                .map(|_| Integer::from(modulus.random_below_ref(rand)))
+                .collect()
+        };
+        let a = sample();
+        let b = sample();
+        let enc = |v: &[Integer], rand: &mut dyn MutRandState| -> Vec<Ciphertext> {
+            v.iter()
+                .map(|x| pk.encrypt(Plaintext::from(x), rand))
+                .collect()
+        };
+        let msg = Round1Message {
+            id: key_share.i,
+            a: enc(&a, rand),
+            b: enc(&b, rand),
+        };
+        let generator = Self {
+            pk,
+            key_share,
+            modulus,
+            a,
+            b,
+            masks: vec![],
+            parties: vec![],
+            masked: vec![],
+        };
+        Ok((generator, msg))
+    }
+
+    /// Processes the first messages of all parties, including this party's own.
+    pub fn round2(
+        &mut self,
+        msgs: &[Round1Message],
+        rand: &mut dyn MutRandState,
+    ) -> Result<Round2Message> {
+        let mut parties: Vec<_> = msgs.iter().map(|m| m.id).collect();
+        parties.sort_unstable();
+        parties.dedup();
+        ensure!(parties.len() == msgs.len(), "duplicate party ids");
+        ensure!(
+            parties.contains(&self.key_share.i),
+            "own message is missing"
+        );
+        for msg in msgs {
+            self.check_ciphers(&msg.a)?;
+            self.check_ciphers(&msg.b)?;
+        }
+
+        let mask_bound = mask_bound(&self.modulus, self.pk.l);
+        let mut ab = Vec::with_capacity(self.a.len());
+        let mut masks = Vec::with_capacity(self.a.len());
+        for (k, b_i) in self.b.iter().enumerate() {
+            let mut enc_a = msgs[0].a[k].clone();
+            for msg in &msgs[1..] {
+                self.pk.add_encrypted(&mut enc_a, &msg.a[k]);
+            }
+            self.pk.mul_plain(&mut enc_a, &Plaintext::from(b_i));
+            self.pk.reencrypt(&mut enc_a, rand);
+            ab.push(enc_a);
+
+            let f = Integer::from(mask_bound.random_below_ref(rand));
+            masks.push(self.pk.encrypt(Plaintext::from(&f), rand));
+            self.masks.push(f);
+        }
+        self.parties = parties;
+        Ok(Round2Message {
+            id: self.key_share.i,
+            ab,
+            masks,
+        })
+    }
+
+    /// Processes the second messages of all parties and returns the partial
+    /// decryptions of the masked products.
+    pub fn round3(&mut self, msgs: &[Round2Message]) -> Result<Round3Message> {
+        let mut ids: Vec<_> = msgs.iter().map(|m| m.id).collect();
+        ids.sort_unstable();
+        ensure!(ids == self.parties, "round 2 parties differ from round 1");
+        for msg in msgs {
+            self.check_ciphers(&msg.ab)?;
+            self.check_ciphers(&msg.masks)?;
+        }
+        self.masked = (0..self.a.len())
+            .map(|k| {
+                let mut acc = msgs[0].ab[k].clone();
+                for msg in &msgs[1..] {
+                    self.pk.add_encrypted(&mut acc, &msg.ab[k]);
+                }
+                for msg in msgs {
+                    self.pk.add_encrypted(&mut acc, &msg.masks[k]);
+                }
+                acc
+            })
+            .collect();
+        let partials = self
+            .masked
+            .iter()
+            .map(|c| self.key_share.share_decrypt(self.pk, c.clone()))
+            .collect();
+        Ok(Round3Message { partials })
+    }
+
+    /// Combines at least w partial decryptions into this party's triple shares.
+    pub fn finish(self, msgs: &[Round3Message]) -> Result<Vec<TripleShare>> {
+        ensure!(
+            msgs.iter().all(|m| m.partials.len() == self.a.len()),
+            "wrong number of partial decryptions"
+        );
+        let bound = max_decryption(&self.modulus, self.pk.l);
+        let leader = self.parties.first().copied() == Some(self.key_share.i);
+        (0..self.a.len())
+            .map(|k| {
+                let partials: Vec<_> = msgs.iter().map(|m| m.partials[k].clone()).collect();
+                let d: Integer = self.pk.share_combine(&partials)?.into();
+                ensure!(d < bound, "masked product is out of range");
+                let mut c = Integer::from(-&self.masks[k]);
+                if leader {
+                    c += d;
+                }
+                c %= &self.modulus;
+                if c < 0 {
+                    c += &self.modulus;
+                }
+                Ok(TripleShare {
+                    a: self.a[k].clone(),
+                    b: self.b[k].clone(),
+                    c,
+                })
+            })
+            .collect()
+    }
+
+    fn check_ciphers(&self, ciphers: &[Ciphertext]) -> Result<()> {
+        ensure!(ciphers.len() == self.a.len(), "wrong number of ciphertexts");
+        ensure!(
+            ciphers
+                .iter()
+                .all(|c| in_mult_group(c.as_ref(), &self.pk.n, &self.pk.n2)),
+            "ciphertext is not in Z*_n^2"
+        );
+        Ok(())
+    }
+}
+
+/// Exclusive upper bound of a single mask f_i: 2^κ * l^2 * M^2
+fn mask_bound(modulus: &Integer, parties: u32) -> Integer {
+    (Integer::from(modulus.square_ref()) * parties * parties) << STATISTICAL_SECURITY
+}
+
+/// Exclusive upper bound of ab + sum f_i: l^2 * M^2 + l * mask_bound
+fn max_decryption(modulus: &Integer, parties: u32) -> Integer {
+    let product = Integer::from(modulus.square_ref()) * parties * parties;
+    product + mask_bound(modulus, parties) * parties
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TripleGenerator;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_triples() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 3, 3).unwrap();
+        let shares = sk.share(&[0, 1, 2], &mut rand);
+        let modulus = Integer::from(65521);
+
+        let (mut generators, msgs1): (Vec<_>, Vec<_>) = shares
+            .iter()
+            .map(|s| TripleGenerator::new(&pk, s, modulus.clone(), 4, &mut rand).unwrap())
+            .unzip();
+        let msgs2: Vec<_> = generators
+            .iter_mut()
+            .map(|g| g.round2(&msgs1, &mut rand).unwrap())
+            .collect();
+        let msgs3: Vec<_> = generators
+            .iter_mut()
+            .map(|g| g.round3(&msgs2).unwrap())
+            .collect();
+        let triples: Vec<_> = generators
+            .into_iter()
+            .map(|g| g.finish(&msgs3).unwrap())
+            .collect();
+
+        for k in 0..4 {
+            let sum = |f: fn(&super::TripleShare) -> &Integer| -> Integer {
+                triples.iter().map(|t| f(&t[k]).clone()).sum::<Integer>() % &modulus
+            };
+            let a = sum(|t| &t.a);
+            let b = sum(|t| &t.b);
+            let c = sum(|t| &t.c);
+            assert_eq!(c, (a * b) % &modulus);
+        }
+    }
+}
@@ -1,11 +1,27 @@
+use crate::keygen::KeygenEvent;
 use anyhow::Result;
 
+#[cfg(feature = "openssl")]
 use openssl::bn::BigNum;
-use rug::integer::Order;
+use rug::integer::{IsPrime, Order};
 use rug::rand::MutRandState;
-use rug::Complete;
 use rug::Integer;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::OnceLock;
 
+/// `len` uniformly random bytes
+pub(crate) fn random_bytes(len: usize, rand: &mut dyn MutRandState) -> Vec<u8> {
+    let x = Integer::from(Integer::random_bits(len as u32 * 8, rand));
+    let digits = x.to_digits::<u8>(Order::MsfBe);
+    let mut bytes = vec![0; len - digits.len()];
+    bytes.extend_from_slice(&digits);
+    bytes
+}
+
+/// Default Miller-Rabin rounds of the prime tests, for an error probability below 2^-80
+pub(crate) const MILLER_RABIN_ROUNDS: u32 = 40;
+
+#[cfg(feature = "openssl")]
 pub(crate) fn generate_safe_prime(bits: usize) -> Result<(Integer, Integer)> {
     let mut sp = BigNum::new()?;
     sp.generate_prime(bits as i32, true, None, None)?;
@@ -15,15 +31,335 @@ pub(crate) fn generate_safe_prime(bits: usize) -> Result<(Integer, Integer)> {
     Ok((p, p1))
 }
 
-/// Generate a random value that is in Z_(op)^*. This simply random chooses
-/// values until we get one with gcd(rop, op) of 1. If one has knowledge about
-/// the value of rop, then calling this function may not be neccessary. i.e.
-/// if rop is prime, we can just call urandomm directly.
-pub(crate) fn random_in_mult_group(op: &Integer, rand: &mut dyn MutRandState) -> Integer {
+#[cfg(feature = "openssl")]
+pub(crate) fn generate_prime(bits: usize) -> Result<Integer> {
+    let mut p = BigNum::new()?;
+    p.generate_prime(bits as i32, false, None, None)?;
+    Ok(Integer::from_digits(&p.to_vec(), Order::MsfBe))
+}
+
+/// Generates a safe prime with candidates drawn from the operating system's random
+/// number generator, see [`PrimeSearch`].
+#[cfg(not(feature = "openssl"))]
+pub(crate) fn generate_safe_prime(bits: usize) -> Result<(Integer, Integer)> {
+    Ok(PrimeSearch::safe(bits as u32).find_parallel())
+}
+
+#[cfg(not(feature = "openssl"))]
+pub(crate) fn generate_prime(bits: usize) -> Result<Integer> {
+    Ok(PrimeSearch::standard(bits as u32).find_parallel().0)
+}
+
+pub(crate) fn os_random_bits(bits: u32) -> Integer {
+    use ::rand::RngCore;
+
+    let mut bytes = vec![0; bits.div_ceil(8) as usize];
+    ::rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let mut x = Integer::from_digits(&bytes, Order::Msf);
+    x.keep_bits_mut(bits);
+    x
+}
+
+/// Odd primes below 2^11 used to sieve prime candidates
+fn small_primes() -> &'static [u32] {
+    static PRIMES: OnceLock<Vec<u32>> = OnceLock::new();
+    PRIMES.get_or_init(|| {
+        const BOUND: usize = 1 << 11;
+        let mut composite = vec![false; BOUND];
+        let mut primes = Vec::new();
+        for i in (3..BOUND).step_by(2) {
+            if !composite[i] {
+                primes.push(i as u32);
+                (i * i..BOUND)
+                    .step_by(2 * i)
+                    .for_each(|j| composite[j] = true);
+            }
+        }
+        primes
+    })
+}
+
+/// Search for a prime p of exactly `bits` bits from random candidates. Returns p
+/// together with (p - 1) / 2, which is prime as well for safe primes.
+#[derive(Debug, Clone)]
+pub(crate) struct PrimeSearch {
+    pub(crate) bits: u32,
+    /// Whether p must be a safe prime p = 2 * p1 + 1 with p1 prime
+    pub(crate) safe: bool,
+    pub(crate) rounds: u32,
+    /// Whether p must be 3 mod 4, which always holds for safe primes
+    pub(crate) three_mod_four: bool,
+    /// Whether the two most significant bits of p must be set, so the product of two
+    /// such primes has exactly the sum of their bit lengths
+    pub(crate) top_two_bits: bool,
+}
+
+impl PrimeSearch {
+    pub(crate) fn safe(bits: u32) -> Self {
+        Self {
+            bits,
+            safe: true,
+            rounds: MILLER_RABIN_ROUNDS,
+            three_mod_four: false,
+            top_two_bits: true,
+        }
+    }
+
+    pub(crate) fn standard(bits: u32) -> Self {
+        Self {
+            safe: false,
+            ..Self::safe(bits)
+        }
+    }
+
+    /// Number of random bits `candidate` needs to return for [`PrimeSearch::test`]
+    pub(crate) fn candidate_bits(&self) -> u32 {
+        if self.safe {
+            self.bits - 1
+        } else {
+            self.bits
+        }
+    }
+
+    /// Turns the random value `candidate` into a candidate of the right form and
+    /// returns (p, (p - 1) / 2) if it is an acceptable prime.
+    ///
+    /// Candidates are first sieved: for safe primes, p1 or p = 2 * p1 + 1 is
+    /// divisible by an odd prime s iff p1 mod s is 0 or (s - 1) / 2. Only the
+    /// survivors are tested with Miller-Rabin, first with a single round each, then
+    /// with the full number of rounds.
+    pub(crate) fn test(&self, mut candidate: Integer) -> Option<(Integer, Integer)> {
+        let bits = self.candidate_bits();
+        candidate.set_bit(bits - 1, true);
+        if self.top_two_bits {
+            candidate.set_bit(bits - 2, true);
+        }
+        candidate.set_bit(0, true);
+        if self.three_mod_four && !self.safe {
+            candidate.set_bit(1, true);
+        }
+        // small candidates may be one of the sieving primes themselves
+        let sieve = bits > 12;
+        let is_prime = |x: &Integer, reps| x.is_probably_prime(reps) != IsPrime::No;
+        if self.safe {
+            let p1 = candidate;
+            if sieve
+                && small_primes().iter().any(|&s| {
+                    let r = p1.mod_u(s);
+                    r == 0 || r == (s - 1) / 2
+                })
+            {
+                return None;
+            }
+            let p: Integer = Integer::from(&p1 << 1) + 1;
+            let found = is_prime(&p1, 1)
+                && is_prime(&p, 1)
+                && is_prime(&p1, self.rounds)
+                && is_prime(&p, self.rounds);
+            found.then_some((p, p1))
+        } else {
+            let p = candidate;
+            if sieve && small_primes().iter().any(|&s| p.mod_u(s) == 0) {
+                return None;
+            }
+            if !is_prime(&p, self.rounds) {
+                return None;
+            }
+            let p1 = Integer::from(&p >> 1);
+            Some((p, p1))
+        }
+    }
+
+    /// Tests candidates returned by `candidate` until one is accepted
+    #[cfg(feature = "test_vectors")]
+    pub(crate) fn find(&self, mut candidate: impl FnMut() -> Integer) -> (Integer, Integer) {
+        loop {
+            if let Some(found) = self.test(candidate()) {
+                break found;
+            }
+        }
+    }
+
+    /// Like [`PrimeSearch::find_parallel_until`] without cancellation
+    #[cfg(not(feature = "openssl"))]
+    pub(crate) fn find_parallel(&self) -> (Integer, Integer) {
+        self.find_parallel_until(&SearchControl::default(), 0)
+            .expect("search is never cancelled")
+    }
+
+    /// Tests candidates from the operating system's random number generator on all
+    /// threads of the rayon pool (sequentially without the `parallel` feature) and
+    /// returns the first acceptable one, or `None` once the search is cancelled.
+    /// `prime` is the index of the searched prime in the progress events.
+    pub(crate) fn find_parallel_until(
+        &self,
+        control: &SearchControl<'_>,
+        prime: usize,
+    ) -> Option<(Integer, Integer)> {
+        let bits = self.candidate_bits();
+        let tested = AtomicU64::new(0);
+        let test_candidate = |()| {
+            if control.is_cancelled() {
+                return Some(None);
+            }
+            let candidates = tested.fetch_add(1, Ordering::Relaxed) + 1;
+            if candidates.is_multiple_of(PROGRESS_INTERVAL) {
+                control.report(KeygenEvent::CandidatesTested { prime, candidates });
+            }
+            self.test(os_random_bits(bits)).map(Some)
+        };
+        #[cfg(feature = "parallel")]
+        let found = {
+            use rayon::iter::ParallelIterator;
+            rayon::iter::repeat(()).find_map_any(test_candidate)
+        };
+        #[cfg(not(feature = "parallel"))]
+        let found = std::iter::repeat(()).find_map(test_candidate);
+        let found = found.expect("infinite iterator")?;
+        control.report(KeygenEvent::PrimeFound {
+            prime,
+            candidates: tested.load(Ordering::Relaxed),
+        });
+        Some(found)
+    }
+
+    /// Finds two distinct primes p and q in parallel whose product has exactly
+    /// `modulus_bits` bits and returns (p, p1, q, q1), or `None` once the search is
+    /// cancelled. Instead of `self.bits`, p has ⌈modulus_bits / 2⌉ and q
+    /// ⌊modulus_bits / 2⌋ bits.
+    pub(crate) fn find_modulus_primes(
+        &self,
+        modulus_bits: u32,
+        control: &SearchControl<'_>,
+    ) -> Option<(Integer, Integer, Integer, Integer)> {
+        let p_search = PrimeSearch {
+            bits: modulus_bits - modulus_bits / 2,
+            top_two_bits: true,
+            ..self.clone()
+        };
+        let q_search = PrimeSearch {
+            bits: modulus_bits / 2,
+            ..p_search.clone()
+        };
+        let (p, p1) = p_search.find_parallel_until(control, 0)?;
+        loop {
+            let (q, q1) = q_search.find_parallel_until(control, 1)?;
+            if p != q {
+                break Some((p, p1, q, q1));
+            }
+        }
+    }
+}
+
+/// Number of tested candidates between two [`KeygenEvent::CandidatesTested`] events
+const PROGRESS_INTERVAL: u64 = 1000;
+
+/// Cancellation and progress reporting of the parallel [`PrimeSearch`]
+#[derive(Default)]
+pub(crate) struct SearchControl<'a> {
+    pub(crate) cancelled: Option<&'a AtomicBool>,
+    pub(crate) progress: Option<&'a (dyn Fn(KeygenEvent) + Sync)>,
+}
+
+impl SearchControl<'_> {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled
+            .is_some_and(|cancelled| cancelled.load(Ordering::Relaxed))
+    }
+
+    fn report(&self, event: KeygenEvent) {
+        if let Some(progress) = self.progress {
+            progress(event);
+        }
+    }
+}
+
+/// Generates a safe prime p = 2 * p1 + 1 of exactly `bits` bits with candidates drawn
+/// from `rand` and returns (p, p1). Unlike [`generate_safe_prime`] the result is fully
+/// determined by the state of `rand`.
+#[cfg(feature = "test_vectors")]
+pub(crate) fn generate_safe_prime_with(
+    bits: usize,
+    rand: &mut dyn MutRandState,
+) -> (Integer, Integer) {
+    let bits = bits as u32;
+    // without forcing the top bits to keep the known-answer tests stable
+    let search = PrimeSearch {
+        top_two_bits: false,
+        ..PrimeSearch::safe(bits)
+    };
+    search.find(|| Integer::from(Integer::random_bits(bits - 1, rand)))
+}
+
+/// Generates two distinct safe primes p and q whose product has exactly `modulus_bits`
+/// bits and returns (p, (p - 1) / 2, q, (q - 1) / 2). The candidates are tested on all
+/// threads of the rayon pool.
+pub(crate) fn generate_modulus_safe_primes(
+    modulus_bits: usize,
+) -> Result<(Integer, Integer, Integer, Integer)> {
+    Ok(PrimeSearch::safe(0)
+        .find_modulus_primes(modulus_bits as u32, &SearchControl::default())
+        .expect("search is never cancelled"))
+}
+
+/// How [`random_in_mult_group`] ensures that its result is coprime to the modulus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UnitCheck {
+    /// No check, for prime moduli and moduli whose prime factors are so large that a
+    /// value of [1, op) is a unit except with negligible probability. Finding a value
+    /// which is not would factor the modulus, see
+    /// <https://crypto.stackexchange.com/q/62371>
+    Skip,
+    /// Rejects values sharing a factor with the modulus
+    Gcd,
+}
+
+/// Generates a uniformly random value of [1, op), which is in Z*_op if `check` is
+/// [`UnitCheck::Gcd`].
+///
+/// The gcd is not computed on the candidate r but on r * b mod op for another random
+/// b. It is 1 iff both are units, and r * b is then uniform and independent of r, so
+/// the variable time gcd reveals nothing about the result. Rejected candidates are
+/// discarded, thus the number of iterations is independent of the result as well.
+pub(crate) fn random_in_mult_group(
+    op: &Integer,
+    check: UnitCheck,
+    rand: &mut dyn MutRandState,
+) -> Integer {
+    let below = Integer::from(op - 1);
     loop {
-        let res = Integer::from(op.random_below_ref(rand));
-        if res.gcd_ref(&op).complete() == 1 {
-            break res;
+        let r = Integer::from(below.random_below_ref(rand)) + 1;
+        if check == UnitCheck::Skip {
+            return r;
+        }
+        let b = Integer::from(below.random_below_ref(rand)) + 1;
+        let blinded = Integer::from(&r * &b) % op;
+        if blinded.gcd(op) == 1 {
+            return r;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{random_in_mult_group, UnitCheck};
+    use rug::rand::RandState;
+    use rug::{Complete, Integer};
+
+    #[test]
+    fn test_random_in_mult_group() {
+        let mut rand = RandState::new();
+        let m = Integer::from(15);
+        let mut seen = [false; 15];
+        for _ in 0..500 {
+            let r = random_in_mult_group(&m, UnitCheck::Gcd, &mut rand);
+            assert!(r > 0 && r < m);
+            assert_eq!(r.gcd_ref(&m).complete(), 1);
+            seen[r.to_usize().unwrap()] = true;
+            let r = random_in_mult_group(&m, UnitCheck::Skip, &mut rand);
+            assert!(r > 0 && r < m);
         }
+        // all 8 units are drawn
+        assert_eq!(seen.iter().filter(|s| **s).count(), 8);
     }
 }
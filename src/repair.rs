@@ -0,0 +1,285 @@
+//! Repair of lost key shares without reconstructing the key.
+//!
+//! If a server loses its share f(t), a quorum of at least w surviving servers, the
+//! helpers, can regenerate it: f(t) = sum_j mu_j * f(j) / scale for the integer
+//! interpolation coefficients mu_j at t. No helper reveals its own term:
+//!
+//! 1. every helper splits mu_j * f(j) into random additive pieces, one for every
+//!    helper, with [`PrivateKeyShare::repair_pieces`],
+//! 2. every helper adds up the pieces it received with
+//!    [`PrivateKeyShare::repair_sum`] and sends the sum to the target,
+//! 3. the target adds up the sums and divides by the scale with
+//!    [`PrivateKeyShare::repair`].
+//!
+//! The pieces travel between the servers and must be sent over authenticated and
+//! encrypted channels.
+//!
+//! Shares dealt over the integers with [`PrivateKey::share_statistical`] or
+//! [`Polynomial::new_statistical`] can always be repaired. Shares reduced mod n * m,
+//! like those of [`PrivateKey::share`], can only be repaired if the sum happens to be
+//! divisible by the scale, which always holds if it is 1, e.g. for two helpers at
+//! the points 1 and 2 repairing the share at 3.
+//!
+//! [`PrivateKey::share_statistical`]: crate::paillier::PrivateKey::share_statistical
+//! [`PrivateKey::share`]: crate::paillier::PrivateKey::share
+//! [`Polynomial::new_statistical`]: crate::paillier::Polynomial::new_statistical
+//!
+//! ```
+//! use pht_crypto::paillier::{generate_key_pair, Polynomial, PrivateKeyShare};
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 4, 2).unwrap();
+//! let poly = Polynomial::new_statistical(&sk, 80, &mut rand);
+//! let shares: Vec<_> = (0..4).map(|i| poly.compute(i)).collect();
+//!
+//! // the server at point 2 lost its share, the servers at 1 and 4 help
+//! let (target, helpers) = (2, [1, 4]);
+//! let pieces: Vec<_> = [&shares[0], &shares[3]]
+//!     .iter()
+//!     .map(|share| share.repair_pieces(&pk, &helpers, target, 80, &mut rand).unwrap())
+//!     .collect();
+//! let sums: Vec<_> = [&shares[0], &shares[3]]
+//!     .iter()
+//!     .map(|share| {
+//!         let received: Vec<_> = pieces.iter().flatten().cloned().collect();
+//!         share.repair_sum(&helpers, target, &received).unwrap()
+//!     })
+//!     .collect();
+//! let repaired = PrivateKeyShare::repair(&pk, &helpers, target, &sums).unwrap();
+//! ```
+
+use crate::paillier::{PrivateKeyShare, PublicKey};
+use crate::util;
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// An additive piece of one helper's term, sent to another helper
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairPiece {
+    /// Evaluation point of the sending helper
+    from: u32,
+    /// Evaluation point of the receiving helper
+    to: u32,
+    target: u32,
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+}
+
+/// The sum of the pieces a helper received, sent to the target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepairSum {
+    from: u32,
+    target: u32,
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+}
+
+impl RepairPiece {
+    /// Evaluation point of the helper this piece must be sent to
+    pub fn to(&self) -> u32 {
+        self.to
+    }
+}
+
+fn check_helpers(pk: &PublicKey, helpers: &[u32], target: u32) -> Result<()> {
+    util::check_evaluation_points(helpers)?;
+    ensure!(target != 0, "evaluation point 0 is not a valid share");
+    ensure!(
+        !helpers.contains(&target),
+        "the target can't help repairing its share"
+    );
+    ensure!(
+        helpers.len() >= pk.w as usize,
+        "at least {} helpers are needed",
+        pk.w
+    );
+    Ok(())
+}
+
+impl PrivateKeyShare {
+    /// Splits this helper's term mu_j * f(j) of the share at `target` into one random
+    /// piece for every helper in `helpers`, including itself. The pieces are masked
+    /// with `security_bits` bits of statistical security.
+    pub fn repair_pieces(
+        &self,
+        pk: &PublicKey,
+        helpers: &[u32],
+        target: u32,
+        security_bits: u32,
+        rand: &mut dyn MutRandState,
+    ) -> Result<Vec<RepairPiece>> {
+        check_helpers(pk, helpers, target)?;
+        let pos = helpers
+            .iter()
+            .position(|id| *id == self.i)
+            .ok_or_else(|| anyhow!("share {} is not one of the helpers", self.i))?;
+        let (coefficients, _) = util::interpolation_coefficients(helpers, target);
+        let term = Integer::from(&coefficients[pos] * &self.si);
+        // masks uniform in [-2^bits, 2^bits)
+        let bits = term.significant_bits() + security_bits;
+        let offset = Integer::from(1) << bits;
+        let mut rest = term;
+        let mut pieces: Vec<RepairPiece> = helpers[1..]
+            .iter()
+            .map(|to| {
+                let mask = Integer::from(Integer::random_bits(bits + 1, rand)) - &offset;
+                rest -= &mask;
+                RepairPiece {
+                    from: self.i,
+                    to: *to,
+                    target,
+                    val: mask,
+                }
+            })
+            .collect();
+        pieces.insert(
+            0,
+            RepairPiece {
+                from: self.i,
+                to: helpers[0],
+                target,
+                val: rest,
+            },
+        );
+        Ok(pieces)
+    }
+
+    /// Adds up the pieces addressed to this helper, exactly one from every helper
+    pub fn repair_sum(
+        &self,
+        helpers: &[u32],
+        target: u32,
+        pieces: &[RepairPiece],
+    ) -> Result<RepairSum> {
+        let mut received = vec![false; helpers.len()];
+        let mut val = Integer::new();
+        for piece in pieces.iter().filter(|piece| piece.to == self.i) {
+            ensure!(piece.target == target, "piece for the wrong target");
+            let pos = helpers
+                .iter()
+                .position(|id| *id == piece.from)
+                .ok_or_else(|| anyhow!("piece of unknown helper {}", piece.from))?;
+            ensure!(!received[pos], "two pieces of helper {}", piece.from);
+            received[pos] = true;
+            val += &piece.val;
+        }
+        ensure!(
+            received.iter().all(|received| *received),
+            "missing pieces of some helpers"
+        );
+        Ok(RepairSum {
+            from: self.i,
+            target,
+            val,
+        })
+    }
+
+    /// Recovers the share at `target` from the sums of all `helpers`
+    pub fn repair(
+        pk: &PublicKey,
+        helpers: &[u32],
+        target: u32,
+        sums: &[RepairSum],
+    ) -> Result<PrivateKeyShare> {
+        check_helpers(pk, helpers, target)?;
+        ensure!(
+            sums.len() == helpers.len(),
+            "expected {} sums",
+            helpers.len()
+        );
+        let mut received = vec![false; helpers.len()];
+        let mut total = Integer::new();
+        for sum in sums {
+            ensure!(sum.target == target, "sum for the wrong target");
+            let pos = helpers
+                .iter()
+                .position(|id| *id == sum.from)
+                .ok_or_else(|| anyhow!("sum of unknown helper {}", sum.from))?;
+            ensure!(!received[pos], "two sums of helper {}", sum.from);
+            received[pos] = true;
+            total += &sum.val;
+        }
+        let (_, scale) = util::interpolation_coefficients(helpers, target);
+        ensure!(
+            total.is_divisible(&scale),
+            "share can't be repaired from shares reduced mod n * m by these helpers"
+        );
+        Ok(PrivateKeyShare::at_point(total.div_exact(&scale), target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RepairPiece, RepairSum};
+    use crate::paillier::{
+        generate_key_pair, Polynomial, PrivateKeyShare, PublicKey, DEFAULT_STATISTICAL_SECURITY,
+    };
+    use anyhow::Result;
+    use rug::rand::RandState;
+
+    fn run_repair(
+        pk: &PublicKey,
+        shares: &[PrivateKeyShare],
+        helpers: &[u32],
+        target: u32,
+    ) -> Result<PrivateKeyShare> {
+        let mut rand = RandState::new();
+        let helper_shares: Vec<_> = shares.iter().filter(|s| helpers.contains(&s.i)).collect();
+        let pieces: Vec<RepairPiece> = helper_shares
+            .iter()
+            .map(|share| {
+                share.repair_pieces(pk, helpers, target, DEFAULT_STATISTICAL_SECURITY, &mut rand)
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+        let sums: Vec<RepairSum> = helper_shares
+            .iter()
+            .map(|share| share.repair_sum(helpers, target, &pieces))
+            .collect::<Result<_>>()?;
+        PrivateKeyShare::repair(pk, helpers, target, &sums)
+    }
+
+    #[test]
+    fn test_repair_share() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 5, 3).unwrap();
+        let poly = Polynomial::new_statistical(&sk, DEFAULT_STATISTICAL_SECURITY, &mut rand);
+        let shares: Vec<_> = (0..5).map(|i| poly.compute(i)).collect();
+
+        let repaired = run_repair(&pk, &shares, &[1, 2, 5], 4).unwrap();
+        assert_eq!(repaired.si, shares[3].si);
+        let repaired = run_repair(&pk, &shares, &[5, 3, 1, 2], 4).unwrap();
+        assert_eq!(repaired.si, shares[3].si);
+
+        let c = pk.encrypt(10.into(), &mut rand);
+        let partials: Vec<_> = [&repaired, &shares[0], &shares[4]]
+            .iter()
+            .map(|share| share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&partials).unwrap(), 10);
+
+        assert!(run_repair(&pk, &shares, &[1, 2], 4).is_err());
+        assert!(run_repair(&pk, &shares, &[1, 2, 4], 4).is_err());
+    }
+
+    #[test]
+    fn test_repair_reduced_share() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 3, 2).unwrap();
+        let poly = Polynomial::new(&sk, &mut rand);
+        let shares: Vec<_> = (0..3).map(|i| poly.compute(i)).collect();
+        // integer interpolation coefficients, no division needed
+        let repaired = run_repair(&pk, &shares, &[1, 2], 3).unwrap();
+        let c = pk.encrypt(10.into(), &mut rand);
+        let partials: Vec<_> = [&repaired, &shares[0]]
+            .iter()
+            .map(|share| share.share_decrypt(&pk, c.clone()))
+            .collect();
+        assert_eq!(pk.share_combine(&partials).unwrap(), 10);
+    }
+}
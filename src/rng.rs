@@ -0,0 +1,161 @@
+//! Random number generators of the `rand` ecosystem as GMP random states.
+//!
+//! Note that `RandState::new()` is a Mersenne Twister with a fixed default seed and
+//! thus not suitable for encryption or key shares. Use [`secure`] instead, which is
+//! seeded from the operating system, or the `*_default` variants like
+//! [`PublicKey::encrypt_default`] which use it internally. For reproducible tests,
+//! [`insecure_seeded`] creates a deterministic state from a seed.
+//!
+//! All randomized operations of the crate take a `&mut dyn MutRandState`. [`rand_state`]
+//! turns any cryptographically secure [`RngCore`] which is `Send + Sync`, like
+//! [`rand::rngs::OsRng`] or [`rand::rngs::StdRng`], into a [`RandState`] that can be
+//! moved to other threads, e.g. to create one state per rayon or tokio task.
+//! Generators bound to a thread like [`rand::rngs::ThreadRng`] become a
+//! [`ThreadRandState`] with [`thread_rand_state`], or are used directly with
+//! [`PublicKey::encrypt_with_rng`].
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::rng;
+//! use rand::rngs::OsRng;
+//!
+//! let (pk, _sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let ciphers: Vec<_> = std::thread::scope(|s| {
+//!     let handles: Vec<_> = (0..4)
+//!         .map(|m| {
+//!             let pk = &pk;
+//!             s.spawn(move || pk.encrypt(m.into(), &mut rng::rand_state(OsRng)))
+//!         })
+//!         .collect();
+//!     handles.into_iter().map(|h| h.join().unwrap()).collect()
+//! });
+//! let c = pk.encrypt_with_rng(42.into(), &mut rand::thread_rng());
+//! ```
+
+use crate::paillier::PublicKey;
+use crate::{Ciphertext, Plaintext};
+use rand::rngs::StdRng;
+use rand::{CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rug::rand::{RandGen, RandState, ThreadRandGen, ThreadRandState};
+
+/// Wraps a cryptographically secure [`RngCore`] as a GMP random generator.
+///
+/// Seeding the resulting state with `RandState::seed` has no effect, the wrapped
+/// generator keeps drawing from its own source.
+#[derive(Debug, Clone)]
+pub struct RngAdapter<R>(pub R);
+
+impl<R: RngCore + CryptoRng + Send + Sync> RandGen for RngAdapter<R> {
+    fn r#gen(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+}
+
+impl<R: RngCore + CryptoRng> ThreadRandGen for RngAdapter<R> {
+    fn r#gen(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+}
+
+/// A random state drawing from `rng`, which can be sent to other threads
+pub fn rand_state<R>(rng: R) -> RandState<'static>
+where
+    R: RngCore + CryptoRng + Send + Sync + 'static,
+{
+    RandState::new_custom_boxed(Box::new(RngAdapter(rng)))
+}
+
+/// A random state drawing from `rng`, which must stay on the current thread
+pub fn thread_rand_state<R>(rng: R) -> ThreadRandState<'static>
+where
+    R: RngCore + CryptoRng + 'static,
+{
+    ThreadRandState::new_custom_boxed(Box::new(RngAdapter(rng)))
+}
+
+/// A cryptographically secure random state seeded from the operating system
+pub fn secure() -> RandState<'static> {
+    rand_state(StdRng::from_entropy())
+}
+
+/// A deterministic random state derived from `seed` for reproducible tests.
+///
+/// **Insecure**: everyone knowing the seed can predict all values drawn from it. The
+/// sequence is stable across versions of this crate.
+pub fn insecure_seeded(seed: u64) -> RandState<'static> {
+    rand_state(ChaCha20Rng::seed_from_u64(seed))
+}
+
+impl PublicKey {
+    /// Like [`PublicKey::encrypt`] with randomness from [`secure`]
+    pub fn encrypt_default(&self, m: Plaintext) -> Ciphertext {
+        self.encrypt(m, &mut secure())
+    }
+
+    /// Like [`PublicKey::encrypt`] but draws the randomness from `rng`
+    pub fn encrypt_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        m: Plaintext,
+        rng: &mut R,
+    ) -> Ciphertext {
+        let mut adapter = RngAdapter(rng);
+        let mut rand = ThreadRandState::new_custom(&mut adapter);
+        self.encrypt(m, &mut rand)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insecure_seeded, rand_state, secure, thread_rand_state};
+    use crate::paillier::generate_key_pair;
+    use rand::rngs::{OsRng, StdRng};
+    use rand::SeedableRng;
+    use rug::Integer;
+
+    #[test]
+    fn test_rng_adapters() {
+        let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let ciphers: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|m| {
+                    let mut rand = rand_state(OsRng);
+                    let pk = &pk;
+                    s.spawn(move || pk.encrypt(m.into(), &mut rand))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for (m, c) in ciphers.iter().enumerate() {
+            assert_eq!(Integer::from(sk.decrypt(c)), m);
+        }
+
+        let c = pk.encrypt(5.into(), &mut thread_rand_state(rand::thread_rng()));
+        assert_eq!(Integer::from(sk.decrypt(&c)), 5);
+        let c1 = pk.encrypt_with_rng(7.into(), &mut StdRng::seed_from_u64(1));
+        let c2 = pk.encrypt_with_rng(7.into(), &mut StdRng::seed_from_u64(1));
+        assert_eq!(c1.as_ref(), c2.as_ref());
+        assert_eq!(Integer::from(sk.decrypt(&c1)), 7);
+    }
+
+    #[test]
+    fn test_default_rngs() {
+        let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let c1 = pk.encrypt_default(9.into());
+        let c2 = pk.encrypt_default(9.into());
+        assert_ne!(c1.as_ref(), c2.as_ref());
+        assert_eq!(Integer::from(sk.decrypt(&c1)), 9);
+
+        let x = Integer::from(Integer::random_bits(64, &mut secure()));
+        let y = Integer::from(Integer::random_bits(64, &mut secure()));
+        assert_ne!(x, y);
+
+        // fixed output of the ChaCha20 stream
+        let z = Integer::from(Integer::random_bits(64, &mut insecure_seeded(7)));
+        assert_eq!(z, 430466185982264601u64);
+        assert_ne!(
+            z,
+            Integer::from(Integer::random_bits(64, &mut insecure_seeded(8)))
+        );
+    }
+}
@@ -0,0 +1,374 @@
+//! Key rotation: switching ciphertexts to a new key without exposing the plaintexts.
+//!
+//! The plaintexts must be in [0, 2^bits) for a declared number of bits. The old
+//! committee re-encrypts them under the new key in a [`KeyRotation`]:
+//!
+//! 1. every participating server of the old committee samples a random mask ρ_i per
+//!    ciphertext and sends Enc_old(ρ_i) and Enc_new(-ρ_i) in a [`MaskShare`],
+//! 2. once all masks were added with [`KeyRotation::add_mask`], the servers partially
+//!    decrypt the [`KeyRotation::masked_ciphertexts`] Enc_old(m + sum_i ρ_i),
+//! 3. [`KeyRotation::finish`] combines the partial decryptions and computes
+//!    Enc_new(m + sum_i ρ_i) * prod_i Enc_new(-ρ_i) = Enc_new(m).
+//!
+//! The masks have [`DEFAULT_STATISTICAL_SECURITY`] bits more than the plaintexts, so
+//! m + sum_i ρ_i reveals nothing about m unless all participants collude. The old
+//! modulus must be large enough that this sum does not wrap around. The servers are
+//! assumed to follow the protocol, a server sending masks that differ under the two
+//! keys changes the rotated plaintexts.
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::rotation::KeyRotation;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (old_pk, old_sk) = generate_key_pair(256, 2, 2).unwrap();
+//! let (new_pk, new_sk) = generate_key_pair(256, 2, 2).unwrap();
+//! let old_shares = old_sk.share(&[0, 1], &mut rand);
+//!
+//! let ciphers = vec![old_pk.encrypt(42.into(), &mut rand)];
+//! let mut rotation =
+//!     KeyRotation::new(old_pk.clone(), new_pk.clone(), ciphers, 64, vec![1, 2]).unwrap();
+//! for share in &old_shares {
+//!     let mask = share.rotation_mask(&old_pk, &new_pk, 1, 64, &mut rand);
+//!     rotation.add_mask(mask).unwrap();
+//! }
+//! for share in &old_shares {
+//!     let masked = rotation.masked_ciphertexts().unwrap();
+//!     let partials = masked.iter().map(|c| share.share_decrypt(&old_pk, c.clone())).collect();
+//!     rotation.add_partials(partials).unwrap();
+//! }
+//! let rotated = rotation.finish(&mut rand).unwrap();
+//! assert_eq!(new_sk.decrypt(&rotated[0]), 42);
+//! ```
+
+use crate::paillier::{
+    PartialDecryption, PrivateKeyShare, PublicKey, DEFAULT_STATISTICAL_SECURITY,
+};
+use crate::proofs::in_mult_group;
+use crate::{util, Ciphertext};
+use anyhow::{anyhow, bail, ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The masks of one server, encrypted under the old and the new key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaskShare {
+    /// Evaluation point of the server's share of the old key
+    id: u32,
+    /// Enc_old(ρ) for every ciphertext
+    old: Vec<Ciphertext>,
+    /// Enc_new(-ρ) for every ciphertext
+    new: Vec<Ciphertext>,
+}
+
+/// Progress of a [`KeyRotation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationPhase {
+    /// Waiting for the masks of the participants
+    Masking,
+    /// Waiting for partial decryptions of the masked ciphertexts
+    Decrypting,
+}
+
+/// Coordinator state of a key rotation
+#[derive(Debug, Clone)]
+pub struct KeyRotation {
+    old_pk: PublicKey,
+    new_pk: PublicKey,
+    ciphers: Vec<Ciphertext>,
+    /// Exclusive upper bound on the masked plaintexts
+    masked_bound: Integer,
+    participants: Vec<u32>,
+    masks: BTreeMap<u32, MaskShare>,
+    masked: Vec<Ciphertext>,
+    partials: BTreeMap<u32, Vec<PartialDecryption>>,
+}
+
+impl MaskShare {
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+}
+
+impl PrivateKeyShare {
+    /// Samples this server's masks for a rotation of `count` ciphertexts whose
+    /// plaintexts have at most `plaintext_bits` bits
+    pub fn rotation_mask(
+        &self,
+        old_pk: &PublicKey,
+        new_pk: &PublicKey,
+        count: usize,
+        plaintext_bits: u32,
+        rand: &mut dyn MutRandState,
+    ) -> MaskShare {
+        let (old, new) = (0..count)
+            .map(|_| {
+                let rho = Integer::from(Integer::random_bits(
+                    plaintext_bits + DEFAULT_STATISTICAL_SECURITY,
+                    rand,
+                ));
+                let neg_rho = Integer::from(&new_pk.n - &rho).modulo(&new_pk.n);
+                (
+                    old_pk.encrypt(rho.into(), rand),
+                    new_pk.encrypt(neg_rho.into(), rand),
+                )
+            })
+            .unzip();
+        MaskShare {
+            id: self.i,
+            old,
+            new,
+        }
+    }
+}
+
+impl KeyRotation {
+    /// Starts the rotation of `ciphers`, whose plaintexts have at most
+    /// `plaintext_bits` bits, with the old committee's servers at the evaluation
+    /// points `participants`. At least w of them must take part.
+    pub fn new(
+        old_pk: PublicKey,
+        new_pk: PublicKey,
+        ciphers: Vec<Ciphertext>,
+        plaintext_bits: u32,
+        participants: Vec<u32>,
+    ) -> Result<Self> {
+        util::check_evaluation_points(&participants)?;
+        ensure!(
+            participants.len() >= old_pk.w as usize,
+            "at least {} participants are needed",
+            old_pk.w
+        );
+        ensure!(
+            ciphers
+                .iter()
+                .all(|c| in_mult_group(c.as_ref(), &old_pk.n, &old_pk.n2)),
+            "ciphertext is not in Z*_n^2"
+        );
+        ensure!(
+            new_pk.n.significant_bits() > plaintext_bits,
+            "plaintexts don't fit the new modulus"
+        );
+        // every mask is below 2^(bits + security), the plaintext adds one more
+        let masked_bound = (Integer::from(participants.len()) + 1)
+            << (plaintext_bits + DEFAULT_STATISTICAL_SECURITY);
+        ensure!(
+            masked_bound <= old_pk.n,
+            "the old modulus is too small to mask {} bit plaintexts",
+            plaintext_bits
+        );
+        Ok(Self {
+            old_pk,
+            new_pk,
+            ciphers,
+            masked_bound,
+            participants,
+            masks: BTreeMap::new(),
+            masked: Vec::new(),
+            partials: BTreeMap::new(),
+        })
+    }
+
+    pub fn phase(&self) -> RotationPhase {
+        if self.masks.len() < self.participants.len() {
+            RotationPhase::Masking
+        } else {
+            RotationPhase::Decrypting
+        }
+    }
+
+    /// Participants whose masks are still missing
+    pub fn missing_masks(&self) -> Vec<u32> {
+        self.participants
+            .iter()
+            .filter(|id| !self.masks.contains_key(id))
+            .copied()
+            .collect()
+    }
+
+    /// Adds the masks of a participant. The masked ciphertexts are computed once
+    /// all participants sent theirs.
+    pub fn add_mask(&mut self, mask: MaskShare) -> Result<()> {
+        ensure!(
+            self.phase() == RotationPhase::Masking,
+            "all masks were already added"
+        );
+        ensure!(
+            self.participants.contains(&mask.id),
+            "server {} is not a participant",
+            mask.id
+        );
+        ensure!(
+            !self.masks.contains_key(&mask.id),
+            "server {} already sent its masks",
+            mask.id
+        );
+        ensure!(
+            mask.old.len() == self.ciphers.len() && mask.new.len() == self.ciphers.len(),
+            "expected {} masks",
+            self.ciphers.len()
+        );
+        let (old, new) = (&self.old_pk, &self.new_pk);
+        ensure!(
+            mask.old
+                .iter()
+                .all(|c| in_mult_group(c.as_ref(), &old.n, &old.n2))
+                && mask
+                    .new
+                    .iter()
+                    .all(|c| in_mult_group(c.as_ref(), &new.n, &new.n2)),
+            "mask is not in Z*_n^2"
+        );
+        self.masks.insert(mask.id, mask);
+        if self.phase() == RotationPhase::Decrypting {
+            self.masked = self.ciphers.clone();
+            for mask in self.masks.values() {
+                for (c, rho) in self.masked.iter_mut().zip(&mask.old) {
+                    self.old_pk.add_encrypted(c, rho);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The ciphertexts with all masks added, to be partially decrypted by the
+    /// participants
+    pub fn masked_ciphertexts(&self) -> Result<&[Ciphertext]> {
+        ensure!(
+            self.phase() == RotationPhase::Decrypting,
+            "masks of {:?} are missing",
+            self.missing_masks()
+        );
+        Ok(&self.masked)
+    }
+
+    /// Adds a participant's partial decryptions of all masked ciphertexts, in order
+    pub fn add_partials(&mut self, partials: Vec<PartialDecryption>) -> Result<()> {
+        ensure!(
+            self.phase() == RotationPhase::Decrypting,
+            "masks of {:?} are missing",
+            self.missing_masks()
+        );
+        ensure!(
+            partials.len() == self.ciphers.len(),
+            "expected {} partial decryptions",
+            self.ciphers.len()
+        );
+        let id = match partials.first() {
+            Some(partial) => partial.id,
+            None => bail!("no partial decryptions"),
+        };
+        ensure!(
+            partials.iter().all(|partial| partial.id == id),
+            "partial decryptions of different servers"
+        );
+        ensure!(
+            self.participants.contains(&id),
+            "server {} is not a participant",
+            id
+        );
+        ensure!(
+            !self.partials.contains_key(&id),
+            "server {} already sent its partial decryptions",
+            id
+        );
+        self.partials.insert(id, partials);
+        Ok(())
+    }
+
+    /// Combines the partial decryptions of at least w participants and returns the
+    /// ciphertexts under the new key, re-randomized
+    pub fn finish(self, rand: &mut dyn MutRandState) -> Result<Vec<Ciphertext>> {
+        ensure!(
+            self.partials.len() >= self.old_pk.w as usize,
+            "at least {} participants must partially decrypt",
+            self.old_pk.w
+        );
+        (0..self.ciphers.len())
+            .map(|pos| {
+                let shares: Vec<_> = self
+                    .partials
+                    .values()
+                    .map(|server| server[pos].clone())
+                    .collect();
+                let masked: Integer = self.old_pk.share_combine(&shares)?.into();
+                if masked >= self.masked_bound {
+                    return Err(anyhow!("plaintext {} exceeds the declared bits", pos));
+                }
+                let mut rotated = self.new_pk.encrypt(masked.into(), rand);
+                for mask in self.masks.values() {
+                    self.new_pk.add_encrypted(&mut rotated, &mask.new[pos]);
+                }
+                Ok(rotated)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyRotation, RotationPhase};
+    use crate::paillier::{generate_key_pair, Polynomial};
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_key_rotation() {
+        let mut rand = RandState::new();
+        let (old_pk, old_sk) = generate_key_pair(256, 3, 2).unwrap();
+        let (new_pk, new_sk) = generate_key_pair(128, 1, 1).unwrap();
+        let poly = Polynomial::new(&old_sk, &mut rand);
+        let old_shares: Vec<_> = (0..3).map(|i| poly.compute(i)).collect();
+        let plaintexts = [0u64, 1, 1 << 40, u32::MAX.into()];
+        let ciphers: Vec<_> = plaintexts
+            .iter()
+            .map(|m| old_pk.encrypt((*m).into(), &mut rand))
+            .collect();
+
+        assert!(
+            KeyRotation::new(old_pk.clone(), new_pk.clone(), ciphers.clone(), 48, vec![1]).is_err()
+        );
+        assert!(KeyRotation::new(
+            old_pk.clone(),
+            new_pk.clone(),
+            ciphers.clone(),
+            200,
+            vec![1, 3]
+        )
+        .is_err());
+        let mut rotation =
+            KeyRotation::new(old_pk.clone(), new_pk.clone(), ciphers, 48, vec![1, 3]).unwrap();
+        let masks: Vec<_> = old_shares
+            .iter()
+            .map(|share| share.rotation_mask(&old_pk, &new_pk, 4, 48, &mut rand))
+            .collect();
+        assert!(rotation.add_mask(masks[1].clone()).is_err());
+        assert!(rotation
+            .add_mask(old_shares[0].rotation_mask(&old_pk, &new_pk, 3, 48, &mut rand))
+            .is_err());
+        rotation.add_mask(masks[0].clone()).unwrap();
+        assert!(rotation.add_mask(masks[0].clone()).is_err());
+        assert!(rotation.masked_ciphertexts().is_err());
+        assert_eq!(rotation.missing_masks(), [3]);
+        rotation.add_mask(masks[2].clone()).unwrap();
+        assert_eq!(rotation.phase(), RotationPhase::Decrypting);
+
+        let masked = rotation.masked_ciphertexts().unwrap().to_vec();
+        let partials = |idx: usize| -> Vec<_> {
+            masked
+                .iter()
+                .map(|c| old_shares[idx].share_decrypt(&old_pk, c.clone()))
+                .collect()
+        };
+        assert!(rotation.add_partials(partials(1)).is_err());
+        rotation.add_partials(partials(0)).unwrap();
+        assert!(rotation.clone().finish(&mut rand).is_err());
+        rotation.add_partials(partials(2)).unwrap();
+        let rotated = rotation.finish(&mut rand).unwrap();
+        for (c, m) in rotated.iter().zip(&plaintexts) {
+            assert_eq!(new_sk.decrypt(c), *m);
+        }
+    }
+}
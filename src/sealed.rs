@@ -0,0 +1,165 @@
+//! Passphrase protected storage of private keys and key shares.
+//!
+//! The versioned encoding of the key (see [`crate::wire::Versioned`]) is encrypted with
+//! ChaCha20-Poly1305 under a key derived from the passphrase with Argon2id. The KDF
+//! parameters are stored in the [`SealedKey`], so they can be increased later without
+//! breaking existing files.
+//!
+//! ```
+//! use pht_crypto::paillier::{generate_key_pair, PrivateKeyShare};
+//! use pht_crypto::sealed::KdfParams;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (_, sk) = generate_key_pair(256, 1, 1).unwrap();
+//! let share = sk.share(&[0], &mut rand).remove(0);
+//! // cheap parameters to keep the example fast, use KdfParams::default() in production
+//! let params = KdfParams { m_cost: 1024, t_cost: 1, p_cost: 1 };
+//! let sealed = share.seal_with_params(b"correct horse", params, &mut rand).unwrap();
+//! assert!(PrivateKeyShare::unseal(&sealed, b"battery staple").is_err());
+//! let unsealed = PrivateKeyShare::unseal(&sealed, b"correct horse").unwrap();
+//! ```
+
+use crate::paillier::{PrivateKey, PrivateKeyShare};
+use crate::rand::random_bytes;
+use crate::wire::Versioned;
+use anyhow::{anyhow, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rug::rand::MutRandState;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+const SALT_BYTES: usize = 16;
+const NONCE_BYTES: usize = 12;
+const KEY_BYTES: usize = 32;
+
+/// Argon2id cost parameters
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct KdfParams {
+    /// Memory in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    /// The OWASP recommendation m = 19 MiB, t = 2, p = 1
+    fn default() -> Self {
+        Self {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+/// A passphrase encrypted key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedKey {
+    params: KdfParams,
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+fn derive_key(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: KdfParams,
+) -> Result<Zeroizing<[u8; KEY_BYTES]>> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(KEY_BYTES))
+        .map_err(|e| anyhow!("invalid KDF parameters: {}", e))?;
+    let mut key = Zeroizing::new([0; KEY_BYTES]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, key.as_mut())
+        .map_err(|e| anyhow!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+pub(crate) fn seal<T: Versioned>(
+    value: &T,
+    passphrase: &[u8],
+    params: KdfParams,
+    rand: &mut dyn MutRandState,
+) -> Result<SealedKey> {
+    let salt = random_bytes(SALT_BYTES, rand);
+    let nonce = random_bytes(NONCE_BYTES, rand);
+    let key = derive_key(passphrase, &salt, params)?;
+    let plaintext = Zeroizing::new(value.to_versioned_bytes());
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| anyhow!("encryption failed"))?;
+    Ok(SealedKey {
+        params,
+        salt,
+        nonce,
+        ciphertext,
+    })
+}
+
+pub(crate) fn unseal<T: Versioned>(sealed: &SealedKey, passphrase: &[u8]) -> Result<T> {
+    if sealed.nonce.len() != NONCE_BYTES {
+        return Err(anyhow!("invalid nonce"));
+    }
+    let key = derive_key(passphrase, &sealed.salt, sealed.params)?;
+    let plaintext = ChaCha20Poly1305::new(Key::from_slice(key.as_ref()))
+        .decrypt(
+            Nonce::from_slice(&sealed.nonce),
+            sealed.ciphertext.as_slice(),
+        )
+        .map_err(|_| anyhow!("wrong passphrase or corrupted key"))?;
+    T::from_versioned_bytes(&Zeroizing::new(plaintext))
+}
+
+macro_rules! impl_seal {
+    ($($ty:ty)+) => {
+        $(
+            impl $ty {
+                /// Encrypts the key under `passphrase` with the default KDF parameters
+                pub fn seal(&self, passphrase: &[u8], rand: &mut dyn MutRandState) -> Result<SealedKey> {
+                    seal(self, passphrase, KdfParams::default(), rand)
+                }
+
+                pub fn seal_with_params(
+                    &self,
+                    passphrase: &[u8],
+                    params: KdfParams,
+                    rand: &mut dyn MutRandState,
+                ) -> Result<SealedKey> {
+                    seal(self, passphrase, params, rand)
+                }
+
+                pub fn unseal(sealed: &SealedKey, passphrase: &[u8]) -> Result<Self> {
+                    unseal(sealed, passphrase)
+                }
+            }
+        )+
+    };
+}
+
+impl_seal!(PrivateKey PrivateKeyShare);
+
+#[cfg(test)]
+mod tests {
+    use crate::paillier::{generate_key_pair, PrivateKey, PrivateKeyShare};
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_seal_unseal() {
+        let mut rand = RandState::new();
+        let (_, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let sealed = sk.seal(b"passphrase", &mut rand).unwrap();
+        assert_eq!(PrivateKey::unseal(&sealed, b"passphrase").unwrap(), sk);
+        assert!(PrivateKey::unseal(&sealed, b"passphrase!").is_err());
+        // the type is authenticated as part of the versioned encoding
+        assert!(PrivateKeyShare::unseal(&sealed, b"passphrase").is_err());
+
+        let mut tampered = sealed;
+        tampered.salt[0] ^= 1;
+        assert!(PrivateKey::unseal(&tampered, b"passphrase").is_err());
+    }
+}
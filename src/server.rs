@@ -0,0 +1,445 @@
+//! gRPC decryption server and client (feature `server`).
+//!
+//! A [`DecryptionServer`] holds the [`PrivateKeyShare`] of one decryption server and
+//! exposes the `ThresholdDecryption` service of `proto/pht_crypto.proto` with tonic:
+//!
+//! - `SubmitCiphertext` stores a ciphertext and returns its id,
+//! - `GetPartialDecryption` partially decrypts a stored ciphertext,
+//! - `Health` reports the key parameters and the number of stored ciphertexts.
+//!
+//! [`DecryptionClient`] calls these RPCs with the crate's types. Every server is
+//! constructed with a [`DecryptionPolicy`] which decides which [`Caller`] may have
+//! which ciphertext partially decrypted, otherwise anyone who can reach the service
+//! could use it as a decryption oracle. [`CiphertextAllowList`] allows single
+//! ciphertexts for the caller presenting a bearer token. The policy only sees what
+//! the caller claims, so deploy the service behind TLS.
+//!
+//! ```no_run
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::server::{CiphertextAllowList, DecryptionClient, DecryptionServer};
+//! use rug::rand::RandState;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(512, 1, 1)?;
+//! let share = sk.share(&[0], &mut rand).remove(0);
+//! let policy = CiphertextAllowList::new();
+//! let server = DecryptionServer::new(pk.clone(), share, policy.clone());
+//! tokio::spawn(server.serve("127.0.0.1:50051".parse()?));
+//!
+//! let c = pk.encrypt(42.into(), &mut rand);
+//! policy.allow(&c, "secret token");
+//! let mut client = DecryptionClient::connect("http://127.0.0.1:50051")
+//!     .await?
+//!     .with_token("secret token")?;
+//! let id = client.submit_ciphertext(&c).await?;
+//! let partial = client.partial_decryption(id).await?;
+//! assert_eq!(pk.share_combine(&[partial])?, 42);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::proofs::in_mult_group;
+use crate::{proto, Ciphertext};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use subtle::ConstantTimeEq;
+use tokio::net::TcpListener;
+use tonic::metadata::{Ascii, MetadataMap, MetadataValue};
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::{Channel, Endpoint, Server};
+use tonic::{Request, Response, Status};
+
+include!(concat!(
+    env!("OUT_DIR"),
+    "/pht_crypto.ThresholdDecryption.rs"
+));
+
+pub use threshold_decryption_client::ThresholdDecryptionClient;
+pub use threshold_decryption_server::{ThresholdDecryption, ThresholdDecryptionServer};
+
+/// Maximum number of ciphertexts stored by default
+pub const DEFAULT_MAX_PENDING: usize = 1024;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitCiphertextRequest {
+    #[prost(message, optional, tag = "1")]
+    pub ciphertext: Option<proto::Ciphertext>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitCiphertextResponse {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetPartialDecryptionRequest {
+    #[prost(uint64, tag = "1")]
+    pub id: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetPartialDecryptionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub partial_decryption: Option<proto::PartialDecryption>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HealthRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct HealthResponse {
+    #[prost(uint32, tag = "1")]
+    pub server_index: u32,
+    #[prost(uint32, tag = "2")]
+    pub decryption_servers: u32,
+    #[prost(uint32, tag = "3")]
+    pub threshold: u32,
+    #[prost(uint64, tag = "4")]
+    pub pending: u64,
+}
+
+/// The caller of an RPC as seen by a [`DecryptionPolicy`]
+#[derive(Debug, Clone)]
+pub struct Caller {
+    /// Address of the peer, if known
+    pub remote_addr: Option<SocketAddr>,
+    /// Metadata sent with the request
+    pub metadata: MetadataMap,
+}
+
+impl Caller {
+    fn from_request<T>(request: &Request<T>) -> Self {
+        Self {
+            remote_addr: request.remote_addr(),
+            metadata: request.metadata().clone(),
+        }
+    }
+
+    /// The token of an `authorization: Bearer <token>` entry in the metadata
+    pub fn bearer_token(&self) -> Option<&str> {
+        self.metadata
+            .get("authorization")?
+            .to_str()
+            .ok()?
+            .strip_prefix("Bearer ")
+    }
+}
+
+/// Decides which callers may have which ciphertexts partially decrypted
+pub trait DecryptionPolicy: Debug + Send + Sync + 'static {
+    /// Called when `caller` submits `cipher` and again before it is partially
+    /// decrypted. An error rejects the request with `PERMISSION_DENIED`.
+    fn authorize(&self, caller: &Caller, cipher: &Ciphertext) -> Result<()>;
+}
+
+/// [`DecryptionPolicy`] which only allows the ciphertexts added with
+/// [`CiphertextAllowList::allow`], each for the caller presenting its bearer token.
+/// Clones share the list, so it can be updated while the server runs.
+#[derive(Debug, Clone, Default)]
+pub struct CiphertextAllowList {
+    allowed: Arc<Mutex<BTreeMap<[u8; 32], String>>>,
+}
+
+impl CiphertextAllowList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows the caller presenting `token` to have `cipher` partially decrypted
+    pub fn allow(&self, cipher: &Ciphertext, token: impl Into<String>) {
+        self.allowed
+            .lock()
+            .unwrap()
+            .insert(cipher.digest(), token.into());
+    }
+
+    /// Withdraws the allowance for `cipher`, e.g. once its decryption round is over
+    pub fn revoke(&self, cipher: &Ciphertext) {
+        self.allowed.lock().unwrap().remove(&cipher.digest());
+    }
+}
+
+impl DecryptionPolicy for CiphertextAllowList {
+    fn authorize(&self, caller: &Caller, cipher: &Ciphertext) -> Result<()> {
+        let token = caller
+            .bearer_token()
+            .ok_or_else(|| anyhow!("missing bearer token"))?;
+        match self.allowed.lock().unwrap().get(&cipher.digest()) {
+            Some(allowed) if bool::from(allowed.as_bytes().ct_eq(token.as_bytes())) => Ok(()),
+            _ => bail!("ciphertext is not allowed for this caller"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    next_id: u64,
+    ciphertexts: BTreeMap<u64, Ciphertext>,
+}
+
+/// The `ThresholdDecryption` service backed by one key share
+#[derive(Debug, Clone)]
+pub struct DecryptionServer {
+    pk: Arc<PublicKey>,
+    key_share: Arc<PrivateKeyShare>,
+    policy: Arc<dyn DecryptionPolicy>,
+    max_pending: usize,
+    pending: Arc<Mutex<Pending>>,
+}
+
+impl DecryptionServer {
+    /// A server which only decrypts what `policy` authorizes
+    pub fn new(pk: PublicKey, key_share: PrivateKeyShare, policy: impl DecryptionPolicy) -> Self {
+        Self {
+            pk: Arc::new(pk),
+            key_share: Arc::new(key_share),
+            policy: Arc::new(policy),
+            max_pending: DEFAULT_MAX_PENDING,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Maximum number of stored ciphertexts, further submissions are rejected.
+    /// Defaults to [`DEFAULT_MAX_PENDING`].
+    pub fn with_max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+
+    pub fn into_service(self) -> ThresholdDecryptionServer<Self> {
+        ThresholdDecryptionServer::new(self)
+    }
+
+    /// Serves the service on `addr` until an error occurs
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await?;
+        Ok(())
+    }
+
+    /// Serves the service on the already bound `listener`
+    pub async fn serve_listener(self, listener: TcpListener) -> Result<()> {
+        let incoming = TcpIncoming::from_listener(listener, true, None)
+            .map_err(|err| anyhow!("invalid listener: {}", err))?;
+        Server::builder()
+            .add_service(self.into_service())
+            .serve_with_incoming(incoming)
+            .await?;
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl ThresholdDecryption for DecryptionServer {
+    async fn submit_ciphertext(
+        &self,
+        request: Request<SubmitCiphertextRequest>,
+    ) -> Result<Response<SubmitCiphertextResponse>, Status> {
+        let caller = Caller::from_request(&request);
+        let cipher: Ciphertext = request
+            .into_inner()
+            .ciphertext
+            .ok_or_else(|| Status::invalid_argument("missing field ciphertext"))?
+            .into();
+        if !in_mult_group(cipher.as_ref(), &self.pk.n, &self.pk.n2) {
+            return Err(Status::invalid_argument("ciphertext is not in Z*_{n^2}"));
+        }
+        self.policy
+            .authorize(&caller, &cipher)
+            .map_err(permission_denied)?;
+        let mut pending = self.pending.lock().unwrap();
+        if pending.ciphertexts.len() >= self.max_pending {
+            return Err(Status::resource_exhausted("too many pending ciphertexts"));
+        }
+        let id = pending.next_id;
+        pending.next_id += 1;
+        pending.ciphertexts.insert(id, cipher);
+        Ok(Response::new(SubmitCiphertextResponse { id }))
+    }
+
+    async fn get_partial_decryption(
+        &self,
+        request: Request<GetPartialDecryptionRequest>,
+    ) -> Result<Response<GetPartialDecryptionResponse>, Status> {
+        let caller = Caller::from_request(&request);
+        let id = request.into_inner().id;
+        let cipher = {
+            let mut pending = self.pending.lock().unwrap();
+            let cipher = pending
+                .ciphertexts
+                .get(&id)
+                .ok_or_else(|| Status::not_found(format!("unknown ciphertext {}", id)))?;
+            // unauthorized requests leave the ciphertext for its rightful caller
+            self.policy
+                .authorize(&caller, cipher)
+                .map_err(permission_denied)?;
+            pending.ciphertexts.remove(&id).unwrap()
+        };
+        let (pk, key_share) = (self.pk.clone(), self.key_share.clone());
+        // the exponentiation takes milliseconds, keep it off the async workers
+        let partial = tokio::task::spawn_blocking(move || key_share.share_decrypt(&pk, cipher))
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        Ok(Response::new(GetPartialDecryptionResponse {
+            partial_decryption: Some((&partial).into()),
+        }))
+    }
+
+    async fn health(
+        &self,
+        _request: Request<HealthRequest>,
+    ) -> Result<Response<HealthResponse>, Status> {
+        Ok(Response::new(HealthResponse {
+            server_index: self.key_share.index(),
+            decryption_servers: self.pk.l,
+            threshold: self.pk.w,
+            pending: self.pending.lock().unwrap().ciphertexts.len() as u64,
+        }))
+    }
+}
+
+fn permission_denied(err: anyhow::Error) -> Status {
+    Status::permission_denied(err.to_string())
+}
+
+/// Client of a [`DecryptionServer`]
+#[derive(Debug, Clone)]
+pub struct DecryptionClient {
+    inner: ThresholdDecryptionClient<Channel>,
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl DecryptionClient {
+    pub async fn connect(endpoint: impl Into<String>) -> Result<Self> {
+        let channel = Endpoint::new(endpoint.into())?.connect().await?;
+        Ok(Self {
+            inner: ThresholdDecryptionClient::new(channel),
+            authorization: None,
+        })
+    }
+
+    /// Sends `token` as `authorization: Bearer <token>` with every request, see
+    /// [`Caller::bearer_token`]
+    pub fn with_token(mut self, token: &str) -> Result<Self> {
+        let value = format!("Bearer {}", token)
+            .parse()
+            .map_err(|_| anyhow!("token is not valid metadata"))?;
+        self.authorization = Some(value);
+        Ok(self)
+    }
+
+    fn request<T>(&self, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(value) = &self.authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", value.clone());
+        }
+        request
+    }
+
+    /// Stores `cipher` on the server and returns its id. The ciphertext is removed
+    /// once its partial decryption was requested.
+    pub async fn submit_ciphertext(&mut self, cipher: &Ciphertext) -> Result<u64> {
+        let request = self.request(SubmitCiphertextRequest {
+            ciphertext: Some(cipher.into()),
+        });
+        Ok(self.inner.submit_ciphertext(request).await?.into_inner().id)
+    }
+
+    pub async fn partial_decryption(&mut self, id: u64) -> Result<PartialDecryption> {
+        let response = self
+            .inner
+            .get_partial_decryption(self.request(GetPartialDecryptionRequest { id }))
+            .await?;
+        let partial = response
+            .into_inner()
+            .partial_decryption
+            .ok_or_else(|| anyhow!("missing field partial_decryption"))?;
+        PartialDecryption::try_from(partial)
+    }
+
+    pub async fn health(&mut self) -> Result<HealthResponse> {
+        Ok(self
+            .inner
+            .health(self.request(HealthRequest {}))
+            .await?
+            .into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CiphertextAllowList, DecryptionClient, DecryptionServer};
+    use crate::paillier::{generate_key_pair, Polynomial};
+    use rug::rand::RandState;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_decryption_servers() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 3, 2).unwrap();
+        let poly = Polynomial::new(&sk, &mut rand);
+        let policy = CiphertextAllowList::new();
+        let mut clients = Vec::new();
+        let mut addrs = Vec::new();
+        for i in 0..3 {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            addrs.push(addr);
+            let server = DecryptionServer::new(pk.clone(), poly.compute(i), policy.clone())
+                .with_max_pending(1);
+            tokio::spawn(server.serve_listener(listener));
+            clients.push(
+                DecryptionClient::connect(format!("http://{}", addr))
+                    .await
+                    .unwrap()
+                    .with_token("token")
+                    .unwrap(),
+            );
+        }
+
+        let health = clients[2].health().await.unwrap();
+        assert_eq!(
+            (health.server_index, health.threshold, health.pending),
+            (2, 2, 0)
+        );
+
+        let c = pk.encrypt(42.into(), &mut rand);
+        assert!(clients[0].submit_ciphertext(&c).await.is_err());
+        policy.allow(&c, "token");
+        let mut partials = Vec::new();
+        for client in &mut clients[..2] {
+            let id = client.submit_ciphertext(&c).await.unwrap();
+            assert!(client.submit_ciphertext(&c).await.is_err());
+            partials.push(client.partial_decryption(id).await.unwrap());
+            assert!(client.partial_decryption(id).await.is_err());
+        }
+        assert_eq!(pk.share_combine(&partials).unwrap(), 42);
+
+        // the allowance is bound to the token and can be revoked
+        let id = clients[2].submit_ciphertext(&c).await.unwrap();
+        policy.allow(&c, "other token");
+        assert!(clients[2].partial_decryption(id).await.is_err());
+        policy.allow(&c, "token");
+        let addr = format!("http://{}", addrs[2]);
+        let mut anonymous = DecryptionClient::connect(addr).await.unwrap();
+        assert!(anonymous.partial_decryption(id).await.is_err());
+        policy.revoke(&c);
+        assert!(clients[2].partial_decryption(id).await.is_err());
+
+        assert!(clients[0]
+            .submit_ciphertext(&pk.n2.clone().into())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! Ed25519 signed envelopes of ciphertexts, partial decryptions, aggregation
+//! contributions and summaries.
+//!
+//! A [`SignedEnvelope`] carries a payload, the id of its signer, a session id and an
+//! Ed25519 signature over the session id and the canonical encoding of the payload,
+//! the deterministic CBOR encoding of [`crate::cbor`] for ciphertexts and partial
+//! decryptions. This lets an aggregator authenticate which station produced a
+//! ciphertext and which server produced a partial decryption. The signer id of a
+//! partial decryption is the index of the server, which
+//! [`IncrementalCombine::add_signed`] and [`PublicKey::share_combine_signed`] check
+//! against the share, together with the session id, the ciphertext digest and the
+//! key fingerprint, so a signed partial decryption can't be replayed in another
+//! session or for another ciphertext. The signer id of a [`Contribution`] is its
+//! client, which [`AggregationRound::submit_signed`] checks.
+//!
+//! Only available with the `signed` feature.
+//!
+//! ```
+//! use ed25519_dalek::SigningKey;
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::signed::SignedEnvelope;
+//! use rug::rand::RandState;
+//! use std::collections::BTreeMap;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 2, 2).unwrap();
+//! let shares = sk.share(&[0, 1], &mut rand);
+//! let keys: Vec<_> = (0..2u8).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+//! let signers: BTreeMap<_, _> = (0..2).map(|i| (i, keys[i as usize].verifying_key())).collect();
+//!
+//! let c = pk.encrypt(42.into(), &mut rand);
+//! let envelopes: Vec<_> = shares
+//!     .iter()
+//!     .zip(&keys)
+//!     .enumerate()
+//!     .map(|(i, (share, key))| {
+//!         let partial = share.share_decrypt(&pk, c.clone());
+//!         SignedEnvelope::sign_in_session(partial, i as u32, b"round 1", key)
+//!     })
+//!     .collect();
+//! let m = pk.share_combine_signed(&c, b"round 1", &envelopes, &signers).unwrap();
+//! assert_eq!(m, 42);
+//! ```
+
+use crate::aggregation::{AggregationRound, AggregationSummary, Contribution};
+use crate::paillier::{IncrementalCombine, PartialDecryption, PublicKey};
+use crate::{Ciphertext, Plaintext};
+use anyhow::{anyhow, ensure, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Values with a canonical encoding that can be signed
+pub trait Signable {
+    /// Domain separator of the signed messages, distinct for every type
+    const DOMAIN: &'static [u8];
+
+    fn canonical_bytes(&self) -> Vec<u8>;
+}
+
+impl Signable for Ciphertext {
+    const DOMAIN: &'static [u8] = b"pht-crypto signed ciphertext v2";
+
+    fn canonical_bytes(&self) -> Vec<u8> {
+        self.to_cbor()
+    }
+}
+
+impl Signable for PartialDecryption {
+    const DOMAIN: &'static [u8] = b"pht-crypto signed partial decryption v2";
+
+    fn canonical_bytes(&self) -> Vec<u8> {
+        self.to_cbor()
+    }
+}
+
+impl Signable for Contribution {
+    const DOMAIN: &'static [u8] = b"pht-crypto signed contribution v2";
+
+    fn canonical_bytes(&self) -> Vec<u8> {
+        Contribution::canonical_bytes(self)
+    }
+}
+
+impl Signable for AggregationSummary {
+    const DOMAIN: &'static [u8] = b"pht-crypto signed aggregation summary v2";
+
+    fn canonical_bytes(&self) -> Vec<u8> {
+        AggregationSummary::canonical_bytes(self)
+    }
+}
+
+/// A payload signed by the signer with id `signer_id` in the session `session`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedEnvelope<T> {
+    payload: T,
+    signer_id: u32,
+    #[serde(default)]
+    session: Vec<u8>,
+    signature: Signature,
+}
+
+impl<T: Signable> SignedEnvelope<T> {
+    /// Signs `payload` outside of any session
+    pub fn sign(payload: T, signer_id: u32, key: &SigningKey) -> Self {
+        Self::sign_in_session(payload, signer_id, &[], key)
+    }
+
+    /// Signs `payload` bound to `session`, e.g. the id of a decryption round
+    pub fn sign_in_session(payload: T, signer_id: u32, session: &[u8], key: &SigningKey) -> Self {
+        let signature = key.sign(&message(&payload, signer_id, session));
+        Self {
+            payload,
+            signer_id,
+            session: session.to_vec(),
+            signature,
+        }
+    }
+
+    /// Checks the signature with the verifying key of the signer
+    pub fn verify(&self, key: &VerifyingKey) -> Result<()> {
+        let message = message(&self.payload, self.signer_id, &self.session);
+        key.verify(&message, &self.signature)
+            .map_err(|_| anyhow!("invalid signature of signer {}", self.signer_id))
+    }
+
+    /// Looks up the verifying key of the signer in `signers` and checks the signature
+    pub fn verify_with(&self, signers: &BTreeMap<u32, VerifyingKey>) -> Result<&T> {
+        let key = signers
+            .get(&self.signer_id)
+            .ok_or_else(|| anyhow!("unknown signer {}", self.signer_id))?;
+        self.verify(key)?;
+        Ok(&self.payload)
+    }
+
+    /// The payload, whose signature is not checked by this method
+    pub fn payload(&self) -> &T {
+        &self.payload
+    }
+
+    pub fn signer_id(&self) -> u32 {
+        self.signer_id
+    }
+
+    pub fn session(&self) -> &[u8] {
+        &self.session
+    }
+
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+}
+
+fn message<T: Signable>(payload: &T, signer_id: u32, session: &[u8]) -> Vec<u8> {
+    let mut message = T::DOMAIN.to_vec();
+    message.extend_from_slice(&signer_id.to_be_bytes());
+    message.extend_from_slice(&(session.len() as u64).to_be_bytes());
+    message.extend_from_slice(session);
+    message.extend_from_slice(&payload.canonical_bytes());
+    message
+}
+
+/// Verifies the envelope, that it was signed by the server of the share in `session`
+/// and that the share is for `cipher` under `pk`
+fn verified_share<'a>(
+    pk: &PublicKey,
+    cipher: &Ciphertext,
+    session: &[u8],
+    envelope: &'a SignedEnvelope<PartialDecryption>,
+    signers: &BTreeMap<u32, VerifyingKey>,
+) -> Result<&'a PartialDecryption> {
+    let share = envelope.verify_with(signers)?;
+    ensure!(
+        share.id.checked_sub(1) == Some(envelope.signer_id),
+        "server {} signed the share of another server",
+        envelope.signer_id
+    );
+    ensure!(
+        envelope.session == session,
+        "share of server {} was signed in another session",
+        envelope.signer_id
+    );
+    ensure!(
+        share.cipher_digest == Some(cipher.digest()),
+        "share of server {} is not for this ciphertext",
+        envelope.signer_id
+    );
+    ensure!(
+        share.key_fingerprint == Some(pk.fingerprint()),
+        "share of server {} was not computed with this key",
+        envelope.signer_id
+    );
+    Ok(share)
+}
+
+impl IncrementalCombine<'_> {
+    /// Like [`IncrementalCombine::add`] but first verifies that the share of `cipher`
+    /// was signed by its server in `session`
+    pub fn add_signed(
+        &mut self,
+        cipher: &Ciphertext,
+        session: &[u8],
+        envelope: &SignedEnvelope<PartialDecryption>,
+        signers: &BTreeMap<u32, VerifyingKey>,
+    ) -> Result<()> {
+        let share = verified_share(self.pk, cipher, session, envelope, signers)?;
+        self.add(share)
+    }
+}
+
+impl AggregationRound {
+    /// Requires every contribution to be signed by its client with the key in `keys`,
+    /// see [`AggregationRound::submit_signed`]
+    pub fn with_client_keys(mut self, keys: BTreeMap<u32, VerifyingKey>) -> Self {
+        self.client_keys = Some(keys);
+        self
+    }
+
+    /// Like [`AggregationRound::submit`] but first verifies that the contribution was
+    /// signed by its client
+    pub fn submit_signed(
+        &mut self,
+        pk: &PublicKey,
+        envelope: &SignedEnvelope<Contribution>,
+    ) -> Result<()> {
+        let keys = self
+            .client_keys
+            .as_ref()
+            .ok_or_else(|| anyhow!("round has no client keys"))?;
+        let contribution = envelope.verify_with(keys)?;
+        ensure!(
+            contribution.client() == envelope.signer_id,
+            "client {} signed the contribution of another client",
+            envelope.signer_id
+        );
+        self.accept(pk, contribution.clone())
+    }
+}
+
+impl PublicKey {
+    /// Like [`PublicKey::share_combine`] but first verifies that every share of
+    /// `cipher` was signed by its server in `session`
+    pub fn share_combine_signed(
+        &self,
+        cipher: &Ciphertext,
+        session: &[u8],
+        envelopes: &[SignedEnvelope<PartialDecryption>],
+        signers: &BTreeMap<u32, VerifyingKey>,
+    ) -> Result<Plaintext> {
+        let shares = envelopes
+            .iter()
+            .map(|envelope| verified_share(self, cipher, session, envelope, signers).cloned())
+            .collect::<Result<Vec<_>>>()?;
+        self.share_combine(&shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignedEnvelope;
+    use crate::aggregation::{AggregationRound, Contribution};
+    use crate::paillier::{generate_key_pair, PartialDecryption};
+    use crate::Ciphertext;
+    use ed25519_dalek::SigningKey;
+    use rug::rand::RandState;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_signed_envelopes() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 3, 2).unwrap();
+        let shares = sk.share(&[0, 2], &mut rand);
+        let keys: Vec<_> = (0..3u8).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let signers: BTreeMap<_, _> = (0..3)
+            .map(|i| (i, keys[i as usize].verifying_key()))
+            .collect();
+
+        let c = pk.encrypt(7.into(), &mut rand);
+        let signed_c = SignedEnvelope::sign(c.clone(), 17, &keys[1]);
+        signed_c.verify(&keys[1].verifying_key()).unwrap();
+        assert!(signed_c.verify(&keys[0].verifying_key()).is_err());
+        let bytes = bincode::serialize(&signed_c).unwrap();
+        let decoded: SignedEnvelope<Ciphertext> = bincode::deserialize(&bytes).unwrap();
+        decoded.verify(&keys[1].verifying_key()).unwrap();
+
+        let envelope = |share: PartialDecryption, signer: u32| {
+            SignedEnvelope::sign_in_session(share, signer, b"round 1", &keys[signer as usize])
+        };
+        let partials: Vec<_> = shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, c.clone()))
+            .collect();
+        let envelopes = vec![
+            envelope(partials[0].clone(), 0),
+            envelope(partials[1].clone(), 2),
+        ];
+        let combine_signed = |envelopes: &[SignedEnvelope<PartialDecryption>]| {
+            pk.share_combine_signed(&c, b"round 1", envelopes, &signers)
+        };
+        assert_eq!(combine_signed(&envelopes).unwrap(), 7);
+        let mut combine = pk.combine_incremental(&[0, 2]).unwrap();
+        for envelope in &envelopes {
+            combine
+                .add_signed(&c, b"round 1", envelope, &signers)
+                .unwrap();
+        }
+        assert_eq!(combine.finish().unwrap(), 7);
+
+        // signed by the wrong server
+        let forged = vec![envelopes[0].clone(), envelope(partials[1].clone(), 1)];
+        assert!(combine_signed(&forged).is_err());
+        // tampered payload
+        let mut tampered = envelopes[0].clone();
+        tampered.payload.val += 1;
+        assert!(combine_signed(&[tampered, envelopes[1].clone()]).is_err());
+        // tampered session
+        let mut tampered = envelopes[0].clone();
+        tampered.session = b"round 2".to_vec();
+        assert!(tampered.verify(&keys[0].verifying_key()).is_err());
+        // unknown signer
+        let mut unknown = signers.clone();
+        unknown.remove(&2);
+        assert!(pk
+            .share_combine_signed(&c, b"round 1", &envelopes, &unknown)
+            .is_err());
+        // stripped metadata
+        let mut stripped = partials[0].clone();
+        stripped.cipher_digest = None;
+        assert!(combine_signed(&[envelope(stripped, 0), envelopes[1].clone()]).is_err());
+    }
+
+    #[test]
+    fn test_replayed_partials() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 2, 2).unwrap();
+        let shares = sk.share(&[0, 1], &mut rand);
+        let keys: Vec<_> = (0..2u8).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let signers: BTreeMap<_, _> = (0..2)
+            .map(|i| (i, keys[i as usize].verifying_key()))
+            .collect();
+        let sign = |c: &Ciphertext, session: &[u8]| -> Vec<_> {
+            shares
+                .iter()
+                .enumerate()
+                .map(|(i, share)| {
+                    let partial = share.share_decrypt(&pk, c.clone());
+                    SignedEnvelope::sign_in_session(partial, i as u32, session, &keys[i])
+                })
+                .collect()
+        };
+
+        let c1 = pk.encrypt(1.into(), &mut rand);
+        let c2 = pk.encrypt(2.into(), &mut rand);
+        let round1 = sign(&c1, b"round 1");
+        let round2 = sign(&c2, b"round 2");
+        assert_eq!(
+            pk.share_combine_signed(&c2, b"round 2", &round2, &signers)
+                .unwrap(),
+            2
+        );
+        // a partial of round 1 replayed in round 2
+        let replayed = vec![round2[0].clone(), round1[1].clone()];
+        assert!(pk
+            .share_combine_signed(&c2, b"round 2", &replayed, &signers)
+            .is_err());
+        // partials of c1 in round 2, and of round 1 for c2
+        let again = sign(&c1, b"round 2");
+        assert!(pk
+            .share_combine_signed(&c2, b"round 2", &again, &signers)
+            .is_err());
+        assert!(pk
+            .share_combine_signed(&c2, b"round 1", &round1, &signers)
+            .is_err());
+        let mut combine = pk.combine_incremental(&[0, 1]).unwrap();
+        assert!(combine
+            .add_signed(&c1, b"round 2", &round1[0], &signers)
+            .is_err());
+    }
+
+    #[test]
+    fn test_signed_contributions() {
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let keys: Vec<_> = (0..3u8).map(|i| SigningKey::from_bytes(&[i; 32])).collect();
+        let clients: BTreeMap<_, _> = (1..3)
+            .map(|i| (i, keys[i as usize].verifying_key()))
+            .collect();
+        let mut round = AggregationRound::new(&pk, 4, vec![1, 2], 1).with_client_keys(clients);
+
+        let unsigned = Contribution::new(&pk, 4, 1, 1.into(), &mut rand);
+        assert!(round.submit(&pk, unsigned.clone()).is_err());
+        // signed by client 2 but naming client 1
+        let forged = SignedEnvelope::sign(unsigned.clone(), 2, &keys[2]);
+        assert!(round.submit_signed(&pk, &forged).is_err());
+        let replayed = SignedEnvelope::sign(
+            Contribution::new(&pk, 3, 1, 1.into(), &mut rand),
+            1,
+            &keys[1],
+        );
+        assert!(round.submit_signed(&pk, &replayed).is_err());
+
+        let signed = SignedEnvelope::sign(unsigned, 1, &keys[1]);
+        round.submit_signed(&pk, &signed).unwrap();
+        assert!(round.submit_signed(&pk, &signed).is_err());
+        assert_eq!(round.missing(), [2]);
+    }
+
+    #[test]
+    fn test_signed_summary() {
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(128, 1, 1).unwrap();
+        let key = SigningKey::from_bytes(&[9; 32]);
+        let mut round = AggregationRound::new(&pk, 1, vec![1, 2], 1);
+        let contribution = Contribution::new(&pk, 1, 2, 3.into(), &mut rand);
+        round.submit(&pk, contribution).unwrap();
+        let summary = round.close(&pk).unwrap().summary();
+        let signed = SignedEnvelope::sign(summary, 0, &key);
+        signed.verify(&key.verifying_key()).unwrap();
+        assert_eq!(signed.payload().included, [2]);
+    }
+}
@@ -0,0 +1,179 @@
+//! Encrypted descriptive statistics with threshold Paillier.
+//!
+//! Every station encrypts the sum, the sum of squares and the count of its local values
+//! as [`EncryptedStats`]. These are aggregated homomorphically and only the totals are
+//! threshold-decrypted, from which [`Stats`] derives the mean and variance. Values may be
+//! negative, decrypted totals above n / 2 are interpreted as negative.
+
+use crate::paillier::{PartialDecryption, PrivateKeyShare, PublicKey};
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::Integer;
+use serde::{Deserialize, Serialize};
+
+/// Homomorphically sums `ciphers`. The sum of no ciphertexts is the trivial
+/// encryption of 0.
+pub fn sum(pk: &PublicKey, ciphers: &[Ciphertext]) -> Ciphertext {
+    pk.sum_encrypted(ciphers)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedStats {
+    pub sum: Ciphertext,
+    pub sum_squares: Ciphertext,
+    pub count: Ciphertext,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsPartialDecryption {
+    sum: PartialDecryption,
+    sum_squares: PartialDecryption,
+    count: PartialDecryption,
+}
+
+/// Decrypted totals
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Stats {
+    #[serde(with = "crate::util::serde_integer")]
+    pub sum: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub sum_squares: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    pub count: Integer,
+}
+
+impl EncryptedStats {
+    /// Encrypts the statistics of a station's local `values`
+    pub fn new(pk: &PublicKey, values: &[Integer], rand: &mut dyn MutRandState) -> Self {
+        let total: Integer = values.iter().sum();
+        let total_squares: Integer = values.iter().map(|v| v.clone().square()).sum();
+        let enc = |v: Integer, rand: &mut dyn MutRandState| {
+            pk.encrypt(Plaintext::from(reduce(pk, v)), rand)
+        };
+        Self {
+            sum: enc(total, rand),
+            sum_squares: enc(total_squares, rand),
+            count: enc(Integer::from(values.len()), rand),
+        }
+    }
+
+    /// Aggregates the statistics of several stations
+    pub fn aggregate(pk: &PublicKey, stats: &[EncryptedStats]) -> Self {
+        let collect = |f: fn(&EncryptedStats) -> &Ciphertext| -> Vec<Ciphertext> {
+            stats.iter().map(|s| f(s).clone()).collect()
+        };
+        Self {
+            sum: sum(pk, &collect(|s| &s.sum)),
+            sum_squares: sum(pk, &collect(|s| &s.sum_squares)),
+            count: sum(pk, &collect(|s| &s.count)),
+        }
+    }
+
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> StatsPartialDecryption {
+        StatsPartialDecryption {
+            sum: key_share.share_decrypt(pk, self.sum.clone()),
+            sum_squares: key_share.share_decrypt(pk, self.sum_squares.clone()),
+            count: key_share.share_decrypt(pk, self.count.clone()),
+        }
+    }
+}
+
+impl Stats {
+    /// Combines the partial decryptions of at least w servers
+    pub fn combine(pk: &PublicKey, partials: &[StatsPartialDecryption]) -> Result<Self> {
+        let combine = |f: fn(&StatsPartialDecryption) -> &PartialDecryption| -> Result<Integer> {
+            let shares: Vec<_> = partials.iter().map(|p| f(p).clone()).collect();
+            let m: Integer = pk.share_combine(&shares)?.into();
+            let half = Integer::from(&pk.n >> 1);
+            Ok(if m > half { m - &pk.n } else { m })
+        };
+        Ok(Self {
+            sum: combine(|p| &p.sum)?,
+            sum_squares: combine(|p| &p.sum_squares)?,
+            count: combine(|p| &p.count)?,
+        })
+    }
+
+    pub fn mean(&self) -> Result<f64> {
+        ensure!(self.count > 0, "no values");
+        Ok(self.sum.to_f64() / self.count.to_f64())
+    }
+
+    /// Population variance E[x^2] - E[x]^2, computed exactly as
+    /// (count * sum_squares - sum^2) / count^2
+    pub fn variance(&self) -> Result<f64> {
+        ensure!(self.count > 0, "no values");
+        let numerator = Integer::from(&self.count * &self.sum_squares) - self.sum.clone().square();
+        Ok(numerator.to_f64() / self.count.clone().square().to_f64())
+    }
+
+    /// Unbiased sample variance (count * sum_squares - sum^2) / (count * (count - 1))
+    pub fn sample_variance(&self) -> Result<f64> {
+        ensure!(self.count > 1, "at least two values are needed");
+        let numerator = Integer::from(&self.count * &self.sum_squares) - self.sum.clone().square();
+        let denominator = &self.count * Integer::from(&self.count - 1);
+        Ok(numerator.to_f64() / denominator.to_f64())
+    }
+}
+
+fn reduce(pk: &PublicKey, x: Integer) -> Integer {
+    if x < 0 {
+        x % &pk.n + &pk.n
+    } else {
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EncryptedStats, Stats};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_stats() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+
+        let stations: Vec<Vec<Integer>> = vec![
+            [2, 4, 4].iter().map(|x| Integer::from(*x)).collect(),
+            [4, 5, 5, 7, 9].iter().map(|x| Integer::from(*x)).collect(),
+            vec![],
+        ];
+        let encrypted: Vec<_> = stations
+            .iter()
+            .map(|values| EncryptedStats::new(&pk, values, &mut rand))
+            .collect();
+        let total = EncryptedStats::aggregate(&pk, &encrypted);
+        let partials: Vec<_> = key_shares
+            .iter()
+            .map(|s| total.share_decrypt(&pk, s))
+            .collect();
+        let stats = Stats::combine(&pk, &partials).unwrap();
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.sum, 40);
+        assert_eq!(stats.mean().unwrap(), 5.0);
+        assert_eq!(stats.variance().unwrap(), 4.0);
+        assert!((stats.sample_variance().unwrap() - 32.0 / 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_negative_values() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(512, 1, 1).unwrap();
+        let key_share = sk.share(&[0], &mut rand).remove(0);
+        let values: Vec<Integer> = [-3, -5, 2].iter().map(|x| Integer::from(*x)).collect();
+        let encrypted = EncryptedStats::new(&pk, &values, &mut rand);
+        let stats = Stats::combine(&pk, &[encrypted.share_decrypt(&pk, &key_share)]).unwrap();
+        assert_eq!(stats.sum, -6);
+        assert_eq!(stats.sum_squares, 38);
+        assert_eq!(stats.mean().unwrap(), -2.0);
+    }
+}
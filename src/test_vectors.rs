@@ -0,0 +1,216 @@
+//! Deterministic key generation and known-answer tests (feature `test_vectors`), so
+//! implementations in other languages can check their interoperability with this
+//! crate.
+//!
+//! **Everything in this module is insecure.** Keys derived from a seed are only as
+//! secret as the seed and the primes are drawn from a non-cryptographic generator.
+//! Never use them for anything but tests.
+//!
+//! Each [`KnownAnswerTest`] lists the modulus, the key shares of the servers 0..w, a
+//! plaintext m with randomness r, the ciphertext g^m * r^n mod n^2 with g = n + 1, the
+//! partial decryptions of all key shares and thereby the expected result of combining
+//! them. All integers are lowercase hex strings. The vectors are fixed: a change of
+//! any value is a breaking change of the encryption or decryption.
+
+use crate::paillier::{self, PartialDecryption, PrivateKey, PrivateKeyShare, PublicKey};
+use crate::rand::generate_safe_prime_with;
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::{MutRandState, RandState};
+use rug::{Complete, Integer};
+use sha3::{Digest, Sha3_256};
+
+/// Returns a GMP random state deterministically seeded with the SHA3-256 hash of `seed`.
+///
+/// **Insecure**: the state is a Mersenne Twister, which is not a cryptographic
+/// random number generator.
+pub fn insecure_seeded_rand(seed: &[u8]) -> RandState<'static> {
+    let digest = Sha3_256::digest(seed);
+    let mut rand = RandState::new();
+    rand.seed(&Integer::from_digits(&digest, rug::integer::Order::Msf));
+    rand
+}
+
+/// Like [`paillier::generate_key_pair`] but derives the key pair deterministically
+/// from `seed`.
+///
+/// **Insecure**: anyone knowing the seed can recompute the private key. Only use this
+/// for tests.
+pub fn insecure_generate_key_pair_from_seed(
+    seed: &[u8],
+    bits: usize,
+    decryption_servers: u32,
+    threshold: u32,
+) -> Result<(PublicKey, PrivateKey)> {
+    let mut rand = insecure_seeded_rand(seed);
+    key_pair_from_rand(bits, decryption_servers, threshold, &mut rand)
+}
+
+fn key_pair_from_rand(
+    bits: usize,
+    decryption_servers: u32,
+    threshold: u32,
+    rand: &mut dyn MutRandState,
+) -> Result<(PublicKey, PrivateKey)> {
+    let (p, p1) = generate_safe_prime_with(bits / 2, rand);
+    let (q, q1) = loop {
+        let (q, q1) = generate_safe_prime_with(bits / 2, rand);
+        if q != p {
+            break (q, q1);
+        }
+    };
+    let (pk, sk, _) = paillier::key_pair_from_primes(p, p1, q, q1, decryption_servers, threshold)?;
+    Ok((pk, sk))
+}
+
+/// A known-answer test for encryption, share decryption and share combination
+#[derive(Debug, Clone, Copy)]
+pub struct KnownAnswerTest {
+    /// Seed the key shares and randomness are derived from
+    pub seed: &'static str,
+    pub bits: usize,
+    pub decryption_servers: u32,
+    pub threshold: u32,
+    pub n: &'static str,
+    /// Index and secret of the key shares
+    pub key_shares: &'static [(u32, &'static str)],
+    pub plaintext: &'static str,
+    pub randomness: &'static str,
+    pub ciphertext: &'static str,
+    /// Index and value of the partial decryptions of `ciphertext`
+    pub partial_decryptions: &'static [(u32, &'static str)],
+}
+
+/// The fixed known-answer tests
+pub const KNOWN_ANSWER_TESTS: &[KnownAnswerTest] = &[
+    KnownAnswerTest {
+        seed: "pht-crypto known answer test 1",
+        bits: 256,
+        decryption_servers: 1,
+        threshold: 1,
+        n: "abcec550fd50c379220e297bad4ffa5f75eb5558a8968c9fd8619018032f6fc5",
+        key_shares: &[
+            (1, "102d158a24c22c574535c31c3c2ec3f39906509c2e0dee32f73854c947babe1a59c0506890193c408a051437877920c9f538f2edade73761d62ab4d058084699"),
+        ],
+        plaintext: "123456789abcdef0",
+        randomness: "53fb0a0e069c787b15d1b2f29f4e0e866b4792a8ae8359c2dff53865f6198fd4",
+        ciphertext: "1d7a6d2a60972b0bce3c45b727ad1c0af926f07c48a9bf8285f88abfe22e7f57c9bd670289161255bb0dfa7ce1c75ed6d8620da64544f6d07947f55f6cee2165",
+        partial_decryptions: &[
+            (1, "186f53d05aa3f2c032b02107911b4cfd7f3aeb820ff89528154f641263c3474b6b8ab7616c233d61"),
+        ],
+    },
+    KnownAnswerTest {
+        seed: "pht-crypto known answer test 2",
+        bits: 256,
+        decryption_servers: 3,
+        threshold: 2,
+        n: "e695e7b5f350bff1617263c517e9a24cfc29e60f8e127314cb3209f7fe5152c9",
+        key_shares: &[
+            (1, "6b0de0bd3e24a20173ca5f5d80d2ee1f78220fd4b9ebc577596ea476b4a2a8ccd766e48cb77b261b3e6e41cee0b12d51f398d46c1862bb7880f25edb3bc272f"),
+            (2, "aa7e6ea529ca5943e1cdf80f7bdc378b453c6fa296f1f13c22a908cb6b3a7113f450eaa1cdc8d67de25447f7e4bfd6370db774ee9a174821dd1f1fee778b4a4"),
+        ],
+        plaintext: "123456789abcdef0",
+        randomness: "31d82f9307fe4d1b49559421f84bda81253135a4f6168a84b747da8979a8ff25",
+        ciphertext: "44fbfdb6db170efa0993d2ca580c160fb6c806eb98434da2d9da7cab268048cdc4a728e65b10589100e68b1773811a091d8620dcb0647603320faf32c1a64af2",
+        partial_decryptions: &[
+            (1, "2898b19ee6a8ce2217f63e76db9f1149fbf5dca5f1f5345851b82860fd6e5000379d220d7a3cda615ddaeaf37a1e90270cdaa56569bf725c6c29f6cf32eeeef8"),
+            (2, "44a306e81e8a30593b5e77685352eef9743a349704164da571c834e86f0f52c34b586376759eb7eda6cdf90900c2686fe5f86f1da5073134d9d709d189fb5efa"),
+        ],
+    },
+];
+
+fn parse(hex: &str) -> Result<Integer> {
+    Integer::from_str_radix(hex, 16).map_err(|e| anyhow!("invalid hex {:?}: {}", hex, e))
+}
+
+impl KnownAnswerTest {
+    /// Checks that this crate reproduces the test: the keys and randomness derived
+    /// from the seed, the ciphertext, the partial decryptions and the decryption.
+    pub fn verify(&self) -> Result<()> {
+        let mut rand = insecure_seeded_rand(self.seed.as_bytes());
+        let (pk, sk) = key_pair_from_rand(
+            self.bits,
+            self.decryption_servers,
+            self.threshold,
+            &mut rand,
+        )?;
+        ensure!(*pk.modulus() == parse(self.n)?, "modulus mismatch");
+
+        let indices: Vec<u32> = (0..self.threshold).collect();
+        let key_shares = sk.share(&indices, &mut rand);
+        ensure!(
+            key_shares.len() == self.key_shares.len(),
+            "number of key shares mismatch"
+        );
+        for (share, (i, si)) in key_shares.iter().zip(self.key_shares) {
+            ensure!(
+                share.i == *i && share.si == parse(si)?,
+                "key share {} mismatch",
+                i
+            );
+        }
+
+        let m = parse(self.plaintext)?;
+        // the vectors were generated with a uniform value of [0, n) coprime to n
+        let r = loop {
+            let r = Integer::from(pk.n.random_below_ref(&mut rand));
+            if r.gcd_ref(&pk.n).complete() == 1 {
+                break r;
+            }
+        };
+        ensure!(r == parse(self.randomness)?, "randomness mismatch");
+        let cipher = pk.encrypt_raw(&m, &r);
+        ensure!(cipher == parse(self.ciphertext)?, "ciphertext mismatch");
+
+        let partials: Vec<PartialDecryption> = key_shares
+            .iter()
+            .map(|share| share.share_decrypt(&pk, cipher.clone().into()))
+            .collect();
+        for (partial, (i, val)) in partials.iter().zip(self.partial_decryptions) {
+            ensure!(
+                partial.id == *i && partial.val == parse(val)?,
+                "partial decryption {} mismatch",
+                i
+            );
+        }
+        let decrypted: Integer = pk.share_combine(&partials)?.into();
+        ensure!(decrypted == m, "decryption mismatch");
+        Ok(())
+    }
+
+    /// The key shares of the test
+    pub fn key_shares(&self) -> Result<Vec<PrivateKeyShare>> {
+        self.key_shares
+            .iter()
+            .map(|(i, si)| {
+                ensure!(*i > 0, "share index must be positive");
+                Ok(PrivateKeyShare {
+                    i: *i,
+                    si: parse(si)?,
+                    key_fingerprint: None,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_answer_tests() {
+        for kat in KNOWN_ANSWER_TESTS {
+            kat.verify().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_seeded_key_generation() {
+        let (pk1, _) = insecure_generate_key_pair_from_seed(b"seed", 128, 2, 2).unwrap();
+        let (pk2, _) = insecure_generate_key_pair_from_seed(b"seed", 128, 2, 2).unwrap();
+        let (pk3, _) = insecure_generate_key_pair_from_seed(b"other", 128, 2, 2).unwrap();
+        assert_eq!(pk1, pk2);
+        assert_ne!(pk1, pk3);
+        assert_eq!(pk1.modulus().significant_bits(), 128);
+    }
+}
@@ -0,0 +1,199 @@
+//! [`Arbitrary`] implementations and proptest strategies for property testing and
+//! fuzzing (feature `testing`).
+//!
+//! **Everything in this module is insecure.** The key pairs have [`TINY_KEY_BITS`] bit
+//! moduli and are derived deterministically from small seeds with
+//! [`crate::test_vectors::insecure_generate_key_pair_from_seed`], so they are found in
+//! milliseconds and cached per seed. Only use them for tests.
+//!
+//! ```
+//! use pht_crypto::testing::key_pairs;
+//! use proptest::prelude::*;
+//!
+//! proptest!(ProptestConfig::with_cases(8), |(key in key_pairs(), m in any::<u32>())| {
+//!     let mut rand = rug::rand::RandState::new();
+//!     let c = key.pk.encrypt(m.into(), &mut rand);
+//!     prop_assert_eq!(key.sk.decrypt(&c), m);
+//! });
+//! ```
+
+use crate::paillier::{Polynomial, PrivateKey, PrivateKeyShare, PublicKey};
+use crate::test_vectors::{insecure_generate_key_pair_from_seed, insecure_seeded_rand};
+use crate::{Ciphertext, Plaintext};
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use rug::integer::Order;
+use rug::Integer;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+/// Bit length of the moduli of the insecure test keys
+pub const TINY_KEY_BITS: usize = 128;
+
+/// Number of distinct seeds [`key_pairs`] and the [`Arbitrary`] key pairs draw from,
+/// so the key generation is cached after a few cases
+pub const KEY_SEEDS: u64 = 16;
+
+/// Key pairs by seed, number of decryption servers and threshold
+type KeyCache = Mutex<HashMap<(u64, u32, u32), InsecureKeyPair>>;
+
+/// An insecure key pair together with the key shares of all decryption servers
+#[derive(Debug, Clone)]
+pub struct InsecureKeyPair {
+    pub seed: u64,
+    pub pk: PublicKey,
+    pub sk: PrivateKey,
+    /// The shares of the servers 0..l
+    pub key_shares: Vec<PrivateKeyShare>,
+}
+
+/// A key pair and a ciphertext of `plaintext` under it
+#[derive(Debug, Clone)]
+pub struct Encryption {
+    pub key: InsecureKeyPair,
+    pub plaintext: Plaintext,
+    pub cipher: Ciphertext,
+}
+
+/// Returns the insecure key pair of `seed` with a [`TINY_KEY_BITS`] bit modulus. The
+/// key pairs are cached, so repeated calls with the same arguments are cheap.
+///
+/// Panics unless 1 <= `threshold` <= `decryption_servers`.
+pub fn tiny_key_pair(seed: u64, decryption_servers: u32, threshold: u32) -> InsecureKeyPair {
+    static CACHE: OnceLock<KeyCache> = OnceLock::new();
+    let cache = CACHE.get_or_init(Default::default);
+    let key = (seed, decryption_servers, threshold);
+    if let Some(pair) = cache.lock().unwrap().get(&key) {
+        return pair.clone();
+    }
+    let seed_bytes = seed.to_be_bytes();
+    let (pk, sk) = insecure_generate_key_pair_from_seed(
+        &seed_bytes,
+        TINY_KEY_BITS,
+        decryption_servers,
+        threshold,
+    )
+    .expect("invalid number of decryption servers or threshold");
+    let mut rand = insecure_seeded_rand(&seed_bytes);
+    let poly = Polynomial::new(&sk, &mut rand);
+    let key_shares = (0..decryption_servers).map(|i| poly.compute(i)).collect();
+    let pair = InsecureKeyPair {
+        seed,
+        pk,
+        sk,
+        key_shares,
+    };
+    cache.lock().unwrap().insert(key, pair.clone());
+    pair
+}
+
+impl InsecureKeyPair {
+    /// Encrypts `m` with randomness derived from `seed`
+    pub fn encrypt(&self, m: Plaintext, seed: u64) -> Ciphertext {
+        let mut rand = insecure_seeded_rand(&seed.to_be_bytes());
+        self.pk.encrypt(m, &mut rand)
+    }
+}
+
+/// Plaintexts of up to 256 bits of either sign
+pub fn plaintexts() -> impl Strategy<Value = Plaintext> {
+    (any::<bool>(), prop::collection::vec(any::<u8>(), 0..=32)).prop_map(|(neg, digits)| {
+        let m = Integer::from_digits(&digits, Order::MsfBe);
+        Plaintext::from(if neg { -m } else { m })
+    })
+}
+
+/// Key pairs with 1 to 4 decryption servers and any threshold, drawn from
+/// [`KEY_SEEDS`] seeds
+pub fn key_pairs() -> impl Strategy<Value = InsecureKeyPair> {
+    (1..=4u32)
+        .prop_flat_map(|l| (0..KEY_SEEDS, Just(l), 1..=l))
+        .prop_map(|(seed, l, w)| tiny_key_pair(seed, l, w))
+}
+
+/// Encryptions of plaintexts in [0, n) under `key`
+pub fn encryptions(key: InsecureKeyPair) -> impl Strategy<Value = Encryption> {
+    (plaintexts(), any::<u64>()).prop_map(move |(m, seed)| {
+        let plaintext: Plaintext = m.as_ref().clone().modulo(key.pk.modulus()).into();
+        Encryption {
+            cipher: key.encrypt(plaintext.clone(), seed),
+            key: key.clone(),
+            plaintext,
+        }
+    })
+}
+
+impl<'a> Arbitrary<'a> for Plaintext {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let neg = bool::arbitrary(u)?;
+        let len = u.int_in_range(0..=32)?;
+        let m = Integer::from_digits(u.bytes(len)?, Order::MsfBe);
+        Ok(if neg { -m } else { m }.into())
+    }
+}
+
+/// Raw values below 2^(2 * [`TINY_KEY_BITS`]), which need not be valid under any key.
+/// Use [`Encryption`] for ciphertexts of known plaintexts.
+impl<'a> Arbitrary<'a> for Ciphertext {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let len = u.int_in_range(0..=TINY_KEY_BITS / 4)?;
+        Ok(Integer::from_digits(u.bytes(len)?, Order::MsfBe).into())
+    }
+}
+
+impl<'a> Arbitrary<'a> for InsecureKeyPair {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let l = u.int_in_range(1..=4)?;
+        let w = u.int_in_range(1..=l)?;
+        Ok(tiny_key_pair(u.int_in_range(0..=KEY_SEEDS - 1)?, l, w))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Encryption {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        let key = InsecureKeyPair::arbitrary(u)?;
+        let plaintext: Plaintext = Plaintext::arbitrary(u)?
+            .as_ref()
+            .clone()
+            .modulo(key.pk.modulus())
+            .into();
+        let cipher = key.encrypt(plaintext.clone(), u.arbitrary()?);
+        Ok(Self {
+            key,
+            plaintext,
+            cipher,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encryptions, key_pairs, tiny_key_pair, Encryption};
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        #[test]
+        fn test_encryptions_combine(
+            enc in key_pairs().prop_flat_map(encryptions)
+        ) {
+            let key = &enc.key;
+            let partials: Vec<_> = key.key_shares[..key.pk.threshold() as usize]
+                .iter()
+                .map(|share| share.share_decrypt(&key.pk, enc.cipher.clone()))
+                .collect();
+            prop_assert_eq!(key.pk.share_combine(&partials).unwrap(), enc.plaintext);
+        }
+    }
+
+    #[test]
+    fn test_arbitrary_encryption() {
+        let key = tiny_key_pair(3, 2, 1);
+        assert_eq!(key.pk.modulus(), tiny_key_pair(3, 2, 1).pk.modulus());
+        let bytes: Vec<u8> = (0..=255).collect();
+        let enc = Encryption::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+        assert_eq!(enc.key.sk.decrypt(&enc.cipher), enc.plaintext);
+    }
+}
@@ -0,0 +1,344 @@
+//! Threshold RSA signatures of Shoup ("Practical Threshold Signatures", Eurocrypt
+//! 2000) over the modulus of a paillier key.
+//!
+//! The safe prime modulus n = p * q of the committee key doubles as RSA modulus, so a
+//! single key ceremony yields both the decryption shares and signing shares. The
+//! dealer shares d = e^-1 mod p'q' over Z_{p'q'} and publishes a [`VerificationKey`]
+//! with v^{s_i} for a random square v. Any w servers sign a message, e.g. an
+//! aggregation result, with a [`SignatureShare`] each, which carries a proof that it
+//! was computed with the server's share. The combined [`Signature`] is a standard RSA
+//! full domain hash signature with exponent e.
+//!
+//! ```
+//! use pht_crypto::dealer::Dealer;
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let dealer = Dealer::new(256, 3, 2).unwrap();
+//! let (vk, shares) = dealer.deal_signing_shares(&mut rand).unwrap();
+//! let msg = b"sum of cohort A: 1234";
+//! let sig_shares: Vec<_> = shares[1..].iter().map(|s| s.sign(&vk, msg, &mut rand)).collect();
+//! let sig = vk.combine(msg, &sig_shares).unwrap();
+//! assert!(vk.verify(msg, &sig));
+//! assert!(!vk.verify(b"sum of cohort A: 1235", &sig));
+//! ```
+
+use crate::metrics;
+use crate::paillier::{PrivateKey, PublicKey};
+use crate::proofs::{self, CHALLENGE_BITS};
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::transcript::Transcript;
+use crate::util;
+use anyhow::{anyhow, ensure, Result};
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+
+/// The public exponent e, a prime larger than any supported number of servers
+pub const PUBLIC_EXPONENT: u32 = 65537;
+
+const HASH_LABEL: &[u8] = b"pht-crypto/threshold-rsa/hash";
+const PROOF_LABEL: &[u8] = b"pht-crypto/threshold-rsa/share-proof";
+
+/// Public key of the signing committee together with the verification keys of its
+/// servers
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct VerificationKey {
+    /// The number of servers needed to sign
+    w: u32,
+    #[serde(with = "crate::util::serde_integer")]
+    n: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    e: Integer,
+    /// Random generator of the squares in Z*_n
+    #[serde(with = "crate::util::serde_integer")]
+    v: Integer,
+    /// v^{s_i} mod n of the server with evaluation point i + 1
+    #[serde(with = "crate::util::serde_integer_vec")]
+    verification_keys: Vec<Integer>,
+}
+
+/// The signing share s_i = f(i) of a server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningShare {
+    i: u32,
+    #[serde(with = "crate::util::serde_integer")]
+    si: Integer,
+}
+
+/// x^{2 Δ s_i} mod n for the hash x of the message, with a proof that its discrete
+/// logarithm equals that of the server's verification key
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SignatureShare {
+    id: u32,
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    c: Integer,
+    #[serde(with = "crate::util::serde_integer")]
+    z: Integer,
+}
+
+/// RSA signature y with y^e = H(msg) mod n
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Signature {
+    #[serde(with = "crate::util::serde_integer")]
+    val: Integer,
+}
+
+/// Deals signing shares for the modulus of `sk` to its l servers. The i-th share
+/// belongs to server i. Fails if the modulus isn't a product of safe primes p = 2p' + 1
+/// and q = 2q' + 1 with e coprime to p'q'.
+pub fn deal(
+    pk: &PublicKey,
+    sk: &PrivateKey,
+    rand: &mut dyn MutRandState,
+) -> Result<(VerificationKey, Vec<SigningShare>)> {
+    ensure!(
+        sk.n == pk.n,
+        "private key does not belong to the public key"
+    );
+    let e = Integer::from(PUBLIC_EXPONENT);
+    ensure!(pk.l < PUBLIC_EXPONENT, "too many servers");
+    // m = p'q', the order of the squares in Z*_n
+    let m = sk.nm.div_exact_ref(&sk.n).complete();
+    let d = e
+        .invert_ref(&m)
+        .map(Integer::from)
+        .ok_or_else(|| anyhow!("public exponent is not invertible"))?;
+    let mut coefficients = vec![m.clone(); pk.w as usize];
+    coefficients[0] = d;
+    for coeff in coefficients.iter_mut().skip(1) {
+        coeff.random_below_mut(rand);
+    }
+    let shares: Vec<SigningShare> = (1..=pk.l)
+        .map(|i| {
+            // Horner's scheme
+            let si = coefficients
+                .iter()
+                .rev()
+                .fold(Integer::new(), |acc, coeff| (acc * i + coeff) % &m);
+            SigningShare { i, si }
+        })
+        .collect();
+    let r = random_in_mult_group(&pk.n, UnitCheck::Gcd, rand);
+    let v = r.square() % &pk.n;
+    let verification_keys = shares
+        .iter()
+        .map(|share| util::secure_pow_mod(&v, &share.si, &pk.n))
+        .collect();
+    let vk = VerificationKey {
+        w: pk.w,
+        n: pk.n.clone(),
+        e,
+        v,
+        verification_keys,
+    };
+    Ok((vk, shares))
+}
+
+impl VerificationKey {
+    pub fn modulus(&self) -> &Integer {
+        &self.n
+    }
+
+    pub fn public_exponent(&self) -> &Integer {
+        &self.e
+    }
+
+    /// The number of servers needed to sign
+    pub fn threshold(&self) -> u32 {
+        self.w
+    }
+
+    /// l!
+    fn delta(&self) -> Integer {
+        Integer::factorial(self.verification_keys.len() as u32).complete()
+    }
+
+    /// Full domain hash of `msg` into Z_n, bound to the key
+    fn hash(&self, msg: &[u8]) -> Integer {
+        let mut transcript = Transcript::new(HASH_LABEL);
+        transcript.append_integer(b"n", &self.n);
+        transcript.append_integer(b"e", &self.e);
+        transcript.append_message(b"message", msg);
+        let bits = self.n.significant_bits() + CHALLENGE_BITS;
+        transcript.challenge_integer(b"hash", bits) % &self.n
+    }
+
+    /// Checks the proof of `share` for `msg`
+    pub fn verify_share(&self, msg: &[u8], share: &SignatureShare) -> bool {
+        metrics::proof_verified("signature_share", self.share_valid(msg, share))
+    }
+
+    fn share_valid(&self, msg: &[u8], share: &SignatureShare) -> bool {
+        let vi = match share
+            .id
+            .checked_sub(1)
+            .and_then(|i| self.verification_keys.get(i as usize))
+        {
+            Some(vi) => vi,
+            None => return false,
+        };
+        if !proofs::in_mult_group(&share.val, &self.n, &self.n) {
+            return false;
+        }
+        let x_tilde = self.hash(msg).pow_mod(&(self.delta() << 2u32), &self.n);
+        let x_tilde = match x_tilde {
+            Ok(x_tilde) => x_tilde,
+            Err(_) => return false,
+        };
+        let xi2 = share.val.clone().square() % &self.n;
+        let neg_c = Integer::from(-&share.c);
+        let commit = |base: &Integer, public: &Integer| -> Option<Integer> {
+            let a = base.pow_mod_ref(&share.z, &self.n)?;
+            let b = public.pow_mod_ref(&neg_c, &self.n)?;
+            Some(Integer::from(a) * Integer::from(b) % &self.n)
+        };
+        match (commit(&self.v, vi), commit(&x_tilde, &xi2)) {
+            (Some(v_r), Some(x_r)) => share.c == self.challenge(&x_tilde, vi, &xi2, &v_r, &x_r),
+            _ => false,
+        }
+    }
+
+    fn challenge(
+        &self,
+        x_tilde: &Integer,
+        vi: &Integer,
+        xi2: &Integer,
+        v_r: &Integer,
+        x_r: &Integer,
+    ) -> Integer {
+        let mut transcript = Transcript::new(PROOF_LABEL);
+        transcript.append_integer(b"n", &self.n);
+        proofs::challenge(
+            &mut transcript,
+            b"share",
+            &[&self.v, x_tilde, vi, xi2, v_r, x_r],
+        )
+    }
+
+    /// Combines at least w signature shares of `msg` into a signature. Fails if a
+    /// share is invalid, see [`VerificationKey::verify_share`].
+    pub fn combine(&self, msg: &[u8], shares: &[SignatureShare]) -> Result<Signature> {
+        ensure!(
+            shares.len() >= self.w as usize,
+            "{} signature shares are needed",
+            self.w
+        );
+        let ids: Vec<u32> = shares.iter().map(|share| share.id).collect();
+        util::check_evaluation_points(&ids)?;
+        for share in shares {
+            ensure!(
+                self.verify_share(msg, share),
+                "signature share of server {} is invalid",
+                share.id - 1
+            );
+        }
+        let delta = self.delta();
+        let (lambdas, scale) = util::lagrange_coefficients(&delta, &ids);
+        debug_assert_eq!(scale, 1);
+        let vals: Vec<&Integer> = shares.iter().map(|share| &share.val).collect();
+        let exps: Vec<Integer> = lambdas.into_iter().map(|lambda| lambda << 1u32).collect();
+        // w = x^{4 Δ^2 d}, so w^e = x^{e'} with e' = 4 Δ^2
+        let w = util::multi_pow_mod(&vals, &exps, &self.n)
+            .ok_or_else(|| anyhow!("signature share is not invertible"))?;
+        let e_prime = delta.square() << 2u32;
+        let (gcd, a, b) = e_prime.extended_gcd(self.e.clone(), Integer::new());
+        ensure!(gcd == 1, "public exponent divides 4 * l!^2");
+        let x = self.hash(msg);
+        let y = util::multi_pow_mod(&[&w, &x], &[a, b], &self.n)
+            .ok_or_else(|| anyhow!("message hash is not invertible"))?;
+        let sig = Signature { val: y };
+        ensure!(self.verify(msg, &sig), "combined signature is invalid");
+        Ok(sig)
+    }
+
+    /// Checks that `sig` is a signature of `msg`
+    pub fn verify(&self, msg: &[u8], sig: &Signature) -> bool {
+        sig.val > 0
+            && sig.val < self.n
+            && sig.val.pow_mod_ref(&self.e, &self.n).map(Integer::from) == Some(self.hash(msg))
+    }
+}
+
+impl SigningShare {
+    /// Zero based index of the server
+    pub fn index(&self) -> u32 {
+        self.i - 1
+    }
+
+    /// Signs `msg` with this share and proves that the signature share is correct
+    pub fn sign(
+        &self,
+        vk: &VerificationKey,
+        msg: &[u8],
+        rand: &mut dyn MutRandState,
+    ) -> SignatureShare {
+        trace_span!("sign_share", server = self.index());
+        let n = &vk.n;
+        let delta = vk.delta();
+        let x = vk.hash(msg);
+        let val = util::secure_pow_mod(&x, &(self.si.clone() * &delta * 2u32), n);
+        let x_tilde = x.pow_mod(&(delta << 2u32), n).unwrap();
+        let xi2 = val.clone().square() % n;
+
+        let r = Integer::from(Integer::random_bits(
+            n.significant_bits() + 2 * CHALLENGE_BITS,
+            rand,
+        ));
+        let v_r = util::secure_pow_mod(&vk.v, &r, n);
+        let x_r = util::secure_pow_mod(&x_tilde, &r, n);
+        let vi = &vk.verification_keys[self.index() as usize];
+        let c = vk.challenge(&x_tilde, vi, &xi2, &v_r, &x_r);
+        let z = self.si.clone() * &c + r;
+        SignatureShare {
+            id: self.i,
+            val,
+            c,
+            z,
+        }
+    }
+}
+
+impl SignatureShare {
+    /// Zero based index of the server
+    pub fn server_id(&self) -> u32 {
+        self.id - 1
+    }
+}
+
+impl Signature {
+    pub fn value(&self) -> &Integer {
+        &self.val
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::deal;
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_threshold_signature() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 4, 3).unwrap();
+        let (vk, shares) = deal(&pk, &sk, &mut rand).unwrap();
+        let msg = b"aggregate";
+        let mut sig_shares: Vec<_> = shares
+            .iter()
+            .map(|share| share.sign(&vk, msg, &mut rand))
+            .collect();
+        assert!(sig_shares.iter().all(|share| vk.verify_share(msg, share)));
+        assert!(!vk.verify_share(b"other", &sig_shares[0]));
+
+        let sig = vk.combine(msg, &sig_shares[1..]).unwrap();
+        assert!(vk.verify(msg, &sig));
+        assert_eq!(vk.combine(msg, &sig_shares[..3]).unwrap(), sig);
+        assert!(vk.combine(msg, &sig_shares[..2]).is_err());
+
+        sig_shares[0].val += 1;
+        assert!(vk.combine(msg, &sig_shares[..3]).is_err());
+    }
+}
@@ -0,0 +1,35 @@
+//! Spans around long-running operations (feature `trace`), created with the
+//! `trace_span!` macro.
+//!
+//! Each span records the sizes of its inputs as fields and emits a `DEBUG` event with
+//! the elapsed time when it ends, so a subscriber shows where e.g. a slow aggregation
+//! round spends its time. The spans are at `INFO` level and named after the operation.
+
+use std::time::Instant;
+use tracing::span::EnteredSpan;
+
+/// Keeps a span entered and reports its duration when dropped
+pub(crate) struct Timed {
+    span: EnteredSpan,
+    start: Instant,
+}
+
+impl Timed {
+    pub(crate) fn enter(span: tracing::Span) -> Self {
+        Self {
+            span: span.entered(),
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Timed {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        tracing::debug!(
+            parent: self.span.id(),
+            elapsed_ms = elapsed.as_secs_f64() * 1000.0,
+            "finished"
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Traits abstracting over the additively homomorphic threshold schemes of this crate,
+//! so protocols can be written once and used with e.g. [`crate::paillier`] and
+//! [`crate::damgard_jurik`].
+
+use crate::{damgard_jurik, elgamal, okamoto_uchiyama, paillier, Ciphertext, Plaintext};
+use anyhow::Result;
+use rug::rand::MutRandState;
+
+/// Public key operations of an additively homomorphic encryption scheme.
+pub trait AdditivelyHomomorphicEncryption {
+    type Ciphertext: Clone;
+
+    fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Self::Ciphertext;
+
+    /// Re-randomizes `cipher` without changing its plaintext.
+    fn reencrypt(&self, cipher: &mut Self::Ciphertext, rand: &mut dyn MutRandState);
+
+    /// Homomorphically adds the plaintext of `cipher2` to `cipher1`.
+    fn add(&self, cipher1: &mut Self::Ciphertext, cipher2: &Self::Ciphertext);
+
+    /// Homomorphically adds `plain` to the plaintext of `cipher`.
+    fn add_plain(&self, cipher: &mut Self::Ciphertext, plain: &Plaintext);
+
+    /// Homomorphically multiplies the plaintext of `cipher` with `plain`.
+    fn mul_plain(&self, cipher: &mut Self::Ciphertext, plain: &Plaintext);
+}
+
+/// Threshold decryption of a scheme where w of l servers holding a key share each
+/// must cooperate to decrypt.
+pub trait ThresholdScheme: AdditivelyHomomorphicEncryption {
+    type KeyShare;
+    type PartialDecryption;
+
+    fn share_decrypt(
+        &self,
+        key_share: &Self::KeyShare,
+        cipher: Self::Ciphertext,
+    ) -> Self::PartialDecryption;
+
+    fn share_combine(&self, shares: &[Self::PartialDecryption]) -> Result<Plaintext>;
+}
+
+macro_rules! impl_additively_homomorphic {
+    ($($scheme:ident)+) => {
+        $(
+            impl AdditivelyHomomorphicEncryption for $scheme::PublicKey {
+                type Ciphertext = Ciphertext;
+
+                fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> Ciphertext {
+                    $scheme::PublicKey::encrypt(self, m, rand)
+                }
+
+                fn reencrypt(&self, cipher: &mut Ciphertext, rand: &mut dyn MutRandState) {
+                    $scheme::PublicKey::reencrypt(self, cipher, rand)
+                }
+
+                fn add(&self, cipher1: &mut Ciphertext, cipher2: &Ciphertext) {
+                    self.add_encrypted(cipher1, cipher2)
+                }
+
+                fn add_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+                    $scheme::PublicKey::add_plain(self, cipher, plain)
+                }
+
+                fn mul_plain(&self, cipher: &mut Ciphertext, plain: &Plaintext) {
+                    $scheme::PublicKey::mul_plain(self, cipher, plain)
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_threshold_scheme {
+    ($($scheme:ident)+) => {
+        $(
+            impl ThresholdScheme for $scheme::PublicKey {
+                type KeyShare = $scheme::PrivateKeyShare;
+                type PartialDecryption = $scheme::PartialDecryption;
+
+                fn share_decrypt(
+                    &self,
+                    key_share: &$scheme::PrivateKeyShare,
+                    cipher: Ciphertext,
+                ) -> $scheme::PartialDecryption {
+                    key_share.share_decrypt(self, cipher)
+                }
+
+                fn share_combine(&self, shares: &[$scheme::PartialDecryption]) -> Result<Plaintext> {
+                    $scheme::PublicKey::share_combine(self, shares)
+                }
+            }
+        )+
+    };
+}
+
+impl_additively_homomorphic!(paillier damgard_jurik okamoto_uchiyama);
+impl_threshold_scheme!(paillier damgard_jurik);
+
+impl AdditivelyHomomorphicEncryption for elgamal::PublicKey {
+    type Ciphertext = elgamal::Ciphertext;
+
+    fn encrypt(&self, m: Plaintext, rand: &mut dyn MutRandState) -> elgamal::Ciphertext {
+        elgamal::PublicKey::encrypt(self, m, rand)
+    }
+
+    fn reencrypt(&self, cipher: &mut elgamal::Ciphertext, rand: &mut dyn MutRandState) {
+        elgamal::PublicKey::reencrypt(self, cipher, rand)
+    }
+
+    fn add(&self, cipher1: &mut elgamal::Ciphertext, cipher2: &elgamal::Ciphertext) {
+        self.add_encrypted(cipher1, cipher2)
+    }
+
+    fn add_plain(&self, cipher: &mut elgamal::Ciphertext, plain: &Plaintext) {
+        elgamal::PublicKey::add_plain(self, cipher, plain)
+    }
+
+    fn mul_plain(&self, cipher: &mut elgamal::Ciphertext, plain: &Plaintext) {
+        elgamal::PublicKey::mul_plain(self, cipher, plain)
+    }
+}
+
+impl ThresholdScheme for elgamal::PublicKey {
+    type KeyShare = elgamal::PrivateKeyShare;
+    type PartialDecryption = elgamal::PartialDecryption;
+
+    fn share_decrypt(
+        &self,
+        key_share: &elgamal::PrivateKeyShare,
+        cipher: elgamal::Ciphertext,
+    ) -> elgamal::PartialDecryption {
+        key_share.share_decrypt(self, cipher)
+    }
+
+    fn share_combine(&self, shares: &[elgamal::PartialDecryption]) -> Result<Plaintext> {
+        elgamal::PublicKey::share_combine(self, shares)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThresholdScheme;
+    use crate::{damgard_jurik, elgamal, paillier};
+    use rug::rand::RandState;
+
+    fn weighted_sum<S: ThresholdScheme>(pk: &S, key_shares: &[S::KeyShare]) -> i32 {
+        let mut rand = RandState::new();
+        let mut sum = pk.encrypt(0.into(), &mut rand);
+        for (weight, value) in [(2, 3), (5, 7)] {
+            let mut c = pk.encrypt(value.into(), &mut rand);
+            pk.mul_plain(&mut c, &weight.into());
+            pk.add(&mut sum, &c);
+        }
+        pk.add_plain(&mut sum, &1.into());
+        pk.reencrypt(&mut sum, &mut rand);
+        let shares: Vec<_> = key_shares
+            .iter()
+            .map(|key_share| pk.share_decrypt(key_share, sum.clone()))
+            .collect();
+        let m: rug::Integer = pk.share_combine(&shares).unwrap().into();
+        m.to_i32().unwrap()
+    }
+
+    #[test]
+    fn test_generic_schemes() {
+        let mut rand = RandState::new();
+        let (pk, sk) = paillier::generate_key_pair(128, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+        assert_eq!(weighted_sum(&pk, &key_shares), 42);
+
+        let (pk, sk) = damgard_jurik::generate_key_pair(128, 2, 2, 2).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+        assert_eq!(weighted_sum(&pk, &key_shares), 42);
+
+        let (pk, sk) = elgamal::generate_key_pair(128, 2, 2, &mut rand).unwrap();
+        let key_shares = sk.share(&[0, 1], &mut rand);
+        assert_eq!(
+            weighted_sum(&pk.with_decode_bound(1 << 10), &key_shares),
+            42
+        );
+    }
+}
@@ -0,0 +1,105 @@
+//! Fiat-Shamir transcripts binding non-interactive proofs to their context.
+//!
+//! A [`Transcript`] absorbs labeled messages and squeezes challenges from everything
+//! absorbed so far. Provers and verifiers must build identical transcripts, which
+//! binds a proof to e.g. the protocol, round and party it was created for. All
+//! proofs in [`crate::proofs`] and [`crate::mixnet`] take a transcript, so they can
+//! be composed and bound to the session context of downstream protocols:
+//!
+//! ```
+//! use pht_crypto::transcript::Transcript;
+//!
+//! let mut transcript = Transcript::new(b"my-protocol");
+//! transcript.append_u64(b"round", 3);
+//! transcript.append_message(b"party", b"station-1");
+//! let challenge = transcript.challenge_integer(b"challenge", 128);
+//! assert!(challenge.significant_bits() <= 128);
+//! ```
+
+use rug::integer::Order;
+use rug::Integer;
+use sha3::{Digest, Sha3_256};
+
+/// Running SHA3-256 based transcript of a protocol
+#[derive(Clone)]
+pub struct Transcript {
+    hasher: Sha3_256,
+}
+
+impl Transcript {
+    /// Creates a new transcript for the protocol identified by `label`.
+    pub fn new(label: &[u8]) -> Self {
+        let mut transcript = Self {
+            hasher: Sha3_256::new(),
+        };
+        transcript.append_message(b"pht-crypto transcript", label);
+        transcript
+    }
+
+    /// Appends a labeled message. Labels and messages are length prefixed, so
+    /// distinct sequences of appends always result in distinct transcripts.
+    pub fn append_message(&mut self, label: &[u8], message: &[u8]) {
+        self.hasher.update((label.len() as u64).to_le_bytes());
+        self.hasher.update(label);
+        self.hasher.update((message.len() as u64).to_le_bytes());
+        self.hasher.update(message);
+    }
+
+    /// Appends a labeled u64 in little-endian encoding.
+    pub fn append_u64(&mut self, label: &[u8], x: u64) {
+        self.append_message(label, &x.to_le_bytes());
+    }
+
+    /// Appends a labeled integer in big-endian encoding of its absolute value
+    /// followed by its sign.
+    pub fn append_integer(&mut self, label: &[u8], x: &Integer) {
+        let mut bytes: Vec<u8> = x.to_digits(Order::MsfBe);
+        bytes.push(u8::from(*x < 0));
+        self.append_message(label, &bytes);
+    }
+
+    /// Fills `dest` with challenge bytes derived from the transcript. The challenge is
+    /// appended to the transcript, so subsequent challenges depend on it.
+    pub fn challenge_bytes(&mut self, label: &[u8], dest: &mut [u8]) {
+        self.append_u64(label, dest.len() as u64);
+        let seed = self.hasher.clone().finalize();
+        for (counter, chunk) in dest.chunks_mut(32).enumerate() {
+            let mut hasher = Sha3_256::new();
+            hasher.update(seed);
+            hasher.update((counter as u64).to_le_bytes());
+            let block = hasher.finalize();
+            chunk.copy_from_slice(&block[..chunk.len()]);
+        }
+        self.append_message(b"challenge", dest);
+    }
+
+    /// Derives a challenge integer in [0, 2^bits) from the transcript.
+    pub fn challenge_integer(&mut self, label: &[u8], bits: u32) -> Integer {
+        let mut bytes = vec![0; bits.div_ceil(8) as usize];
+        self.challenge_bytes(label, &mut bytes);
+        let mut rop = Integer::from_digits(&bytes, Order::MsfBe);
+        rop.keep_bits_mut(bits);
+        rop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transcript;
+
+    #[test]
+    fn test_transcript_binding() {
+        let challenge = |party: &[u8]| {
+            let mut transcript = Transcript::new(b"test");
+            transcript.append_message(b"party", party);
+            transcript.challenge_integer(b"e", 128)
+        };
+        assert_eq!(challenge(b"a"), challenge(b"a"));
+        assert_ne!(challenge(b"a"), challenge(b"b"));
+
+        let mut transcript = Transcript::new(b"test");
+        let e1 = transcript.challenge_integer(b"e", 128);
+        let e2 = transcript.challenge_integer(b"e", 128);
+        assert_ne!(e1, e2);
+    }
+}
@@ -1,4 +1,10 @@
+use crate::par::prelude::*;
+use anyhow::{ensure, Result};
+use rug::integer::Order;
+use rug::rand::MutRandState;
 use rug::{Assign, Complete, Integer};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroizing;
 
 /// Chinese remainder theorem case where k = 2 using Bezout's identity. Unlike
 /// other mpz functions rop must not be an aliased with any of the other
@@ -12,17 +18,368 @@ pub(crate) fn crt2(
     con2_a: &Integer,
     con2_m: &Integer,
 ) -> Integer {
-    let mut t = con1_m.gcd_ref(&con2_m).complete();
+    let mut t = con1_m.gcd_ref(con2_m).complete();
     assert_eq!(t, 1);
-    let mut res = con2_m.clone().invert(&con1_m).unwrap();
+    let mut res = con2_m.clone().invert(con1_m).unwrap();
     res *= (con2_m * con1_a).complete();
-    t.assign(con1_m.clone().invert(&con2_m).unwrap() * con1_m * con2_a);
+    t.assign(con1_m.clone().invert(con2_m).unwrap() * con1_m * con2_a);
     res += t;
     t = (con1_m * con2_m).complete();
     res %= t;
     res
 }
 
+/// Checks that the evaluation points of the shares to combine are nonzero and unique
+pub(crate) fn check_evaluation_points(ids: &[u32]) -> Result<()> {
+    ensure!(!ids.contains(&0), "evaluation point 0 is not a valid share");
+    let mut sorted = ids.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    ensure!(sorted.len() == ids.len(), "shares must be unique");
+    Ok(())
+}
+
+/// Computes the Lagrange coefficients at 0 of all `ids`, scaled to make them integers:
+/// lambda_i = delta * scale * prod_{j != i} -id_j / (id_i - id_j). Returns them together
+/// with the smallest such scale, which is 1 for delta = l! and ids of at most l.
+/// Other evaluation points need a larger scale, which the combination divides out
+/// mod n.
+///
+/// The numerator delta * prod_j id_j is shared by all coefficients and computed only
+/// once, so every coefficient needs a single big division instead of one per id.
+pub(crate) fn lagrange_coefficients(delta: &Integer, ids: &[u32]) -> (Vec<Integer>, Integer) {
+    let numerator = small_product(delta.clone(), ids.iter().map(|id| *id as i64));
+    // prod_{j != i} -id_j = (-1)^(w - 1) * prod_j id_j / id_i
+    let negate = ids.len().is_multiple_of(2);
+    let denominators: Vec<Integer> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id_i)| {
+            let differences = ids
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, id_j)| {
+                    assert_ne!(id_i, id_j, "`share_combine` must be passed unique shares");
+                    *id_i as i64 - *id_j as i64
+                });
+            small_product(Integer::from(*id_i), differences)
+        })
+        .collect();
+    let scale = denominators
+        .iter()
+        .fold(Integer::from(1), |scale, denominator| {
+            let gcd = denominator.gcd_ref(&numerator).complete();
+            scale.lcm(&denominator.div_exact_ref(&gcd).complete())
+        });
+    let numerator = numerator * &scale;
+    let lambdas = denominators
+        .iter()
+        .map(|denominator| {
+            let lambda = numerator.div_exact_ref(denominator).complete();
+            if negate {
+                -lambda
+            } else {
+                lambda
+            }
+        })
+        .collect();
+    (lambdas, scale)
+}
+
+/// Computes the coefficients for interpolating at `x` from the points `ids`, scaled to
+/// make them integers: mu_i = scale * prod_{j != i} (x - id_j) / (id_i - id_j). Returns
+/// them with the smallest such scale.
+pub(crate) fn interpolation_coefficients(ids: &[u32], x: u32) -> (Vec<Integer>, Integer) {
+    let fractions: Vec<(Integer, Integer)> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id_i)| {
+            let others = ids.iter().enumerate().filter(|(j, _)| *j != i);
+            let numerator = small_product(
+                Integer::from(1),
+                others.clone().map(|(_, id_j)| x as i64 - *id_j as i64),
+            );
+            let denominator = small_product(
+                Integer::from(1),
+                others.map(|(_, id_j)| *id_i as i64 - *id_j as i64),
+            );
+            (numerator, denominator)
+        })
+        .collect();
+    let scale = fractions
+        .iter()
+        .fold(Integer::from(1), |scale, (numerator, denominator)| {
+            let gcd = denominator.gcd_ref(numerator).complete();
+            scale.lcm(&denominator.div_exact_ref(&gcd).complete())
+        });
+    let coefficients = fractions
+        .into_iter()
+        .map(|(numerator, denominator)| (numerator * &scale).div_exact(&denominator))
+        .collect();
+    (coefficients, scale)
+}
+
+/// base^exp mod m for a secret exponent `exp` and odd `m`. Uses GMP's side channel
+/// silent exponentiation, whose running time and memory accesses only depend on the
+/// sizes of the operands. Negative exponents use the inverse of the base, 0 is
+/// returned if it has none.
+pub(crate) fn secure_pow_mod(base: &Integer, exp: &Integer, m: &Integer) -> Integer {
+    debug_assert!(m.is_odd());
+    let mut base = base.modulo_ref(m).complete();
+    if *exp == 0 {
+        return Integer::from(1) % m;
+    }
+    if *exp < 0 {
+        base = match base.invert(m) {
+            Ok(inv) => inv,
+            Err(_) => return Integer::new(),
+        };
+        return base.secure_pow_mod(&Integer::from(-exp), m);
+    }
+    base.secure_pow_mod(exp, m)
+}
+
+/// exp + k * order for a random 64 bit k. Exponentiations of elements whose order
+/// divides `order` are unchanged, but the bits of the exponent differ between calls,
+/// so side channel traces of several exponentiations can't be combined.
+pub(crate) fn blind_exponent(
+    exp: &Integer,
+    order: &Integer,
+    rand: &mut dyn MutRandState,
+) -> Integer {
+    let k = Integer::from(Integer::random_bits(64, rand));
+    k * order + exp
+}
+
+/// Compares `a` and `b` in time which only depends on their sizes
+pub(crate) fn ct_eq_integer(a: &Integer, b: &Integer) -> Choice {
+    let len = a
+        .significant_digits::<u64>()
+        .max(b.significant_digits::<u64>());
+    let mut a_digits = Zeroizing::new(vec![0u64; len]);
+    let mut b_digits = Zeroizing::new(vec![0u64; len]);
+    a.write_digits(&mut a_digits, Order::Lsf);
+    b.write_digits(&mut b_digits, Order::Lsf);
+    let sign_eq = (a.cmp0() as i8 as u8).ct_eq(&(b.cmp0() as i8 as u8));
+    a_digits.ct_eq(&b_digits) & sign_eq
+}
+
+/// Computes prod_i bases[i]^exps[i] mod m by Straus' method: the squarings are shared
+/// by all bases and every base is multiplied in once per 4 bit window of its exponent.
+/// Negative exponents use the inverse of their base. Returns `None` if such a base is
+/// not invertible.
+pub(crate) fn multi_pow_mod(bases: &[&Integer], exps: &[Integer], m: &Integer) -> Option<Integer> {
+    const WINDOW: u32 = 4;
+    debug_assert_eq!(bases.len(), exps.len());
+    // tables[i][d - 1] = bases[i]^d mod m for 0 < d < 2^WINDOW
+    let mut tables = Vec::with_capacity(bases.len());
+    for (base, exp) in bases.iter().zip(exps) {
+        let base = if *exp < 0 {
+            base.invert_ref(m)?.complete()
+        } else {
+            Integer::from(*base % m)
+        };
+        let mut row = Vec::with_capacity((1 << WINDOW) - 1);
+        row.push(base);
+        for d in 1..(1 << WINDOW) - 1 {
+            let next = Integer::from(&row[d - 1] * &row[0]) % m;
+            row.push(next);
+        }
+        tables.push(row);
+    }
+    let exps: Vec<Integer> = exps.iter().map(|exp| exp.abs_ref().complete()).collect();
+    let bits = exps
+        .iter()
+        .map(Integer::significant_bits)
+        .max()
+        .unwrap_or(0);
+    let mut acc = Integer::from(1);
+    for window in (0..bits.div_ceil(WINDOW)).rev() {
+        if acc != 1 {
+            for _ in 0..WINDOW {
+                acc.square_mut();
+                acc %= m;
+            }
+        }
+        let offset = window * WINDOW;
+        for (row, exp) in tables.iter().zip(&exps) {
+            let digit = (0..WINDOW).fold(0, |digit, k| {
+                digit | (usize::from(exp.get_bit(offset + k)) << k)
+            });
+            if digit != 0 {
+                acc *= &row[digit - 1];
+                acc %= m;
+            }
+        }
+    }
+    Some(acc % m)
+}
+
+/// [`multi_pow_mod`] on chunks of the bases in parallel
+pub(crate) fn multi_pow_mod_par(
+    bases: &[&Integer],
+    exps: &[Integer],
+    m: &Integer,
+) -> Option<Integer> {
+    // large enough to share most squarings, small enough to use several threads for
+    // hundreds of shares
+    const CHUNK: usize = 16;
+    let chunks: Vec<Option<Integer>> = (0..bases.len().div_ceil(CHUNK))
+        .into_par_iter()
+        .map(|k| {
+            let range = k * CHUNK..bases.len().min((k + 1) * CHUNK);
+            multi_pow_mod(&bases[range.clone()], &exps[range], m)
+        })
+        .collect();
+    chunks
+        .into_iter()
+        .try_fold(Integer::from(1), |acc, chunk| Some(acc * chunk? % m))
+}
+
+/// Multiplies `acc` with the small `factors`, collecting them in an i64 as long as
+/// possible to save big integer multiplications
+fn small_product(mut acc: Integer, factors: impl Iterator<Item = i64>) -> Integer {
+    let mut word = 1i64;
+    for factor in factors {
+        word = match word.checked_mul(factor) {
+            Some(word) => word,
+            None => {
+                acc *= word;
+                factor
+            }
+        };
+    }
+    acc *= word;
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        blind_exponent, ct_eq_integer, lagrange_coefficients, multi_pow_mod, secure_pow_mod,
+    };
+    use rug::rand::RandState;
+    use rug::{Complete, Integer};
+
+    #[test]
+    fn test_multi_pow_mod() {
+        let mut rand = RandState::new();
+        let m: Integer = Integer::from(Integer::u_pow_u(5, 80)) * 7 + 3;
+        let bases: Vec<Integer> = (0..5)
+            .map(|_| Integer::from(m.random_below_ref(&mut rand)))
+            .collect();
+        let mut exps: Vec<Integer> = (0..5)
+            .map(|i| Integer::from(Integer::random_bits(40 * i, &mut rand)))
+            .collect();
+        exps[2] = -exps[2].clone();
+        let base_refs: Vec<&Integer> = bases.iter().collect();
+        let expected = bases
+            .iter()
+            .zip(&exps)
+            .fold(Integer::from(1), |acc, (b, e)| {
+                acc * Integer::from(b.pow_mod_ref(e, &m).unwrap()) % &m
+            });
+        assert_eq!(multi_pow_mod(&base_refs, &exps, &m).unwrap(), expected);
+        assert_eq!(multi_pow_mod(&[], &[], &m).unwrap(), 1);
+        assert!(multi_pow_mod(
+            &[&Integer::from(5)],
+            &[Integer::from(-1)],
+            &Integer::from(25)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_secure_pow_mod() {
+        let mut rand = RandState::new();
+        // 11 * 23, the order of Z*_m divides 10 * 22
+        let m = Integer::from(253);
+        let order = Integer::from(220);
+        for base in [0, 1, 5, 252, -3, 300] {
+            let base = Integer::from(base);
+            for exp in [0, 1, 2, 37, 219, -1, -38] {
+                let exp = Integer::from(exp);
+                // 0 if the base is not invertible for a negative exponent
+                let expected = base
+                    .pow_mod_ref(&exp, &m)
+                    .map(Integer::from)
+                    .unwrap_or_default();
+                assert_eq!(secure_pow_mod(&base, &exp, &m), expected);
+                if base.gcd_ref(&m).complete() == 1 && exp >= 0 {
+                    let blinded = blind_exponent(&exp, &order, &mut rand);
+                    assert!(blinded >= exp);
+                    assert_eq!(secure_pow_mod(&base, &blinded, &m), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_ct_eq_integer() {
+        let big: Integer = Integer::from(1) << 200;
+        let values = [
+            Integer::new(),
+            Integer::from(1),
+            Integer::from(-1),
+            Integer::from(u64::MAX),
+            big.clone(),
+            -big.clone(),
+            big + 1,
+        ];
+        for a in &values {
+            for b in &values {
+                assert_eq!(bool::from(ct_eq_integer(a, b)), a == b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lagrange_coefficients() {
+        let delta = Integer::from(Integer::factorial(10));
+        for ids in [vec![], vec![3], vec![1, 2, 3], vec![7, 2, 10, 4]] {
+            let (lambdas, scale) = lagrange_coefficients(&delta, &ids);
+            assert_eq!(scale, 1);
+            for (i, lambda) in lambdas.iter().enumerate() {
+                let mut expected = delta.clone();
+                for (j, id) in ids.iter().enumerate().filter(|(j, _)| *j != i) {
+                    expected *= -(*id as i64);
+                    expected /= ids[i] as i64 - ids[j] as i64;
+                }
+                assert_eq!(*lambda, expected);
+            }
+        }
+
+        // arbitrary points interpolate f(x) = 5 + 3x + 2x^2 at 0 with a larger scale
+        let ids = [100, 7, 1_000_000];
+        let (lambdas, scale) = lagrange_coefficients(&delta, &ids);
+        assert!(scale > 1);
+        let interpolated = ids
+            .iter()
+            .zip(&lambdas)
+            .fold(Integer::new(), |acc, (id, lambda)| {
+                let x = Integer::from(*id);
+                let y: Integer = Integer::from(&x * &x) * 2 + x * 3 + 5;
+                acc + y * lambda
+            });
+        assert_eq!(interpolated, delta * scale * 5);
+    }
+}
+
+/// Deserializes an evaluation point of a share, rejecting 0
+pub(crate) fn deserialize_point<'de, D>(d: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let point = <u32 as serde::Deserialize>::deserialize(d)?;
+    if point == 0 {
+        return Err(serde::de::Error::custom(
+            "evaluation point 0 is not a valid share",
+        ));
+    }
+    Ok(point)
+}
+
 /// This implements more efficient ser/de for rug::Integer. The standard implementation simply
 /// [uses to_string_radix](https://docs.rs/rug/1.12.0/src/rug/integer/serde.rs.html#26-38) while
 /// this uses the more efficient to/from_digits
@@ -86,3 +443,38 @@ pub(crate) mod serde_integer {
         }
     }
 }
+
+/// Ser/de for `Vec<rug::Integer>` using the encoding of [`serde_integer`]
+pub(crate) mod serde_integer_vec {
+    use rug::Integer;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    struct Ser<'a>(&'a Integer);
+    impl Serialize for Ser<'_> {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            super::serde_integer::serialize(self.0, s)
+        }
+    }
+
+    struct De(Integer);
+    impl<'de> Deserialize<'de> for De {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            super::serde_integer::deserialize(d).map(De)
+        }
+    }
+
+    pub(crate) fn serialize<S>(v: &[Integer], s: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        s.collect_seq(v.iter().map(Ser))
+    }
+
+    pub(crate) fn deserialize<'de, D>(d: D) -> Result<Vec<Integer>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let v: Vec<De> = Vec::deserialize(d)?;
+        Ok(v.into_iter().map(|i| i.0).collect())
+    }
+}
@@ -0,0 +1,268 @@
+//! Vectors of ciphertexts and plaintexts with element-wise operations.
+//!
+//! The operations of [`CiphertextVec`] run in parallel with the `parallel` feature and
+//! check that the lengths of their operands match.
+//!
+//! ```
+//! use pht_crypto::paillier::generate_key_pair;
+//! use pht_crypto::vector::{CiphertextVec, PlaintextVec};
+//! use rug::rand::RandState;
+//!
+//! let mut rand = RandState::new();
+//! let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+//!
+//! let x: PlaintextVec = vec![1, 2, 3].into_iter().collect();
+//! let y: PlaintextVec = vec![10, 20, 30].into_iter().collect();
+//! let cx = CiphertextVec::encrypt(&pk, &x, &mut rand);
+//! let cy = CiphertextVec::encrypt(&pk, &y, &mut rand);
+//!
+//! let sum = cx.add(&pk, &cy).unwrap();
+//! assert_eq!(sum.decrypt(&sk), vec![11, 22, 33].into_iter().collect());
+//! let weights: PlaintextVec = vec![1, 0, -1].into_iter().collect();
+//! let dot = cx.dot(&pk, &weights).unwrap();
+//! assert_eq!(sk.decrypt(&dot), pk.modulus().clone() - 2);
+//! ```
+
+use crate::metrics;
+use crate::paillier::{PartialDecryption, PrivateKey, PrivateKeyShare, PublicKey};
+use crate::par::map_collect;
+use crate::rand::{random_in_mult_group, UnitCheck};
+use crate::util::multi_pow_mod_par;
+use crate::{Ciphertext, Plaintext};
+use anyhow::{ensure, Result};
+use rug::rand::MutRandState;
+use rug::{Complete, Integer};
+use serde::{Deserialize, Serialize};
+use std::iter::FromIterator;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaintextVec(Vec<Plaintext>);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CiphertextVec(Vec<Ciphertext>);
+
+impl PlaintextVec {
+    pub fn as_slice(&self) -> &[Plaintext] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<Plaintext> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<Plaintext>> for PlaintextVec {
+    fn from(v: Vec<Plaintext>) -> Self {
+        Self(v)
+    }
+}
+
+impl<T: Into<Plaintext>> FromIterator<T> for PlaintextVec {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl CiphertextVec {
+    /// Encrypts every element of `plain`
+    pub fn encrypt(pk: &PublicKey, plain: &PlaintextVec, rand: &mut dyn MutRandState) -> Self {
+        trace_span!("encrypt_batch", len = plain.len());
+        // the randomness is drawn sequentially, the exponentiations run in parallel
+        let jobs: Vec<(&Plaintext, Integer)> = plain
+            .0
+            .iter()
+            .map(|m| (m, random_in_mult_group(&pk.n, UnitCheck::Skip, rand)))
+            .collect();
+        metrics::encryptions(jobs.len());
+        Self(map_collect(&jobs, |_, (m, r)| {
+            pk.encrypt_raw(m.as_ref(), r).into()
+        }))
+    }
+
+    pub fn as_slice(&self) -> &[Ciphertext] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<Ciphertext> {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The elements in `range`
+    pub fn slice(&self, range: Range<usize>) -> Result<Self> {
+        ensure!(
+            range.start <= range.end && range.end <= self.len(),
+            "range {:?} is out of bounds for length {}",
+            range,
+            self.len()
+        );
+        Ok(Self(self.0[range].to_vec()))
+    }
+
+    /// Element-wise sum of the plaintexts
+    pub fn add(&self, pk: &PublicKey, other: &CiphertextVec) -> Result<Self> {
+        self.check_len(other.len())?;
+        Ok(Self(map_collect(&self.0, |i, c| {
+            let mut c = c.clone();
+            pk.add_encrypted(&mut c, &other.0[i]);
+            c
+        })))
+    }
+
+    /// Element-wise sum with the plaintexts of `other`
+    pub fn add_plain(&self, pk: &PublicKey, other: &PlaintextVec) -> Result<Self> {
+        self.check_len(other.len())?;
+        Ok(Self(map_collect(&self.0, |i, c| {
+            let mut c = c.clone();
+            pk.add_plain(&mut c, &other.0[i]);
+            c
+        })))
+    }
+
+    /// Multiplies every plaintext with the scalar `k`
+    pub fn mul(&self, pk: &PublicKey, k: &Plaintext) -> Self {
+        let k: Plaintext = k.as_ref().modulo_ref(&pk.n).complete().into();
+        Self(map_collect(&self.0, |_, c| {
+            let mut c = c.clone();
+            pk.mul_plain(&mut c, &k);
+            c
+        }))
+    }
+
+    /// Enc(sum_i m_i * w_i) for the plaintexts m_i and the `weights`, computed with a
+    /// single multi-exponentiation
+    pub fn dot(&self, pk: &PublicKey, weights: &PlaintextVec) -> Result<Ciphertext> {
+        self.check_len(weights.len())?;
+        let bases: Vec<&Integer> = self.0.iter().map(AsRef::as_ref).collect();
+        let exps: Vec<Integer> = weights
+            .0
+            .iter()
+            .map(|w| w.as_ref().modulo_ref(&pk.n).complete())
+            .collect();
+        // the exponents are non-negative, so no base has to be inverted
+        Ok(multi_pow_mod_par(&bases, &exps, &pk.n2).unwrap().into())
+    }
+
+    /// Re-randomizes all elements
+    pub fn reencrypt(&mut self, pk: &PublicKey, rand: &mut dyn MutRandState) {
+        pk.reencrypt_batch(&mut self.0, rand);
+    }
+
+    pub fn decrypt(&self, sk: &PrivateKey) -> PlaintextVec {
+        PlaintextVec(map_collect(&self.0, |_, c| sk.decrypt(c)))
+    }
+
+    pub fn share_decrypt(
+        &self,
+        pk: &PublicKey,
+        key_share: &PrivateKeyShare,
+    ) -> Vec<PartialDecryption> {
+        map_collect(&self.0, |_, c| key_share.share_decrypt(pk, c.clone()))
+    }
+
+    /// Combines the partial decryptions of at least w servers, one vector per server
+    /// as returned by [`CiphertextVec::share_decrypt`]
+    pub fn share_combine(
+        &self,
+        pk: &PublicKey,
+        partials: &[Vec<PartialDecryption>],
+    ) -> Result<PlaintextVec> {
+        ensure!(
+            partials.iter().all(|server| server.len() == self.len()),
+            "expected {} partial decryptions per server",
+            self.len()
+        );
+        trace_span!(
+            "share_combine_batch",
+            len = self.len(),
+            servers = partials.len()
+        );
+        let plain = map_collect(&self.0, |i, _| {
+            let shares: Vec<_> = partials.iter().map(|server| server[i].clone()).collect();
+            pk.share_combine(&shares)
+        });
+        Ok(PlaintextVec(plain.into_iter().collect::<Result<_>>()?))
+    }
+
+    fn check_len(&self, len: usize) -> Result<()> {
+        ensure!(
+            self.len() == len,
+            "length mismatch: {} and {}",
+            self.len(),
+            len
+        );
+        Ok(())
+    }
+}
+
+impl From<Vec<Ciphertext>> for CiphertextVec {
+    fn from(v: Vec<Ciphertext>) -> Self {
+        Self(v)
+    }
+}
+
+impl FromIterator<Ciphertext> for CiphertextVec {
+    fn from_iter<I: IntoIterator<Item = Ciphertext>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CiphertextVec, PlaintextVec};
+    use crate::paillier::generate_key_pair;
+    use rug::rand::RandState;
+
+    #[test]
+    fn test_vector_ops() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(128, 2, 2).unwrap();
+        let key_shares = sk.clone().share(&[0, 1], &mut rand);
+        let x: PlaintextVec = (0..20).collect();
+        let cx = CiphertextVec::encrypt(&pk, &x, &mut rand);
+        assert_eq!(cx.decrypt(&sk), x);
+
+        let doubled = cx.mul(&pk, &2.into());
+        assert_eq!(doubled.decrypt(&sk), (0..20).map(|i| 2 * i).collect());
+        let shifted = cx.add_plain(&pk, &(0..20).collect()).unwrap();
+        assert_eq!(shifted.decrypt(&sk), doubled.decrypt(&sk));
+        assert!(cx.add(&pk, &cx.slice(0..19).unwrap()).is_err());
+        assert!(cx.slice(5..21).is_err());
+
+        let ones: PlaintextVec = (0..20).map(|_| 1).collect();
+        let dot = cx.dot(&pk, &ones).unwrap();
+        assert_eq!(sk.decrypt(&dot), 190);
+
+        let mut tail = cx.slice(15..20).unwrap();
+        let before = tail.clone();
+        tail.reencrypt(&pk, &mut rand);
+        assert_ne!(before.as_slice()[0].as_ref(), tail.as_slice()[0].as_ref());
+        let partials: Vec<_> = key_shares
+            .iter()
+            .map(|share| tail.share_decrypt(&pk, share))
+            .collect();
+        assert_eq!(
+            tail.share_combine(&pk, &partials).unwrap(),
+            (15..20).collect()
+        );
+        assert!(tail
+            .share_combine(&pk, &[partials[0][..4].to_vec()])
+            .is_err());
+    }
+}
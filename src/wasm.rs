@@ -0,0 +1,157 @@
+//! Browser API for client-side encryption (feature `wasm`).
+//!
+//! A [`StudyKey`] encrypts questionnaire answers in the browser under the public key of
+//! a study, so plaintexts never leave the client. It only needs the modulus n of the
+//! key, e.g. `base64(pk.modulus().to_digits(Order::Msf))` published by the study, and
+//! uses the pure-Rust [`NumBigint`] backend, so it builds for `wasm32-unknown-unknown`
+//! with `--no-default-features --features wasm`.
+//!
+//! Ciphertexts are exchanged as standard base64 strings of the canonical fixed size
+//! encoding of [`crate::wire`], so the aggregator decodes them with
+//! `Ciphertext::from_bytes`. Invalid input throws a JavaScript `Error`.
+
+use crate::backend::{Arithmetic, Encryptor, NumBigint};
+use anyhow::{ensure, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use num_bigint::BigUint;
+use rand::rngs::OsRng;
+use wasm_bindgen::prelude::*;
+
+/// The public key of a study
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct StudyKey {
+    enc: Encryptor<NumBigint>,
+    /// Length of an encoded ciphertext in bytes
+    len: usize,
+}
+
+#[wasm_bindgen]
+impl StudyKey {
+    /// Creates the key from the base64 encoded big endian bytes of its modulus
+    #[wasm_bindgen(constructor)]
+    pub fn new(modulus_base64: &str) -> Result<StudyKey, JsError> {
+        Self::from_modulus_base64(modulus_base64).map_err(to_js)
+    }
+
+    /// Length of a decoded ciphertext in bytes
+    #[wasm_bindgen(js_name = ciphertextLength)]
+    pub fn ciphertext_len(&self) -> usize {
+        self.len
+    }
+
+    /// Encrypts `value`
+    pub fn encrypt(&self, value: u32) -> String {
+        let cipher = self.enc.encrypt(&value.into(), &mut OsRng);
+        self.encode(&cipher)
+    }
+
+    /// Encrypts the unsigned big endian value `bytes`, which must be less than n
+    #[wasm_bindgen(js_name = encryptBytes)]
+    pub fn encrypt_bytes(&self, bytes: &[u8]) -> Result<String, JsError> {
+        self.try_encrypt_bytes(bytes).map_err(to_js)
+    }
+
+    /// A fresh encryption of the same value, which can't be linked to `cipher`
+    pub fn reencrypt(&self, cipher: &str) -> Result<String, JsError> {
+        self.try_reencrypt(cipher).map_err(to_js)
+    }
+
+    /// Encrypts the sum of the values of `a` and `b`
+    #[wasm_bindgen(js_name = addEncrypted)]
+    pub fn add_encrypted(&self, a: &str, b: &str) -> Result<String, JsError> {
+        self.try_add_encrypted(a, b).map_err(to_js)
+    }
+}
+
+impl StudyKey {
+    fn from_modulus_base64(modulus_base64: &str) -> Result<Self> {
+        let n = NumBigint::from_bytes_be(&STANDARD.decode(modulus_base64)?);
+        ensure!(n.bits() > 1, "modulus is too small");
+        let enc = Encryptor::<NumBigint>::new(n);
+        let len = NumBigint::bits(&NumBigint::mul(enc.modulus(), enc.modulus())).div_ceil(8);
+        Ok(Self {
+            enc,
+            len: len as usize,
+        })
+    }
+
+    fn try_encrypt_bytes(&self, bytes: &[u8]) -> Result<String> {
+        let m = NumBigint::from_bytes_be(bytes);
+        ensure!(m < *self.enc.modulus(), "value must be less than n");
+        Ok(self.encode(&self.enc.encrypt(&m, &mut OsRng)))
+    }
+
+    fn try_reencrypt(&self, cipher: &str) -> Result<String> {
+        let mut cipher = self.decode(cipher)?;
+        self.enc.reencrypt(&mut cipher, &mut OsRng);
+        Ok(self.encode(&cipher))
+    }
+
+    fn try_add_encrypted(&self, a: &str, b: &str) -> Result<String> {
+        let mut sum = self.decode(a)?;
+        self.enc.add(&mut sum, &self.decode(b)?);
+        Ok(self.encode(&sum))
+    }
+
+    fn encode(&self, cipher: &BigUint) -> String {
+        let digits = NumBigint::to_bytes_be(cipher);
+        let mut bytes = vec![0; self.len - digits.len()];
+        bytes.extend_from_slice(&digits);
+        STANDARD.encode(bytes)
+    }
+
+    /// Decodes a canonically encoded ciphertext and checks that it is in Z*_{n^2}
+    fn decode(&self, cipher: &str) -> Result<BigUint> {
+        let bytes = STANDARD.decode(cipher)?;
+        ensure!(
+            bytes.len() == self.len,
+            "ciphertext must be encoded in {} bytes",
+            self.len
+        );
+        let c = NumBigint::from_bytes_be(&bytes);
+        let n = self.enc.modulus();
+        ensure!(
+            c < NumBigint::mul(n, n) && NumBigint::invert_mod(&c, n).is_some(),
+            "ciphertext is not in Z*_n^2"
+        );
+        Ok(c)
+    }
+}
+
+fn to_js(err: anyhow::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+#[cfg(all(test, feature = "gmp"))]
+mod tests {
+    use super::StudyKey;
+    use crate::paillier::generate_key_pair;
+    use crate::Ciphertext;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use rug::integer::Order;
+
+    #[test]
+    fn test_browser_encryption() {
+        let (pk, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let key = StudyKey::from_modulus_base64(
+            &STANDARD.encode(pk.modulus().to_digits::<u8>(Order::Msf)),
+        )
+        .unwrap();
+        assert_eq!(key.ciphertext_len(), pk.ciphertext_len());
+
+        let a = key.encrypt(40);
+        let b = key.try_encrypt_bytes(&[2]).unwrap();
+        let sum = key
+            .try_add_encrypted(&a, &key.try_reencrypt(&b).unwrap())
+            .unwrap();
+        let c = Ciphertext::from_bytes(&pk, &STANDARD.decode(sum).unwrap()).unwrap();
+        assert_eq!(sk.decrypt(&c), 42);
+
+        assert!(key.try_reencrypt(&STANDARD.encode([1, 2, 3])).is_err());
+        assert!(key.try_encrypt_bytes(&[0xff; 17]).is_err());
+        assert!(StudyKey::from_modulus_base64("not base64").is_err());
+    }
+}
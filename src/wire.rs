@@ -0,0 +1,279 @@
+//! Canonical, fixed size byte encodings for the wire.
+//!
+//! The serde representation of [`Ciphertext`] uses the minimal number of bytes, which
+//! leaks the magnitude of the value and makes the size of messages vary. The encoding
+//! here pads every Paillier ciphertext to ⌈|n^2| / 8⌉ big endian bytes, so a vector of
+//! ciphertexts can be framed by its length alone and encoding is deterministic.
+//!
+//! # Versioned encoding
+//! Types implementing [`Versioned`] can additionally be serialized with a header of
+//! the magic bytes `PHT`, the format version, a scheme and a type identifier followed
+//! by the bincode encoding of the value. Decoding rejects blobs of another scheme or
+//! type, and of versions from before the last layout change of the type or from the
+//! future, instead of silently misparsing them.
+
+use crate::audit;
+use crate::paillier::{self, PublicKey};
+use crate::proofs::in_mult_group;
+use crate::Ciphertext;
+use anyhow::{anyhow, ensure, Result};
+use rug::integer::Order;
+use rug::{Complete, Integer};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const MAGIC: &[u8; 3] = b"PHT";
+/// Version written by the versioned encoding. Must be increased on every layout change
+/// of a [`Versioned`] type, which then sets its [`Versioned::MIN_VERSION`] to it, so
+/// blobs of unchanged types stay readable.
+/// Version 2 added the optional prime factors to the Paillier private key.
+/// Version 3 added the key fingerprint to the Paillier private key share.
+pub const FORMAT_VERSION: u8 = 3;
+const HEADER_LEN: usize = MAGIC.len() + 3;
+
+/// Scheme identifier of the versioned encoding
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Scheme {
+    /// Values like [`Ciphertext`] which are shared by several schemes
+    Generic = 0,
+    Paillier = 1,
+}
+
+/// Type identifier of the versioned encoding
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Kind {
+    PublicKey = 1,
+    PrivateKey = 2,
+    PrivateKeyShare = 3,
+    Ciphertext = 4,
+    DealingTranscript = 5,
+    DealerCheckpoint = 6,
+    PartialDecryption = 7,
+}
+
+/// Self-describing serialization with a version, scheme and type header
+pub trait Versioned: Serialize + DeserializeOwned {
+    const SCHEME: Scheme;
+    const KIND: Kind;
+    /// Oldest version with the current layout of the type
+    const MIN_VERSION: u8;
+
+    fn to_versioned_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[FORMAT_VERSION, Self::SCHEME as u8, Self::KIND as u8]);
+        bincode::serialize_into(&mut bytes, self).expect("serialization into a Vec can't fail");
+        bytes
+    }
+
+    fn from_versioned_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() >= HEADER_LEN && bytes.starts_with(MAGIC),
+            "missing versioned header"
+        );
+        let (version, scheme, kind) = (bytes[3], bytes[4], bytes[5]);
+        ensure!(
+            (Self::MIN_VERSION..=FORMAT_VERSION).contains(&version),
+            "unsupported format version {} of {:?}, expected {} to {}",
+            version,
+            Self::KIND,
+            Self::MIN_VERSION,
+            FORMAT_VERSION
+        );
+        ensure!(
+            scheme == Self::SCHEME as u8 && kind == Self::KIND as u8,
+            "blob contains scheme {} type {}, expected {:?} {:?}",
+            scheme,
+            kind,
+            Self::SCHEME,
+            Self::KIND
+        );
+        bincode::deserialize(&bytes[HEADER_LEN..]).map_err(|e| anyhow!("invalid payload: {}", e))
+    }
+}
+
+macro_rules! impl_versioned {
+    ($($ty:ty: $scheme:ident $kind:ident since $min:literal),+) => {
+        $(
+            impl Versioned for $ty {
+                const SCHEME: Scheme = Scheme::$scheme;
+                const KIND: Kind = Kind::$kind;
+                const MIN_VERSION: u8 = $min;
+            }
+        )+
+    };
+}
+
+impl_versioned!(
+    paillier::PublicKey: Paillier PublicKey since 1,
+    paillier::PrivateKey: Paillier PrivateKey since 2,
+    paillier::PrivateKeyShare: Paillier PrivateKeyShare since 3,
+    Ciphertext: Generic Ciphertext since 1,
+    audit::DealingTranscript: Paillier DealingTranscript since 2,
+    paillier::PartialDecryption: Paillier PartialDecryption since 3
+);
+
+#[cfg(feature = "sealed")]
+impl_versioned!(crate::dealer::DealerCheckpoint: Paillier DealerCheckpoint since 2);
+
+impl PublicKey {
+    /// Length of a canonically encoded ciphertext in bytes
+    pub fn ciphertext_len(&self) -> usize {
+        (self.n2.significant_bits() as usize).div_ceil(8)
+    }
+}
+
+impl Ciphertext {
+    /// Encodes the ciphertext as exactly `pk.ciphertext_len()` big endian bytes. Values
+    /// outside of [0, n^2), e.g. deserialized from untrusted input, are encoded as
+    /// their residue mod n^2, which decrypts to the same plaintext.
+    pub fn to_bytes(&self, pk: &PublicKey) -> Vec<u8> {
+        let len = pk.ciphertext_len();
+        let digits = if self.val >= 0 && self.val < pk.n2 {
+            self.val.to_digits::<u8>(Order::MsfBe)
+        } else {
+            self.val
+                .modulo_ref(&pk.n2)
+                .complete()
+                .to_digits(Order::MsfBe)
+        };
+        let mut bytes = vec![0; len - digits.len()];
+        bytes.extend_from_slice(&digits);
+        bytes
+    }
+
+    /// Decodes a canonically encoded ciphertext and checks that it is in Z*_{n^2}
+    pub fn from_bytes(pk: &PublicKey, bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == pk.ciphertext_len(),
+            "ciphertext must be encoded in {} bytes",
+            pk.ciphertext_len()
+        );
+        let val = Integer::from_digits(bytes, Order::MsfBe);
+        ensure!(
+            in_mult_group(&val, &pk.n, &pk.n2),
+            "ciphertext is not in Z*_n^2"
+        );
+        Ok(Self { val })
+    }
+}
+
+/// Encodes `ciphers` as the concatenation of their canonical encodings
+pub fn encode_ciphertexts(pk: &PublicKey, ciphers: &[Ciphertext]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(ciphers.len() * pk.ciphertext_len());
+    for cipher in ciphers {
+        bytes.extend_from_slice(&cipher.to_bytes(pk));
+    }
+    bytes
+}
+
+/// Inverse of [`encode_ciphertexts`]
+pub fn decode_ciphertexts(pk: &PublicKey, bytes: &[u8]) -> Result<Vec<Ciphertext>> {
+    let len = pk.ciphertext_len();
+    ensure!(
+        bytes.len().is_multiple_of(len),
+        "length is not a multiple of the ciphertext length"
+    );
+    bytes
+        .chunks(len)
+        .map(|chunk| Ciphertext::from_bytes(pk, chunk))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_ciphertexts, encode_ciphertexts, Versioned, FORMAT_VERSION};
+    use crate::paillier::{generate_key_pair, PrivateKey, PrivateKeyShare, PublicKey};
+    use crate::Ciphertext;
+    use rug::rand::RandState;
+    use rug::Integer;
+
+    #[test]
+    fn test_fixed_size_encoding() {
+        let mut rand = RandState::new();
+        let (pk, _) = generate_key_pair(256, 1, 1).unwrap();
+        let mut ciphers: Vec<_> = (0..5).map(|i| pk.encrypt(i.into(), &mut rand)).collect();
+        // a small value must still be padded to the full length
+        ciphers.push(Integer::from(2).into());
+        for c in &ciphers {
+            assert_eq!(c.to_bytes(&pk).len(), pk.ciphertext_len());
+        }
+
+        let bytes = encode_ciphertexts(&pk, &ciphers);
+        assert_eq!(bytes.len(), 6 * pk.ciphertext_len());
+        let decoded = decode_ciphertexts(&pk, &bytes).unwrap();
+        assert!(decoded
+            .iter()
+            .zip(&ciphers)
+            .all(|(a, b)| a.as_ref() == b.as_ref()));
+
+        assert!(decode_ciphertexts(&pk, &bytes[1..]).is_err());
+        // unreduced and negative values are encoded as their residue mod n^2
+        let c = pk.encrypt(3.into(), &mut rand);
+        for val in [c.as_ref().clone() + &pk.n2, c.as_ref().clone() - &pk.n2] {
+            let bytes = Ciphertext::from(val).to_bytes(&pk);
+            assert_eq!(Ciphertext::from_bytes(&pk, &bytes).unwrap(), c);
+        }
+        let zero = vec![0; pk.ciphertext_len()];
+        assert!(Ciphertext::from_bytes(&pk, &zero).is_err());
+    }
+
+    #[test]
+    fn test_versioned() {
+        let mut rand = RandState::new();
+        let (pk, sk) = generate_key_pair(256, 1, 1).unwrap();
+        let pk_bytes = pk.to_versioned_bytes();
+        assert_eq!(PublicKey::from_versioned_bytes(&pk_bytes).unwrap(), pk);
+        let sk_bytes = sk.to_versioned_bytes();
+        assert_eq!(PrivateKey::from_versioned_bytes(&sk_bytes).unwrap(), sk);
+        let mut without_factors = sk.clone();
+        without_factors.factors = None;
+        let decoded = PrivateKey::from_versioned_bytes(&without_factors.to_versioned_bytes());
+        assert!(decoded.unwrap().factors().is_none());
+        let share = sk.share(&[0], &mut rand).remove(0);
+        assert!(PrivateKeyShare::from_versioned_bytes(&share.to_versioned_bytes()).is_ok());
+        let c = pk.encrypt(1.into(), &mut rand);
+        let c_bytes = c.to_versioned_bytes();
+        assert_eq!(
+            Ciphertext::from_versioned_bytes(&c_bytes).unwrap().as_ref(),
+            c.as_ref()
+        );
+
+        assert!(PrivateKey::from_versioned_bytes(&pk_bytes).is_err());
+        let mut future = pk_bytes.clone();
+        future[3] = FORMAT_VERSION + 1;
+        assert!(PublicKey::from_versioned_bytes(&future).is_err());
+        assert!(PublicKey::from_versioned_bytes(&pk_bytes[3..]).is_err());
+    }
+
+    #[test]
+    fn test_older_versions() {
+        let hex = |s: &str| -> Vec<u8> {
+            (0..s.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+                .collect()
+        };
+        // a version 2 public key with n = 3233 for 1 of 2 servers
+        let pk_v2 = hex(concat!(
+            "50485402010101000000020000000200000000000000a10c0200000000000000a20c03000000000000",
+            "00417d9f0100000000000000020200000000000000d70b"
+        ));
+        let pk = PublicKey::from_versioned_bytes(&pk_v2).unwrap();
+        assert_eq!(pk, PublicKey::from_modulus(3233.into(), 2, 1).unwrap());
+        // a version 1 ciphertext of the value 123456
+        let c_v1 = hex("504854010004030000000000000040e201");
+        assert_eq!(
+            Ciphertext::from_versioned_bytes(&c_v1).unwrap().as_ref(),
+            &123456
+        );
+
+        // the layout of key shares changed in version 3
+        let mut rand = RandState::new();
+        let (_, sk) = generate_key_pair(128, 1, 1).unwrap();
+        let mut share_v2 = sk.share(&[0], &mut rand).remove(0).to_versioned_bytes();
+        share_v2[3] = 2;
+        assert!(PrivateKeyShare::from_versioned_bytes(&share_v2).is_err());
+    }
+}